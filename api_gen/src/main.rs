@@ -1,9 +1,20 @@
+use common::configuration;
 use std::env;
-use utoipa::OpenApi;
 
 fn main() {
-    let path = env::args().nth(1).expect("Missing path argument for openapi spec file.");
-    let open_api = web_server::web_api::ApiDoc::openapi();
+    let path = env::args()
+        .nth(1)
+        .expect("Missing path argument for openapi spec file.");
+    // The servers are taken from the service configuration, when one is given.
+    let servers = env::args()
+        .nth(2)
+        .map(|config_path| match configuration::get_configuration(&config_path) {
+            Ok(config) => config.openapi_servers,
+            Err(err) => panic!("{}", err),
+        })
+        .unwrap_or_default();
+
+    let open_api = web_server::openapi::api_doc(&servers);
     match open_api.to_yaml() {
         Ok(yaml_desc) => {
             std::fs::write(path, yaml_desc).unwrap();
@@ -12,4 +23,18 @@ fn main() {
             panic!("{:?}", err);
         }
     }
+
+    if let Some(schema_path) = env::args().nth(3) {
+        write_graphql_schema(&schema_path);
+    }
+}
+
+#[cfg(feature = "graphql")]
+fn write_graphql_schema(path: &str) {
+    std::fs::write(path, web_server::graphql::schema_sdl()).unwrap();
+}
+
+#[cfg(not(feature = "graphql"))]
+fn write_graphql_schema(_path: &str) {
+    panic!("The GraphQL schema can only be exported when built with the `graphql` feature.");
 }
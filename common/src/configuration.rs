@@ -1,4 +1,5 @@
 use config::{Config, ConfigError, FileFormat};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 
@@ -13,8 +14,42 @@ const CONFIG_DB_PORT: &str = "service.db_port";
 const CONFIG_DB_NAME: &str = "service.db_name";
 const CONFIG_DB_USERNAME: &str = "service.db_username";
 const CONFIG_DB_PASSWORD: &str = "service.db_password";
+const CONFIG_OPENAPI_SERVERS: &str = "openapi.servers";
+const CONFIG_MAX_DATA_AGE_HOURS: &str = "service.max_data_age_hours";
+const CONFIG_PUBLIC_URL: &str = "service.public_url";
+const CONFIG_UNSUBSCRIBE_SECRET: &str = "service.unsubscribe_secret";
+const CONFIG_ALLOW_PRIVATE_TARGETS: &str = "service.allow_private_targets";
+const CONFIG_SMS_URL: &str = "sms.url";
+const CONFIG_SMS_TOKEN: &str = "sms.token";
+const CONFIG_SMS_USERNAME: &str = "sms.username";
+const CONFIG_SMS_PASSWORD: &str = "sms.password";
+const CONFIG_SMS_SENDER: &str = "sms.sender";
+const CONFIG_SMS_CONTENT_TYPE: &str = "sms.content_type";
+const CONFIG_SMS_PAYLOAD_TEMPLATE: &str = "sms.payload_template";
+const CONFIG_SMS_MAX_SEGMENTS: &str = "sms.max_segments";
+const CONFIG_SMTP_HOST: &str = "smtp.host";
+const CONFIG_SMTP_PORT: &str = "smtp.port";
+const CONFIG_SMTP_USERNAME: &str = "smtp.username";
+const CONFIG_SMTP_PASSWORD: &str = "smtp.password";
+const CONFIG_SMTP_FROM: &str = "smtp.from";
+const CONFIG_SMTP_STARTTLS: &str = "smtp.starttls";
+const CONFIG_SMTP_CA_FILE: &str = "smtp.ca_file";
+const CONFIG_WEBHOOK_MAX_FAILURES: &str = "webhook.max_failures";
+const CONFIG_SUBSCRIPTIONS_MAX_PER_CLIENT: &str = "subscriptions.max_per_client_per_hour";
+const CONFIG_SUBSCRIPTIONS_MAX_PENDING: &str = "subscriptions.max_pending_per_contact";
+const CONFIG_SUBSCRIPTIONS_CLIENT_HEADER: &str = "subscriptions.client_address_header";
+const CONFIG_OUTBOX_MAX_ATTEMPTS: &str = "outbox.max_attempts";
+const CONFIG_OUTBOX_RETRY_DELAY_SECS: &str = "outbox.retry_delay_secs";
+const CONFIG_OUTBOX_MAX_RETRY_DELAY_SECS: &str = "outbox.max_retry_delay_secs";
+const CONFIG_OUTBOX_POLL_INTERVAL_SECS: &str = "outbox.poll_interval_secs";
+const CONFIG_OUTBOX_BATCH_SIZE: &str = "outbox.batch_size";
+const CONFIG_TEMPLATES: &str = "templates";
 
-#[derive(Debug, Clone, PartialEq, Default)]
+const DEFAULT_SMS_CONTENT_TYPE: &str = "application/json";
+const DEFAULT_SMS_PAYLOAD_TEMPLATE: &str = r#"{"from": "{{from}}", "to": "{{to}}", "text": "{{message}}"}"#;
+
+/// Its `Debug` output hides the passwords, the tokens and the secrets, the configuration being logged at startup.
+#[derive(Clone, PartialEq, Default)]
 pub struct ServiceConfiguration {
     pub url: String,
     pub categories: Vec<String>,
@@ -27,6 +62,159 @@ pub struct ServiceConfiguration {
     pub db_user: Option<String>,
     pub db_password: Option<String>,
     pub db_name: Option<String>,
+    pub openapi_servers: Vec<String>,
+    /// The status reports the data as stale when no feed was stored for longer than this, it is always fresh if unset.
+    pub max_data_age_hours: Option<u32>,
+    /// The address the service is reachable at from the internet, e.g. `https://enel.lab.wicked`, the links of the
+    /// notifications point there. The first OpenAPI server is used when unset.
+    pub public_url: Option<String>,
+    /// The key signing the unsubscribe links and the management tokens of the notifications, mandatory for the web
+    /// server since it always sends notifications.
+    pub unsubscribe_secret: Option<String>,
+    /// Lets the webhook, ntfy and Gotify subscriptions target private, loopback and link-local addresses. Off by
+    /// default, the subscribers could reach the network of the service otherwise.
+    pub allow_private_targets: bool,
+    /// SMS notifications are not sent when no gateway is configured.
+    pub sms_gateway: Option<SmsGatewayConfiguration>,
+    /// E-mail notifications are not sent when no SMTP relay is configured.
+    pub smtp: Option<SmtpConfiguration>,
+    pub webhook: WebhookConfiguration,
+    pub subscriptions: SubscriptionLimits,
+    pub outbox: OutboxConfiguration,
+    pub templates: TemplatesConfiguration,
+}
+
+/// A generic HTTP SMS gateway, each message is POSTed to `url` as the rendered `payload_template`.
+#[derive(Clone, PartialEq)]
+pub struct SmsGatewayConfiguration {
+    pub url: String,
+    /// Sent as a bearer token, takes precedence over the basic credentials.
+    pub token: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The sender name or number, rendered in place of `{{from}}`.
+    pub sender: Option<String>,
+    /// JSON and form payloads get the placeholder values escaped accordingly.
+    pub content_type: String,
+    /// The request body, `{{from}}`, `{{to}}` and `{{message}}` are replaced by the values of each message.
+    pub payload_template: String,
+    /// Longer messages are truncated to fit, concatenated SMS are billed by segment.
+    pub max_segments: u32,
+}
+
+impl SmsGatewayConfiguration {
+    pub fn new(url: String) -> SmsGatewayConfiguration {
+        SmsGatewayConfiguration {
+            url,
+            token: None,
+            username: None,
+            password: None,
+            sender: None,
+            content_type: DEFAULT_SMS_CONTENT_TYPE.to_string(),
+            payload_template: DEFAULT_SMS_PAYLOAD_TEMPLATE.to_string(),
+            max_segments: 1,
+        }
+    }
+}
+
+/// The SMTP relay the e-mail notifications are submitted to.
+#[derive(Clone, PartialEq)]
+pub struct SmtpConfiguration {
+    pub host: String,
+    pub port: u16,
+    /// Authenticates with `AUTH PLAIN` when set, only over STARTTLS.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// The sender address of the messages.
+    pub from: String,
+    /// The session is upgraded with STARTTLS before authenticating, disable only for unauthenticated relays on a
+    /// trusted network.
+    pub starttls: bool,
+    /// A PEM file with the authorities of relays not trusted by the system, e.g. an internal one.
+    pub ca_file: Option<String>,
+}
+
+impl SmtpConfiguration {
+    pub fn new(host: String, from: String) -> SmtpConfiguration {
+        SmtpConfiguration {
+            host,
+            port: 587,
+            username: None,
+            password: None,
+            from,
+            starttls: true,
+            ca_file: None,
+        }
+    }
+}
+
+/// How the notification jobs are dispatched and retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxConfiguration {
+    /// Attempts of a notification before it is dead-lettered.
+    pub max_attempts: u32,
+    /// The delay before the first retry, doubled for each of the following ones up to `max_retry_delay_secs`.
+    pub retry_delay_secs: u64,
+    pub max_retry_delay_secs: u64,
+    /// How often the due notifications are looked for besides the feeds being stored.
+    pub poll_interval_secs: u64,
+    /// Notifications claimed at once by the dispatcher.
+    pub batch_size: u32,
+}
+
+impl Default for OutboxConfiguration {
+    fn default() -> Self {
+        OutboxConfiguration {
+            max_attempts: 8,
+            retry_delay_secs: 60,
+            max_retry_delay_secs: 6 * 3600,
+            poll_interval_secs: 30,
+            batch_size: 50,
+        }
+    }
+}
+
+/// When the outbox gives up on a webhook, its deliveries being retried like the other notifications.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookConfiguration {
+    /// Notifications dead-lettered in a row after which the webhook is disabled.
+    pub max_failures: u32,
+}
+
+impl Default for WebhookConfiguration {
+    fn default() -> Self {
+        WebhookConfiguration { max_failures: 5 }
+    }
+}
+
+/// How many subscriptions may be created, so that the service cannot be used to flood a contact with verification
+/// codes or the database with subscriptions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscriptionLimits {
+    /// Subscriptions created or replaced by a client in an hour.
+    pub max_per_client_per_hour: u32,
+    /// Subscriptions of a contact created or given the contact in the last hour, still waiting for their verification.
+    pub max_pending_per_contact: u32,
+    /// The header in which the proxy in front of the service passes the address of the client, e.g.
+    /// `X-Forwarded-For`, its last address being taken. The address of the connection is taken when unset.
+    pub client_address_header: Option<String>,
+}
+
+impl Default for SubscriptionLimits {
+    fn default() -> Self {
+        SubscriptionLimits {
+            max_per_client_per_hour: 10,
+            max_pending_per_contact: 3,
+            client_address_header: None,
+        }
+    }
+}
+
+/// Files overriding the built-in notification templates.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TemplatesConfiguration {
+    /// Paths by language and template name, e.g. `ro.summary` for the `summary` key of the `[templates.ro]` table.
+    pub files: BTreeMap<String, String>,
 }
 
 pub struct ServiceConfigurationBuilder {
@@ -41,6 +229,17 @@ pub struct ServiceConfigurationBuilder {
     db_user: Option<String>,
     db_password: Option<String>,
     db_name: Option<String>,
+    openapi_servers: Vec<String>,
+    max_data_age_hours: Option<u32>,
+    public_url: Option<String>,
+    unsubscribe_secret: Option<String>,
+    allow_private_targets: bool,
+    sms_gateway: Option<SmsGatewayConfiguration>,
+    smtp: Option<SmtpConfiguration>,
+    webhook: WebhookConfiguration,
+    subscriptions: SubscriptionLimits,
+    outbox: OutboxConfiguration,
+    templates: TemplatesConfiguration,
 }
 
 #[derive(Debug, PartialEq)]
@@ -89,6 +288,17 @@ impl Default for ServiceConfigurationBuilder {
             db_user: None,
             db_password: None,
             db_name: None,
+            openapi_servers: Vec::new(),
+            max_data_age_hours: None,
+            public_url: None,
+            unsubscribe_secret: None,
+            allow_private_targets: false,
+            sms_gateway: None,
+            smtp: None,
+            webhook: WebhookConfiguration::default(),
+            subscriptions: SubscriptionLimits::default(),
+            outbox: OutboxConfiguration::default(),
+            templates: TemplatesConfiguration::default(),
         }
     }
 }
@@ -166,6 +376,72 @@ impl ServiceConfigurationBuilder {
         self
     }
 
+    /// Sets the server URLs advertised by the OpenAPI document, e.g. `https://enel.lab.wicked`.
+    pub fn openapi_servers(&mut self, openapi_servers: Vec<String>) -> &mut Self {
+        self.openapi_servers = openapi_servers;
+        self
+    }
+
+    /// Sets the age of the newest stored feed past which the status reports the data as stale.
+    pub fn max_data_age_hours(&mut self, max_data_age_hours: u32) -> &mut Self {
+        self.max_data_age_hours = Some(max_data_age_hours);
+        self
+    }
+
+    /// Sets the public address of the service, without a trailing slash.
+    pub fn public_url(&mut self, public_url: String) -> &mut Self {
+        self.public_url = Some(public_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Sets the key signing the unsubscribe links.
+    pub fn unsubscribe_secret(&mut self, unsubscribe_secret: String) -> &mut Self {
+        self.unsubscribe_secret = Some(unsubscribe_secret);
+        self
+    }
+
+    /// Sets whether the subscriptions may target the internal addresses.
+    pub fn allow_private_targets(&mut self, allow_private_targets: bool) -> &mut Self {
+        self.allow_private_targets = allow_private_targets;
+        self
+    }
+
+    /// Sets the HTTP gateway SMS notifications are sent through.
+    pub fn sms_gateway(&mut self, sms_gateway: SmsGatewayConfiguration) -> &mut Self {
+        self.sms_gateway = Some(sms_gateway);
+        self
+    }
+
+    /// Sets the SMTP relay e-mail notifications are sent through.
+    pub fn smtp(&mut self, smtp: SmtpConfiguration) -> &mut Self {
+        self.smtp = Some(smtp);
+        self
+    }
+
+    /// Sets the retries of the webhook deliveries.
+    pub fn webhook(&mut self, webhook: WebhookConfiguration) -> &mut Self {
+        self.webhook = webhook;
+        self
+    }
+
+    /// Sets how many subscriptions may be created.
+    pub fn subscriptions(&mut self, subscriptions: SubscriptionLimits) -> &mut Self {
+        self.subscriptions = subscriptions;
+        self
+    }
+
+    /// Sets the retries and the pace of the notification dispatcher.
+    pub fn outbox(&mut self, outbox: OutboxConfiguration) -> &mut Self {
+        self.outbox = outbox;
+        self
+    }
+
+    /// Sets the files overriding the built-in notification templates.
+    pub fn templates(&mut self, templates: TemplatesConfiguration) -> &mut Self {
+        self.templates = templates;
+        self
+    }
+
     /// Builds the `ServiceConfiguration` instance.
     /// Returns an `Err` if the mandatory `url` field has not been set.
     pub fn build(self) -> Result<ServiceConfiguration, ConfigurationError> {
@@ -185,10 +461,87 @@ impl ServiceConfigurationBuilder {
             db_user: self.db_user,
             db_password: self.db_password,
             db_name: self.db_name,
+            openapi_servers: self.openapi_servers,
+            max_data_age_hours: self.max_data_age_hours,
+            public_url: self.public_url,
+            unsubscribe_secret: self.unsubscribe_secret,
+            allow_private_targets: self.allow_private_targets,
+            sms_gateway: self.sms_gateway,
+            smtp: self.smtp,
+            webhook: self.webhook,
+            subscriptions: self.subscriptions,
+            outbox: self.outbox,
+            templates: self.templates,
         })
     }
 }
 
+/// Shown in place of the secrets set in the configuration.
+fn redacted(secret: &Option<String>) -> Option<&'static str> {
+    secret.as_ref().map(|_| "<redacted>")
+}
+
+impl Debug for ServiceConfiguration {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("ServiceConfiguration")
+            .field("url", &self.url)
+            .field("categories", &self.categories)
+            .field("pushgateway_server", &self.pushgateway_server)
+            .field("http_port", &self.http_port)
+            .field("cors_permissive", &self.cors_permissive)
+            .field("log_level", &self.log_level)
+            .field("db_host", &self.db_host)
+            .field("db_port", &self.db_port)
+            .field("db_user", &self.db_user)
+            .field("db_password", &redacted(&self.db_password))
+            .field("db_name", &self.db_name)
+            .field("openapi_servers", &self.openapi_servers)
+            .field("max_data_age_hours", &self.max_data_age_hours)
+            .field("public_url", &self.public_url)
+            .field("unsubscribe_secret", &redacted(&self.unsubscribe_secret))
+            .field("allow_private_targets", &self.allow_private_targets)
+            .field("sms_gateway", &self.sms_gateway)
+            .field("smtp", &self.smtp)
+            .field("webhook", &self.webhook)
+            .field("subscriptions", &self.subscriptions)
+            .field("outbox", &self.outbox)
+            .field("templates", &self.templates)
+            .finish()
+    }
+}
+
+impl Debug for SmsGatewayConfiguration {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("SmsGatewayConfiguration")
+            .field("url", &self.url)
+            .field("token", &redacted(&self.token))
+            .field("username", &self.username)
+            .field("password", &redacted(&self.password))
+            .field("sender", &self.sender)
+            .field("content_type", &self.content_type)
+            .field("payload_template", &self.payload_template)
+            .field("max_segments", &self.max_segments)
+            .finish()
+    }
+}
+
+impl Debug for SmtpConfiguration {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("SmtpConfiguration")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &redacted(&self.password))
+            .field("from", &self.from)
+            .field("starttls", &self.starttls)
+            .field("ca_file", &self.ca_file)
+            .finish()
+    }
+}
+
 impl Display for ServiceConfiguration {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(
@@ -256,19 +609,211 @@ fn convert_configuration(raw_config: &Config) -> Result<ServiceConfiguration, Co
         config_builder.db_password(value.clone());
     });
 
+    let _ = raw_config.get::<u32>(CONFIG_MAX_DATA_AGE_HOURS).inspect(|value| {
+        config_builder.max_data_age_hours(*value);
+    });
+
+    let _ = raw_config.get_string(CONFIG_PUBLIC_URL).inspect(|value| {
+        config_builder.public_url(value.clone());
+    });
+    let _ = raw_config.get_string(CONFIG_UNSUBSCRIBE_SECRET).inspect(|value| {
+        config_builder.unsubscribe_secret(value.clone());
+    });
+    let _ = raw_config.get_bool(CONFIG_ALLOW_PRIVATE_TARGETS).inspect(|value| {
+        config_builder.allow_private_targets(*value);
+    });
+
+    let _ = raw_config.get_array(CONFIG_OPENAPI_SERVERS).inspect(|values| {
+        config_builder.openapi_servers(values.iter().map(|value| value.to_string()).collect());
+    });
+
+    if let Some(sms_gateway) = convert_sms_gateway(raw_config)? {
+        config_builder.sms_gateway(sms_gateway);
+    }
+    if let Some(smtp) = convert_smtp(raw_config)? {
+        config_builder.smtp(smtp);
+    }
+    config_builder.webhook(convert_webhook(raw_config)?);
+    config_builder.subscriptions(convert_subscriptions(raw_config)?);
+    config_builder.outbox(convert_outbox(raw_config)?);
+    config_builder.templates(convert_templates(raw_config)?);
+
     config_builder.build()
 }
 
+fn convert_sms_gateway(raw_config: &Config) -> Result<Option<SmsGatewayConfiguration>, ConfigurationError> {
+    let Ok(url) = raw_config.get_string(CONFIG_SMS_URL) else {
+        return Ok(None);
+    };
+
+    let mut sms_gateway = SmsGatewayConfiguration::new(url);
+    sms_gateway.token = raw_config.get_string(CONFIG_SMS_TOKEN).ok();
+    sms_gateway.username = raw_config.get_string(CONFIG_SMS_USERNAME).ok();
+    sms_gateway.password = raw_config.get_string(CONFIG_SMS_PASSWORD).ok();
+    sms_gateway.sender = raw_config.get_string(CONFIG_SMS_SENDER).ok();
+    let _ = raw_config.get_string(CONFIG_SMS_CONTENT_TYPE).inspect(|value| {
+        sms_gateway.content_type = value.clone();
+    });
+    let _ = raw_config.get_string(CONFIG_SMS_PAYLOAD_TEMPLATE).inspect(|value| {
+        sms_gateway.payload_template = value.clone();
+    });
+    let _ = raw_config.get::<u32>(CONFIG_SMS_MAX_SEGMENTS).inspect(|value| {
+        sms_gateway.max_segments = *value;
+    });
+
+    if sms_gateway.max_segments == 0 {
+        return Err(ConfigurationError::from_str("sms.max_segments must be at least 1."));
+    }
+    if !sms_gateway.payload_template.contains("{{message}}") {
+        return Err(ConfigurationError::from_str(
+            "sms.payload_template has to contain the {{message}} placeholder.",
+        ));
+    }
+
+    Ok(Some(sms_gateway))
+}
+
+fn convert_smtp(raw_config: &Config) -> Result<Option<SmtpConfiguration>, ConfigurationError> {
+    let Ok(host) = raw_config.get_string(CONFIG_SMTP_HOST) else {
+        return Ok(None);
+    };
+    let from = raw_config
+        .get_string(CONFIG_SMTP_FROM)
+        .map_err(|_| ConfigurationError::from_str("smtp.from is mandatory when smtp.host is set."))?;
+
+    let mut smtp = SmtpConfiguration::new(host, from);
+    smtp.username = raw_config.get_string(CONFIG_SMTP_USERNAME).ok();
+    smtp.password = raw_config.get_string(CONFIG_SMTP_PASSWORD).ok();
+    smtp.ca_file = raw_config.get_string(CONFIG_SMTP_CA_FILE).ok();
+    let _ = raw_config.get::<u16>(CONFIG_SMTP_PORT).inspect(|value| {
+        smtp.port = *value;
+    });
+    let _ = raw_config.get_bool(CONFIG_SMTP_STARTTLS).inspect(|value| {
+        smtp.starttls = *value;
+    });
+    if smtp.username.is_some() && !smtp.starttls {
+        return Err(ConfigurationError::from_str(
+            "smtp.username needs smtp.starttls, the credentials are never sent in clear text.",
+        ));
+    }
+
+    Ok(Some(smtp))
+}
+
+fn convert_webhook(raw_config: &Config) -> Result<WebhookConfiguration, ConfigurationError> {
+    let mut webhook = WebhookConfiguration::default();
+    let _ = raw_config.get::<u32>(CONFIG_WEBHOOK_MAX_FAILURES).inspect(|value| {
+        webhook.max_failures = *value;
+    });
+
+    if webhook.max_failures == 0 {
+        return Err(ConfigurationError::from_str("webhook.max_failures must be at least 1."));
+    }
+    Ok(webhook)
+}
+
+fn convert_subscriptions(raw_config: &Config) -> Result<SubscriptionLimits, ConfigurationError> {
+    let mut subscriptions = SubscriptionLimits::default();
+    let _ = raw_config
+        .get::<u32>(CONFIG_SUBSCRIPTIONS_MAX_PER_CLIENT)
+        .inspect(|value| {
+            subscriptions.max_per_client_per_hour = *value;
+        });
+    let _ = raw_config
+        .get::<u32>(CONFIG_SUBSCRIPTIONS_MAX_PENDING)
+        .inspect(|value| {
+            subscriptions.max_pending_per_contact = *value;
+        });
+    let _ = raw_config
+        .get_string(CONFIG_SUBSCRIPTIONS_CLIENT_HEADER)
+        .inspect(|value| {
+            subscriptions.client_address_header = Some(value.clone());
+        });
+
+    if subscriptions.max_per_client_per_hour == 0 || subscriptions.max_pending_per_contact == 0 {
+        return Err(ConfigurationError::from_str(
+            "subscriptions.max_per_client_per_hour and subscriptions.max_pending_per_contact must be at least 1.",
+        ));
+    }
+    if let Some(header) = &subscriptions.client_address_header
+        && (header.is_empty()
+            || !header
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'))
+    {
+        return Err(ConfigurationError::from_string(format!(
+            "subscriptions.client_address_header is not a header name: `{}`.",
+            header
+        )));
+    }
+    Ok(subscriptions)
+}
+
+fn convert_outbox(raw_config: &Config) -> Result<OutboxConfiguration, ConfigurationError> {
+    let mut outbox = OutboxConfiguration::default();
+    let _ = raw_config.get::<u32>(CONFIG_OUTBOX_MAX_ATTEMPTS).inspect(|value| {
+        outbox.max_attempts = *value;
+    });
+    let _ = raw_config.get::<u64>(CONFIG_OUTBOX_RETRY_DELAY_SECS).inspect(|value| {
+        outbox.retry_delay_secs = *value;
+    });
+    let _ = raw_config
+        .get::<u64>(CONFIG_OUTBOX_MAX_RETRY_DELAY_SECS)
+        .inspect(|value| {
+            outbox.max_retry_delay_secs = *value;
+        });
+    let _ = raw_config
+        .get::<u64>(CONFIG_OUTBOX_POLL_INTERVAL_SECS)
+        .inspect(|value| {
+            outbox.poll_interval_secs = *value;
+        });
+    let _ = raw_config.get::<u32>(CONFIG_OUTBOX_BATCH_SIZE).inspect(|value| {
+        outbox.batch_size = *value;
+    });
+
+    if outbox.max_attempts == 0 || outbox.poll_interval_secs == 0 || outbox.batch_size == 0 {
+        return Err(ConfigurationError::from_str(
+            "outbox.max_attempts, outbox.poll_interval_secs and outbox.batch_size must be at least 1.",
+        ));
+    }
+    Ok(outbox)
+}
+
+fn convert_templates(raw_config: &Config) -> Result<TemplatesConfiguration, ConfigurationError> {
+    let mut templates = TemplatesConfiguration::default();
+    let Ok(languages) = raw_config.get_table(CONFIG_TEMPLATES) else {
+        return Ok(templates);
+    };
+    for (language, files) in languages {
+        let files = files.into_table().map_err(|_| {
+            ConfigurationError::from_string(format!("templates.{} must be a table of template files.", language))
+        })?;
+        for (name, path) in files {
+            templates
+                .files
+                .insert(format!("{}.{}", language, name), path.into_string()?);
+        }
+    }
+    Ok(templates)
+}
+
 #[cfg(test)]
 mod configuration_tests {
     use config::Config;
 
     use crate::configuration::{
-        convert_configuration, ConfigurationError, ServiceConfigurationBuilder, CONFIG_CORS_PERMISSIVE, CONFIG_HTTP_PORT,
-        CONFIG_LOG_LEVEL, CONFIG_PUSHGATEWAY_SERVER,
+        CONFIG_CORS_PERMISSIVE, CONFIG_HTTP_PORT, CONFIG_LOG_LEVEL, CONFIG_PUSHGATEWAY_SERVER, ConfigurationError,
+        ServiceConfigurationBuilder, convert_configuration,
     };
 
-    use super::{ServiceConfiguration, CONFIG_FILTER_CATEGORIES, CONFIG_URL};
+    use super::{
+        CONFIG_FILTER_CATEGORIES, CONFIG_MAX_DATA_AGE_HOURS, CONFIG_OPENAPI_SERVERS, CONFIG_OUTBOX_MAX_ATTEMPTS,
+        CONFIG_OUTBOX_RETRY_DELAY_SECS, CONFIG_PUBLIC_URL, CONFIG_SMS_MAX_SEGMENTS, CONFIG_SMS_PAYLOAD_TEMPLATE,
+        CONFIG_SMS_TOKEN, CONFIG_SMS_URL, CONFIG_SMTP_FROM, CONFIG_SMTP_HOST, CONFIG_SMTP_PORT, CONFIG_SMTP_STARTTLS,
+        CONFIG_SMTP_USERNAME, CONFIG_SUBSCRIPTIONS_CLIENT_HEADER, CONFIG_SUBSCRIPTIONS_MAX_PENDING,
+        CONFIG_UNSUBSCRIBE_SECRET, CONFIG_URL, CONFIG_WEBHOOK_MAX_FAILURES, OutboxConfiguration, ServiceConfiguration,
+        SmsGatewayConfiguration, SmtpConfiguration, SubscriptionLimits, TemplatesConfiguration, WebhookConfiguration,
+    };
     #[test]
     fn test_service_configuration_builder_minimal() {
         let mut builder = ServiceConfigurationBuilder::default();
@@ -279,7 +824,7 @@ mod configuration_tests {
         assert_eq!(config.url, "http://localhost:8000");
         assert_eq!(config.categories.len(), 0);
         assert_eq!(config.http_port, 8080);
-        assert_eq!(config.cors_permissive, false);
+        assert!(!config.cors_permissive);
         assert_eq!(config.log_level, "info");
     }
 
@@ -304,7 +849,7 @@ mod configuration_tests {
         assert_eq!(config.categories, vec!["web", "api"]);
         assert_eq!(config.pushgateway_server, Some("http://prometheus:9091".to_string()));
         assert_eq!(config.http_port, 9000);
-        assert_eq!(config.cors_permissive, true);
+        assert!(config.cors_permissive);
         assert_eq!(config.log_level, "debug");
         assert_eq!(config.db_host, Some("db.example.com".to_string()));
         assert_eq!(config.db_port, Some(5432));
@@ -361,8 +906,322 @@ mod configuration_tests {
             db_user: None,
             db_port: None,
             db_name: None,
+            openapi_servers: vec![],
+            max_data_age_hours: None,
+            public_url: None,
+            unsubscribe_secret: None,
+            allow_private_targets: false,
+            sms_gateway: None,
+            smtp: None,
+            webhook: WebhookConfiguration::default(),
+            subscriptions: SubscriptionLimits::default(),
+            outbox: OutboxConfiguration::default(),
+            templates: TemplatesConfiguration::default(),
         };
 
         assert_eq!(service_config, expected_config);
     }
+
+    #[test]
+    fn config_loads_openapi_servers() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| {
+                x.set_default(
+                    CONFIG_OPENAPI_SERVERS,
+                    vec!["https://enel.lab.wicked", "http://localhost:8080"],
+                )
+            })
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let service_config = convert_configuration(&config_sample).unwrap();
+
+        assert_eq!(
+            service_config.openapi_servers,
+            vec![
+                "https://enel.lab.wicked".to_string(),
+                "http://localhost:8080".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn config_loads_max_data_age() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| x.set_default(CONFIG_MAX_DATA_AGE_HOURS, 36))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let service_config = convert_configuration(&config_sample).unwrap();
+
+        assert_eq!(service_config.max_data_age_hours, Some(36));
+    }
+
+    #[test]
+    fn config_loads_public_url() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| x.set_default(CONFIG_PUBLIC_URL, "https://enel.lab.wicked/"))
+            .and_then(|x| x.set_default(CONFIG_UNSUBSCRIBE_SECRET, "s3cr3t"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let service_config = convert_configuration(&config_sample).unwrap();
+
+        assert_eq!(service_config.public_url, Some("https://enel.lab.wicked".to_string()));
+        assert_eq!(service_config.unsubscribe_secret, Some("s3cr3t".to_string()));
+    }
+
+    #[test]
+    fn config_loads_sms_gateway() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| x.set_default(CONFIG_SMS_URL, "https://sms.example.ro/send"))
+            .and_then(|x| x.set_default(CONFIG_SMS_TOKEN, "secret"))
+            .and_then(|x| x.set_default(CONFIG_SMS_MAX_SEGMENTS, 2))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let service_config = convert_configuration(&config_sample).unwrap();
+
+        let mut expected = SmsGatewayConfiguration::new("https://sms.example.ro/send".to_string());
+        expected.token = Some("secret".to_string());
+        expected.max_segments = 2;
+        assert_eq!(service_config.sms_gateway, Some(expected));
+    }
+
+    #[test]
+    fn config_rejects_sms_templates_without_message() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| x.set_default(CONFIG_SMS_URL, "https://sms.example.ro/send"))
+            .and_then(|x| x.set_default(CONFIG_SMS_PAYLOAD_TEMPLATE, "to={{to}}"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert!(convert_configuration(&config_sample).is_err());
+    }
+
+    #[test]
+    fn config_loads_smtp() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| x.set_default(CONFIG_SMTP_HOST, "smtp.example.ro"))
+            .and_then(|x| x.set_default(CONFIG_SMTP_PORT, 2525))
+            .and_then(|x| x.set_default(CONFIG_SMTP_FROM, "alerte@example.ro"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let service_config = convert_configuration(&config_sample).unwrap();
+
+        let mut expected = SmtpConfiguration::new("smtp.example.ro".to_string(), "alerte@example.ro".to_string());
+        expected.port = 2525;
+        assert_eq!(service_config.smtp, Some(expected));
+    }
+
+    #[test]
+    fn config_requires_the_smtp_sender() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| x.set_default(CONFIG_SMTP_HOST, "smtp.example.ro"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert!(convert_configuration(&config_sample).is_err());
+    }
+
+    #[test]
+    fn config_refuses_smtp_credentials_without_starttls() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| x.set_default(CONFIG_SMTP_HOST, "smtp.example.ro"))
+            .and_then(|x| x.set_default(CONFIG_SMTP_FROM, "alerte@example.ro"))
+            .and_then(|x| x.set_default(CONFIG_SMTP_USERNAME, "alerte"))
+            .and_then(|x| x.set_default(CONFIG_SMTP_STARTTLS, false))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert!(convert_configuration(&config_sample).is_err());
+    }
+
+    #[test]
+    fn config_loads_outbox_backoff() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| x.set_default(CONFIG_OUTBOX_MAX_ATTEMPTS, 3))
+            .and_then(|x| x.set_default(CONFIG_OUTBOX_RETRY_DELAY_SECS, 10))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let service_config = convert_configuration(&config_sample).unwrap();
+
+        assert_eq!(
+            service_config.outbox,
+            OutboxConfiguration {
+                max_attempts: 3,
+                retry_delay_secs: 10,
+                ..OutboxConfiguration::default()
+            }
+        );
+    }
+
+    #[test]
+    fn config_loads_template_files() {
+        let config_sample = Config::builder()
+            .add_source(config::File::from_str(
+                r#"
+                [service]
+                url = "https://google.com"
+                http_port = 8090
+                cors_permissive = true
+                log_level = "debug"
+                [filter]
+                categories = []
+                [templates.ro]
+                summary = "/etc/enel/templates/summary.ro.hbs"
+                [templates.en]
+                email_html = "/etc/enel/templates/email.en.hbs"
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+
+        let service_config = convert_configuration(&config_sample).unwrap();
+
+        assert_eq!(
+            service_config.templates,
+            TemplatesConfiguration {
+                files: [
+                    ("en.email_html", "/etc/enel/templates/email.en.hbs"),
+                    ("ro.summary", "/etc/enel/templates/summary.ro.hbs"),
+                ]
+                .into_iter()
+                .map(|(key, path)| (key.to_string(), path.to_string()))
+                .collect(),
+            }
+        );
+    }
+
+    #[test]
+    fn config_loads_webhook_retries() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| x.set_default(CONFIG_WEBHOOK_MAX_FAILURES, 10))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let service_config = convert_configuration(&config_sample).unwrap();
+
+        assert_eq!(service_config.webhook, WebhookConfiguration { max_failures: 10 });
+    }
+
+    #[test]
+    fn config_loads_subscription_limits() {
+        let config_sample = Config::builder()
+            .set_default(CONFIG_URL, "https://google.com")
+            .and_then(|x| x.set_default(CONFIG_FILTER_CATEGORIES, Vec::<String>::new()))
+            .and_then(|x| x.set_default(CONFIG_HTTP_PORT, 8090))
+            .and_then(|x| x.set_default(CONFIG_CORS_PERMISSIVE, "true"))
+            .and_then(|x| x.set_default(CONFIG_LOG_LEVEL, "debug"))
+            .and_then(|x| x.set_default(CONFIG_SUBSCRIPTIONS_MAX_PENDING, 1))
+            .and_then(|x| x.set_default(CONFIG_SUBSCRIPTIONS_CLIENT_HEADER, "X-Forwarded-For"))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let service_config = convert_configuration(&config_sample).unwrap();
+
+        assert_eq!(
+            service_config.subscriptions,
+            SubscriptionLimits {
+                max_pending_per_contact: 1,
+                client_address_header: Some(String::from("X-Forwarded-For")),
+                ..SubscriptionLimits::default()
+            }
+        );
+    }
+
+    #[test]
+    fn debug_output_hides_the_secrets() {
+        let mut sms_gateway = SmsGatewayConfiguration::new(String::from("https://sms.example.ro/send"));
+        sms_gateway.token = Some(String::from("sms-token"));
+        sms_gateway.password = Some(String::from("sms-password"));
+        let mut smtp = SmtpConfiguration::new(String::from("smtp.example.ro"), String::from("alerte@example.ro"));
+        smtp.username = Some(String::from("alerte"));
+        smtp.password = Some(String::from("smtp-password"));
+        let mut builder = ServiceConfigurationBuilder::default();
+        builder
+            .url(String::from("https://google.com"))
+            .unsubscribe_secret(String::from("unsubscribe-secret"))
+            .sms_gateway(sms_gateway)
+            .smtp(smtp);
+        let mut service_config = builder.build().unwrap();
+        service_config.db_password = Some(String::from("db-password"));
+
+        let debug = format!("{:?}", service_config);
+
+        for secret in [
+            "sms-token",
+            "sms-password",
+            "smtp-password",
+            "unsubscribe-secret",
+            "db-password",
+        ] {
+            assert!(!debug.contains(secret), "{} is logged", secret);
+        }
+        assert!(debug.contains("alerte@example.ro"));
+    }
 }
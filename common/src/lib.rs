@@ -1,5 +1,6 @@
 pub mod configuration;
-use chrono::NaiveDate;
+pub mod normalization;
+use chrono::{NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
@@ -7,6 +8,8 @@ use sqlx::FromRow;
 pub struct Record {
     pub id: String,
     pub date: NaiveDate,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
     pub county: String,
     pub location: String,
     pub title: String,
@@ -0,0 +1,121 @@
+/// Words the provider puts in front of a locality name which are not part of the name itself.
+const LOCALITY_PREFIXES: [&str; 12] = [
+    "loc",
+    "localitatea",
+    "mun",
+    "municipiul",
+    "oras",
+    "orasul",
+    "com",
+    "comuna",
+    "sat",
+    "satul",
+    "jud",
+    "judetul",
+];
+
+/// Replaces the Romanian diacritics (both the comma and the cedilla variants) with their plain ASCII letter.
+pub fn fold_diacritics(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            'ă' | 'â' | 'á' => 'a',
+            'Ă' | 'Â' | 'Á' => 'A',
+            'î' | 'í' => 'i',
+            'Î' | 'Í' => 'I',
+            'ș' | 'ş' => 's',
+            'Ș' | 'Ş' => 'S',
+            'ț' | 'ţ' => 't',
+            'Ț' | 'Ţ' => 'T',
+            'é' => 'e',
+            'É' => 'E',
+            'ó' => 'o',
+            'Ó' => 'O',
+            'ú' => 'u',
+            'Ú' => 'U',
+            other => other,
+        })
+        .collect()
+}
+
+/// Converts a name into a lookup key: no diacritics, lowercase and words separated by a single `-`.
+pub fn to_key(value: &str) -> String {
+    fold_diacritics(value)
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+/// Normalizes a county name as found in the RSS feed or typed by a user (e.g. `Jud. CARAȘ-SEVERIN`) into the key
+/// used by the `counties` table (e.g. `caras-severin`).
+pub fn normalize_county(county: &str) -> String {
+    let key = strip_prefixes(&to_key(county));
+
+    match key.as_str() {
+        "bucharest" | "municipiul-bucuresti" | "mun-bucuresti" => "bucuresti".to_string(),
+        _ => key,
+    }
+}
+
+/// Normalizes a locality name as found in the RSS feed (e.g. `LOC. TURCOAIA`, `BUTENI(P)`,
+/// `MATNICU MARE,COPACELE`) into the key used by the `localities` table (e.g. `turcoaia`, `buteni`,
+/// `matnicu-mare`). Only the first locality is kept when the provider lists several of them.
+pub fn normalize_locality(location: &str) -> String {
+    let first = location.split(',').next().unwrap_or_default();
+    let without_remarks = match first.find('(') {
+        Some(position) => &first[..position],
+        None => first,
+    };
+
+    strip_prefixes(&to_key(without_remarks))
+}
+
+fn strip_prefixes(key: &str) -> String {
+    let mut words: Vec<&str> = key.split('-').collect();
+    while words.len() > 1 && LOCALITY_PREFIXES.contains(&words[0]) {
+        words.remove(0);
+    }
+    words.join("-")
+}
+
+#[cfg(test)]
+mod normalization_tests {
+    use super::{fold_diacritics, normalize_county, normalize_locality, to_key};
+
+    #[test]
+    fn fold_diacritics_both_variants() {
+        assert_eq!("Brasov Timis Iasi", fold_diacritics("Brașov Timiş Iași"));
+        assert_eq!("TARGU MURES", fold_diacritics("TÂRGU MUREȘ"));
+    }
+
+    #[test]
+    fn to_key_collapses_separators() {
+        assert_eq!("bistrita-nasaud", to_key("  Bistrița -  Năsăud "));
+        assert_eq!("bucuresti-sector-2", to_key("BUCURESTI SECTOR.2"));
+    }
+
+    #[test]
+    fn normalize_county_prefixes_and_aliases() {
+        assert_eq!("caras-severin", normalize_county("Jud. CARAS-SEVERIN"));
+        assert_eq!("timis", normalize_county("TIMIS"));
+        assert_eq!("bucuresti", normalize_county("Municipiul București"));
+        assert_eq!("satu-mare", normalize_county("satu mare"));
+    }
+
+    #[test]
+    fn normalize_locality_feed_values() {
+        assert_eq!("turcoaia", normalize_locality("LOC. TURCOAIA"));
+        assert_eq!("buteni", normalize_locality("BUTENI(P)"));
+        assert_eq!("soimos", normalize_locality("SOIMOS(P), VALEA SOIMOSULUI"));
+        assert_eq!("matnicu-mare", normalize_locality("MATNICU MARE,COPACELE,OHABA MATNIC"));
+        assert_eq!("urziceni", normalize_locality("ORASUL URZICENI, INTRERUPERE PARTIALA"));
+        assert_eq!("cluj-napoca", normalize_locality("Cluj-Napoca"));
+    }
+
+    #[test]
+    fn normalize_locality_keeps_prefix_only_names() {
+        assert_eq!("sat", normalize_locality("SAT"));
+    }
+}
@@ -0,0 +1,28 @@
+--liquibase formatted sql
+
+--changeset author:florin id:007
+--comment: Gazetteer used to place incidents on the map. Keys are lowercase, without diacritics and dash separated.
+
+CREATE TABLE counties
+(
+    key       VARCHAR(64) PRIMARY KEY,
+    name      VARCHAR(255)     NOT NULL,
+    latitude  DOUBLE PRECISION NOT NULL,
+    longitude DOUBLE PRECISION NOT NULL
+);
+
+CREATE TABLE localities
+(
+    id         BIGSERIAL PRIMARY KEY,
+    county_key VARCHAR(64)      NOT NULL REFERENCES counties (key),
+    name       VARCHAR(255)     NOT NULL,
+    name_key   VARCHAR(255)     NOT NULL,
+    latitude   DOUBLE PRECISION NOT NULL,
+    longitude  DOUBLE PRECISION NOT NULL
+);
+
+CREATE UNIQUE INDEX unique_locality_key ON localities (county_key, name_key);
+
+--rollback
+-- DROP TABLE localities;
+-- DROP TABLE counties;
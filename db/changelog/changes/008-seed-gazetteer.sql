@@ -0,0 +1,209 @@
+--liquibase formatted sql
+
+--changeset author:florin id:008
+--comment: Seed the gazetteer with the county centres and the main localities of each county
+
+INSERT INTO counties (key, name, latitude, longitude)
+VALUES
+    ('alba', 'Alba', 46.077, 23.58),
+    ('arad', 'Arad', 46.186, 21.312),
+    ('arges', 'Argeș', 44.856, 24.869),
+    ('bacau', 'Bacău', 46.567, 26.913),
+    ('bihor', 'Bihor', 47.047, 21.918),
+    ('bistrita-nasaud', 'Bistrița-Năsăud', 47.135, 24.49),
+    ('botosani', 'Botoșani', 47.748, 26.669),
+    ('brasov', 'Brașov', 45.658, 25.601),
+    ('braila', 'Brăila', 45.271, 27.957),
+    ('bucuresti', 'București', 44.4268, 26.1025),
+    ('buzau', 'Buzău', 45.152, 26.823),
+    ('caras-severin', 'Caraș-Severin', 45.3, 21.889),
+    ('calarasi', 'Călărași', 44.206, 27.312),
+    ('cluj', 'Cluj', 46.77, 23.59),
+    ('constanta', 'Constanța', 44.159, 28.634),
+    ('covasna', 'Covasna', 45.867, 25.79),
+    ('dambovita', 'Dâmbovița', 44.927, 25.456),
+    ('dolj', 'Dolj', 44.317, 23.8),
+    ('galati', 'Galați', 45.436, 28.053),
+    ('giurgiu', 'Giurgiu', 43.903, 25.969),
+    ('gorj', 'Gorj', 45.046, 23.274),
+    ('harghita', 'Harghita', 46.36, 25.801),
+    ('hunedoara', 'Hunedoara', 45.876, 22.913),
+    ('ialomita', 'Ialomița', 44.563, 27.366),
+    ('iasi', 'Iași', 47.158, 27.601),
+    ('ilfov', 'Ilfov', 44.5, 26.2),
+    ('maramures', 'Maramureș', 47.66, 23.57),
+    ('mehedinti', 'Mehedinți', 44.63, 22.66),
+    ('mures', 'Mureș', 46.54, 24.56),
+    ('neamt', 'Neamț', 46.93, 26.37),
+    ('olt', 'Olt', 44.43, 24.365),
+    ('prahova', 'Prahova', 44.95, 26.01),
+    ('satu-mare', 'Satu Mare', 47.79, 22.89),
+    ('salaj', 'Sălaj', 47.182, 23.057),
+    ('sibiu', 'Sibiu', 45.793, 24.121),
+    ('suceava', 'Suceava', 47.651, 26.255),
+    ('teleorman', 'Teleorman', 43.97, 25.33),
+    ('timis', 'Timiș', 45.75, 21.23),
+    ('tulcea', 'Tulcea', 45.18, 28.8),
+    ('valcea', 'Vâlcea', 45.1, 24.37),
+    ('vaslui', 'Vaslui', 46.64, 27.73),
+    ('vrancea', 'Vrancea', 45.698, 27.183);
+
+INSERT INTO localities (county_key, name, name_key, latitude, longitude)
+VALUES
+    ('alba', 'Alba Iulia', 'alba-iulia', 46.067, 23.570),
+    ('alba', 'Aiud', 'aiud', 46.312, 23.729),
+    ('alba', 'Blaj', 'blaj', 46.176, 23.917),
+    ('alba', 'Sebeș', 'sebes', 45.958, 23.571),
+    ('arad', 'Arad', 'arad', 46.186, 21.312),
+    ('arad', 'Lipova', 'lipova', 46.089, 21.692),
+    ('arad', 'Ineu', 'ineu', 46.426, 21.840),
+    ('arad', 'Pecica', 'pecica', 46.170, 21.067),
+    ('arad', 'Chișineu-Criș', 'chisineu-cris', 46.522, 21.516),
+    ('arad', 'Sebiș', 'sebis', 46.372, 22.117),
+    ('arad', 'Nădlac', 'nadlac', 46.167, 20.750),
+    ('arges', 'Pitești', 'pitesti', 44.856, 24.869),
+    ('arges', 'Curtea de Argeș', 'curtea-de-arges', 45.139, 24.679),
+    ('arges', 'Câmpulung', 'campulung', 45.268, 25.047),
+    ('arges', 'Mioveni', 'mioveni', 44.956, 24.940),
+    ('bacau', 'Bacău', 'bacau', 46.567, 26.913),
+    ('bacau', 'Onești', 'onesti', 46.250, 26.767),
+    ('bihor', 'Oradea', 'oradea', 47.047, 21.918),
+    ('bihor', 'Salonta', 'salonta', 46.800, 21.650),
+    ('bistrita-nasaud', 'Bistrița', 'bistrita', 47.135, 24.490),
+    ('bistrita-nasaud', 'Beclean', 'beclean', 47.180, 24.180),
+    ('botosani', 'Botoșani', 'botosani', 47.748, 26.669),
+    ('botosani', 'Dorohoi', 'dorohoi', 47.960, 26.400),
+    ('brasov', 'Brașov', 'brasov', 45.658, 25.601),
+    ('brasov', 'Făgăraș', 'fagaras', 45.842, 24.973),
+    ('brasov', 'Săcele', 'sacele', 45.617, 25.694),
+    ('braila', 'Brăila', 'braila', 45.271, 27.957),
+    ('braila', 'Ianca', 'ianca', 45.135, 27.475),
+    ('bucuresti', 'București', 'bucuresti', 44.427, 26.103),
+    ('buzau', 'Buzău', 'buzau', 45.152, 26.823),
+    ('buzau', 'Râmnicu Sărat', 'ramnicu-sarat', 45.380, 27.056),
+    ('caras-severin', 'Reșița', 'resita', 45.300, 21.889),
+    ('caras-severin', 'Caransebeș', 'caransebes', 45.414, 22.222),
+    ('caras-severin', 'Oravița', 'oravita', 45.039, 21.686),
+    ('caras-severin', 'Moldova Nouă', 'moldova-noua', 44.737, 21.664),
+    ('caras-severin', 'Bocșa', 'bocsa', 45.375, 21.709),
+    ('caras-severin', 'Oțelu Roșu', 'otelu-rosu', 45.520, 22.367),
+    ('caras-severin', 'Anina', 'anina', 45.080, 21.855),
+    ('caras-severin', 'Carașova', 'carasova', 45.197, 21.862),
+    ('caras-severin', 'Berzasca', 'berzasca', 44.647, 21.957),
+    ('calarasi', 'Călărași', 'calarasi', 44.206, 27.312),
+    ('calarasi', 'Oltenița', 'oltenita', 44.087, 26.637),
+    ('cluj', 'Cluj-Napoca', 'cluj-napoca', 46.770, 23.590),
+    ('cluj', 'Turda', 'turda', 46.567, 23.783),
+    ('cluj', 'Dej', 'dej', 47.143, 23.876),
+    ('constanta', 'Constanța', 'constanta', 44.159, 28.634),
+    ('constanta', 'Mangalia', 'mangalia', 43.817, 28.583),
+    ('constanta', 'Medgidia', 'medgidia', 44.250, 28.283),
+    ('constanta', 'Năvodari', 'navodari', 44.321, 28.613),
+    ('constanta', 'Mihail Kogălniceanu', 'mihail-kogalniceanu', 44.367, 28.460),
+    ('constanta', 'Eforie', 'eforie', 44.058, 28.633),
+    ('constanta', 'Ovidiu', 'ovidiu', 44.270, 28.560),
+    ('constanta', 'Cernavodă', 'cernavoda', 44.338, 28.033),
+    ('constanta', 'Techirghiol', 'techirghiol', 44.056, 28.595),
+    ('constanta', 'Hârșova', 'harsova', 44.687, 27.952),
+    ('constanta', 'Murfatlar', 'murfatlar', 44.174, 28.408),
+    ('constanta', 'Negru Vodă', 'negru-voda', 43.818, 28.212),
+    ('covasna', 'Sfântu Gheorghe', 'sfantu-gheorghe', 45.867, 25.790),
+    ('covasna', 'Târgu Secuiesc', 'targu-secuiesc', 46.000, 26.133),
+    ('dambovita', 'Târgoviște', 'targoviste', 44.927, 25.456),
+    ('dambovita', 'Moreni', 'moreni', 44.981, 25.644),
+    ('dolj', 'Craiova', 'craiova', 44.317, 23.800),
+    ('dolj', 'Calafat', 'calafat', 43.991, 22.934),
+    ('dolj', 'Băilești', 'bailesti', 44.030, 23.350),
+    ('galati', 'Galați', 'galati', 45.436, 28.053),
+    ('galati', 'Tecuci', 'tecuci', 45.849, 27.434),
+    ('giurgiu', 'Giurgiu', 'giurgiu', 43.903, 25.969),
+    ('giurgiu', 'Bolintin-Vale', 'bolintin-vale', 44.448, 25.757),
+    ('giurgiu', 'Mihăilești', 'mihailesti', 44.327, 25.907),
+    ('gorj', 'Târgu Jiu', 'targu-jiu', 45.046, 23.274),
+    ('gorj', 'Motru', 'motru', 44.803, 22.971),
+    ('harghita', 'Miercurea Ciuc', 'miercurea-ciuc', 46.360, 25.801),
+    ('harghita', 'Odorheiu Secuiesc', 'odorheiu-secuiesc', 46.305, 25.302),
+    ('harghita', 'Gheorgheni', 'gheorgheni', 46.723, 25.601),
+    ('hunedoara', 'Deva', 'deva', 45.876, 22.913),
+    ('hunedoara', 'Hunedoara', 'hunedoara', 45.750, 22.900),
+    ('hunedoara', 'Petroșani', 'petrosani', 45.412, 23.373),
+    ('hunedoara', 'Orăștie', 'orastie', 45.839, 23.198),
+    ('hunedoara', 'Brad', 'brad', 46.129, 22.790),
+    ('hunedoara', 'Hațeg', 'hateg', 45.608, 22.950),
+    ('hunedoara', 'Simeria', 'simeria', 45.850, 23.010),
+    ('hunedoara', 'Vulcan', 'vulcan', 45.381, 23.292),
+    ('hunedoara', 'Lupeni', 'lupeni', 45.360, 23.238),
+    ('ialomita', 'Slobozia', 'slobozia', 44.563, 27.366),
+    ('ialomita', 'Urziceni', 'urziceni', 44.718, 26.641),
+    ('ialomita', 'Fetești', 'fetesti', 44.386, 27.833),
+    ('ialomita', 'Țăndărei', 'tandarei', 44.647, 27.660),
+    ('iasi', 'Iași', 'iasi', 47.158, 27.601),
+    ('iasi', 'Pașcani', 'pascani', 47.249, 26.722),
+    ('ilfov', 'Buftea', 'buftea', 44.563, 25.949),
+    ('ilfov', 'Voluntari', 'voluntari', 44.490, 26.187),
+    ('ilfov', 'Otopeni', 'otopeni', 44.550, 26.067),
+    ('ilfov', 'Pantelimon', 'pantelimon', 44.453, 26.203),
+    ('ilfov', 'Mogoșoaia', 'mogosoaia', 44.528, 26.000),
+    ('ilfov', 'Chitila', 'chitila', 44.508, 25.982),
+    ('ilfov', 'Popești-Leordeni', 'popesti-leordeni', 44.380, 26.170),
+    ('ilfov', 'Bragadiru', 'bragadiru', 44.371, 25.975),
+    ('ilfov', 'Măgurele', 'magurele', 44.349, 26.029),
+    ('ilfov', 'Tunari', 'tunari', 44.550, 26.140),
+    ('ilfov', 'Brănești', 'branesti', 44.450, 26.333),
+    ('maramures', 'Baia Mare', 'baia-mare', 47.659, 23.568),
+    ('maramures', 'Sighetu Marmației', 'sighetu-marmatiei', 47.928, 23.893),
+    ('mehedinti', 'Drobeta-Turnu Severin', 'drobeta-turnu-severin', 44.631, 22.656),
+    ('mehedinti', 'Orșova', 'orsova', 44.725, 22.396),
+    ('mures', 'Târgu Mureș', 'targu-mures', 46.542, 24.558),
+    ('mures', 'Sighișoara', 'sighisoara', 46.219, 24.791),
+    ('mures', 'Reghin', 'reghin', 46.776, 24.708),
+    ('neamt', 'Piatra Neamț', 'piatra-neamt', 46.927, 26.371),
+    ('neamt', 'Roman', 'roman', 46.921, 26.927),
+    ('olt', 'Slatina', 'slatina', 44.430, 24.364),
+    ('olt', 'Caracal', 'caracal', 44.112, 24.350),
+    ('prahova', 'Ploiești', 'ploiesti', 44.946, 26.013),
+    ('prahova', 'Câmpina', 'campina', 45.125, 25.733),
+    ('prahova', 'Sinaia', 'sinaia', 45.350, 25.551),
+    ('satu-mare', 'Satu Mare', 'satu-mare', 47.790, 22.885),
+    ('satu-mare', 'Carei', 'carei', 47.684, 22.467),
+    ('salaj', 'Zalău', 'zalau', 47.182, 23.057),
+    ('salaj', 'Șimleu Silvaniei', 'simleu-silvaniei', 47.228, 22.797),
+    ('sibiu', 'Sibiu', 'sibiu', 45.793, 24.121),
+    ('sibiu', 'Mediaș', 'medias', 46.164, 24.351),
+    ('suceava', 'Suceava', 'suceava', 47.651, 26.255),
+    ('suceava', 'Rădăuți', 'radauti', 47.842, 25.919),
+    ('suceava', 'Fălticeni', 'falticeni', 47.459, 26.300),
+    ('teleorman', 'Alexandria', 'alexandria', 43.970, 25.333),
+    ('teleorman', 'Roșiorii de Vede', 'rosiorii-de-vede', 44.112, 24.988),
+    ('teleorman', 'Turnu Măgurele', 'turnu-magurele', 43.752, 24.871),
+    ('teleorman', 'Zimnicea', 'zimnicea', 43.656, 25.366),
+    ('timis', 'Timișoara', 'timisoara', 45.754, 21.226),
+    ('timis', 'Lugoj', 'lugoj', 45.689, 21.903),
+    ('timis', 'Făget', 'faget', 45.850, 22.180),
+    ('timis', 'Giroc', 'giroc', 45.694, 21.236),
+    ('timis', 'Ghiroda', 'ghiroda', 45.764, 21.300),
+    ('timis', 'Moșnița Nouă', 'mosnita-noua', 45.717, 21.317),
+    ('timis', 'Sânandrei', 'sanandrei', 45.856, 21.169),
+    ('timis', 'Dumbrăvița', 'dumbravita', 45.800, 21.245),
+    ('timis', 'Jimbolia', 'jimbolia', 45.791, 20.717),
+    ('timis', 'Sânnicolau Mare', 'sannicolau-mare', 46.072, 20.629),
+    ('timis', 'Deta', 'deta', 45.389, 21.225),
+    ('timis', 'Buziaș', 'buzias', 45.649, 21.604),
+    ('timis', 'Recaș', 'recas', 45.797, 21.505),
+    ('tulcea', 'Tulcea', 'tulcea', 45.180, 28.805),
+    ('tulcea', 'Isaccea', 'isaccea', 45.270, 28.460),
+    ('tulcea', 'Măcin', 'macin', 45.244, 28.135),
+    ('tulcea', 'Babadag', 'babadag', 44.894, 28.714),
+    ('tulcea', 'Sulina', 'sulina', 45.156, 29.653),
+    ('tulcea', 'Mahmudia', 'mahmudia', 45.084, 29.084),
+    ('valcea', 'Râmnicu Vâlcea', 'ramnicu-valcea', 45.100, 24.369),
+    ('valcea', 'Drăgășani', 'dragasani', 44.661, 24.261),
+    ('vaslui', 'Vaslui', 'vaslui', 46.640, 27.729),
+    ('vaslui', 'Bârlad', 'barlad', 46.231, 27.669),
+    ('vaslui', 'Huși', 'husi', 46.674, 28.060),
+    ('vrancea', 'Focșani', 'focsani', 45.697, 27.186),
+    ('vrancea', 'Adjud', 'adjud', 46.100, 27.180);
+
+--rollback
+-- DELETE FROM localities;
+-- DELETE FROM counties;
@@ -0,0 +1,14 @@
+--liquibase formatted sql
+
+--changeset author:florin id:009
+--comment: Normalized county and location of each incident, matched against the gazetteer keys.
+
+ALTER TABLE incidents ADD COLUMN county_key VARCHAR(64);
+ALTER TABLE incidents ADD COLUMN location_key TEXT;
+
+CREATE INDEX incident_county_location_key ON incidents (county_key, location_key);
+
+--rollback
+-- DROP INDEX IF EXISTS incident_county_location_key;
+-- ALTER TABLE incidents DROP COLUMN location_key;
+-- ALTER TABLE incidents DROP COLUMN county_key;
@@ -0,0 +1,17 @@
+--liquibase formatted sql
+
+--changeset author:florin id:010
+--comment: Time window of the outage, revision bumped on every change and the moment the outage was withdrawn.
+
+ALTER TABLE incidents ADD COLUMN start_time TIME;
+ALTER TABLE incidents ADD COLUMN end_time TIME;
+ALTER TABLE incidents ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE incidents ADD COLUMN updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now();
+ALTER TABLE incidents ADD COLUMN cancelled_at TIMESTAMP WITH TIME ZONE;
+
+--rollback
+-- ALTER TABLE incidents DROP COLUMN cancelled_at;
+-- ALTER TABLE incidents DROP COLUMN updated_at;
+-- ALTER TABLE incidents DROP COLUMN revision;
+-- ALTER TABLE incidents DROP COLUMN end_time;
+-- ALTER TABLE incidents DROP COLUMN start_time;
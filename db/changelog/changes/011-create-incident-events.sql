@@ -0,0 +1,18 @@
+--liquibase formatted sql
+
+--changeset author:florin id:011
+--comment: Journal of the incidents created, updated or cancelled by the scraper, replayed to the stream clients.
+
+CREATE TABLE incident_events
+(
+    id          BIGSERIAL PRIMARY KEY,
+    incident_id BIGINT      NOT NULL REFERENCES incidents (id) ON DELETE CASCADE,
+    kind        VARCHAR(16) NOT NULL,
+    revision    INTEGER     NOT NULL,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX incident_event_incident ON incident_events (incident_id);
+
+--rollback
+-- DROP TABLE incident_events;
@@ -0,0 +1,11 @@
+--liquibase formatted sql
+
+--changeset author:florin id:012
+--comment: Prefix searches on the locality keys used by the autocomplete.
+
+CREATE INDEX locality_name_key_prefix ON localities (name_key text_pattern_ops);
+CREATE INDEX incident_location_key_prefix ON incidents (location_key text_pattern_ops);
+
+--rollback
+-- DROP INDEX IF EXISTS incident_location_key_prefix;
+-- DROP INDEX IF EXISTS locality_name_key_prefix;
@@ -0,0 +1,12 @@
+--liquibase formatted sql
+
+--changeset author:florin id:013
+--comment: Links the incidents republished by the provider under another id to the canonical incident.
+
+ALTER TABLE incidents ADD COLUMN duplicate_of BIGINT REFERENCES incidents (id) ON DELETE SET NULL;
+
+CREATE INDEX incident_duplicate_of ON incidents (duplicate_of) WHERE duplicate_of IS NOT NULL;
+
+--rollback
+-- DROP INDEX IF EXISTS incident_duplicate_of;
+-- ALTER TABLE incidents DROP COLUMN duplicate_of;
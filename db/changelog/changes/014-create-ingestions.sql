@@ -0,0 +1,18 @@
+--liquibase formatted sql
+
+--changeset author:florin id:014
+--comment: Feeds successfully stored by the scraper endpoint, tells how fresh the incidents are.
+
+CREATE TABLE ingestions
+(
+    id                BIGSERIAL PRIMARY KEY,
+    received_count    INTEGER NOT NULL,
+    stored_count      INTEGER NOT NULL,
+    cancelled_count   INTEGER NOT NULL,
+    completed_at      TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX ingestion_completed_at ON ingestions (completed_at);
+
+--rollback
+-- DROP TABLE ingestions;
@@ -0,0 +1,27 @@
+--liquibase formatted sql
+
+--changeset author:florin id:015
+--comment: Notification subscriptions, matching incidents by place, street and time of day, delivered through a channel.
+
+CREATE TABLE subscriptions
+(
+    id             BIGSERIAL PRIMARY KEY,
+    county_key     VARCHAR(64)  NOT NULL REFERENCES counties (key),
+    locality       VARCHAR(255),
+    locality_key   VARCHAR(255),
+    street_pattern VARCHAR(255),
+    window_start   TIME,
+    window_end     TIME,
+    channel        VARCHAR(16)  NOT NULL,
+    contact        VARCHAR(512) NOT NULL,
+    state          VARCHAR(16)  NOT NULL DEFAULT 'active',
+    created_at     TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    updated_at     TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    CONSTRAINT subscription_window CHECK ((window_start IS NULL) = (window_end IS NULL) AND window_start < window_end)
+);
+
+CREATE INDEX subscription_place ON subscriptions (county_key, locality_key) WHERE state = 'active';
+CREATE INDEX subscription_contact ON subscriptions (contact);
+
+--rollback
+-- DROP TABLE subscriptions;
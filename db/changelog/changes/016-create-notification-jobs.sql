@@ -0,0 +1,20 @@
+--liquibase formatted sql
+
+--changeset author:florin id:016
+--comment: Notifications owed to the subscriptions matching a revision of an incident, at most one per revision.
+
+CREATE TABLE notification_jobs
+(
+    id              BIGSERIAL PRIMARY KEY,
+    subscription_id BIGINT      NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    incident_id     BIGINT      NOT NULL REFERENCES incidents (id) ON DELETE CASCADE,
+    revision        INTEGER     NOT NULL,
+    reason          VARCHAR(16) NOT NULL,
+    created_at      TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX unique_notification_job ON notification_jobs (subscription_id, incident_id, revision);
+CREATE INDEX notification_job_incident ON notification_jobs (incident_id);
+
+--rollback
+-- DROP TABLE notification_jobs;
@@ -0,0 +1,11 @@
+--liquibase formatted sql
+
+--changeset author:florin id:017
+--comment: The secret signing the webhook deliveries and the count of consecutive failed deliveries of each subscription.
+
+ALTER TABLE subscriptions
+    ADD COLUMN signing_secret VARCHAR(128),
+    ADD COLUMN failure_count  INTEGER NOT NULL DEFAULT 0;
+
+--rollback
+-- ALTER TABLE subscriptions DROP COLUMN signing_secret, DROP COLUMN failure_count;
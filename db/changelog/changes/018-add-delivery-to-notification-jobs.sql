@@ -0,0 +1,18 @@
+--liquibase formatted sql
+
+--changeset author:florin id:018
+--comment: Delivery of the notification jobs: pending until delivered, skipped or dead-lettered, retried with backoff.
+
+ALTER TABLE notification_jobs
+    ADD COLUMN state           VARCHAR(16)              NOT NULL DEFAULT 'pending',
+    ADD COLUMN attempts        INTEGER                  NOT NULL DEFAULT 0,
+    ADD COLUMN next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    ADD COLUMN last_error      VARCHAR(512),
+    ADD COLUMN delivered_at    TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX notification_job_due ON notification_jobs (next_attempt_at) WHERE state = 'pending';
+
+--rollback
+-- DROP INDEX notification_job_due;
+-- ALTER TABLE notification_jobs DROP COLUMN state, DROP COLUMN attempts, DROP COLUMN next_attempt_at,
+--     DROP COLUMN last_error, DROP COLUMN delivered_at;
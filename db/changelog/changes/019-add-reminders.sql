@@ -0,0 +1,21 @@
+--liquibase formatted sql
+
+--changeset author:florin id:019
+--comment: Reminders before the outages: chosen per subscription, scheduled as notification jobs due at their time.
+
+ALTER TABLE subscriptions
+    ADD COLUMN reminders VARCHAR(32)[] NOT NULL DEFAULT '{}';
+
+ALTER TABLE notification_jobs
+    ADD COLUMN reminder VARCHAR(32);
+
+DROP INDEX unique_notification_job;
+CREATE UNIQUE INDEX unique_notification_job
+    ON notification_jobs (subscription_id, incident_id, revision, COALESCE(reminder, ''));
+
+--rollback
+-- DROP INDEX unique_notification_job;
+-- DELETE FROM notification_jobs WHERE reminder IS NOT NULL;
+-- CREATE UNIQUE INDEX unique_notification_job ON notification_jobs (subscription_id, incident_id, revision);
+-- ALTER TABLE notification_jobs DROP COLUMN reminder;
+-- ALTER TABLE subscriptions DROP COLUMN reminders;
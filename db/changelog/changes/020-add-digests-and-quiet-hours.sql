@@ -0,0 +1,26 @@
+--liquibase formatted sql
+
+--changeset author:florin id:020
+--comment: Daily and weekly digests per subscription, quiet hours per contact delaying the non-urgent notifications.
+
+ALTER TABLE subscriptions
+    ADD COLUMN digest      VARCHAR(16),
+    ADD COLUMN digest_time TIME,
+    ADD CONSTRAINT subscription_digest CHECK ((digest IS NULL) = (digest_time IS NULL));
+
+CREATE TABLE quiet_hours
+(
+    id         BIGSERIAL PRIMARY KEY,
+    channel    VARCHAR(16)  NOT NULL,
+    contact    VARCHAR(512) NOT NULL,
+    start_time TIME         NOT NULL,
+    end_time   TIME         NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    CONSTRAINT quiet_hours_window CHECK (start_time <> end_time)
+);
+
+CREATE UNIQUE INDEX unique_quiet_hours ON quiet_hours (channel, contact);
+
+--rollback
+-- DROP TABLE quiet_hours;
+-- ALTER TABLE subscriptions DROP CONSTRAINT subscription_digest, DROP COLUMN digest, DROP COLUMN digest_time;
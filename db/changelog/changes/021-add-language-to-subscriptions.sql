@@ -0,0 +1,10 @@
+--liquibase formatted sql
+
+--changeset author:florin id:021
+--comment: Language of the notifications of each subscription, the existing ones keep receiving English.
+
+ALTER TABLE subscriptions
+    ADD COLUMN language VARCHAR(2) NOT NULL DEFAULT 'en';
+
+--rollback
+-- ALTER TABLE subscriptions DROP COLUMN language;
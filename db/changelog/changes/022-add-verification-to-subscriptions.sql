@@ -0,0 +1,26 @@
+--liquibase formatted sql
+
+--changeset author:florin id:022
+--comment: Double opt-in: nothing is sent to a subscription before the code sent to its contact is confirmed.
+
+ALTER TABLE subscriptions
+    ADD COLUMN verification_code     VARCHAR(16),
+    ADD COLUMN verification_due_at   TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN verification_failures INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN verified_at           TIMESTAMP WITH TIME ZONE;
+
+-- The subscriptions created before the verification confirm their contact like the new ones: each gets a code, sent
+-- right away and valid for 24 hours from now, and receives no notification meanwhile. The six digits come from a
+-- random UUID, random() is not meant for secrets.
+UPDATE subscriptions
+SET verification_code   = lpad((('x' || substr(md5(gen_random_uuid()::text), 1, 8))::bit(32)::bigint % 1000000)::text,
+                               6, '0'),
+    verification_due_at = now(),
+    updated_at          = now();
+
+CREATE INDEX subscription_verification_due ON subscriptions (verification_due_at) WHERE verified_at IS NULL;
+
+--rollback
+-- DROP INDEX subscription_verification_due;
+-- ALTER TABLE subscriptions DROP COLUMN verification_code, DROP COLUMN verification_due_at,
+--     DROP COLUMN verification_failures, DROP COLUMN verified_at;
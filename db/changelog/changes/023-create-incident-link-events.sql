@@ -0,0 +1,15 @@
+--liquibase formatted sql
+
+--changeset author:florin id:023
+--comment: Journal of the duplicate links, which change the responses without changing the incidents.
+
+CREATE TABLE incident_link_events
+(
+    id           BIGSERIAL PRIMARY KEY,
+    incident_id  BIGINT                   NOT NULL REFERENCES incidents (id) ON DELETE CASCADE,
+    duplicate_of BIGINT,
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+--rollback
+-- DROP TABLE incident_link_events;
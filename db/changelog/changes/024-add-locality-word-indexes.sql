@@ -0,0 +1,12 @@
+--liquibase formatted sql
+
+--changeset author:florin id:024
+--comment: Searches of the beginning of a word anywhere in the locality keys used by the autocomplete.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX locality_name_key_trigram ON localities USING gin (name_key gin_trgm_ops);
+CREATE INDEX incident_location_key_trigram ON incidents USING gin (location_key gin_trgm_ops);
+
+--rollback
+-- DROP INDEX IF EXISTS incident_location_key_trigram;
+-- DROP INDEX IF EXISTS locality_name_key_trigram;
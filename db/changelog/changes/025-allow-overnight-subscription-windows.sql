@@ -0,0 +1,13 @@
+--liquibase formatted sql
+
+--changeset author:florin id:025
+--comment: Time windows of the subscriptions ending before they start run overnight, only empty windows are refused.
+
+ALTER TABLE subscriptions DROP CONSTRAINT subscription_window;
+ALTER TABLE subscriptions ADD CONSTRAINT subscription_window
+    CHECK ((window_start IS NULL) = (window_end IS NULL) AND window_start <> window_end);
+
+--rollback
+-- ALTER TABLE subscriptions DROP CONSTRAINT subscription_window;
+-- ALTER TABLE subscriptions ADD CONSTRAINT subscription_window
+--     CHECK ((window_start IS NULL) = (window_end IS NULL) AND window_start < window_end);
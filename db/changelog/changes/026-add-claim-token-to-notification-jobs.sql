@@ -0,0 +1,13 @@
+--liquibase formatted sql
+
+--changeset author:florin id:026
+--comment: Each claim of the notification jobs is tagged, only the dispatcher holding the claim records their outcome.
+
+ALTER TABLE notification_jobs ADD COLUMN claim_token VARCHAR(32);
+
+-- The lease is renewed and the outcome recorded by the token, for every delivery.
+CREATE INDEX notification_job_claim ON notification_jobs (claim_token) WHERE claim_token IS NOT NULL;
+
+--rollback
+-- DROP INDEX notification_job_claim;
+-- ALTER TABLE notification_jobs DROP COLUMN claim_token;
@@ -0,0 +1,13 @@
+--liquibase formatted sql
+
+--changeset author:florin id:027
+--comment: The events are streamed in the order their transactions started, the ids are taken before the commit.
+
+-- The events stored before are taken as stored by this transaction, they are ordered by id between them.
+ALTER TABLE incident_events ADD COLUMN xact_id BIGINT NOT NULL DEFAULT txid_current();
+
+CREATE INDEX incident_event_position ON incident_events (xact_id, id);
+
+--rollback
+-- DROP INDEX incident_event_position;
+-- ALTER TABLE incident_events DROP COLUMN xact_id;
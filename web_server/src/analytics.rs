@@ -0,0 +1,431 @@
+use crate::AppState;
+use crate::caching::CachedParameters;
+use crate::error::{ApiError, ProblemDetails};
+use crate::extract::Query;
+use crate::web_api::SortDirection;
+use axum::Json;
+use axum::extract::State;
+use chrono::{Days, NaiveDate, NaiveTime, TimeDelta, Utc};
+use common::normalization::normalize_county;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, QueryBuilder};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::Deref;
+use utoipa::{IntoParams, ToSchema};
+
+const DEFAULT_ROWS: usize = 50;
+const MAX_ROWS: usize = 100;
+
+/// Length of the window when only its end, or nothing, is given.
+const DEFAULT_WINDOW_DAYS: u64 = 365;
+
+/// Cancelled incidents did not cut anybody off and duplicates repeat another outage, so they are left out. The names
+/// come from the gazetteer and are made out of the keys for the places missing from it.
+const OUTAGES_SELECT: &str = "SELECT i.county_key, \
+ COALESCE(c.name, initcap(replace(i.county_key, '-', ' '))) AS county_name, \
+ NULLIF(i.location_key, '') AS location_key, \
+ COALESCE(l.name, initcap(replace(NULLIF(i.location_key, ''), '-', ' '))) AS locality_name, \
+ i.day, i.start_time, i.end_time \
+ FROM incidents i \
+ LEFT JOIN counties c ON c.key = i.county_key \
+ LEFT JOIN localities l ON l.county_key = i.county_key AND l.name_key = i.location_key \
+ WHERE i.cancelled_at IS NULL AND i.duplicate_of IS NULL AND i.county_key IS NOT NULL";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsGrouping {
+    #[default]
+    Locality,
+    County,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsSort {
+    #[default]
+    OutageCount,
+    TotalDuration,
+    AverageDuration,
+    LongestStreak,
+    MedianInterval,
+}
+
+#[derive(Deserialize, IntoParams, Default)]
+pub struct OutageAnalyticsParams {
+    /// Aggregation level, `locality` by default.
+    pub group_by: Option<AnalyticsGrouping>,
+    /// County name or key, matched regardless of diacritics and case.
+    pub county: Option<String>,
+    /// First day of the window, a year before `to` by default.
+    #[param(value_type = Option<String>, format = Date)]
+    pub from: Option<NaiveDate>,
+    /// Last day of the window, today by default.
+    #[param(value_type = Option<String>, format = Date)]
+    pub to: Option<NaiveDate>,
+    /// Sort key, `outage_count` by default.
+    pub sort: Option<AnalyticsSort>,
+    /// Sort direction, descending by default, which gives the worst affected places first.
+    pub direction: Option<SortDirection>,
+    /// Maximum number of rows returned, 50 by default and at most 100.
+    pub count: Option<usize>,
+}
+
+impl OutageAnalyticsParams {
+    /// The first and the last day of the window and the number of rows returned.
+    fn window(&self) -> Result<(NaiveDate, NaiveDate, usize), ApiError> {
+        let to = self.to.unwrap_or_else(|| Utc::now().date_naive());
+        let from = self.from.unwrap_or(to - Days::new(DEFAULT_WINDOW_DAYS));
+        if from > to {
+            return Err(ApiError::InvalidParameter(String::from(
+                "`from` must not be after `to`.",
+            )));
+        }
+        let count = self.count.unwrap_or(DEFAULT_ROWS);
+        if count > MAX_ROWS {
+            return Err(ApiError::InvalidParameter(format!(
+                "`count` must not be more than {}.",
+                MAX_ROWS
+            )));
+        }
+        Ok((from, to, count))
+    }
+}
+
+impl CachedParameters for Query<OutageAnalyticsParams> {
+    fn check(&self) -> Result<(), ApiError> {
+        self.window().map(|_| ())
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OutageAnalyticsResponse {
+    #[schema(value_type = String, format = Date)]
+    pub from: NaiveDate,
+    #[schema(value_type = String, format = Date)]
+    pub to: NaiveDate,
+    pub statistics: Vec<OutageStatistics>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct OutageStatistics {
+    #[schema(example = "timis")]
+    pub county_key: String,
+    #[schema(example = "Timiș")]
+    pub county: String,
+    /// Only set when grouping by locality.
+    #[schema(example = "timisoara")]
+    pub locality_key: Option<String>,
+    #[schema(example = "Timișoara")]
+    pub locality: Option<String>,
+    pub outage_count: u64,
+    /// Sum of the durations of the incidents announced with a start and an end time.
+    pub total_duration_minutes: i64,
+    /// Average over the incidents announced with a start and an end time, if any.
+    pub average_duration_minutes: Option<f64>,
+    /// Largest number of consecutive days with at least one outage.
+    pub longest_streak_days: u32,
+    /// Median number of days between two consecutive days with outages, if there were at least two of them.
+    pub median_interval_days: Option<f64>,
+}
+
+#[derive(Debug, FromRow)]
+struct OutageRow {
+    county_key: String,
+    county_name: String,
+    location_key: Option<String>,
+    locality_name: Option<String>,
+    day: NaiveDate,
+    start_time: Option<NaiveTime>,
+    end_time: Option<NaiveTime>,
+}
+
+impl OutageRow {
+    /// Outages ending before their start end on the next day, as in the calendar. The ones ending when they start are
+    /// taken as announced without a time window.
+    fn duration_minutes(&self) -> Option<i64> {
+        match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) if end > start => Some((end - start).num_minutes()),
+            (Some(start), Some(end)) if end < start => Some((end - start + TimeDelta::days(1)).num_minutes()),
+            _ => None,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/analytics/outages",
+    params(
+        OutageAnalyticsParams
+    ),
+    responses(
+        (status=200, description = "Recurrence of the outages per locality or county over the window.", body=OutageAnalyticsResponse),
+        (status=400, description = "The window ends before it starts or too many rows are requested.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error reading the incidents.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_outage_analytics(
+    state: State<AppState>,
+    params: Query<OutageAnalyticsParams>,
+) -> Result<Json<OutageAnalyticsResponse>, ApiError> {
+    let (from, to, count) = params.window()?;
+
+    let mut query_builder = QueryBuilder::new(OUTAGES_SELECT);
+    query_builder
+        .push(" AND i.day BETWEEN ")
+        .push_bind(from)
+        .push(" AND ")
+        .push_bind(to);
+    if let Some(county) = &params.county {
+        query_builder
+            .push(" AND i.county_key = ")
+            .push_bind(normalize_county(county));
+    }
+    query_builder.push(" ORDER BY i.day");
+
+    let rows: Vec<OutageRow> = query_builder.build_query_as().fetch_all(state.pg_pool.deref()).await?;
+
+    let mut statistics = outage_statistics(rows, params.group_by.unwrap_or_default());
+    sort_statistics(
+        &mut statistics,
+        params.sort.unwrap_or_default(),
+        params.direction.unwrap_or_default(),
+    );
+    statistics.truncate(count);
+
+    Ok(Json(OutageAnalyticsResponse { from, to, statistics }))
+}
+
+/// Outages of a place being aggregated.
+struct OutageGroup {
+    statistics: OutageStatistics,
+    /// Distinct days with outages, in order.
+    days: Vec<NaiveDate>,
+    /// Number of outages announced with a start and an end time.
+    timed_outages: u64,
+}
+
+/// Aggregates the outages, which have to be ordered by day. Incidents without a locality only count for their county.
+fn outage_statistics(rows: Vec<OutageRow>, grouping: AnalyticsGrouping) -> Vec<OutageStatistics> {
+    let mut groups: BTreeMap<(String, Option<String>), OutageGroup> = BTreeMap::new();
+
+    for row in rows {
+        let (locality_key, locality) = match grouping {
+            AnalyticsGrouping::County => (None, None),
+            AnalyticsGrouping::Locality if row.location_key.is_none() => continue,
+            AnalyticsGrouping::Locality => (row.location_key.clone(), row.locality_name.clone()),
+        };
+        let duration = row.duration_minutes();
+
+        let group = groups
+            .entry((row.county_key.clone(), locality_key.clone()))
+            .or_insert_with(|| OutageGroup {
+                statistics: OutageStatistics {
+                    county_key: row.county_key.clone(),
+                    county: row.county_name.clone(),
+                    locality_key,
+                    locality,
+                    outage_count: 0,
+                    total_duration_minutes: 0,
+                    average_duration_minutes: None,
+                    longest_streak_days: 0,
+                    median_interval_days: None,
+                },
+                days: Vec::new(),
+                timed_outages: 0,
+            });
+
+        group.statistics.outage_count += 1;
+        if let Some(duration) = duration {
+            group.statistics.total_duration_minutes += duration;
+            group.timed_outages += 1;
+        }
+        if group.days.last() != Some(&row.day) {
+            group.days.push(row.day);
+        }
+    }
+
+    groups
+        .into_values()
+        .map(|group| {
+            let mut statistics = group.statistics;
+            if group.timed_outages > 0 {
+                statistics.average_duration_minutes =
+                    Some(statistics.total_duration_minutes as f64 / group.timed_outages as f64);
+            }
+            statistics.longest_streak_days = longest_streak(&group.days);
+            statistics.median_interval_days = median_interval(&group.days);
+            statistics
+        })
+        .collect()
+}
+
+/// Longest run of consecutive days among distinct, ordered days.
+fn longest_streak(days: &[NaiveDate]) -> u32 {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous: Option<NaiveDate> = None;
+
+    for day in days {
+        current = match previous {
+            Some(previous) if previous.succ_opt() == Some(*day) => current + 1,
+            _ => 1,
+        };
+        longest = longest.max(current);
+        previous = Some(*day);
+    }
+    longest
+}
+
+/// Median of the gaps, in days, between distinct, ordered days.
+fn median_interval(days: &[NaiveDate]) -> Option<f64> {
+    let mut intervals: Vec<i64> = days.windows(2).map(|pair| (pair[1] - pair[0]).num_days()).collect();
+    if intervals.is_empty() {
+        return None;
+    }
+
+    intervals.sort_unstable();
+    let middle = intervals.len() / 2;
+    Some(if intervals.len().is_multiple_of(2) {
+        (intervals[middle - 1] + intervals[middle]) as f64 / 2.0
+    } else {
+        intervals[middle] as f64
+    })
+}
+
+/// Sorts by the requested key, the places without a value for it last, then by county and locality.
+fn sort_statistics(statistics: &mut [OutageStatistics], sort: AnalyticsSort, direction: SortDirection) {
+    statistics.sort_by(|left, right| {
+        let ordering = match (sort_value(left, sort), sort_value(right, sort)) {
+            (Some(left), Some(right)) => {
+                let ordering = left.total_cmp(&right);
+                match direction {
+                    SortDirection::Asc => ordering,
+                    SortDirection::Desc => ordering.reverse(),
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+
+        ordering
+            .then_with(|| left.county_key.cmp(&right.county_key))
+            .then_with(|| left.locality_key.cmp(&right.locality_key))
+    });
+}
+
+fn sort_value(statistics: &OutageStatistics, sort: AnalyticsSort) -> Option<f64> {
+    match sort {
+        AnalyticsSort::OutageCount => Some(statistics.outage_count as f64),
+        AnalyticsSort::TotalDuration => Some(statistics.total_duration_minutes as f64),
+        AnalyticsSort::AverageDuration => statistics.average_duration_minutes,
+        AnalyticsSort::LongestStreak => Some(statistics.longest_streak_days as f64),
+        AnalyticsSort::MedianInterval => statistics.median_interval_days,
+    }
+}
+
+#[cfg(test)]
+mod analytics_tests {
+    use super::{
+        AnalyticsGrouping, AnalyticsSort, OutageRow, longest_streak, median_interval, outage_statistics,
+        sort_statistics,
+    };
+    use crate::web_api::SortDirection;
+    use chrono::{NaiveDate, NaiveTime};
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+    }
+
+    fn row(location_key: Option<&str>, day_of_month: u32, hours: Option<(u32, u32)>) -> OutageRow {
+        OutageRow {
+            county_key: "timis".to_string(),
+            county_name: "Timiș".to_string(),
+            location_key: location_key.map(str::to_string),
+            locality_name: location_key.map(str::to_uppercase),
+            day: day(day_of_month),
+            start_time: hours.map(|(start, _)| NaiveTime::from_hms_opt(start, 0, 0).unwrap()),
+            end_time: hours.map(|(_, end)| NaiveTime::from_hms_opt(end, 0, 0).unwrap()),
+        }
+    }
+
+    #[test]
+    fn streaks_of_consecutive_days() {
+        assert_eq!(0, longest_streak(&[]));
+        assert_eq!(1, longest_streak(&[day(1), day(3)]));
+        assert_eq!(3, longest_streak(&[day(1), day(2), day(5), day(6), day(7), day(9)]));
+    }
+
+    #[test]
+    fn median_of_the_intervals() {
+        assert_eq!(None, median_interval(&[day(1)]));
+        assert_eq!(Some(2.0), median_interval(&[day(1), day(3), day(4), day(10)]));
+        assert_eq!(Some(4.5), median_interval(&[day(1), day(3), day(10)]));
+    }
+
+    #[test]
+    fn statistics_per_locality() {
+        let rows = vec![
+            row(Some("giroc"), 1, Some((9, 13))),
+            row(Some("giroc"), 1, Some((14, 16))),
+            row(Some("giroc"), 2, None),
+            row(Some("giroc"), 6, Some((17, 9))),
+            row(Some("lugoj"), 4, Some((8, 10))),
+            row(None, 5, Some((8, 10))),
+        ];
+
+        let statistics = outage_statistics(rows, AnalyticsGrouping::Locality);
+
+        assert_eq!(2, statistics.len());
+        let giroc = &statistics[0];
+        assert_eq!(Some("giroc".to_string()), giroc.locality_key);
+        assert_eq!(4, giroc.outage_count);
+        assert_eq!(1320, giroc.total_duration_minutes);
+        assert_eq!(Some(440.0), giroc.average_duration_minutes);
+        assert_eq!(2, giroc.longest_streak_days);
+        assert_eq!(Some(2.5), giroc.median_interval_days);
+        assert_eq!(None, statistics[1].median_interval_days);
+    }
+
+    #[test]
+    fn statistics_per_county_include_unknown_localities() {
+        let rows = vec![
+            row(Some("giroc"), 1, Some((9, 13))),
+            row(Some("lugoj"), 2, Some((8, 10))),
+            row(None, 3, None),
+        ];
+
+        let statistics = outage_statistics(rows, AnalyticsGrouping::County);
+
+        assert_eq!(1, statistics.len());
+        assert_eq!(None, statistics[0].locality_key);
+        assert_eq!(3, statistics[0].outage_count);
+        assert_eq!(3, statistics[0].longest_streak_days);
+        assert_eq!(Some(180.0), statistics[0].average_duration_minutes);
+    }
+
+    #[test]
+    fn missing_values_sort_last() {
+        let rows = vec![
+            row(Some("giroc"), 1, None),
+            row(Some("lugoj"), 1, Some((8, 10))),
+            row(Some("lugoj"), 9, Some((8, 10))),
+            row(Some("recas"), 1, Some((8, 12))),
+        ];
+        let mut statistics = outage_statistics(rows, AnalyticsGrouping::Locality);
+
+        let localities = |statistics: &[super::OutageStatistics]| -> Vec<String> {
+            statistics.iter().map(|s| s.locality_key.clone().unwrap()).collect()
+        };
+
+        sort_statistics(&mut statistics, AnalyticsSort::AverageDuration, SortDirection::Asc);
+        assert_eq!(vec!["lugoj", "recas", "giroc"], localities(&statistics));
+
+        sort_statistics(&mut statistics, AnalyticsSort::AverageDuration, SortDirection::Desc);
+        assert_eq!(vec!["recas", "lugoj", "giroc"], localities(&statistics));
+
+        sort_statistics(&mut statistics, AnalyticsSort::OutageCount, SortDirection::Desc);
+        assert_eq!(vec!["lugoj", "giroc", "recas"], localities(&statistics));
+    }
+}
@@ -0,0 +1,211 @@
+use crate::AppState;
+use crate::error::ApiError;
+use axum::extract::{FromRequestParts, Request, State};
+use axum::http::header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use log::error;
+use sqlx::{Error, FromRow, Pool, Postgres};
+
+/// The data only changes when the scraper runs, so clients may reuse a response for a minute and then have to
+/// revalidate it, which is cheap thanks to the ETag.
+const CACHE_CONTROL_VALUE: &str = "public, max-age=60, must-revalidate";
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Every change of the incidents made by the scraper and of their duplicate links is journaled, so the last journal
+/// entries identify the version of the data. The calendar and the address lookup select the incidents relative to the
+/// current day, so the version changes every day as well. The last entries are looked up by their primary key, the
+/// query running for every request.
+const DATA_VERSION_QUERY: &str = "SELECT (SELECT COALESCE(MAX(id), 0) FROM incident_events) AS version, \
+ (SELECT COALESCE(MAX(id), 0) FROM incident_link_events) AS links_version, \
+ GREATEST((SELECT created_at FROM incident_events ORDER BY id DESC LIMIT 1), \
+ (SELECT created_at FROM incident_link_events ORDER BY id DESC LIMIT 1)) AS modified_at";
+
+#[derive(Debug, FromRow)]
+struct JournalPosition {
+    version: i64,
+    links_version: i64,
+    modified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataVersion {
+    pub version: i64,
+    pub links_version: i64,
+    pub day: NaiveDate,
+    /// The moment of the last change, but not earlier than the start of `day`.
+    pub modified_at: DateTime<Utc>,
+}
+
+impl DataVersion {
+    pub fn etag(&self) -> String {
+        format!(
+            "W/\"incidents-{}-{}-{}\"",
+            self.version,
+            self.links_version,
+            self.day.format("%Y%m%d")
+        )
+    }
+
+    pub fn last_modified(&self) -> String {
+        self.modified_at.format(HTTP_DATE_FORMAT).to_string()
+    }
+
+    /// Checks the conditional headers of a request. `If-None-Match` takes precedence over `If-Modified-Since`.
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+            let etag = self.etag();
+            return if_none_match.to_str().is_ok_and(|tags| {
+                tags.split(',')
+                    .map(str::trim)
+                    .any(|tag| tag == "*" || weak_tag(tag) == weak_tag(&etag))
+            });
+        }
+
+        let if_modified_since = headers
+            .get(IF_MODIFIED_SINCE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+
+        // HTTP dates have no fractional seconds.
+        if_modified_since.is_some_and(|since| self.modified_at.timestamp() <= since.timestamp())
+    }
+
+    fn apply_headers(&self, headers: &mut HeaderMap) {
+        if let Ok(etag) = HeaderValue::from_str(&self.etag()) {
+            headers.insert(ETAG, etag);
+        }
+        if let Ok(last_modified) = HeaderValue::from_str(&self.last_modified()) {
+            headers.insert(LAST_MODIFIED, last_modified);
+        }
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL_VALUE));
+    }
+}
+
+/// ETags are compared with the weak comparison, as required for `If-None-Match`.
+fn weak_tag(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+pub async fn data_version(pg_pool: &Pool<Postgres>) -> Result<DataVersion, Error> {
+    let position: JournalPosition = sqlx::query_as(DATA_VERSION_QUERY).fetch_one(pg_pool).await?;
+    let day = Utc::now().date_naive();
+    let start_of_day = day.and_time(NaiveTime::MIN).and_utc();
+
+    Ok(DataVersion {
+        version: position.version,
+        links_version: position.links_version,
+        day,
+        modified_at: position
+            .modified_at
+            .map_or(start_of_day, |modified_at| modified_at.max(start_of_day)),
+    })
+}
+
+/// The parameters of an endpoint reading the incidents, as its handler extracts them. They are checked before answering
+/// a 304, so that the invalid requests get their error without the handler reading the data.
+pub trait CachedParameters: FromRequestParts<AppState> + Send {
+    /// What the extractors do not check, short of reading the data.
+    fn check(&self) -> Result<(), ApiError> {
+        Ok(())
+    }
+}
+
+impl CachedParameters for () {}
+
+/// Answers `304 Not Modified` to the valid conditional requests for data which did not change, before the handler
+/// runs, and adds the validators and the `Cache-Control` header to the successful responses. Only meant for the
+/// endpoints reading the incidents, layered on each of them with the parameters of its handler.
+///
+/// The version is read before the handler runs, a change made meanwhile is then served again on the next request.
+pub async fn conditional_get<P: CachedParameters>(state: State<AppState>, request: Request, next: Next) -> Response {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return next.run(request).await;
+    }
+
+    let version = match data_version(&state.pg_pool).await {
+        Ok(version) => version,
+        Err(err) => {
+            error!("Could not read the data version, serving without validators: {}", err);
+            return next.run(request).await;
+        }
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let valid = P::from_request_parts(&mut parts, &state)
+        .await
+        .is_ok_and(|parameters| parameters.check().is_ok());
+    let request = Request::from_parts(parts, body);
+    if valid && version.is_not_modified(request.headers()) {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        version.apply_headers(response.headers_mut());
+        return response;
+    }
+
+    let mut response = next.run(request).await;
+    if response.status() == StatusCode::OK {
+        version.apply_headers(response.headers_mut());
+    }
+    response
+}
+
+#[cfg(test)]
+mod caching_tests {
+    use super::DataVersion;
+    use axum::http::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
+    use axum::http::{HeaderMap, HeaderValue};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    fn version() -> DataVersion {
+        DataVersion {
+            version: 42,
+            links_version: 7,
+            day: NaiveDate::from_ymd_opt(2025, 7, 31).unwrap(),
+            modified_at: Utc.with_ymd_and_hms(2025, 7, 31, 13, 20, 53).unwrap(),
+        }
+    }
+
+    fn headers(name: axum::http::HeaderName, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn validators_format() {
+        assert_eq!("W/\"incidents-42-7-20250731\"", version().etag());
+        assert_eq!("Thu, 31 Jul 2025 13:20:53 GMT", version().last_modified());
+    }
+
+    #[test]
+    fn if_none_match_weak_comparison() {
+        assert!(version().is_not_modified(&headers(IF_NONE_MATCH, "\"incidents-42-7-20250731\"")));
+        assert!(version().is_not_modified(&headers(
+            IF_NONE_MATCH,
+            "W/\"incidents-41-7-20250731\", W/\"incidents-42-7-20250731\""
+        )));
+        assert!(version().is_not_modified(&headers(IF_NONE_MATCH, "*")));
+        assert!(!version().is_not_modified(&headers(IF_NONE_MATCH, "W/\"incidents-42-7-20250730\"")));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence() {
+        let mut headers = headers(IF_NONE_MATCH, "W/\"incidents-42-7-20250730\"");
+        headers.insert(
+            IF_MODIFIED_SINCE,
+            HeaderValue::from_static("Thu, 31 Jul 2025 13:20:53 GMT"),
+        );
+
+        assert!(!version().is_not_modified(&headers));
+    }
+
+    #[test]
+    fn if_modified_since() {
+        assert!(version().is_not_modified(&headers(IF_MODIFIED_SINCE, "Thu, 31 Jul 2025 13:20:53 GMT")));
+        assert!(!version().is_not_modified(&headers(IF_MODIFIED_SINCE, "Thu, 31 Jul 2025 13:20:52 GMT")));
+        assert!(!version().is_not_modified(&headers(IF_MODIFIED_SINCE, "yesterday")));
+    }
+}
@@ -0,0 +1,306 @@
+use crate::AppState;
+use crate::caching::CachedParameters;
+use crate::error::{ApiError, ProblemDetails};
+use crate::extract::Query;
+use crate::web_api::Incident;
+use axum::extract::State;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::IntoResponse;
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, Utc};
+use common::normalization::{normalize_county, normalize_locality};
+use serde::Deserialize;
+use sqlx::QueryBuilder;
+use std::ops::Deref;
+use utoipa::IntoParams;
+
+const CALENDAR_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+/// How far back the feed goes, so a subscription still shows the outages of the last weeks.
+const CALENDAR_PAST_DAYS: u64 = 30;
+
+const CALENDAR_TIMEZONE: &str = "Europe/Bucharest";
+
+/// The times announced by the provider are local times. The definition of the time zone is embedded so calendar
+/// applications do not need to know about `Europe/Bucharest`.
+const VTIMEZONE: [&str; 17] = [
+    "BEGIN:VTIMEZONE",
+    "TZID:Europe/Bucharest",
+    "BEGIN:STANDARD",
+    "DTSTART:19701025T040000",
+    "TZOFFSETFROM:+0300",
+    "TZOFFSETTO:+0200",
+    "TZNAME:EET",
+    "RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU",
+    "END:STANDARD",
+    "BEGIN:DAYLIGHT",
+    "DTSTART:19700329T030000",
+    "TZOFFSETFROM:+0200",
+    "TZOFFSETTO:+0300",
+    "TZNAME:EEST",
+    "RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU",
+    "END:DAYLIGHT",
+    "END:VTIMEZONE",
+];
+
+/// Content lines longer than this many octets have to be folded.
+const MAX_LINE_OCTETS: usize = 75;
+
+#[derive(Deserialize, IntoParams, Default)]
+pub struct CalendarFiltering {
+    /// County name, matched regardless of diacritics and case (e.g. `Timiș`).
+    pub county: Option<String>,
+    /// Locality name, matched regardless of diacritics and case (e.g. `Timișoara`).
+    pub locality: Option<String>,
+}
+
+impl CachedParameters for Query<CalendarFiltering> {}
+
+#[utoipa::path(
+    get,
+    path = "/api/calendar.ics",
+    params(
+        CalendarFiltering
+    ),
+    responses(
+        (status=200, description = "iCalendar feed with an event for each matching incident.", body=String, content_type = "text/calendar"),
+        (status=500, description = "Error getting the incidents.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_calendar(
+    state: State<AppState>,
+    filtering: Query<CalendarFiltering>,
+) -> Result<impl IntoResponse, ApiError> {
+    let since = Utc::now().date_naive() - Days::new(CALENDAR_PAST_DAYS);
+
+    let mut query_builder = QueryBuilder::new("SELECT * FROM incidents WHERE duplicate_of IS NULL AND day >= ");
+    query_builder.push_bind(since);
+
+    if let Some(county) = &filtering.county {
+        query_builder
+            .push(" AND county_key = ")
+            .push_bind(normalize_county(county));
+    }
+    if let Some(locality) = &filtering.locality {
+        query_builder
+            .push(" AND location_key = ")
+            .push_bind(normalize_locality(locality));
+    }
+    query_builder.push(" ORDER BY day, start_time NULLS FIRST, id");
+
+    let incidents: Vec<Incident> = query_builder.build_query_as().fetch_all(state.pg_pool.deref()).await?;
+
+    let name = calendar_name(&filtering);
+    Ok((
+        [
+            (CONTENT_TYPE, CALENDAR_CONTENT_TYPE),
+            (CONTENT_DISPOSITION, "inline; filename=\"outages.ics\""),
+        ],
+        render_calendar(&name, &incidents),
+    ))
+}
+
+fn calendar_name(filtering: &CalendarFiltering) -> String {
+    let places: Vec<&str> = [&filtering.locality, &filtering.county]
+        .into_iter()
+        .filter_map(|place| place.as_deref())
+        .collect();
+
+    if places.is_empty() {
+        "Planned outages".to_string()
+    } else {
+        format!("Planned outages - {}", places.join(", "))
+    }
+}
+
+pub fn render_calendar(name: &str, incidents: &[Incident]) -> String {
+    let mut lines: Vec<String> = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//enel-stop//Planned outages//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+        format!("X-WR-TIMEZONE:{}", CALENDAR_TIMEZONE),
+    ];
+    lines.extend(VTIMEZONE.iter().map(|line| line.to_string()));
+
+    for incident in incidents {
+        lines.extend(render_event(incident));
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+fn render_event(incident: &Incident) -> Vec<String> {
+    let mut lines = vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:incident-{}@enel-stop", incident.id),
+        format!("DTSTAMP:{}", format_utc(incident.updated_at)),
+        format!("LAST-MODIFIED:{}", format_utc(incident.updated_at)),
+        // Calendar applications only replace an event when its sequence increases.
+        format!("SEQUENCE:{}", incident.revision - 1),
+    ];
+
+    match (incident.start_time, incident.end_time) {
+        (Some(start_time), Some(end_time)) => {
+            let start = incident.day.and_time(start_time);
+            let mut end = incident.day.and_time(end_time);
+            if end <= start {
+                end = end + Days::new(1);
+            }
+            lines.push(format!("DTSTART;TZID={}:{}", CALENDAR_TIMEZONE, format_local(start)));
+            lines.push(format!("DTEND;TZID={}:{}", CALENDAR_TIMEZONE, format_local(end)));
+        }
+        _ => {
+            lines.push(format!("DTSTART;VALUE=DATE:{}", format_date(incident.day)));
+            lines.push(format!("DTEND;VALUE=DATE:{}", format_date(incident.day + Days::new(1))));
+        }
+    }
+
+    lines.push(format!(
+        "SUMMARY:{}",
+        escape_text(&format!("Planned outage: {} ({})", incident.location, incident.county))
+    ));
+    lines.push(format!(
+        "LOCATION:{}",
+        escape_text(&format!("{}, {}", incident.location, incident.county))
+    ));
+    lines.push(format!("DESCRIPTION:{}", escape_text(incident.description.trim())));
+
+    let status = if incident.cancelled_at.is_some() {
+        "CANCELLED"
+    } else {
+        "CONFIRMED"
+    };
+    lines.push(format!("STATUS:{}", status));
+    lines.push("END:VEVENT".to_string());
+
+    lines
+}
+
+fn format_utc(moment: DateTime<Utc>) -> String {
+    moment.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn format_local(moment: NaiveDateTime) -> String {
+    moment.format("%Y%m%dT%H%M%S").to_string()
+}
+
+fn format_date(day: NaiveDate) -> String {
+    day.format("%Y%m%d").to_string()
+}
+
+/// Escapes the characters with a special meaning in iCalendar text values.
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Splits a content line in chunks of at most 75 octets, without breaking UTF-8 characters, and terminates it with
+/// CRLF. Continuation lines start with a space.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut line_octets = 0;
+
+    for c in line.chars() {
+        if line_octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            line_octets = 1;
+        }
+        folded.push(c);
+        line_octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+
+    folded
+}
+
+#[cfg(test)]
+mod calendar_tests {
+    use super::{escape_text, fold_line, render_calendar};
+    use crate::web_api::Incident;
+    use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+
+    fn incident() -> Incident {
+        Incident {
+            external_id: "134691 - Retele Electrice".to_string(),
+            county: "TULCEA".to_string(),
+            location: "LOC. TURCOAIA".to_string(),
+            day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+            start_time: NaiveTime::from_hms_opt(9, 0, 0),
+            end_time: NaiveTime::from_hms_opt(17, 0, 0),
+            description: "Strada: -  SC URANUS; CARIERA\n Numar: ".to_string(),
+            id: 42,
+            revision: 3,
+            updated_at: Utc.with_ymd_and_hms(2025, 7, 31, 13, 20, 53).unwrap(),
+            cancelled_at: None,
+            duplicate_of: None,
+        }
+    }
+
+    #[test]
+    fn event_with_time_window() {
+        let calendar = render_calendar("Planned outages", &[incident()]);
+
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        assert!(calendar.contains("\r\nUID:incident-42@enel-stop\r\n"));
+        assert!(calendar.contains("\r\nDTSTAMP:20250731T132053Z\r\n"));
+        assert!(calendar.contains("\r\nSEQUENCE:2\r\n"));
+        assert!(calendar.contains("\r\nDTSTART;TZID=Europe/Bucharest:20250808T090000\r\n"));
+        assert!(calendar.contains("\r\nDTEND;TZID=Europe/Bucharest:20250808T170000\r\n"));
+        assert!(calendar.contains("\r\nDESCRIPTION:Strada: -  SC URANUS\\; CARIERA\\n Numar:\r\n"));
+        assert!(calendar.contains("\r\nSTATUS:CONFIRMED\r\n"));
+    }
+
+    #[test]
+    fn cancelled_all_day_event() {
+        let mut incident = incident();
+        incident.start_time = None;
+        incident.cancelled_at = Some(Utc::now());
+
+        let calendar = render_calendar("Planned outages", &[incident]);
+
+        assert!(calendar.contains("\r\nDTSTART;VALUE=DATE:20250808\r\n"));
+        assert!(calendar.contains("\r\nDTEND;VALUE=DATE:20250809\r\n"));
+        assert!(calendar.contains("\r\nSTATUS:CANCELLED\r\n"));
+    }
+
+    #[test]
+    fn window_over_midnight_ends_next_day() {
+        let mut incident = incident();
+        incident.start_time = NaiveTime::from_hms_opt(22, 0, 0);
+        incident.end_time = NaiveTime::from_hms_opt(6, 0, 0);
+
+        let calendar = render_calendar("Planned outages", &[incident]);
+
+        assert!(calendar.contains("\r\nDTEND;TZID=Europe/Bucharest:20250809T060000\r\n"));
+    }
+
+    #[test]
+    fn escape_text_special_characters() {
+        assert_eq!("a\\, b\\; c\\\\d\\ne", escape_text("a, b; c\\d\ne"));
+    }
+
+    #[test]
+    fn fold_line_keeps_utf8_characters() {
+        let line = format!("DESCRIPTION:{}", "ș".repeat(60));
+
+        let folded = fold_line(&line);
+        let physical_lines: Vec<&str> = folded.trim_end_matches("\r\n").split("\r\n").collect();
+
+        assert_eq!(2, physical_lines.len());
+        assert!(physical_lines.iter().all(|l| l.len() <= 75));
+        assert!(physical_lines[1].starts_with(' '));
+        assert_eq!(line, physical_lines.concat().replacen(' ', "", 1));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::AppState;
+use crate::caching::CachedParameters;
+use crate::error::{ApiError, ProblemDetails};
+use crate::extract::Query;
+use axum::Json;
+use axum::extract::State;
+use chrono::Utc;
+use common::normalization::{normalize_county, to_key};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+use std::ops::Deref;
+use utoipa::{IntoParams, ToSchema};
+
+const DEFAULT_SUGGESTIONS: u64 = 20;
+const MAX_SUGGESTIONS: u64 = 100;
+
+pub(crate) const COUNTIES_QUERY: &str = "SELECT c.key, c.name, c.latitude, c.longitude, \
+ COUNT(i.id) AS incident_count, \
+ COUNT(i.id) FILTER (WHERE i.day >= $1 AND i.cancelled_at IS NULL) AS upcoming_count \
+ FROM counties c LEFT JOIN incidents i ON i.county_key = c.key AND i.duplicate_of IS NULL \
+ GROUP BY c.key ORDER BY c.name";
+
+/// Localities come from the gazetteer and from the stored incidents, so the villages missing from the gazetteer can be
+/// picked as well. Their name is then made out of the key.
+const LOCALITIES_SELECT: &str = "SELECT county_key, name_key AS key, \
+ COALESCE(MAX(name), initcap(replace(name_key, '-', ' '))) AS name, \
+ SUM(incident_count) AS incident_count \
+ FROM (SELECT county_key, name_key, name, 0 AS incident_count FROM localities \
+ UNION ALL SELECT county_key, location_key, NULL, 1 FROM incidents \
+ WHERE county_key IS NOT NULL AND location_key IS NOT NULL AND location_key <> '' AND duplicate_of IS NULL) known";
+
+#[derive(Debug, Serialize, ToSchema, FromRow)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject), graphql(complex))]
+pub struct CountySummary {
+    /// Canonical key of the county, lowercase and without diacritics.
+    #[schema(example = "caras-severin")]
+    pub key: String,
+    #[schema(example = "Caraș-Severin")]
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Number of incidents ever stored for the county.
+    pub incident_count: i64,
+    /// Number of incidents planned from today on which are not cancelled.
+    pub upcoming_count: i64,
+}
+
+#[derive(Deserialize, IntoParams, Default)]
+pub struct LocalitiesFiltering {
+    /// County name or key, matched regardless of diacritics and case.
+    pub county: Option<String>,
+    /// Beginning of the locality name or of one of its words, matched regardless of diacritics and case.
+    pub prefix: Option<String>,
+    /// Maximum number of localities returned, 20 by default and at most 100.
+    pub count: Option<u64>,
+}
+
+impl CachedParameters for Query<LocalitiesFiltering> {}
+
+#[derive(Debug, Serialize, ToSchema, FromRow)]
+pub struct LocalitySuggestion {
+    #[schema(example = "timis")]
+    pub county_key: String,
+    #[schema(example = "timisoara")]
+    pub key: String,
+    #[schema(example = "Timișoara")]
+    pub name: String,
+    /// Number of incidents ever stored for the locality.
+    pub incident_count: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/counties",
+    responses(
+        (status=200, description = "The counties with their incident counts.", body=[CountySummary]),
+        (status=500, description = "Error getting the counties.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_counties(state: State<AppState>) -> Result<Json<Vec<CountySummary>>, ApiError> {
+    let counties: Vec<CountySummary> = sqlx::query_as(COUNTIES_QUERY)
+        .bind(Utc::now().date_naive())
+        .fetch_all(state.pg_pool.deref())
+        .await?;
+
+    Ok(Json(counties))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/localities",
+    params(
+        LocalitiesFiltering
+    ),
+    responses(
+        (status=200, description = "Localities matching the prefix, the ones whose name starts with it and the ones with the most incidents first.", body=[LocalitySuggestion]),
+        (status=500, description = "Error getting the localities.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_localities(
+    state: State<AppState>,
+    filtering: Query<LocalitiesFiltering>,
+) -> Result<Json<Vec<LocalitySuggestion>>, ApiError> {
+    let mut query_builder = QueryBuilder::new(LOCALITIES_SELECT);
+    push_localities_filters(&mut query_builder, &filtering);
+
+    let localities: Vec<LocalitySuggestion> = query_builder.build_query_as().fetch_all(state.pg_pool.deref()).await?;
+
+    Ok(Json(localities))
+}
+
+/// Pushes the filters, grouping, ranking and limit of the localities query. Keys only contain letters, digits and
+/// dashes, so the prefix never contains `LIKE` wildcards. The beginning of the keys is searched with their
+/// `text_pattern_ops` indexes, the beginning of the other words with their trigram indexes.
+fn push_localities_filters(query_builder: &mut QueryBuilder<'_, Postgres>, filtering: &LocalitiesFiltering) {
+    let prefix = filtering.prefix.as_deref().map(to_key).unwrap_or_default();
+
+    query_builder.push(" WHERE TRUE");
+    if let Some(county) = &filtering.county {
+        query_builder
+            .push(" AND county_key = ")
+            .push_bind(normalize_county(county));
+    }
+    if !prefix.is_empty() {
+        query_builder
+            .push(" AND (name_key LIKE ")
+            .push_bind(format!("{}%", prefix))
+            .push(" OR name_key LIKE ")
+            .push_bind(format!("%-{}%", prefix))
+            .push(")");
+    }
+
+    query_builder
+        .push(" GROUP BY county_key, name_key ORDER BY name_key LIKE ")
+        .push_bind(format!("{}%", prefix))
+        .push(" DESC, incident_count DESC, name_key, county_key LIMIT ")
+        .push_bind(filtering.count.unwrap_or(DEFAULT_SUGGESTIONS).min(MAX_SUGGESTIONS) as i64);
+}
+
+#[cfg(test)]
+mod catalogue_tests {
+    use super::{LOCALITIES_SELECT, LocalitiesFiltering, push_localities_filters};
+    use sqlx::{Postgres, QueryBuilder};
+
+    #[test]
+    fn localities_query_with_prefix() {
+        let filtering = LocalitiesFiltering {
+            county: Some("Timiș".to_string()),
+            prefix: Some("Timișoa".to_string()),
+            count: Some(500),
+        };
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(LOCALITIES_SELECT);
+        push_localities_filters(&mut query_builder, &filtering);
+
+        assert!(query_builder.sql().ends_with(
+            " WHERE TRUE AND county_key = $1 AND (name_key LIKE $2 OR name_key LIKE $3) \
+             GROUP BY county_key, name_key ORDER BY name_key LIKE $4 DESC, incident_count DESC, name_key, county_key \
+             LIMIT $5"
+        ));
+    }
+
+    #[test]
+    fn localities_query_without_filters() {
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(LOCALITIES_SELECT);
+        push_localities_filters(&mut query_builder, &LocalitiesFiltering::default());
+
+        assert!(
+            query_builder
+                .sql()
+                .contains(" WHERE TRUE GROUP BY county_key, name_key ")
+        );
+    }
+}
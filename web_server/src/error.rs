@@ -0,0 +1,252 @@
+use axum::Json;
+use axum::extract::Request;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Header carrying the correlation id. A valid id sent by the client is kept, so the logs of the callers and of the
+/// service can be matched.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Longer ids sent by clients are replaced, they would only bloat the logs.
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+tokio::task_local! {
+    static CORRELATION_ID: String;
+}
+
+/// Stable identifiers of the problems, for the clients to act upon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidParameter,
+    InvalidBody,
+    InvalidHeader,
+    InvalidFeed,
+    Forbidden,
+    NotFound,
+    TooManyRequests,
+    DatabaseError,
+    InternalError,
+}
+
+/// Error of the API handlers, answered as an RFC 7807 problem. The causes of the server errors are logged with the
+/// correlation id of the request but never sent to the client.
+#[derive(Debug)]
+pub enum ApiError {
+    InvalidParameter(String),
+    /// The body was refused before reaching the handler, with the status of the refusal: 400 when it is not JSON, 413
+    /// when it is too large, 415 without the JSON content type and 422 when it does not fit the request.
+    InvalidBody(StatusCode, String),
+    InvalidHeader(String),
+    InvalidFeed(String),
+    /// The request does not carry the token of the resource.
+    Forbidden(String),
+    NotFound(String),
+    /// The client or the contact made too many requests of the kind lately.
+    TooManyRequests(String),
+    Database(sqlx::Error),
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::InvalidParameter(_) | ApiError::InvalidHeader(_) | ApiError::InvalidFeed(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            ApiError::InvalidBody(status, _) => *status,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Database(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ApiError::InvalidParameter(_) => ErrorCode::InvalidParameter,
+            ApiError::InvalidBody(_, _) => ErrorCode::InvalidBody,
+            ApiError::InvalidHeader(_) => ErrorCode::InvalidHeader,
+            ApiError::InvalidFeed(_) => ErrorCode::InvalidFeed,
+            ApiError::Forbidden(_) => ErrorCode::Forbidden,
+            ApiError::NotFound(_) => ErrorCode::NotFound,
+            ApiError::TooManyRequests(_) => ErrorCode::TooManyRequests,
+            ApiError::Database(_) => ErrorCode::DatabaseError,
+            ApiError::Internal(_) => ErrorCode::InternalError,
+        }
+    }
+
+    /// The explanation sent to the client.
+    pub fn detail(&self) -> String {
+        match self {
+            ApiError::InvalidParameter(detail)
+            | ApiError::InvalidBody(_, detail)
+            | ApiError::InvalidHeader(detail)
+            | ApiError::InvalidFeed(detail)
+            | ApiError::Forbidden(detail)
+            | ApiError::NotFound(detail)
+            | ApiError::TooManyRequests(detail) => detail.clone(),
+            ApiError::Database(_) | ApiError::Internal(_) => {
+                String::from("The request could not be processed, please report its correlation id.")
+            }
+        }
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Database(err) => write!(f, "Database error: {}", err),
+            ApiError::Internal(cause) => write!(f, "Internal error: {}", cause),
+            _ => write!(f, "{}", self.detail()),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        ApiError::Database(err)
+    }
+}
+
+/// RFC 7807 problem details, extended with the error code and the correlation id of the request.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProblemDetails {
+    /// Always `about:blank`, the problems are told apart by their `code`.
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    pub problem_type: String,
+    #[schema(example = "Bad Request")]
+    pub title: String,
+    #[schema(example = 400)]
+    pub status: u16,
+    #[schema(example = "Unknown field `county_key`.")]
+    pub detail: String,
+    pub code: ErrorCode,
+    /// Id of the request in the logs, also sent in the `X-Request-Id` header.
+    #[schema(example = "0b6f3c8e-5c1a-4c4e-9a57-2f0d6f1f4a8b")]
+    pub correlation_id: Option<String>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let correlation_id = current_correlation_id();
+        let log_id = correlation_id.as_deref().unwrap_or("-");
+        if status.is_server_error() {
+            error!("[{}] {}", log_id, self);
+        } else {
+            info!("[{}] {} {}", log_id, status.as_u16(), self);
+        }
+
+        let problem = ProblemDetails {
+            problem_type: String::from("about:blank"),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code: self.code(),
+            correlation_id,
+        };
+
+        let mut response = (status, Json(problem)).into_response();
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_CONTENT_TYPE));
+        response
+    }
+}
+
+/// The correlation id of the request being handled, if it went through the [`correlation_id`] middleware.
+pub fn current_correlation_id() -> Option<String> {
+    CORRELATION_ID.try_with(String::clone).ok()
+}
+
+/// Gives every request a correlation id, available to the handlers through [`current_correlation_id`] and sent back
+/// in the `X-Request-Id` header.
+pub async fn correlation_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let mut response = CORRELATION_ID.scope(id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod error_tests {
+    use super::{ApiError, CORRELATION_ID, ErrorCode, ProblemDetails, is_valid_request_id};
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    async fn problem(error: ApiError) -> (StatusCode, String, ProblemDetails) {
+        let response = error.into_response();
+        let status = response.status();
+        let content_type = response.headers()["content-type"].to_str().unwrap().to_string();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, content_type, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn client_errors_explain_the_problem() {
+        let (status, content_type, problem) = CORRELATION_ID
+            .scope(
+                "request-1".to_string(),
+                problem(ApiError::InvalidParameter("Unknown field `x`.".to_string())),
+            )
+            .await;
+
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+        assert_eq!("application/problem+json", content_type);
+        assert_eq!("about:blank", problem.problem_type);
+        assert_eq!("Bad Request", problem.title);
+        assert_eq!(400, problem.status);
+        assert_eq!("Unknown field `x`.", problem.detail);
+        assert_eq!(ErrorCode::InvalidParameter, problem.code);
+        assert_eq!(Some("request-1".to_string()), problem.correlation_id);
+    }
+
+    #[tokio::test]
+    async fn server_errors_hide_the_cause() {
+        let (status, _, problem) = problem(ApiError::Database(sqlx::Error::PoolTimedOut)).await;
+
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, status);
+        assert_eq!(ErrorCode::DatabaseError, problem.code);
+        assert!(!problem.detail.contains("pool"));
+        assert_eq!(None, problem.correlation_id);
+    }
+
+    #[test]
+    fn request_ids_from_clients() {
+        assert!(is_valid_request_id("0b6f3c8e-5c1a-4c4e-9a57-2f0d6f1f4a8b"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("id with spaces"));
+        assert!(!is_valid_request_id(&"x".repeat(65)));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::AppState;
+use crate::error::{ApiError, ProblemDetails};
+use crate::extract::Query;
+use crate::web_api::{Incident, IncidentsFiltering, push_incidents_filters};
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Sse;
+use axum::response::sse::{Event, KeepAlive};
+use log::{debug, error};
+use serde::Serialize;
+use sqlx::{FromRow, Pool, Postgres, QueryBuilder};
+use std::convert::Infallible;
+use std::ops::Deref;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+use utoipa::ToSchema;
+
+const LAST_EVENT_ID: &str = "Last-Event-ID";
+
+/// Events are replayed in batches of this size, so a client coming back after a long time does not load the whole
+/// journal at once.
+const EVENTS_BATCH_SIZE: i64 = 500;
+
+/// Number of notifications kept for slow stream clients. A client which falls behind only misses wake-ups: the events
+/// themselves are always read from the journal.
+pub const INCIDENT_NOTIFICATIONS_CAPACITY: usize = 16;
+
+pub(crate) const LAST_EVENT_QUERY: &str = "SELECT COALESCE(MAX(id), 0) FROM incident_events";
+
+/// The position of a client coming back, the transaction of the last event it received.
+const RESUME_POSITION_QUERY: &str =
+    "SELECT COALESCE((SELECT xact_id FROM incident_events WHERE id <= $1 ORDER BY id DESC LIMIT 1), 0)";
+
+/// The position of a new client: the events of the transactions still running may be sent to it.
+const CURRENT_POSITION_QUERY: &str = "SELECT txid_snapshot_xmin(txid_current_snapshot())";
+
+const EVENTS_SELECT: &str = "SELECT e.id AS event_id, e.xact_id, e.kind, e.revision AS event_revision, i.* \
+ FROM incident_events e JOIN (SELECT * FROM incidents";
+
+#[derive(Debug, FromRow)]
+struct IncidentEventRow {
+    event_id: i64,
+    xact_id: i64,
+    kind: String,
+    event_revision: i32,
+    #[sqlx(flatten)]
+    incident: Incident,
+}
+
+/// Payload of the stream events. The SSE event type is the `kind` and the SSE event id is the journal id.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IncidentEvent {
+    /// `created`, `updated` or `cancelled`.
+    #[schema(example = "updated")]
+    pub kind: String,
+    /// Revision of the incident the event is about. The incident is its current state, of a later revision when it
+    /// changed again since.
+    #[schema(example = 2)]
+    pub revision: i32,
+    pub incident: Incident,
+}
+
+/// Creates the channel used to wake up the stream clients whenever incidents were stored.
+pub fn incident_notifications() -> broadcast::Sender<()> {
+    broadcast::channel(INCIDENT_NOTIFICATIONS_CAPACITY).0
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/incidents/stream",
+    params(
+        IncidentsFiltering,
+        ("Last-Event-ID" = Option<i64>, Header, description = "Id of the last event received, the events after it are replayed.")
+    ),
+    responses(
+        (status=200, description = "Server-Sent Events stream of the created, updated and cancelled incidents. The paging and sorting parameters are ignored.", body=IncidentEvent, content_type = "text/event-stream"),
+        (status=400, description = "Invalid filters or Last-Event-ID header.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error reading the incident events.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn stream_incidents(
+    state: State<AppState>,
+    filtering: Query<IncidentsFiltering>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    // Subscribing before reading the position guarantees no notification is lost in between.
+    let notifications = state.incident_notifications.subscribe();
+
+    let position = match headers.get(LAST_EVENT_ID) {
+        Some(value) => {
+            let last_event_id = value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<i64>().ok())
+                .ok_or_else(|| ApiError::InvalidHeader(String::from("Invalid Last-Event-ID header.")))?;
+            let xact_id = sqlx::query_scalar(RESUME_POSITION_QUERY)
+                .bind(last_event_id)
+                .fetch_one(state.pg_pool.deref())
+                .await?;
+            (xact_id, last_event_id)
+        }
+        None => {
+            let xact_id = sqlx::query_scalar(CURRENT_POSITION_QUERY)
+                .fetch_one(state.pg_pool.deref())
+                .await?;
+            (xact_id, 0)
+        }
+    };
+
+    let (sender, receiver) = mpsc::channel(EVENTS_BATCH_SIZE as usize);
+    tokio::spawn(forward_events(
+        state.pg_pool.clone(),
+        filtering.0,
+        position,
+        notifications,
+        sender,
+    ));
+
+    Ok(Sse::new(ReceiverStream::new(receiver)).keep_alive(KeepAlive::default()))
+}
+
+/// Sends the events after the position, the transaction and the id of the last event sent, then waits for new
+/// incidents to be stored. Stops when the client disconnects.
+///
+/// The ids are taken before the commit, so an event may become visible after events of higher ids: the events are
+/// sent in the order of their transactions, only once every transaction started before theirs is finished.
+async fn forward_events(
+    pg_pool: Arc<Pool<Postgres>>,
+    filtering: IncidentsFiltering,
+    mut position: (i64, i64),
+    mut notifications: broadcast::Receiver<()>,
+    sender: mpsc::Sender<Result<Event, Infallible>>,
+) {
+    loop {
+        let mut query_builder = QueryBuilder::new(EVENTS_SELECT);
+        push_incidents_filters(&mut query_builder, &filtering);
+        query_builder
+            .push(") i ON i.id = e.incident_id WHERE (e.xact_id, e.id) > (")
+            .push_bind(position.0)
+            .push(", ")
+            .push_bind(position.1)
+            .push(") AND e.xact_id < txid_snapshot_xmin(txid_current_snapshot()) ORDER BY e.xact_id, e.id LIMIT ")
+            .push(EVENTS_BATCH_SIZE);
+
+        let rows: Vec<IncidentEventRow> = match query_builder.build_query_as().fetch_all(pg_pool.deref()).await {
+            Ok(rows) => rows,
+            Err(err) => {
+                error!("Could not read the incident events: {}", err);
+                return;
+            }
+        };
+        let complete_batch = rows.len() as i64 == EVENTS_BATCH_SIZE;
+
+        for row in rows {
+            position = (row.xact_id, row.event_id);
+            if sender.send(Ok(to_sse_event(row))).await.is_err() {
+                debug!("Stream client disconnected.");
+                return;
+            }
+        }
+        if complete_batch {
+            continue;
+        }
+
+        tokio::select! {
+            notification = notifications.recv() => match notification {
+                Ok(()) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            },
+            _ = sender.closed() => {
+                debug!("Stream client disconnected.");
+                return;
+            }
+        }
+    }
+}
+
+fn to_sse_event(row: IncidentEventRow) -> Event {
+    let event = Event::default().id(row.event_id.to_string()).event(&row.kind);
+    let payload = IncidentEvent {
+        kind: row.kind,
+        revision: row.event_revision,
+        incident: row.incident,
+    };
+
+    event
+        .json_data(payload)
+        .expect("Incident events are always serializable.")
+}
@@ -0,0 +1,443 @@
+use crate::AppState;
+use crate::caching::CachedParameters;
+use crate::error::{ApiError, ProblemDetails};
+use crate::extract::Query;
+use crate::web_api::{Incident, IncidentsFiltering, push_incidents_filters};
+use arrow_array::{
+    ArrayRef, Date32Array, Int32Array, Int64Array, RecordBatch, StringArray, Time32SecondArray,
+    TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::IntoResponse;
+use chrono::{NaiveDate, NaiveTime, Timelike};
+use log::{debug, error};
+use parquet::arrow::ArrowWriter;
+use serde::Deserialize;
+use sqlx::{Postgres, QueryBuilder, Transaction};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use utoipa::{IntoParams, ToSchema};
+
+const EXPORT_COLUMNS: [&str; 12] = [
+    "id",
+    "external_id",
+    "county",
+    "location",
+    "day",
+    "start_time",
+    "end_time",
+    "description",
+    "revision",
+    "updated_at",
+    "cancelled_at",
+    "duplicate_of",
+];
+
+const DECLARE_CURSOR: &str = "DECLARE export_cursor NO SCROLL CURSOR FOR SELECT *";
+
+/// Number of rows fetched from the cursor at once. Each batch is encoded and sent to the client before the next one
+/// is fetched, so this bounds the memory used by an export.
+const FETCH_QUERY: &str = "FETCH 1000 FROM export_cursor";
+
+/// How many encoded batches can wait for a slow client before fetching from the cursor is paused.
+const EXPORT_CHANNEL_CAPACITY: usize = 4;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+    Parquet,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    fn content_disposition(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "attachment; filename=\"incidents.csv\"",
+            ExportFormat::Ndjson => "attachment; filename=\"incidents.ndjson\"",
+            ExportFormat::Parquet => "attachment; filename=\"incidents.parquet\"",
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ExportParams {
+    pub format: ExportFormat,
+    pub county: Option<String>,
+    #[param(value_type = Option<String>, format = Date)]
+    pub day: Option<NaiveDate>,
+    /// Also exports the incidents republished under another id, which are left out by default.
+    pub include_duplicates: Option<bool>,
+}
+
+impl CachedParameters for Query<ExportParams> {}
+
+/// A writer whose content can be drained while the encoder still owns it, so every batch is sent as soon as it is
+/// encoded.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Bytes {
+        Bytes::from(std::mem::take(&mut *self.0.lock().unwrap()))
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Encoder {
+    Csv(Box<csv::Writer<SharedBuffer>>),
+    Ndjson,
+    Parquet(Box<ArrowWriter<SharedBuffer>>),
+}
+
+struct ExportWriter {
+    buffer: SharedBuffer,
+    encoder: Encoder,
+}
+
+impl ExportWriter {
+    fn new(format: ExportFormat) -> Result<ExportWriter, String> {
+        let buffer = SharedBuffer::default();
+
+        let encoder = match format {
+            ExportFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(buffer.clone());
+                writer.write_record(EXPORT_COLUMNS).map_err(|e| e.to_string())?;
+                Encoder::Csv(Box::new(writer))
+            }
+            ExportFormat::Ndjson => Encoder::Ndjson,
+            ExportFormat::Parquet => {
+                let writer = ArrowWriter::try_new(buffer.clone(), parquet_schema(), None).map_err(|e| e.to_string())?;
+                Encoder::Parquet(Box::new(writer))
+            }
+        };
+
+        Ok(ExportWriter { buffer, encoder })
+    }
+
+    /// Encodes the incidents and returns everything written since the previous call.
+    fn write_batch(&mut self, incidents: &[Incident]) -> Result<Bytes, String> {
+        match &mut self.encoder {
+            Encoder::Csv(writer) => {
+                for incident in incidents {
+                    writer
+                        .write_record([
+                            incident.id.to_string().as_str(),
+                            &incident.external_id,
+                            &incident.county,
+                            &incident.location,
+                            &incident.day.to_string(),
+                            &optional_to_string(incident.start_time),
+                            &optional_to_string(incident.end_time),
+                            &incident.description,
+                            &incident.revision.to_string(),
+                            &incident.updated_at.to_rfc3339(),
+                            &optional_to_string(incident.cancelled_at.map(|at| at.to_rfc3339())),
+                            &optional_to_string(incident.duplicate_of),
+                        ])
+                        .map_err(|e| e.to_string())?;
+                }
+                writer.flush().map_err(|e| e.to_string())?;
+            }
+            Encoder::Ndjson => {
+                for incident in incidents {
+                    serde_json::to_writer(&mut self.buffer, incident).map_err(|e| e.to_string())?;
+                    self.buffer.write_all(b"\n").map_err(|e| e.to_string())?;
+                }
+            }
+            Encoder::Parquet(writer) => {
+                writer.write(&to_record_batch(incidents)?).map_err(|e| e.to_string())?;
+                // Closes the row group so it is written out instead of being buffered until the end of the export.
+                writer.flush().map_err(|e| e.to_string())?;
+                writer.sync().map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(self.buffer.take())
+    }
+
+    /// Completes the export (e.g. the Parquet footer) and returns the remaining bytes.
+    fn finish(self) -> Result<Bytes, String> {
+        match self.encoder {
+            Encoder::Csv(writer) => {
+                writer.into_inner().map_err(|e| e.to_string())?;
+            }
+            Encoder::Ndjson => {}
+            Encoder::Parquet(writer) => {
+                writer.close().map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(self.buffer.take())
+    }
+}
+
+fn optional_to_string<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn parquet_schema() -> Arc<Schema> {
+    let utc_timestamp = DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("external_id", DataType::Utf8, false),
+        Field::new("county", DataType::Utf8, false),
+        Field::new("location", DataType::Utf8, false),
+        Field::new("day", DataType::Date32, false),
+        Field::new("start_time", DataType::Time32(TimeUnit::Second), true),
+        Field::new("end_time", DataType::Time32(TimeUnit::Second), true),
+        Field::new("description", DataType::Utf8, false),
+        Field::new("revision", DataType::Int32, false),
+        Field::new("updated_at", utc_timestamp.clone(), false),
+        Field::new("cancelled_at", utc_timestamp, true),
+        Field::new("duplicate_of", DataType::Int64, true),
+    ]))
+}
+
+fn to_record_batch(incidents: &[Incident]) -> Result<RecordBatch, String> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    let seconds = |time: Option<NaiveTime>| time.map(|t| t.num_seconds_from_midnight() as i32);
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(incidents.iter().map(|i| i.id))),
+        Arc::new(StringArray::from_iter_values(incidents.iter().map(|i| &i.external_id))),
+        Arc::new(StringArray::from_iter_values(incidents.iter().map(|i| &i.county))),
+        Arc::new(StringArray::from_iter_values(incidents.iter().map(|i| &i.location))),
+        Arc::new(Date32Array::from_iter_values(
+            incidents.iter().map(|i| (i.day - epoch).num_days() as i32),
+        )),
+        Arc::new(Time32SecondArray::from_iter(
+            incidents.iter().map(|i| seconds(i.start_time)),
+        )),
+        Arc::new(Time32SecondArray::from_iter(
+            incidents.iter().map(|i| seconds(i.end_time)),
+        )),
+        Arc::new(StringArray::from_iter_values(incidents.iter().map(|i| &i.description))),
+        Arc::new(Int32Array::from_iter_values(incidents.iter().map(|i| i.revision))),
+        Arc::new(
+            TimestampMicrosecondArray::from_iter_values(incidents.iter().map(|i| i.updated_at.timestamp_micros()))
+                .with_timezone("UTC"),
+        ),
+        Arc::new(
+            TimestampMicrosecondArray::from_iter(
+                incidents.iter().map(|i| i.cancelled_at.map(|at| at.timestamp_micros())),
+            )
+            .with_timezone("UTC"),
+        ),
+        Arc::new(Int64Array::from_iter(incidents.iter().map(|i| i.duplicate_of))),
+    ];
+
+    RecordBatch::try_new(parquet_schema(), columns).map_err(|e| e.to_string())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/incidents/export",
+    params(
+        ExportParams
+    ),
+    responses(
+        (status=200, description = "All the incidents matching the filters, streamed in the requested format.",
+            content(
+                (String = "text/csv"),
+                (String = "application/x-ndjson"),
+                (Vec<u8> = "application/vnd.apache.parquet")
+            )
+        ),
+        (status=400, description = "Invalid format or filters.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error exporting the incidents.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn export_incidents(
+    state: State<AppState>,
+    params: Query<ExportParams>,
+) -> Result<impl IntoResponse, ApiError> {
+    let format = params.format;
+    let filtering = IncidentsFiltering {
+        county: params.county.clone(),
+        day: params.day,
+        include_duplicates: params.include_duplicates,
+        ..Default::default()
+    };
+
+    let writer = ExportWriter::new(format).map_err(ApiError::Internal)?;
+
+    // The cursor is declared before answering so a database failure still results in a proper error response.
+    let mut transaction = state.pg_pool.begin().await?;
+
+    let mut query_builder = QueryBuilder::new(DECLARE_CURSOR);
+    query_builder.push(" FROM incidents");
+    push_incidents_filters(&mut query_builder, &filtering);
+    query_builder.push(" ORDER BY day DESC, id");
+
+    query_builder.build().execute(&mut *transaction).await?;
+
+    let (sender, receiver) = mpsc::channel(EXPORT_CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        if let Err(err) = stream_incidents(transaction, writer, &sender).await {
+            error!("Export of the incidents failed: {}", err);
+            let _ = sender.send(Err(io::Error::other(err))).await;
+        }
+    });
+
+    Ok((
+        [
+            (CONTENT_TYPE, format.content_type()),
+            (CONTENT_DISPOSITION, format.content_disposition()),
+        ],
+        Body::from_stream(ReceiverStream::new(receiver)),
+    ))
+}
+
+async fn stream_incidents(
+    mut transaction: Transaction<'static, Postgres>,
+    mut writer: ExportWriter,
+    sender: &mpsc::Sender<Result<Bytes, io::Error>>,
+) -> Result<(), String> {
+    loop {
+        let incidents: Vec<Incident> = sqlx::query_as(FETCH_QUERY)
+            .fetch_all(&mut *transaction)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if incidents.is_empty() {
+            break;
+        }
+
+        let bytes = writer.write_batch(&incidents)?;
+        if sender.send(Ok(bytes)).await.is_err() {
+            debug!("Client went away, stopping the export.");
+            return Ok(());
+        }
+    }
+
+    let bytes = writer.finish()?;
+    let _ = sender.send(Ok(bytes)).await;
+
+    transaction.commit().await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod export_tests {
+    use super::{ExportFormat, ExportWriter};
+    use crate::web_api::Incident;
+    use chrono::{NaiveDate, NaiveTime, Utc};
+
+    fn incidents() -> Vec<Incident> {
+        vec![
+            Incident {
+                external_id: "134691 - Retele Electrice".to_string(),
+                county: "TULCEA".to_string(),
+                location: "LOC. TURCOAIA".to_string(),
+                day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+                start_time: NaiveTime::from_hms_opt(9, 0, 0),
+                end_time: NaiveTime::from_hms_opt(17, 0, 0),
+                description: "Strada: -  SC URANUS, \"CARIERA\"".to_string(),
+                id: 1,
+                revision: 1,
+                updated_at: Utc::now(),
+                cancelled_at: None,
+                duplicate_of: None,
+            },
+            Incident {
+                external_id: "134690 - Retele Electrice".to_string(),
+                county: "TULCEA".to_string(),
+                location: "LOC. CATALOI".to_string(),
+                day: NaiveDate::from_ymd_opt(2025, 8, 7).unwrap(),
+                start_time: None,
+                end_time: None,
+                description: "Strada: -".to_string(),
+                id: 2,
+                revision: 2,
+                updated_at: Utc::now(),
+                cancelled_at: Some(Utc::now()),
+                duplicate_of: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn csv_has_header_and_quoted_values() {
+        let mut writer = ExportWriter::new(ExportFormat::Csv).unwrap();
+        let mut output = writer.write_batch(&incidents()).unwrap().to_vec();
+        output.extend_from_slice(&writer.finish().unwrap());
+
+        let text = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!(
+            "id,external_id,county,location,day,start_time,end_time,description,revision,updated_at,cancelled_at,duplicate_of",
+            lines[0]
+        );
+        assert!(lines[1].starts_with(
+            "1,134691 - Retele Electrice,TULCEA,LOC. TURCOAIA,2025-08-08,09:00:00,17:00:00,\"Strada: -  SC URANUS, \"\"CARIERA\"\"\",1,"
+        ));
+        assert!(lines[1].ends_with(','));
+    }
+
+    #[test]
+    fn csv_header_without_incidents() {
+        let writer = ExportWriter::new(ExportFormat::Csv).unwrap();
+
+        let output = writer.finish().unwrap();
+        assert_eq!(
+            "id,external_id,county,location,day,start_time,end_time,description,revision,updated_at,cancelled_at,duplicate_of\n",
+            String::from_utf8_lossy(&output)
+        );
+    }
+
+    #[test]
+    fn ndjson_one_incident_per_line() {
+        let mut writer = ExportWriter::new(ExportFormat::Ndjson).unwrap();
+        let output = writer.write_batch(&incidents()).unwrap();
+
+        let text = String::from_utf8(output.to_vec()).unwrap();
+        let lines: Vec<serde_json::Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(2, lines.len());
+        assert_eq!("LOC. CATALOI", lines[1]["location"]);
+        assert_eq!("2025-08-07", lines[1]["day"]);
+    }
+
+    #[test]
+    fn parquet_is_written_per_batch() {
+        let mut writer = ExportWriter::new(ExportFormat::Parquet).unwrap();
+        let mut output = writer.write_batch(&incidents()).unwrap().to_vec();
+        assert!(output.starts_with(b"PAR1"));
+
+        output.extend_from_slice(&writer.write_batch(&incidents()).unwrap());
+        output.extend_from_slice(&writer.finish().unwrap());
+        assert!(output.ends_with(b"PAR1"));
+
+        let reader =
+            parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(axum::body::Bytes::from(output))
+                .unwrap()
+                .build()
+                .unwrap();
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(4, rows);
+    }
+}
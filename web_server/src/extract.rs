@@ -0,0 +1,119 @@
+use crate::error::ApiError;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{FromRequest, FromRequestParts};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use std::ops::{Deref, DerefMut};
+
+/// Query string extractor answering the invalid parameters with a problem, like the errors of the handlers.
+#[derive(Debug, Clone, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct Query<T>(pub T);
+
+/// Path extractor answering the invalid segments with a problem.
+#[derive(Debug, Clone, FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct Path<T>(pub T);
+
+/// JSON body extractor answering the invalid bodies with a problem, with the status `axum::Json` refuses them with. It
+/// is answered as `axum::Json` is.
+#[derive(Debug, Clone, FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::InvalidParameter(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::InvalidParameter(rejection.body_text())
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::InvalidBody(rejection.status(), rejection.body_text())
+    }
+}
+
+macro_rules! deref_extractor {
+    ($extractor:ident) => {
+        impl<T> Deref for $extractor<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $extractor<T> {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+    };
+}
+
+deref_extractor!(Query);
+deref_extractor!(Path);
+deref_extractor!(Json);
+
+#[cfg(test)]
+mod extract_tests {
+    use super::{Json, Query};
+    use crate::error::ApiError;
+    use axum::body::Body;
+    use axum::extract::{FromRequest, FromRequestParts};
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{Request, StatusCode};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Paging {
+        #[allow(dead_code)]
+        count: u32,
+    }
+
+    #[tokio::test]
+    async fn invalid_query_strings_are_problems() {
+        let (mut parts, _) = Request::get("/?count=many").body(()).unwrap().into_parts();
+
+        let rejection = Query::<Paging>::from_request_parts(&mut parts, &()).await.unwrap_err();
+
+        assert!(matches!(rejection, ApiError::InvalidParameter(detail) if detail.contains("count")));
+    }
+
+    #[tokio::test]
+    async fn invalid_bodies_are_problems() {
+        let request = Request::post("/")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from("{\"count\": -1}"))
+            .unwrap();
+
+        let rejection = Json::<Paging>::from_request(request, &()).await.unwrap_err();
+
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, rejection.status());
+        assert!(matches!(rejection, ApiError::InvalidBody(_, detail) if detail.contains("count")));
+    }
+
+    #[tokio::test]
+    async fn bodies_without_the_json_content_type_are_unsupported() {
+        let request = Request::post("/")
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from("{\"count\": 1}"))
+            .unwrap();
+
+        let rejection = Json::<Paging>::from_request(request, &()).await.unwrap_err();
+
+        assert_eq!(StatusCode::UNSUPPORTED_MEDIA_TYPE, rejection.status());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::AppState;
+use crate::error::{ApiError, ProblemDetails};
+use crate::extract::Query;
+use crate::web_api::{IncidentsFiltering, push_incidents_order, push_incidents_selection};
+use axum::Json;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use chrono::NaiveDate;
+use serde::Serialize;
+use sqlx::{FromRow, QueryBuilder};
+use std::ops::Deref;
+use utoipa::ToSchema;
+
+const GEOJSON_CONTENT_TYPE: &str = "application/geo+json";
+
+/// Incidents are matched against the gazetteer by their normalized county and location. When the locality is not
+/// known, the incident is placed in the centre of its county.
+const GEOJSON_SELECT: &str = "SELECT i.id, i.external_id, i.county, i.location, i.day, i.description, \
+ COALESCE(l.latitude, c.latitude) AS latitude, COALESCE(l.longitude, c.longitude) AS longitude, \
+ CASE WHEN l.id IS NOT NULL THEN 'locality' WHEN c.key IS NOT NULL THEN 'county' END AS precision \
+ FROM (SELECT *";
+
+const GEOJSON_JOIN: &str = ") i \
+ LEFT JOIN localities l ON l.county_key = i.county_key AND l.name_key = i.location_key \
+ LEFT JOIN counties c ON c.key = i.county_key";
+
+#[derive(Debug, FromRow)]
+struct LocatedIncident {
+    id: i64,
+    external_id: String,
+    county: String,
+    location: String,
+    day: NaiveDate,
+    description: String,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    precision: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IncidentFeatureCollection {
+    #[serde(rename = "type")]
+    #[schema(example = "FeatureCollection")]
+    pub collection_type: String,
+    pub features: Vec<IncidentFeature>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IncidentFeature {
+    #[serde(rename = "type")]
+    #[schema(example = "Feature")]
+    pub feature_type: String,
+    pub id: i64,
+    /// Missing when neither the locality nor the county of the incident are in the gazetteer.
+    pub geometry: Option<PointGeometry>,
+    pub properties: IncidentProperties,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PointGeometry {
+    #[serde(rename = "type")]
+    #[schema(example = "Point")]
+    pub geometry_type: String,
+    /// Longitude and latitude, in this order, as required by GeoJSON.
+    pub coordinates: [f64; 2],
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IncidentProperties {
+    pub external_id: String,
+    pub county: String,
+    pub location: String,
+    #[schema(value_type = String, format = Date)]
+    pub day: NaiveDate,
+    pub description: String,
+    /// `locality` when the coordinates are the ones of the locality, `county` when they are the county centre.
+    pub precision: Option<String>,
+}
+
+impl From<LocatedIncident> for IncidentFeature {
+    fn from(incident: LocatedIncident) -> Self {
+        let geometry = match (incident.longitude, incident.latitude) {
+            (Some(longitude), Some(latitude)) => Some(PointGeometry {
+                geometry_type: "Point".to_string(),
+                coordinates: [longitude, latitude],
+            }),
+            _ => None,
+        };
+
+        IncidentFeature {
+            feature_type: "Feature".to_string(),
+            id: incident.id,
+            geometry,
+            properties: IncidentProperties {
+                external_id: incident.external_id,
+                county: incident.county,
+                location: incident.location,
+                day: incident.day,
+                description: incident.description,
+                precision: incident.precision,
+            },
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/incidents.geojson",
+    params(
+        IncidentsFiltering
+    ),
+    responses(
+        (status=200, description = "Incidents as GeoJSON features.", body=IncidentFeatureCollection, content_type = "application/geo+json"),
+        (status=400, description = "Invalid filters.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error getting the incidents.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_incidents_geojson(
+    state: State<AppState>,
+    filtering: Query<IncidentsFiltering>,
+) -> Result<impl IntoResponse, ApiError> {
+    let mut query_builder = QueryBuilder::new(GEOJSON_SELECT);
+    push_incidents_selection(&mut query_builder, &filtering);
+    query_builder.push(GEOJSON_JOIN);
+    push_incidents_order(&mut query_builder, &filtering, "i.");
+
+    let incidents: Vec<LocatedIncident> = query_builder.build_query_as().fetch_all(state.pg_pool.deref()).await?;
+
+    let collection = IncidentFeatureCollection {
+        collection_type: "FeatureCollection".to_string(),
+        features: incidents.into_iter().map(IncidentFeature::from).collect(),
+    };
+    Ok(([(CONTENT_TYPE, GEOJSON_CONTENT_TYPE)], Json(collection)))
+}
+
+#[cfg(test)]
+mod geojson_tests {
+    use super::{IncidentFeature, LocatedIncident};
+    use chrono::NaiveDate;
+
+    fn located_incident(latitude: Option<f64>, longitude: Option<f64>) -> LocatedIncident {
+        LocatedIncident {
+            id: 7,
+            external_id: "134691 - Retele Electrice".to_string(),
+            county: "TULCEA".to_string(),
+            location: "LOC. TULCEA".to_string(),
+            day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+            description: "Strada: Pacii".to_string(),
+            latitude,
+            longitude,
+            precision: latitude.map(|_| "locality".to_string()),
+        }
+    }
+
+    #[test]
+    fn feature_coordinates_are_longitude_first() {
+        let feature = IncidentFeature::from(located_incident(Some(45.18), Some(28.805)));
+
+        let geometry = feature.geometry.unwrap();
+        assert_eq!("Point", geometry.geometry_type);
+        assert_eq!([28.805, 45.18], geometry.coordinates);
+        assert_eq!(Some("locality".to_string()), feature.properties.precision);
+    }
+
+    #[test]
+    fn feature_without_coordinates_has_no_geometry() {
+        let feature = IncidentFeature::from(located_incident(None, None));
+
+        assert!(feature.geometry.is_none());
+        assert_eq!(7, feature.id);
+    }
+}
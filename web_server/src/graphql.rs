@@ -0,0 +1,364 @@
+use crate::AppState;
+use crate::catalogue::{COUNTIES_QUERY, CountySummary};
+use crate::extract::Json;
+use crate::web_api::{
+    Incident, IncidentSort, IncidentsFiltering, SortDirection, push_incidents_filters, push_incidents_order,
+};
+use async_graphql::connection::{Connection, CursorType, Edge};
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Error, InputObject, Object, Request, Response, Schema,
+    SimpleObject,
+};
+use axum::extract::State;
+use axum::response::Html;
+use chrono::{DateTime, NaiveDate, Utc};
+use log::error;
+use sqlx::{FromRow, Pool, Postgres, QueryBuilder};
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock};
+use utoipa::OpenApi;
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 100;
+
+/// Deep enough for the counties with their upcoming incidents and the history of each of them, which keeps a single
+/// request from loading the whole database.
+const MAX_QUERY_DEPTH: usize = 8;
+
+/// Every field counts for one and the fields of the connections for every incident of the page, so a page of
+/// incidents with their history and duplicates fits, but not the history of the upcoming incidents of every county.
+const MAX_QUERY_COMPLEXITY: usize = 10_000;
+
+/// Counties of the gazetteer, Bucharest included.
+const COUNTY_COUNT: usize = 42;
+
+const INCIDENT_QUERY: &str = "SELECT * FROM incidents WHERE id = $1";
+
+/// The histories of the incidents of a response are read all at once.
+const HISTORIES_QUERY: &str = "SELECT incident_id, id AS event_id, kind, revision, created_at FROM incident_events \
+ WHERE incident_id = ANY($1) ORDER BY id";
+
+/// The duplicates of the incidents of a response are read all at once.
+const DUPLICATES_OF_QUERY: &str = "SELECT * FROM incidents WHERE duplicate_of = ANY($1) ORDER BY id";
+
+pub type IncidentsSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// The schema has no state of its own, the pool is passed along with every request.
+static SCHEMA: LazyLock<IncidentsSchema> = LazyLock::new(|| {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+});
+
+#[derive(OpenApi)]
+#[openapi(paths(execute_graphql, get_graphiql))]
+pub struct GraphqlApiDoc;
+
+/// The GraphQL schema in SDL, exported by `api_gen`.
+pub fn schema_sdl() -> String {
+    SCHEMA.sdl()
+}
+
+/// Filters of the incidents, the same as the ones of `/api/incidents/all`. Paging is done with the connection
+/// arguments instead of `offset` and `count`.
+#[derive(Debug, Default, InputObject)]
+pub struct IncidentFilter {
+    pub county: Option<String>,
+    pub day: Option<NaiveDate>,
+    /// Sort key, `DAY` by default.
+    pub sort: Option<IncidentSort>,
+    /// Sort direction, descending by default.
+    pub direction: Option<SortDirection>,
+    /// Also lists the incidents republished under another id, which are left out by default.
+    pub include_duplicates: Option<bool>,
+}
+
+impl IncidentFilter {
+    fn filtering(&self) -> IncidentsFiltering {
+        IncidentsFiltering {
+            county: self.county.clone(),
+            offset: None,
+            count: None,
+            day: self.day,
+            sort: self.sort,
+            direction: self.direction,
+            include_duplicates: self.include_duplicates,
+        }
+    }
+}
+
+/// An entry of the journal of an incident.
+#[derive(Debug, Clone, SimpleObject, FromRow)]
+pub struct IncidentRevision {
+    #[graphql(skip)]
+    pub incident_id: i64,
+    pub event_id: i64,
+    /// `created`, `updated` or `cancelled`.
+    pub kind: String,
+    pub revision: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Incidents matching the filter, paged with the connection cursors.
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn incidents(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<IncidentFilter>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<usize, Incident>, Error> {
+        let filtering = filter.unwrap_or_default().filtering();
+        incidents_connection(ctx, &filtering, None, first, after).await
+    }
+
+    async fn incident(&self, ctx: &Context<'_>, id: i64) -> Result<Option<Incident>, Error> {
+        sqlx::query_as(INCIDENT_QUERY)
+            .bind(id)
+            .fetch_optional(pg_pool(ctx)?.as_ref())
+            .await
+            .map_err(internal_error)
+    }
+
+    /// The counties of the gazetteer with their incident counts.
+    #[graphql(complexity = "COUNTY_COUNT * child_complexity")]
+    async fn counties(&self, ctx: &Context<'_>) -> Result<Vec<CountySummary>, Error> {
+        sqlx::query_as(COUNTIES_QUERY)
+            .bind(Utc::now().date_naive())
+            .fetch_all(pg_pool(ctx)?.as_ref())
+            .await
+            .map_err(internal_error)
+    }
+}
+
+#[ComplexObject]
+impl CountySummary {
+    /// Incidents planned from today on which are not cancelled, the earliest first.
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn upcoming_incidents(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> Result<Connection<usize, Incident>, Error> {
+        let filtering = IncidentsFiltering {
+            sort: Some(IncidentSort::Day),
+            direction: Some(SortDirection::Asc),
+            ..IncidentsFiltering::default()
+        };
+        incidents_connection(ctx, &filtering, Some(&self.key), first, after).await
+    }
+}
+
+#[ComplexObject]
+impl Incident {
+    /// The changes made by the provider to the incident, the oldest first.
+    async fn history(&self, ctx: &Context<'_>) -> Result<Vec<IncidentRevision>, Error> {
+        let histories = ctx.data::<DataLoader<HistoryLoader>>()?;
+        Ok(histories.load_one(self.id).await?.unwrap_or_default())
+    }
+
+    /// The announcements of the same works republished by the provider under other ids.
+    async fn duplicates(&self, ctx: &Context<'_>) -> Result<Vec<Incident>, Error> {
+        let duplicates = ctx.data::<DataLoader<DuplicatesLoader>>()?;
+        Ok(duplicates.load_one(self.id).await?.unwrap_or_default())
+    }
+}
+
+/// Loads the histories of the incidents of a response with a single query.
+pub struct HistoryLoader(Arc<Pool<Postgres>>);
+
+impl Loader<i64> for HistoryLoader {
+    type Value = Vec<IncidentRevision>;
+    type Error = Error;
+
+    async fn load(&self, ids: &[i64]) -> Result<HashMap<i64, Self::Value>, Self::Error> {
+        let revisions: Vec<IncidentRevision> = sqlx::query_as(HISTORIES_QUERY)
+            .bind(ids)
+            .fetch_all(self.0.as_ref())
+            .await
+            .map_err(internal_error)?;
+
+        let mut histories: HashMap<i64, Self::Value> = HashMap::new();
+        for revision in revisions {
+            histories.entry(revision.incident_id).or_default().push(revision);
+        }
+        Ok(histories)
+    }
+}
+
+/// Loads the duplicates of the incidents of a response with a single query.
+pub struct DuplicatesLoader(Arc<Pool<Postgres>>);
+
+impl Loader<i64> for DuplicatesLoader {
+    type Value = Vec<Incident>;
+    type Error = Error;
+
+    async fn load(&self, ids: &[i64]) -> Result<HashMap<i64, Self::Value>, Self::Error> {
+        let incidents: Vec<Incident> = sqlx::query_as(DUPLICATES_OF_QUERY)
+            .bind(ids)
+            .fetch_all(self.0.as_ref())
+            .await
+            .map_err(internal_error)?;
+
+        let mut duplicates: HashMap<i64, Self::Value> = HashMap::new();
+        for incident in incidents {
+            if let Some(canonical) = incident.duplicate_of {
+                duplicates.entry(canonical).or_default().push(incident);
+            }
+        }
+        Ok(duplicates)
+    }
+}
+
+/// Complexity of a page of incidents, its fields count for every incident of the page.
+fn page_complexity(first: Option<i32>, child_complexity: usize) -> usize {
+    let page_size = first.map_or(DEFAULT_PAGE_SIZE, |first| (first.max(0) as usize).min(MAX_PAGE_SIZE));
+    page_size.saturating_mul(child_complexity)
+}
+
+/// Reads a page of incidents. The cursors are the positions of the incidents in the listing, so the pages follow any
+/// of the sort keys. `upcoming_in_county` restricts the listing to the upcoming incidents of a county.
+async fn incidents_connection(
+    ctx: &Context<'_>,
+    filtering: &IncidentsFiltering,
+    upcoming_in_county: Option<&str>,
+    first: Option<i32>,
+    after: Option<String>,
+) -> Result<Connection<usize, Incident>, Error> {
+    let start = match after {
+        Some(cursor) => usize::decode_cursor(&cursor).map_err(|_| Error::new("Invalid cursor."))? + 1,
+        None => 0,
+    };
+    let page_size = match first {
+        Some(first) if first < 0 => return Err(Error::new("`first` must not be negative.")),
+        Some(first) => (first as usize).min(MAX_PAGE_SIZE),
+        None => DEFAULT_PAGE_SIZE,
+    };
+
+    let mut query_builder = QueryBuilder::new("SELECT * FROM (SELECT * FROM incidents");
+    push_incidents_filters(&mut query_builder, filtering);
+    query_builder.push(") i");
+    if let Some(county_key) = upcoming_in_county {
+        query_builder
+            .push(" WHERE county_key = ")
+            .push_bind(county_key.to_string())
+            .push(" AND day >= ")
+            .push_bind(Utc::now().date_naive())
+            .push(" AND cancelled_at IS NULL");
+    }
+    push_incidents_order(&mut query_builder, filtering, "");
+    // One more incident than requested tells whether there is a next page.
+    query_builder
+        .push(" LIMIT ")
+        .push_bind(page_size as i64 + 1)
+        .push(" OFFSET ")
+        .push_bind(start as i64);
+
+    let mut incidents: Vec<Incident> = query_builder
+        .build_query_as()
+        .fetch_all(pg_pool(ctx)?.as_ref())
+        .await
+        .map_err(internal_error)?;
+
+    let has_next_page = incidents.len() > page_size;
+    incidents.truncate(page_size);
+
+    let mut connection = Connection::new(start > 0, has_next_page);
+    connection.edges.extend(
+        incidents
+            .into_iter()
+            .enumerate()
+            .map(|(index, incident)| Edge::new(start + index, incident)),
+    );
+    Ok(connection)
+}
+
+fn pg_pool<'a>(ctx: &Context<'a>) -> Result<&'a Arc<Pool<Postgres>>, Error> {
+    ctx.data::<Arc<Pool<Postgres>>>()
+}
+
+fn internal_error(err: sqlx::Error) -> Error {
+    error!("{}", err);
+    Error::new("Internal Server Error")
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/graphql",
+    request_body(content = Object, description = "GraphQL request with the `query`, the optional `operationName` and `variables`."),
+    responses(
+        (status=200, description = "GraphQL response, the errors are reported in its `errors` member.", body=Object),
+        (status=400, description = "The body is not a GraphQL request."),
+    )
+)]
+pub async fn execute_graphql(state: State<AppState>, Json(request): Json<Request>) -> Json<Response> {
+    // The loaders batch and cache the reads of a single request.
+    let request = request
+        .data(state.pg_pool.clone())
+        .data(DataLoader::new(HistoryLoader(state.pg_pool.clone()), tokio::spawn))
+        .data(DataLoader::new(DuplicatesLoader(state.pg_pool.clone()), tokio::spawn));
+    Json(SCHEMA.execute(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/graphql",
+    responses(
+        (status=200, description = "GraphiQL page exploring the GraphQL schema.", body=String, content_type = "text/html")
+    )
+)]
+pub async fn get_graphiql() -> Html<String> {
+    // Relative to the page, so it works behind any path prefix.
+    Html(
+        GraphiQLSource::build()
+            .endpoint("graphql")
+            .title("Planned outages GraphQL")
+            .finish(),
+    )
+}
+
+#[cfg(test)]
+mod graphql_tests {
+    use super::{SCHEMA, schema_sdl};
+
+    #[test]
+    fn schema_exports_nested_types() {
+        let sdl = schema_sdl();
+
+        assert!(sdl.contains("input IncidentFilter"));
+        assert!(sdl.contains("type IncidentConnection"));
+        assert!(sdl.contains("upcomingIncidents(first: Int, after: String): IncidentConnection!"));
+        assert!(sdl.contains("history: [IncidentRevision!]!"));
+    }
+
+    #[tokio::test]
+    async fn invalid_paging_arguments() {
+        let response = SCHEMA
+            .execute(r#"{ incidents(after: "x") { edges { cursor } } }"#)
+            .await;
+        assert_eq!("Invalid cursor.", response.errors[0].message);
+
+        let response = SCHEMA.execute("{ incidents(first: -1) { edges { cursor } } }").await;
+        assert_eq!("`first` must not be negative.", response.errors[0].message);
+    }
+
+    #[tokio::test]
+    async fn too_complex_queries_are_refused() {
+        let response = SCHEMA
+            .execute(
+                "{ counties { upcomingIncidents(first: 100) { edges { node { id day history { eventId kind } \
+                 duplicates { id day } } } } } }",
+            )
+            .await;
+
+        assert_eq!("Query is too complex.", response.errors[0].message);
+    }
+}
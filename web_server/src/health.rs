@@ -0,0 +1,233 @@
+use crate::AppState;
+use crate::error::{ApiError, ProblemDetails};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Pool, Postgres};
+use std::ops::Deref;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::time::timeout;
+use utoipa::ToSchema;
+
+/// Probes give up quickly, a pool waiting for a connection would only delay the answer.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The changelog the binary was built with, its last changeset has to be applied before serving.
+const CHANGELOG: &str = include_str!("../../db/changelog/db.changelog-master.yaml");
+
+static LATEST_MIGRATION: LazyLock<String> = LazyLock::new(|| latest_migration(CHANGELOG).unwrap_or_default());
+
+/// Liquibase stores the path of the changelog files as it was given to it, so only the file name is compared.
+const MIGRATION_APPLIED_QUERY: &str =
+    "SELECT EXISTS (SELECT 1 FROM databasechangelog WHERE right(filename, char_length($1)) = $1)";
+
+const LAST_INGESTION_QUERY: &str = "SELECT completed_at, received_count, stored_count, cancelled_count \
+ FROM ingestions ORDER BY completed_at DESC LIMIT 1";
+
+const INCIDENT_COUNTS_QUERY: &str = "SELECT MAX(day) FILTER (WHERE duplicate_of IS NULL) AS newest_incident_day, \
+ COUNT(*) FILTER (WHERE duplicate_of IS NULL) AS incident_count, \
+ COUNT(*) FILTER (WHERE duplicate_of IS NULL AND cancelled_at IS NULL AND day >= $1) AS upcoming_incident_count, \
+ COUNT(*) FILTER (WHERE duplicate_of IS NULL AND cancelled_at IS NOT NULL) AS cancelled_incident_count, \
+ COUNT(*) FILTER (WHERE duplicate_of IS NOT NULL) AS duplicate_incident_count \
+ FROM incidents";
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Liveness {
+    #[schema(example = "ok")]
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    /// A connection of the pool answered.
+    pub database: bool,
+    /// The last changeset known to the service is applied.
+    pub migrations: bool,
+    #[schema(example = "014-create-ingestions.sql")]
+    pub latest_migration: String,
+}
+
+/// A feed stored by the scraper endpoint.
+#[derive(Debug, Serialize, Deserialize, ToSchema, FromRow)]
+pub struct Ingestion {
+    #[schema(value_type = String, format = DateTime)]
+    pub completed_at: DateTime<Utc>,
+    /// Incidents in the feed.
+    pub received_count: i32,
+    pub stored_count: i32,
+    /// Incidents cancelled because the feed no longer announced them.
+    pub cancelled_count: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, FromRow)]
+pub struct IncidentCounts {
+    #[schema(value_type = Option<String>, format = Date)]
+    pub newest_incident_day: Option<NaiveDate>,
+    /// Incidents which are not republished copies of another one.
+    pub incident_count: i64,
+    pub upcoming_incident_count: i64,
+    pub cancelled_incident_count: i64,
+    pub duplicate_incident_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ServiceStatus {
+    pub last_ingestion: Option<Ingestion>,
+    /// A feed was stored recently enough, always true when no maximum age is configured.
+    pub fresh_data: bool,
+    #[serde(flatten)]
+    pub incidents: IncidentCounts,
+}
+
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    responses(
+        (status=200, description = "The process is able to answer.", body=Liveness)
+    )
+)]
+pub async fn get_liveness() -> Json<Liveness> {
+    Json(Liveness {
+        status: String::from("ok"),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    responses(
+        (status=200, description = "The database is reachable and migrated.", body=Readiness),
+        (status=503, description = "One of the checks failed.", body=Readiness)
+    )
+)]
+/// The freshness of the incidents is left out: the scraper posts the feeds through the service, a pod taken out for
+/// stale incidents would never receive the next feed. It is reported by the status instead.
+pub async fn get_readiness(state: State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let pg_pool = state.pg_pool.deref();
+    let database = timeout(READINESS_TIMEOUT, sqlx::query("SELECT 1").execute(pg_pool))
+        .await
+        .is_ok_and(|result| result.is_ok());
+    let migrations = database && migration_applied(pg_pool, &LATEST_MIGRATION).await;
+
+    let readiness = Readiness {
+        ready: database && migrations,
+        database,
+        migrations,
+        latest_migration: LATEST_MIGRATION.clone(),
+    };
+    if !readiness.ready {
+        warn!("Not ready to serve: {:?}", readiness);
+    }
+
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/status",
+    responses(
+        (status=200, description = "The last stored feed, whether it is recent enough and the incident counts.", body=ServiceStatus),
+        (status=500, description = "Error reading the status.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_status(state: State<AppState>) -> Result<Json<ServiceStatus>, ApiError> {
+    let last_ingestion: Option<Ingestion> = sqlx::query_as(LAST_INGESTION_QUERY)
+        .fetch_optional(state.pg_pool.deref())
+        .await?;
+    let incidents: IncidentCounts = sqlx::query_as(INCIDENT_COUNTS_QUERY)
+        .bind(Utc::now().date_naive())
+        .fetch_one(state.pg_pool.deref())
+        .await?;
+
+    let fresh_data = is_fresh(
+        last_ingestion.as_ref().map(|ingestion| ingestion.completed_at),
+        state.max_data_age,
+        Utc::now(),
+    );
+    if !fresh_data {
+        warn!("No feed was stored recently, the last one: {:?}", last_ingestion);
+    }
+
+    Ok(Json(ServiceStatus {
+        last_ingestion,
+        fresh_data,
+        incidents,
+    }))
+}
+
+async fn migration_applied(pg_pool: &Pool<Postgres>, migration: &str) -> bool {
+    let applied = sqlx::query_scalar(MIGRATION_APPLIED_QUERY)
+        .bind(migration)
+        .fetch_one(pg_pool);
+    match timeout(READINESS_TIMEOUT, applied).await {
+        Ok(Ok(applied)) => applied,
+        Ok(Err(err)) => {
+            warn!("Could not read the applied migrations: {}", err);
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+/// The file name of the last changeset included by the master changelog.
+fn latest_migration(changelog: &str) -> Option<String> {
+    changelog
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("file:"))
+        .filter_map(|path| path.trim().rsplit('/').next())
+        .map(str::to_string)
+        .next_back()
+}
+
+/// Without a maximum age the data is always fresh, otherwise a feed has to have been stored within it.
+fn is_fresh(last_ingestion_at: Option<DateTime<Utc>>, max_age: Option<TimeDelta>, now: DateTime<Utc>) -> bool {
+    match (max_age, last_ingestion_at) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(max_age), Some(last_ingestion_at)) => now - last_ingestion_at <= max_age,
+    }
+}
+
+#[cfg(test)]
+mod health_tests {
+    use super::{CHANGELOG, is_fresh, latest_migration};
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use std::path::Path;
+
+    #[test]
+    fn latest_migration_is_the_last_included_changeset() {
+        let changelog = "databaseChangeLog:\n  - include:\n      file: changelog/changes/001-a.sql\n  - include:\n      \
+         file: changelog/changes/002-b.sql\n";
+
+        assert_eq!(Some("002-b.sql".to_string()), latest_migration(changelog));
+        assert_eq!(None, latest_migration("databaseChangeLog:\n"));
+    }
+
+    #[test]
+    fn latest_migration_exists() {
+        let migration = latest_migration(CHANGELOG).unwrap();
+
+        assert!(Path::new("../db/changelog/changes").join(migration).exists());
+    }
+
+    #[test]
+    fn freshness_of_the_data() {
+        let now = Utc.with_ymd_and_hms(2025, 8, 8, 12, 0, 0).unwrap();
+        let max_age = Some(TimeDelta::hours(24));
+
+        assert!(is_fresh(None, None, now));
+        assert!(!is_fresh(None, max_age, now));
+        assert!(is_fresh(Some(now - TimeDelta::hours(23)), max_age, now));
+        assert!(!is_fresh(Some(now - TimeDelta::hours(25)), max_age, now));
+    }
+}
@@ -1,9 +1,29 @@
 use crate::metrics::Metrics;
+use crate::notifications::consent::Links;
+use chrono::TimeDelta;
+use common::configuration::SubscriptionLimits;
 use sqlx::{Pool, Postgres};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, broadcast};
 
+pub mod analytics;
+pub mod caching;
+pub mod calendar;
+pub mod catalogue;
+pub mod error;
+pub mod events;
+pub mod export;
+pub mod extract;
+pub mod geojson;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod health;
+pub mod lookup;
 pub mod metrics;
+pub mod notifications;
+pub mod openapi;
+pub mod rate_limit;
+pub mod routes;
 pub mod scraper;
 pub mod web_api;
 
@@ -13,4 +33,16 @@ pub struct AppState {
     pub pg_pool: Arc<Pool<Postgres>>,
     pub categories: Vec<String>,
     pub metrics: Arc<RwLock<Metrics>>,
-}
\ No newline at end of file
+    /// Notified every time the scraper stored incidents, wakes up the incident streams.
+    pub incident_notifications: broadcast::Sender<()>,
+    /// Server URLs advertised by the OpenAPI document.
+    pub openapi_servers: Vec<String>,
+    /// Age of the last stored feed past which the status reports stale data, it is always fresh when unset.
+    pub max_data_age: Option<TimeDelta>,
+    /// Signs the unsubscribe links of the messages and checks the ones followed.
+    pub links: Links,
+    /// The subscriptions may target private, loopback and link-local addresses.
+    pub allow_private_targets: bool,
+    /// How many subscriptions the clients and the contacts may create.
+    pub subscription_limits: SubscriptionLimits,
+}
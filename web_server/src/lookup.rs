@@ -0,0 +1,435 @@
+use crate::AppState;
+use crate::caching::CachedParameters;
+use crate::error::{ApiError, ProblemDetails};
+use crate::extract::Query;
+use crate::web_api::Incident;
+use axum::Json;
+use axum::extract::State;
+use chrono::Utc;
+use common::normalization::{normalize_locality, to_key};
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, Pool, Postgres};
+use std::ops::Deref;
+use strsim::jaro_winkler;
+use utoipa::{IntoParams, ToSchema};
+
+/// Words of an address or of an incident description which do not name a street: street types, building parts and
+/// administrative prefixes.
+const NON_STREET_WORDS: [&str; 39] = [
+    "str",
+    "strada",
+    "strazile",
+    "bd",
+    "bld",
+    "bdul",
+    "bulevardul",
+    "calea",
+    "sos",
+    "soseaua",
+    "aleea",
+    "al",
+    "piata",
+    "intrarea",
+    "int",
+    "splai",
+    "splaiul",
+    "drum",
+    "drumul",
+    "nr",
+    "numar",
+    "bl",
+    "bloc",
+    "sc",
+    "scara",
+    "ap",
+    "et",
+    "jud",
+    "judetul",
+    "loc",
+    "localitatea",
+    "mun",
+    "municipiul",
+    "oras",
+    "orasul",
+    "com",
+    "comuna",
+    "sat",
+    "partial",
+];
+
+/// Street words shorter than this are initials or abbreviations which match too many descriptions.
+const MIN_STREET_WORD_LENGTH: usize = 3;
+
+/// Similarity under which a street is not considered to be one of the streets listed by an incident.
+pub(crate) const MIN_STREET_SIMILARITY: f64 = 0.85;
+
+/// Confidence given to the street of an incident whose description does not list any street, the outage usually
+/// covering the whole locality.
+const UNLISTED_STREETS_CONFIDENCE: f64 = 0.6;
+
+/// Confidence given to the incidents of the county which are not announced for the locality of the address.
+const OTHER_LOCALITY_CONFIDENCE: f64 = 0.5;
+
+const MIN_CONFIDENCE: f64 = 0.5;
+
+const KNOWN_LOCALITIES_QUERY: &str = "SELECT county_key, name_key FROM localities \
+ UNION SELECT county_key, location_key FROM incidents \
+ WHERE day >= $1 AND county_key IS NOT NULL AND location_key IS NOT NULL";
+
+const KNOWN_COUNTIES_QUERY: &str = "SELECT key FROM counties";
+
+const UPCOMING_INCIDENTS_QUERY: &str = "SELECT * FROM incidents \
+ WHERE day >= $1 AND cancelled_at IS NULL AND duplicate_of IS NULL AND county_key = $2 ORDER BY day, id";
+
+#[derive(Deserialize, IntoParams)]
+pub struct AddressLookupParams {
+    /// Free-form address, e.g. `Cluj-Napoca, Str. Observatorului 12`.
+    pub address: String,
+    /// Maximum number of incidents returned, 20 by default.
+    pub count: Option<usize>,
+}
+
+/// The addresses are only recognized from the places read with the incidents.
+impl CachedParameters for Query<AddressLookupParams> {}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AddressLookupResponse {
+    /// Key of the county the address was resolved to.
+    #[schema(example = "cluj")]
+    pub county: String,
+    /// Key of the locality the address was resolved to, when one was recognized.
+    #[schema(example = "cluj-napoca")]
+    pub locality: Option<String>,
+    /// Words of the address used to look for the street in the incident descriptions.
+    #[schema(example = "observatorului")]
+    pub street: Option<String>,
+    /// Upcoming incidents, the most likely to affect the address first.
+    pub matches: Vec<AddressMatch>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AddressMatch {
+    /// Between 0 and 1, how likely it is for the incident to affect the address.
+    #[schema(example = 0.95)]
+    pub confidence: f64,
+    pub incident: Incident,
+}
+
+/// The county and locality keys recognized in an address and the remaining words naming the street.
+#[derive(Debug, PartialEq)]
+struct ResolvedAddress {
+    county: Option<String>,
+    locality: Option<String>,
+    street_words: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/incidents/lookup",
+    params(
+        AddressLookupParams
+    ),
+    responses(
+        (status=200, description = "Upcoming incidents which may affect the address, ranked by confidence.", body=AddressLookupResponse),
+        (status=400, description = "The county or locality of the address could not be recognized.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error getting the incidents.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn lookup_address(
+    state: State<AppState>,
+    params: Query<AddressLookupParams>,
+) -> Result<Json<AddressLookupResponse>, ApiError> {
+    let today = Utc::now().date_naive();
+
+    let known = load_known_places(state.pg_pool.deref(), today).await?;
+    let resolved = resolve_address(&params.address, &known.0, &known.1);
+
+    let county = resolved.county.clone().ok_or_else(|| {
+        ApiError::InvalidParameter(String::from(
+            "Could not recognize the county or locality of the address.",
+        ))
+    })?;
+
+    let incidents: Vec<Incident> = sqlx::query_as(UPCOMING_INCIDENTS_QUERY)
+        .bind(today)
+        .bind(&county)
+        .fetch_all(state.pg_pool.deref())
+        .await?;
+
+    let mut matches: Vec<AddressMatch> = incidents
+        .into_iter()
+        .map(|incident| AddressMatch {
+            confidence: confidence(&resolved, &incident),
+            incident,
+        })
+        .filter(|address_match| address_match.confidence >= MIN_CONFIDENCE)
+        .collect();
+    // Stable sort, the incidents closest in time come first among the ones with the same confidence.
+    matches.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    matches.truncate(params.count.unwrap_or(20));
+
+    Ok(Json(AddressLookupResponse {
+        county,
+        locality: resolved.locality,
+        street: (!resolved.street_words.is_empty()).then(|| resolved.street_words.join(" ")),
+        matches,
+    }))
+}
+
+async fn load_known_places(
+    pg_pool: &Pool<Postgres>,
+    today: chrono::NaiveDate,
+) -> Result<(Vec<String>, Vec<(String, String)>), Error> {
+    let counties: Vec<String> = sqlx::query_scalar(KNOWN_COUNTIES_QUERY).fetch_all(pg_pool).await?;
+    let localities: Vec<(String, String)> = sqlx::query_as(KNOWN_LOCALITIES_QUERY)
+        .bind(today)
+        .fetch_all(pg_pool)
+        .await?;
+
+    Ok((counties, localities))
+}
+
+/// Recognizes the county and locality keys in an address. When several localities are found, the ones in a county
+/// also named in the address and then the ones with the longest name are preferred, so `Str. Alba Iulia, Turda` is
+/// resolved to Turda and not to the county of Alba. The words which are left name the street.
+fn resolve_address(address: &str, counties: &[String], localities: &[(String, String)]) -> ResolvedAddress {
+    let words: Vec<String> = to_key(address).split('-').map(str::to_string).collect();
+    let mut used = vec![false; words.len()];
+
+    let county_matches: Vec<(&String, usize, usize)> = counties
+        .iter()
+        .filter_map(|county| find_words(&words, county).map(|(start, len)| (county, start, len)))
+        .collect();
+    let longest_county = county_matches.iter().max_by_key(|(_, _, len)| *len);
+
+    let locality = localities
+        .iter()
+        .filter_map(|(county, name)| find_words(&words, name).map(|(start, len)| (county, name, start, len)))
+        .max_by_key(|(county, _, _, len)| {
+            let county_named = county_matches.iter().any(|(named, _, _)| named == county);
+            (county_named, *len)
+        });
+
+    let county_words = match locality {
+        Some((county, _, start, len)) => {
+            used[start..start + len].fill(true);
+            // The county is written after the locality, a county named before it is part of the street.
+            find_words(&words[start + len..], county)
+                .map(|(county_start, county_len)| (start + len + county_start, county_len))
+        }
+        None => longest_county.map(|(_, start, len)| (*start, *len)),
+    };
+    if let Some((start, len)) = county_words {
+        used[start..start + len].fill(true);
+    }
+
+    ResolvedAddress {
+        county: locality
+            .map(|(county, _, _, _)| county)
+            .or(longest_county.map(|(county, _, _)| *county))
+            .cloned(),
+        locality: locality.map(|(_, name, _, _)| name.clone()),
+        street_words: street_words(&words, &used),
+    }
+}
+
+/// Finds the words of a key, in order and next to each other, among the words of an address.
+fn find_words(words: &[String], key: &str) -> Option<(usize, usize)> {
+    let key_words: Vec<&str> = key.split('-').collect();
+    if key.is_empty() || key_words.len() > words.len() {
+        return None;
+    }
+
+    (0..=words.len() - key_words.len())
+        .find(|start| {
+            words[*start..*start + key_words.len()]
+                .iter()
+                .zip(key_words.iter())
+                .all(|(word, key_word)| word == key_word)
+        })
+        .map(|start| (start, key_words.len()))
+}
+
+fn street_words(words: &[String], used: &[bool]) -> Vec<String> {
+    words
+        .iter()
+        .zip(used.iter())
+        .filter(|(_, used)| !**used)
+        .map(|(word, _)| word)
+        .filter(|word| is_street_word(word))
+        .cloned()
+        .collect()
+}
+
+fn is_street_word(word: &str) -> bool {
+    word.chars().count() >= MIN_STREET_WORD_LENGTH
+        && !word.chars().all(|c| c.is_ascii_digit())
+        && !NON_STREET_WORDS.contains(&word)
+}
+
+fn confidence(address: &ResolvedAddress, incident: &Incident) -> f64 {
+    let description_words = street_words_of(&incident.description);
+
+    let locality_confidence = match &address.locality {
+        Some(locality) if !is_announced_for(locality, incident, &description_words) => OTHER_LOCALITY_CONFIDENCE,
+        _ => 1.0,
+    };
+
+    let street_confidence = if address.street_words.is_empty() {
+        1.0
+    } else if description_words.is_empty() {
+        UNLISTED_STREETS_CONFIDENCE
+    } else {
+        let similarity = street_similarity(&address.street_words, &description_words);
+        if similarity < MIN_STREET_SIMILARITY {
+            return 0.0;
+        }
+        similarity
+    };
+
+    (locality_confidence * street_confidence * 100.0).round() / 100.0
+}
+
+/// The words of a text which may name a street: of an incident description, where they may also name a locality next
+/// to the one of the incident, or of a street typed by a user.
+pub(crate) fn street_words_of(text: &str) -> Vec<String> {
+    to_key(text)
+        .split('-')
+        .filter(|word| is_street_word(word))
+        .map(str::to_string)
+        .collect()
+}
+
+/// An incident is announced for a locality when it is its location or when the description mentions it.
+pub(crate) fn is_announced_for(locality: &str, incident: &Incident, description_words: &[String]) -> bool {
+    normalize_locality(&incident.location) == locality || find_words(description_words, locality).is_some()
+}
+
+/// Average, over the words of the street, of the best similarity with a word of the description.
+pub(crate) fn street_similarity(street_words: &[String], description_words: &[String]) -> f64 {
+    let total: f64 = street_words
+        .iter()
+        .map(|street_word| {
+            description_words
+                .iter()
+                .map(|description_word| jaro_winkler(street_word, description_word))
+                .fold(0.0, f64::max)
+        })
+        .sum();
+
+    total / street_words.len() as f64
+}
+
+#[cfg(test)]
+mod lookup_tests {
+    use super::{ResolvedAddress, confidence, resolve_address};
+    use crate::web_api::Incident;
+    use chrono::{NaiveDate, Utc};
+
+    fn counties() -> Vec<String> {
+        ["alba", "cluj", "tulcea"].iter().map(|c| c.to_string()).collect()
+    }
+
+    fn localities() -> Vec<(String, String)> {
+        [
+            ("alba", "sebes"),
+            ("cluj", "cluj-napoca"),
+            ("cluj", "turda"),
+            ("tulcea", "tulcea"),
+        ]
+        .iter()
+        .map(|(county, name)| (county.to_string(), name.to_string()))
+        .collect()
+    }
+
+    fn incident(location_key: &str, description: &str) -> Incident {
+        Incident {
+            external_id: "134691 - Retele Electrice".to_string(),
+            county: "CLUJ".to_string(),
+            location: format!("LOC. {}", location_key.to_uppercase()),
+            day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+            start_time: None,
+            end_time: None,
+            description: description.to_string(),
+            id: 1,
+            revision: 1,
+            updated_at: Utc::now(),
+            cancelled_at: None,
+            duplicate_of: None,
+        }
+    }
+
+    fn address(locality: Option<&str>, street_words: &[&str]) -> ResolvedAddress {
+        ResolvedAddress {
+            county: Some("cluj".to_string()),
+            locality: locality.map(str::to_string),
+            street_words: street_words.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn resolve_locality_and_street() {
+        let resolved = resolve_address("Cluj-Napoca, Str. Observatorului 12", &counties(), &localities());
+
+        assert_eq!(address(Some("cluj-napoca"), &["observatorului"]), resolved);
+    }
+
+    #[test]
+    fn resolve_prefers_locality_over_county_in_street_name() {
+        let resolved = resolve_address("Str. Alba Iulia nr. 3, Turda", &counties(), &localities());
+
+        assert_eq!(Some("cluj".to_string()), resolved.county);
+        assert_eq!(Some("turda".to_string()), resolved.locality);
+        assert_eq!(vec!["alba".to_string(), "iulia".to_string()], resolved.street_words);
+    }
+
+    #[test]
+    fn resolve_locality_named_like_its_county() {
+        let resolved = resolve_address("Strada Pacii 7, Tulcea, jud. Tulcea", &counties(), &localities());
+
+        assert_eq!(Some("tulcea".to_string()), resolved.county);
+        assert_eq!(Some("tulcea".to_string()), resolved.locality);
+        assert_eq!(vec!["pacii".to_string()], resolved.street_words);
+    }
+
+    #[test]
+    fn resolve_keeps_street_named_like_the_county() {
+        let resolved = resolve_address("Str. Alba Iulia 5, Sebeș", &counties(), &localities());
+
+        assert_eq!(Some("sebes".to_string()), resolved.locality);
+        assert_eq!(vec!["alba".to_string(), "iulia".to_string()], resolved.street_words);
+    }
+
+    #[test]
+    fn resolve_county_only() {
+        let resolved = resolve_address("Jud. Cluj, Str. Memorandumului", &counties(), &localities());
+
+        assert_eq!(Some("cluj".to_string()), resolved.county);
+        assert_eq!(None, resolved.locality);
+    }
+
+    #[test]
+    fn resolve_unknown_place() {
+        let resolved = resolve_address("Str. Observatorului 12", &counties(), &localities());
+
+        assert_eq!(None, resolved.county);
+    }
+
+    #[test]
+    fn confidence_ranks_street_matches() {
+        let address = address(Some("cluj-napoca"), &["observatorului"]);
+
+        let listed = incident("cluj-napoca", "Strada: STR OBSERVATORULUI, REPUBLICII Numar: ");
+        let misspelled = incident("cluj-napoca", "Strada: STR OBSERVATORULU Numar: ");
+        let unlisted = incident("cluj-napoca", "Strada:  Numar: ");
+        let other_street = incident("cluj-napoca", "Strada: STR MEMORANDUMULUI Numar: ");
+        let other_locality = incident("turda", "Strada: STR OBSERVATORULUI Numar: ");
+
+        assert_eq!(1.0, confidence(&address, &listed));
+        assert!(confidence(&address, &misspelled) > 0.9);
+        assert!(confidence(&address, &misspelled) < 1.0);
+        assert_eq!(0.6, confidence(&address, &unlisted));
+        assert_eq!(0.0, confidence(&address, &other_street));
+        assert_eq!(0.5, confidence(&address, &other_locality));
+    }
+}
@@ -1,17 +1,22 @@
-use axum::routing::post;
-use axum::{middleware, routing::get, Router};
+use chrono::TimeDelta;
 use common::configuration::{self, ServiceConfiguration};
-use log::{info, LevelFilter};
+use log::{LevelFilter, error, info};
 use simple_logger::SimpleLogger;
 use sqlx::postgres::PgPoolOptions;
 use std::env;
+use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::{net::TcpListener, runtime};
 use tower_http::cors::CorsLayer;
-use web_server::metrics::{monitor_endpoint, serve_metrics, Metrics};
-use web_server::{scraper, web_api, AppState};
+use web_server::events::incident_notifications;
+use web_server::metrics::Metrics;
+use web_server::notifications::consent::Links;
+use web_server::notifications::dispatcher::{Dispatcher, configured_notifiers};
+use web_server::notifications::templates::Templates;
+use web_server::scraper::persistence::backfill_normalized_keys;
+use web_server::{AppState, routes};
 
 fn main() {
     let config = load_configuration();
@@ -24,6 +29,15 @@ fn main() {
 
     info!("Using configuration: {:?}", config);
 
+    let links = match Links::configured(&config) {
+        Ok(links) => links,
+        Err(err) => panic!("The links of the notifications cannot be signed: {}", err),
+    };
+    let templates = match Templates::new(&config.templates, links.clone()) {
+        Ok(templates) => Arc::new(templates),
+        Err(err) => panic!("The notification templates are not valid: {}", err),
+    };
+
     let app_metrics = Metrics::default();
 
     let tokio_runtime = runtime::Builder::new_multi_thread()
@@ -39,15 +53,36 @@ fn main() {
         let db_host = config.db_host.clone().unwrap();
         let connection_string = format!("postgres://{}:{}@{}", db_user, db_password, db_host);
         let pg_pool = PgPoolOptions::new().connect(connection_string.as_str()).await.unwrap();
+        let pg_pool = Arc::new(pg_pool);
+
+        if let Err(err) = backfill_normalized_keys(pg_pool.clone()).await {
+            error!("Could not backfill the normalized keys of the incidents: {}", err);
+        }
+
+        let notifiers = configured_notifiers(&config, templates);
+        let dispatcher = Dispatcher::new(
+            pg_pool.clone(),
+            notifiers,
+            config.outbox.clone(),
+            config.webhook.clone(),
+        );
+        let incident_notifications = incident_notifications();
+        tokio::spawn(dispatcher.run(incident_notifications.subscribe()));
 
         let state = AppState {
             ping_msg: "The state of ping.".to_string(),
             categories: config.categories,
             metrics: Arc::new(RwLock::new(app_metrics)),
-            pg_pool: Arc::new(pg_pool),
+            incident_notifications,
+            openapi_servers: config.openapi_servers,
+            max_data_age: config.max_data_age_hours.map(|hours| TimeDelta::hours(hours.into())),
+            links,
+            allow_private_targets: config.allow_private_targets,
+            subscription_limits: config.subscriptions,
+            pg_pool,
         };
 
-        let mut app = create_app(state);
+        let mut app = routes::create_app(state);
 
         if config.cors_permissive {
             app = app.layer(CorsLayer::permissive());
@@ -55,21 +90,13 @@ fn main() {
         let addr = format!("0.0.0.0:{}", config.http_port);
         let listener = TcpListener::bind(addr).await.expect("Could not open port.");
 
-        axum::serve(listener, app).await.unwrap();
+        // The clients creating subscriptions are told apart by their address.
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .unwrap();
     });
 }
 
-fn create_app(state: AppState) -> Router {
-    Router::new()
-        .route("/api/ping", get(web_api::ping))
-        .route("/api/incidents/count", get(web_api::count_incidents))
-        .route("/api/incidents/all", get(web_api::get_all_incidents))
-        .route("/scraper", post(scraper::scraper_api::submit_rss))
-        .route("/metrics", get(serve_metrics))
-        .route_layer(middleware::from_fn_with_state(state.clone(), monitor_endpoint))
-        .with_state(state)
-}
-
 fn load_configuration() -> ServiceConfiguration {
     let cli_arg = env::args().nth(1);
     let config = cli_arg.map(|file_path| configuration::get_configuration(&file_path));
@@ -78,11 +105,10 @@ fn load_configuration() -> ServiceConfiguration {
         panic!("No configuration has been provided.");
     }
 
-    let config = config.unwrap();
-    if config.is_err() {
-        panic!("some other config issue: {}", config.unwrap_err());
-    }
-    let config = config.unwrap();
+    let config = match config.unwrap() {
+        Ok(config) => config,
+        Err(err) => panic!("some other config issue: {}", err),
+    };
 
     info!("Configuration: {}", config);
 
@@ -1,7 +1,7 @@
-use crate::metrics::AppMetrics::{RequestProcessingTime, RssIncidentsCount};
 use crate::AppState;
+use crate::error::{ApiError, ProblemDetails};
+use crate::metrics::AppMetrics::{RequestProcessingTime, RssIncidentsCount};
 use axum::extract::{MatchedPath, Request, State};
-use axum::http::StatusCode;
 use axum::middleware::Next;
 use axum::response::Response;
 use log::debug;
@@ -116,14 +116,20 @@ pub async fn monitor_endpoint(state: State<AppState>, request: Request, next: Ne
     response
 }
 
-pub async fn serve_metrics(state: State<AppState>) -> Result<String, (StatusCode, String)> {
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+            (status=200, description = "Prometheus metrics in the text exposition format.", body=String, content_type = "text/plain"),
+            (status=500, description = "Error encoding the metrics.", body=ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn serve_metrics(state: State<AppState>) -> Result<String, ApiError> {
     let mut buffer = String::new();
     let metrics_registry = &state.metrics.read().await.registry;
-    let r = encode_registry(&mut buffer, metrics_registry);
 
-    if r.is_err() {
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, buffer));
-    }
+    encode_registry(&mut buffer, metrics_registry)
+        .map_err(|err| ApiError::Internal(format!("Could not encode the metrics: {}", err)))?;
 
     Ok(buffer)
 }
@@ -0,0 +1,486 @@
+use crate::AppState;
+use crate::error::{ApiError, ProblemDetails};
+use crate::extract::{Json, Path, Query};
+use crate::notifications::notifier::public_url;
+use crate::notifications::subscriptions::Subscription;
+use crate::notifications::templates::Language;
+use axum::extract::State;
+use axum::http::header::ACCEPT;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, TimeDelta, Utc};
+use common::configuration::ServiceConfiguration;
+use handlebars::html_escape;
+use hmac::{Hmac, Mac};
+use log::info;
+use serde::Deserialize;
+use sha2::Sha256;
+use sqlx::FromRow;
+use std::ops::Deref;
+use std::sync::Arc;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+/// Wrong codes a subscription takes before it has to be replaced to get a new one.
+pub const MAX_VERIFICATION_FAILURES: i32 = 5;
+
+/// The codes are valid for a day after the subscription was created or replaced, and sent only meanwhile.
+pub const VERIFICATION_VALIDITY_HOURS: i32 = 24;
+
+/// The signatures of the tokens are cut to 96 bits, keeping the links short enough for an SMS.
+const SIGNATURE_BYTES: usize = 12;
+
+/// The purposes of the signed tokens, part of what is signed so that a token only serves its own.
+const UNSUBSCRIBE: &str = "unsubscribe";
+const MANAGE: &str = "manage";
+
+const SUBSCRIPTION_QUERY: &str = "SELECT * FROM subscriptions WHERE id = $1";
+
+const VERIFY_QUERY: &str = "UPDATE subscriptions SET verified_at = now(), verification_code = NULL, \
+ verification_due_at = NULL WHERE id = $1 AND verified_at IS NULL AND verification_code = $2 \
+ AND verification_failures < $3 AND updated_at > now() - make_interval(hours => $4) RETURNING *";
+
+/// Every refused code counts, so that the codes cannot be guessed.
+const FAILED_VERIFICATION_QUERY: &str = "UPDATE subscriptions SET verification_failures = verification_failures + 1 \
+ WHERE id = $1 AND verified_at IS NULL RETURNING verification_failures, updated_at";
+
+const DELETE_SUBSCRIPTION_QUERY: &str = "DELETE FROM subscriptions WHERE id = $1";
+
+/// The code confirming the contact of a new subscription, sent before any of its notifications.
+#[derive(Debug, Clone, FromRow)]
+pub struct Verification {
+    #[sqlx(flatten)]
+    pub subscription: Subscription,
+    #[sqlx(rename = "verification_code")]
+    pub code: String,
+}
+
+/// The links of the messages to the service: the verification of the subscriptions and their unsubscribe links, signed
+/// so that they work without logging in and cannot be made up for the subscriptions of others. The management tokens,
+/// given to whoever creates a subscription, are signed the same way.
+#[derive(Clone)]
+pub struct Links {
+    public_url: String,
+    secret: Arc<str>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct VerificationCode {
+    /// The code sent to the contact of the subscription.
+    #[param(example = "042517")]
+    pub code: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct UnsubscribeToken {
+    /// The token of the unsubscribe links of the messages.
+    pub token: String,
+}
+
+impl Links {
+    pub fn new(public_url: &str, secret: &str) -> Links {
+        Links {
+            public_url: public_url.trim_end_matches('/').to_string(),
+            secret: Arc::from(secret),
+        }
+    }
+
+    /// The links of the configured service. The secret is mandatory: the webhook, ntfy and Gotify notifiers need no
+    /// configuration, so every service sends links, and they have to keep working across restarts and replicas.
+    pub fn configured(configuration: &ServiceConfiguration) -> Result<Links, String> {
+        match configuration.unsubscribe_secret.as_deref().map(str::trim) {
+            Some(secret) if !secret.is_empty() => Ok(Links::new(&public_url(configuration), secret)),
+            _ => Err(String::from(
+                "service.unsubscribe_secret is mandatory, it signs the links of the notifications.",
+            )),
+        }
+    }
+
+    /// Opening the link verifies the subscription, the code can also be typed in.
+    pub fn verification_url(&self, subscription_id: i64, code: &str) -> String {
+        format!(
+            "{}/api/subscriptions/{}/verify?code={}",
+            self.public_url, subscription_id, code
+        )
+    }
+
+    pub fn unsubscribe_url(&self, subscription_id: i64) -> String {
+        format!(
+            "{}/api/unsubscribe?token={}",
+            self.public_url,
+            self.unsubscribe_token(subscription_id)
+        )
+    }
+
+    /// The id of the subscription and its signature, e.g. `7.Xq3vO0hmLw2RUm5J`.
+    pub fn unsubscribe_token(&self, subscription_id: i64) -> String {
+        self.token(UNSUBSCRIBE, subscription_id)
+    }
+
+    /// The subscription of an unsubscribe token signed with the secret of the service.
+    pub fn subscription_of(&self, token: &str) -> Option<i64> {
+        self.signed_subscription(UNSUBSCRIBE, token)
+    }
+
+    /// Reads, replaces and deletes the subscription, shaped as the unsubscribe tokens but never accepted as one.
+    pub fn management_token(&self, subscription_id: i64) -> String {
+        self.token(MANAGE, subscription_id)
+    }
+
+    /// The subscription of a management token signed with the secret of the service.
+    pub fn managed_subscription(&self, token: &str) -> Option<i64> {
+        self.signed_subscription(MANAGE, token)
+    }
+
+    fn token(&self, purpose: &str, subscription_id: i64) -> String {
+        let signature = self.mac(purpose, subscription_id).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            subscription_id,
+            URL_SAFE_NO_PAD.encode(&signature[..SIGNATURE_BYTES])
+        )
+    }
+
+    fn signed_subscription(&self, purpose: &str, token: &str) -> Option<i64> {
+        let (subscription_id, signature) = token.trim().split_once('.')?;
+        let subscription_id: i64 = subscription_id.parse().ok()?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        if signature.len() != SIGNATURE_BYTES {
+            return None;
+        }
+        self.mac(purpose, subscription_id)
+            .verify_truncated_left(&signature)
+            .ok()
+            .map(|_| subscription_id)
+    }
+
+    fn mac(&self, purpose: &str, subscription_id: i64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).expect("HMAC takes keys of any size.");
+        mac.update(format!("{}:{}", purpose, subscription_id).as_bytes());
+        mac
+    }
+}
+
+/// Six random digits, typed in as easily from an SMS as from an e-mail.
+pub fn new_verification_code() -> String {
+    format!("{:06}", Uuid::new_v4().as_u128() % 1_000_000)
+}
+
+/// The page of the verification links, confirming the contact only when its form is sent: the mail scanners opening
+/// the links of the messages should not confirm the subscriptions of others.
+#[utoipa::path(
+    get,
+    path = "/api/subscriptions/{id}/verify",
+    params(("id" = i64, Path, description = "Id of the subscription."), VerificationCode),
+    responses(
+        (status=200, description = "Page confirming the contact of the subscription, or telling it is confirmed.", body=String, content_type = "text/html"),
+        (status=404, description = "No subscription has this id.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error getting the subscription.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn verification_page(
+    state: State<AppState>,
+    Path(id): Path<i64>,
+    Query(verification): Query<VerificationCode>,
+) -> Result<Html<String>, ApiError> {
+    let subscription: Option<Subscription> = sqlx::query_as(SUBSCRIPTION_QUERY)
+        .bind(id)
+        .fetch_optional(state.pg_pool.deref())
+        .await?;
+    let subscription = subscription.ok_or_else(|| ApiError::NotFound(format!("No subscription has the id {}.", id)))?;
+
+    let page = match (subscription.language, subscription.verified_at.is_some()) {
+        (Language::Ro, true) => Page::done(
+            "ro",
+            "Notificările sunt confirmate",
+            "Vă trimitem notificările de acum.",
+        ),
+        (Language::En, true) => Page::done("en", "The notifications are confirmed", "They are sent from now on."),
+        (Language::Ro, false) => Page::form("ro", "Confirmați notificările", "Confirm"),
+        (Language::En, false) => Page::form("en", "Confirm the notifications", "Confirm"),
+    };
+    Ok(page.render(&state.links.verification_url(id, &verification.code)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/subscriptions/{id}/verify",
+    params(("id" = i64, Path, description = "Id of the subscription."), VerificationCode),
+    responses(
+        (status=200, description = "The contact of the subscription is verified, the notifications are sent from now on.", body=Subscription),
+        (status=303, description = "The form of the verification page was sent, the page tells the contact is verified."),
+        (status=400, description = "Wrong or expired code, or too many wrong codes.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=404, description = "No subscription has this id.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error verifying the subscription.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn confirm_verification(
+    state: State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Query(verification): Query<VerificationCode>,
+) -> Result<Response, ApiError> {
+    let url = state.links.verification_url(id, verification.code.trim());
+    let verified = verify_subscription(state, Path(id), Query(verification)).await?;
+    Ok(if sent_by_browser(&headers) {
+        Redirect::to(&url).into_response()
+    } else {
+        verified.into_response()
+    })
+}
+
+/// Verifies the contact of the subscription with the code sent to it.
+pub async fn verify_subscription(
+    state: State<AppState>,
+    Path(id): Path<i64>,
+    Query(verification): Query<VerificationCode>,
+) -> Result<Json<Subscription>, ApiError> {
+    let verified: Option<Subscription> = sqlx::query_as(VERIFY_QUERY)
+        .bind(id)
+        .bind(verification.code.trim())
+        .bind(MAX_VERIFICATION_FAILURES)
+        .bind(VERIFICATION_VALIDITY_HOURS)
+        .fetch_optional(state.pg_pool.deref())
+        .await?;
+    if let Some(subscription) = verified {
+        info!("Verified the contact of the subscription {}.", id);
+        return Ok(Json(subscription));
+    }
+
+    let failed: Option<(i32, DateTime<Utc>)> = sqlx::query_as(FAILED_VERIFICATION_QUERY)
+        .bind(id)
+        .fetch_optional(state.pg_pool.deref())
+        .await?;
+    let Some((failures, updated_at)) = failed else {
+        // Sending the form again answers as the first time.
+        let subscription: Option<Subscription> = sqlx::query_as(SUBSCRIPTION_QUERY)
+            .bind(id)
+            .fetch_optional(state.pg_pool.deref())
+            .await?;
+        return subscription
+            .map(Json)
+            .ok_or_else(|| ApiError::NotFound(format!("No subscription has the id {}.", id)));
+    };
+
+    let message = if failures >= MAX_VERIFICATION_FAILURES {
+        "Too many wrong codes, delete the subscription and create it again to get a new one."
+    } else if updated_at + TimeDelta::hours(VERIFICATION_VALIDITY_HOURS.into()) < Utc::now() {
+        "The code expired, delete the subscription and create it again to get a new one."
+    } else {
+        "Wrong verification code."
+    };
+    Err(ApiError::InvalidParameter(String::from(message)))
+}
+
+/// The page of the unsubscribe links, deleting the subscription only when its form is sent.
+#[utoipa::path(
+    get,
+    path = "/api/unsubscribe",
+    params(UnsubscribeToken),
+    responses(
+        (status=200, description = "Page confirming the unsubscribe, or telling the subscription is deleted.", body=String, content_type = "text/html"),
+        (status=400, description = "The token is not valid.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error getting the subscription.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn unsubscribe_page(
+    state: State<AppState>,
+    Query(unsubscribe): Query<UnsubscribeToken>,
+) -> Result<Html<String>, ApiError> {
+    let id = unsubscribed_subscription(&state, &unsubscribe)?;
+    let subscription: Option<Subscription> = sqlx::query_as(SUBSCRIPTION_QUERY)
+        .bind(id)
+        .fetch_optional(state.pg_pool.deref())
+        .await?;
+
+    let page = match subscription.map(|subscription| subscription.language) {
+        Some(Language::Ro) => Page::form("ro", "Dezabonare de la notificări", "Dezabonare"),
+        Some(Language::En) => Page::form("en", "Unsubscribe from the notifications", "Unsubscribe"),
+        None => Page::done("en", "Unsubscribed", "No more notifications are sent."),
+    };
+    Ok(page.render(&state.links.unsubscribe_url(id)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/unsubscribe",
+    params(UnsubscribeToken),
+    responses(
+        (status=204, description = "The subscription of the token was deleted, or it was already. The e-mail clients unsubscribing in one click POST here."),
+        (status=303, description = "The form of the unsubscribe page was sent, the page tells the subscription is deleted."),
+        (status=400, description = "The token is not valid.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error deleting the subscription.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn confirm_unsubscribe(
+    state: State<AppState>,
+    headers: HeaderMap,
+    Query(unsubscribe): Query<UnsubscribeToken>,
+) -> Result<Response, ApiError> {
+    let url = state
+        .links
+        .unsubscribe_url(unsubscribed_subscription(&state, &unsubscribe)?);
+    let deleted = self::unsubscribe(state, Query(unsubscribe)).await?;
+    Ok(if sent_by_browser(&headers) {
+        Redirect::to(&url).into_response()
+    } else {
+        deleted.into_response()
+    })
+}
+
+/// Deletes the subscription of the unsubscribe token.
+pub async fn unsubscribe(
+    state: State<AppState>,
+    Query(unsubscribe): Query<UnsubscribeToken>,
+) -> Result<StatusCode, ApiError> {
+    let id = unsubscribed_subscription(&state, &unsubscribe)?;
+
+    let deleted = sqlx::query(DELETE_SUBSCRIPTION_QUERY)
+        .bind(id)
+        .execute(state.pg_pool.deref())
+        .await?;
+    if deleted.rows_affected() > 0 {
+        info!("Deleted the subscription {} through its unsubscribe link.", id);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn unsubscribed_subscription(state: &AppState, unsubscribe: &UnsubscribeToken) -> Result<i64, ApiError> {
+    state
+        .links
+        .subscription_of(&unsubscribe.token)
+        .ok_or_else(|| ApiError::InvalidParameter(String::from("Invalid unsubscribe token.")))
+}
+
+/// The forms of the pages are sent by browsers, shown the page again once done. The e-mail clients unsubscribing in one
+/// click and the API clients get the answer of the API.
+fn sent_by_browser(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// The pages of the links of the messages, a form posting back to the link or telling it was done.
+struct Page {
+    lang: &'static str,
+    title: &'static str,
+    text: Option<&'static str>,
+    button: Option<&'static str>,
+}
+
+impl Page {
+    fn form(lang: &'static str, title: &'static str, button: &'static str) -> Page {
+        Page {
+            lang,
+            title,
+            text: None,
+            button: Some(button),
+        }
+    }
+
+    fn done(lang: &'static str, title: &'static str, text: &'static str) -> Page {
+        Page {
+            lang,
+            title,
+            text: Some(text),
+            button: None,
+        }
+    }
+
+    fn render(&self, action: &str) -> Html<String> {
+        let text = self.text.map(|text| format!("<p>{}</p>\n", text)).unwrap_or_default();
+        let form = self
+            .button
+            .map(|button| {
+                format!(
+                    "<form method=\"post\" action=\"{}\"><button type=\"submit\">{}</button></form>\n",
+                    html_escape(action),
+                    button
+                )
+            })
+            .unwrap_or_default();
+        Html(format!(
+            "<!DOCTYPE html>\n<html lang=\"{lang}\">\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n\
+             <body>\n<h1>{title}</h1>\n{text}{form}</body>\n</html>\n",
+            lang = self.lang,
+            title = self.title,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod consent_tests {
+    use super::{Links, new_verification_code};
+    use common::configuration::ServiceConfigurationBuilder;
+
+    #[test]
+    fn unsubscribe_tokens_name_their_subscription() {
+        let links = Links::new("https://enel.lab.wicked/", "secret");
+
+        let token = links.unsubscribe_token(7);
+
+        assert_eq!(Some(7), links.subscription_of(&token));
+        assert_eq!(
+            format!("https://enel.lab.wicked/api/unsubscribe?token={}", token),
+            links.unsubscribe_url(7)
+        );
+        assert_eq!(
+            "https://enel.lab.wicked/api/subscriptions/7/verify?code=042517",
+            links.verification_url(7, "042517")
+        );
+    }
+
+    #[test]
+    fn forged_unsubscribe_tokens_are_refused() {
+        let links = Links::new("https://enel.lab.wicked", "secret");
+        let token = links.unsubscribe_token(7);
+        let (_, signature) = token.split_once('.').unwrap();
+
+        assert_eq!(None, links.subscription_of(&format!("8.{}", signature)));
+        assert_eq!(None, links.subscription_of(&format!("7.{}", &signature[1..])));
+        assert_eq!(None, links.subscription_of("7"));
+        assert_eq!(
+            None,
+            Links::new("https://enel.lab.wicked", "other").subscription_of(&token)
+        );
+    }
+
+    #[test]
+    fn management_tokens_only_manage() {
+        let links = Links::new("https://enel.lab.wicked", "secret");
+        let token = links.management_token(7);
+
+        assert_eq!(Some(7), links.managed_subscription(&token));
+        assert_ne!(links.unsubscribe_token(7), token);
+        assert_eq!(None, links.subscription_of(&token));
+        assert_eq!(None, links.managed_subscription(&links.unsubscribe_token(7)));
+    }
+
+    #[test]
+    fn configured_links_need_a_secret() {
+        let mut builder = ServiceConfigurationBuilder::default();
+        builder.url(String::from("https://enel.lab.wicked/rss"));
+        let configuration = builder.build().unwrap();
+
+        assert!(Links::configured(&configuration).is_err());
+
+        let mut builder = ServiceConfigurationBuilder::default();
+        builder
+            .url(String::from("https://enel.lab.wicked/rss"))
+            .unsubscribe_secret(String::from("secret"));
+        let links = Links::configured(&builder.build().unwrap()).unwrap();
+
+        assert_eq!(Some(7), links.subscription_of(&links.unsubscribe_token(7)));
+    }
+
+    #[test]
+    fn verification_codes_have_six_digits() {
+        let code = new_verification_code();
+
+        assert_eq!(6, code.len());
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::notifications::local_time;
+use crate::notifications::rules::NotificationReason;
+use crate::notifications::subscriptions::Subscription;
+use crate::web_api::Incident;
+use chrono::{DateTime, Datelike, Days, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// How often the changes matching a subscription are collected into a single message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+pub enum DigestSchedule {
+    /// Every day at the digest time.
+    Daily,
+    /// Every Monday at the digest time.
+    Weekly,
+}
+
+/// The changes collected for a subscription since its previous digest.
+#[derive(Debug, Clone)]
+pub struct Digest {
+    pub subscription: Subscription,
+    /// The latest change of each incident, the earliest outages first.
+    pub changes: Vec<DigestChange>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DigestChange {
+    pub reason: NotificationReason,
+    pub incident: Incident,
+}
+
+impl Digest {
+    /// Keeps the latest change of each incident and orders them by the start of the outages.
+    pub fn new(subscription: Subscription, changes: Vec<(i32, DigestChange)>) -> Digest {
+        let mut changes = changes;
+        changes.sort_by(|(revision, change), (other_revision, other)| {
+            (change.incident.id, other_revision).cmp(&(other.incident.id, revision))
+        });
+        changes.dedup_by_key(|(_, change)| change.incident.id);
+
+        let mut changes: Vec<DigestChange> = changes.into_iter().map(|(_, change)| change).collect();
+        changes.sort_by_key(|change| (change.incident.day, change.incident.start_time, change.incident.id));
+        Digest { subscription, changes }
+    }
+}
+
+/// The first digest time after `now`, in Romanian time.
+pub fn next_digest_at(schedule: DigestSchedule, time: NaiveTime, now: DateTime<Utc>) -> DateTime<Utc> {
+    let local_now = local_time::to_local(now);
+    let mut day = local_now.date();
+    loop {
+        let scheduled = schedule == DigestSchedule::Daily || day.weekday() == Weekday::Mon;
+        if scheduled && day.and_time(time) > local_now {
+            return local_time::to_utc(day.and_time(time));
+        }
+        day = day + Days::new(1);
+    }
+}
+
+#[cfg(test)]
+mod digest_tests {
+    use super::{Digest, DigestChange, DigestSchedule, next_digest_at};
+    use crate::notifications::rules::NotificationReason;
+    use crate::notifications::subscriptions::{NotificationChannel, Subscription, SubscriptionState};
+    use crate::notifications::templates::Language;
+    use crate::web_api::Incident;
+    use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+
+    fn change(id: i64, day: u32, reason: NotificationReason) -> DigestChange {
+        DigestChange {
+            reason,
+            incident: Incident {
+                external_id: format!("{} - Retele Electrice", id),
+                county: "TULCEA".to_string(),
+                location: "LOC. ISACCEA".to_string(),
+                day: NaiveDate::from_ymd_opt(2025, 8, day).unwrap(),
+                start_time: NaiveTime::from_hms_opt(9, 0, 0),
+                end_time: NaiveTime::from_hms_opt(13, 0, 0),
+                description: String::new(),
+                id,
+                revision: 1,
+                updated_at: Utc::now(),
+                cancelled_at: None,
+                duplicate_of: None,
+            },
+        }
+    }
+
+    fn subscription() -> Subscription {
+        Subscription {
+            id: 3,
+            county_key: "tulcea".to_string(),
+            locality: None,
+            locality_key: None,
+            street_pattern: None,
+            window_start: None,
+            window_end: None,
+            channel: NotificationChannel::Sms,
+            contact: "+40722123456".to_string(),
+            state: SubscriptionState::Active,
+            reminders: vec![],
+            digest: Some(DigestSchedule::Weekly),
+            digest_time: NaiveTime::from_hms_opt(18, 0, 0),
+            language: Language::En,
+            signing_secret: None,
+            failure_count: 0,
+            verified_at: Some(Utc::now()),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn daily_digests_go_out_at_their_time() {
+        let time = NaiveTime::from_hms_opt(18, 0, 0).unwrap();
+        let morning = Utc.with_ymd_and_hms(2025, 8, 7, 6, 0, 0).unwrap();
+        let evening = Utc.with_ymd_and_hms(2025, 8, 7, 15, 0, 0).unwrap();
+
+        assert_eq!(
+            Utc.with_ymd_and_hms(2025, 8, 7, 15, 0, 0).unwrap(),
+            next_digest_at(DigestSchedule::Daily, time, morning)
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2025, 8, 8, 15, 0, 0).unwrap(),
+            next_digest_at(DigestSchedule::Daily, time, evening)
+        );
+    }
+
+    #[test]
+    fn weekly_digests_go_out_on_mondays() {
+        let time = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+        let thursday = Utc.with_ymd_and_hms(2025, 8, 7, 6, 0, 0).unwrap();
+        let monday_morning = Utc.with_ymd_and_hms(2025, 8, 11, 4, 0, 0).unwrap();
+
+        assert_eq!(
+            Utc.with_ymd_and_hms(2025, 8, 11, 5, 0, 0).unwrap(),
+            next_digest_at(DigestSchedule::Weekly, time, thursday)
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2025, 8, 11, 5, 0, 0).unwrap(),
+            next_digest_at(DigestSchedule::Weekly, time, monday_morning)
+        );
+    }
+
+    #[test]
+    fn digests_keep_the_latest_change_of_each_outage() {
+        let digest = Digest::new(
+            subscription(),
+            vec![
+                (1, change(7, 12, NotificationReason::New)),
+                (1, change(8, 9, NotificationReason::New)),
+                (2, change(7, 12, NotificationReason::Cancelled)),
+            ],
+        );
+
+        assert_eq!(
+            vec![(8, NotificationReason::New), (7, NotificationReason::Cancelled)],
+            digest
+                .changes
+                .iter()
+                .map(|change| (change.incident.id, change.reason))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,694 @@
+use crate::notifications::consent::{MAX_VERIFICATION_FAILURES, VERIFICATION_VALIDITY_HOURS, Verification};
+use crate::notifications::digest::{Digest, DigestChange};
+use crate::notifications::email::EmailNotifier;
+use crate::notifications::local_time;
+use crate::notifications::notifier::{
+    DeliveryError, Notification, Notifier, Urgency, outage_urgency, public_url, urgency,
+};
+use crate::notifications::push::{GotifyNotifier, NtfyNotifier};
+use crate::notifications::quiet_hours::QuietHours;
+use crate::notifications::reminders::{Reminder, obsolete_reminder};
+use crate::notifications::rules::NotificationReason;
+use crate::notifications::sms::SmsNotifier;
+use crate::notifications::subscriptions::{NotificationChannel, Subscription, SubscriptionState};
+use crate::notifications::templates::Templates;
+use crate::notifications::webhook::WebhookNotifier;
+use crate::web_api::Incident;
+use chrono::{DateTime, Utc};
+use common::configuration::{OutboxConfiguration, ServiceConfiguration, WebhookConfiguration};
+use log::{error, info, warn};
+use sqlx::{FromRow, Pool, Postgres};
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::sleep;
+use uuid::Uuid;
+
+/// A claimed job is not claimed again before this long, a dispatcher stopped in the middle of a delivery leaves it
+/// to be tried again once the lease is over. The lease of the jobs still waiting in a batch is renewed before each
+/// delivery.
+const CLAIM_LEASE_SECS: f64 = 300.0;
+
+/// The due jobs of the channels with a notifier, oldest first. Claiming counts the attempt, pushes the next one past
+/// the lease and tags the jobs with the token of the claim, so that concurrent dispatchers never deliver the same job
+/// at once and only the one holding the claim records the outcome.
+const CLAIM_QUERY: &str = "UPDATE notification_jobs SET attempts = attempts + 1, \
+ next_attempt_at = now() + make_interval(secs => $3), claim_token = $5 \
+ WHERE id IN (SELECT j.id FROM notification_jobs j JOIN subscriptions s ON s.id = j.subscription_id \
+ WHERE j.state = $4 AND j.next_attempt_at <= now() AND s.channel = ANY($1) \
+ AND (s.digest IS NULL OR j.reminder IS NOT NULL) \
+ ORDER BY j.next_attempt_at, j.id LIMIT $2 FOR UPDATE OF j SKIP LOCKED) \
+ RETURNING id, subscription_id, incident_id, revision, reason, reminder, attempts, claim_token";
+
+/// The subscriptions with a digest which has due changes, the reminders being sent on their own.
+const DUE_DIGESTS_QUERY: &str = "SELECT DISTINCT j.subscription_id FROM notification_jobs j \
+ JOIN subscriptions s ON s.id = j.subscription_id \
+ WHERE j.state = $3 AND j.next_attempt_at <= now() AND j.reminder IS NULL AND s.digest IS NOT NULL \
+ AND s.channel = ANY($1) LIMIT $2";
+
+/// All the due changes of a digest are claimed together, so that they are sent in a single message.
+const CLAIM_DIGEST_QUERY: &str = "UPDATE notification_jobs SET attempts = attempts + 1, \
+ next_attempt_at = now() + make_interval(secs => $2), claim_token = $4 \
+ WHERE id IN (SELECT id FROM notification_jobs \
+ WHERE subscription_id = $1 AND state = $3 AND next_attempt_at <= now() AND reminder IS NULL \
+ FOR UPDATE SKIP LOCKED) \
+ RETURNING id, subscription_id, incident_id, revision, reason, reminder, attempts, claim_token";
+
+/// Pushes back the lease of the jobs of a claim not finished yet.
+const RENEW_QUERY: &str = "UPDATE notification_jobs SET next_attempt_at = now() + make_interval(secs => $2) \
+ WHERE claim_token = $1 AND state = $3";
+
+/// The codes of the new contacts still to be sent, claimed like the jobs. The codes no longer accepted are not sent.
+const CLAIM_VERIFICATIONS_QUERY: &str = "UPDATE subscriptions SET verification_due_at = now() + make_interval(secs => $3) \
+ WHERE id IN (SELECT id FROM subscriptions \
+ WHERE verified_at IS NULL AND verification_due_at <= now() AND channel = ANY($1) AND state <> $4 \
+ AND verification_failures < $5 AND updated_at > now() - make_interval(hours => $6) \
+ ORDER BY verification_due_at, id LIMIT $2 FOR UPDATE SKIP LOCKED) \
+ RETURNING *";
+
+const VERIFICATION_SENT_QUERY: &str = "UPDATE subscriptions SET verification_due_at = NULL WHERE id = $1";
+
+const VERIFICATION_RETRY_QUERY: &str =
+    "UPDATE subscriptions SET verification_due_at = now() + make_interval(secs => $2) WHERE id = $1";
+
+const SUBSCRIPTIONS_QUERY: &str = "SELECT * FROM subscriptions WHERE id = ANY($1)";
+
+const INCIDENTS_QUERY: &str = "SELECT * FROM incidents WHERE id = ANY($1)";
+
+const QUIET_HOURS_QUERY: &str = "SELECT * FROM quiet_hours WHERE contact = ANY($1)";
+
+/// The outcome of a job is only recorded by the dispatcher still holding its claim, the others changing nothing.
+const DELIVERED_QUERY: &str = "UPDATE notification_jobs SET state = $2, delivered_at = now(), last_error = NULL, \
+ claim_token = NULL WHERE id = $1 AND claim_token = $3";
+
+const RETRY_QUERY: &str = "UPDATE notification_jobs SET next_attempt_at = now() + make_interval(secs => $2), \
+ last_error = $3, claim_token = NULL WHERE id = $1 AND claim_token = $4";
+
+/// A deferred job was not attempted, its claim is not counted.
+const DEFER_QUERY: &str = "UPDATE notification_jobs SET next_attempt_at = $2, attempts = attempts - 1, \
+ claim_token = NULL WHERE id = $1 AND claim_token = $3";
+
+const CLOSE_QUERY: &str =
+    "UPDATE notification_jobs SET state = $2, last_error = $3, claim_token = NULL WHERE id = $1 AND claim_token = $4";
+
+const RECORD_SUCCESS_QUERY: &str = "UPDATE subscriptions SET failure_count = 0 WHERE id = $1 AND failure_count > 0";
+
+/// The failure reaching the limit disables the subscription in the same statement.
+const RECORD_FAILURE_QUERY: &str = "UPDATE subscriptions SET failure_count = failure_count + 1, \
+ state = CASE WHEN failure_count + 1 >= $2 THEN $3 ELSE state END WHERE id = $1 RETURNING state";
+
+/// Longer delivery errors are cut to fit their column.
+const MAX_ERROR_LENGTH: usize = 512;
+
+/// Where a notification job is in its delivery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+pub enum JobState {
+    /// Waiting for its first or next attempt, its digest or the end of the quiet hours of its contact.
+    Pending,
+    Delivered,
+    /// Not sent: the subscription is no longer active or its new contact is not verified, the outage is over or
+    /// announced by another incident, or the reminder is obsolete.
+    Skipped,
+    /// Rejected by the channel, or failing every attempt. Kept with the last error for inspection.
+    Dead,
+}
+
+#[derive(Debug, FromRow)]
+struct ClaimedJob {
+    id: i64,
+    subscription_id: i64,
+    incident_id: i64,
+    revision: i32,
+    reason: NotificationReason,
+    reminder: Option<Reminder>,
+    attempts: i32,
+    claim_token: String,
+}
+
+/// The subscriptions, incidents and quiet hours of the claimed jobs.
+struct Batch {
+    subscriptions: HashMap<i64, Subscription>,
+    incidents: HashMap<i64, Incident>,
+    quiet_hours: HashMap<(NotificationChannel, String), QuietHours>,
+}
+
+impl Batch {
+    /// The end of the quiet hours of the contact of the subscription, when they are on.
+    fn quiet_until(&self, subscription: &Subscription, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.quiet_hours
+            .get(&(subscription.channel, subscription.contact.clone()))
+            .and_then(|quiet_hours| quiet_hours.until(now))
+    }
+}
+
+/// Delivers the notification jobs through the notifier of their channel. The jobs are enqueued together with the
+/// incident changes, so none is lost when the service restarts or a channel is down, and each is delivered once. The
+/// reminders and the digests are jobs due at their time, the dispatcher is their scheduler. It also disables the
+/// webhooks which keep failing.
+pub struct Dispatcher {
+    pg_pool: Arc<Pool<Postgres>>,
+    notifiers: HashMap<NotificationChannel, Box<dyn Notifier>>,
+    configuration: OutboxConfiguration,
+    webhook: WebhookConfiguration,
+}
+
+impl Dispatcher {
+    /// The jobs of the channels without a notifier stay pending until one is configured.
+    pub fn new(
+        pg_pool: Arc<Pool<Postgres>>,
+        notifiers: Vec<Box<dyn Notifier>>,
+        configuration: OutboxConfiguration,
+        webhook: WebhookConfiguration,
+    ) -> Dispatcher {
+        Dispatcher {
+            pg_pool,
+            notifiers: notifiers
+                .into_iter()
+                .map(|notifier| (notifier.channel(), notifier))
+                .collect(),
+            configuration,
+            webhook,
+        }
+    }
+
+    /// Delivers the due jobs until none is left, then waits for the next feed or the poll interval.
+    pub async fn run(self, mut feeds: broadcast::Receiver<()>) {
+        let poll_interval = Duration::from_secs(self.configuration.poll_interval_secs);
+        info!(
+            "Dispatching the notifications of the channels {:?}.",
+            self.notifiers.keys().collect::<Vec<_>>()
+        );
+        loop {
+            while let Ok(dispatched) = self.dispatch_due().await {
+                if dispatched < self.configuration.batch_size as usize {
+                    break;
+                }
+            }
+
+            tokio::select! {
+                received = feeds.recv() => {
+                    if let Err(RecvError::Closed) = received {
+                        sleep(poll_interval).await;
+                    }
+                }
+                _ = sleep(poll_interval) => {}
+            }
+        }
+    }
+
+    /// Sends the codes of the new contacts, then claims a batch of due jobs and tries to deliver each of them, then sends
+    /// the due digests. Returns the number of claimed jobs and codes.
+    pub async fn dispatch_due(&self) -> Result<usize, String> {
+        let channels: Vec<NotificationChannel> = self.notifiers.keys().copied().collect();
+        let verifications = self.dispatch_verifications(&channels).await?;
+        let claim_token = Uuid::new_v4().simple().to_string();
+        let jobs: Vec<ClaimedJob> = sqlx::query_as(CLAIM_QUERY)
+            .bind(&channels)
+            .bind(self.configuration.batch_size as i64)
+            .bind(CLAIM_LEASE_SECS)
+            .bind(JobState::Pending)
+            .bind(&claim_token)
+            .fetch_all(self.pg_pool.deref())
+            .await
+            .map_err(|e| {
+                error!("Could not claim the due notifications: {}", e);
+                e.to_string()
+            })?;
+
+        if !jobs.is_empty() {
+            let batch = self.load(&jobs).await?;
+            for job in jobs.iter() {
+                self.renew_lease(&claim_token).await;
+                let outcome = self.dispatch(job, &batch, Utc::now()).await;
+                if let Some(subscription) = batch.subscriptions.get(&job.subscription_id) {
+                    self.count_failures(subscription, &outcome).await;
+                }
+                self.record_outcome(job, outcome).await;
+            }
+        }
+        let digest_jobs = self.dispatch_digests(&channels).await?;
+        Ok(verifications + jobs.len() + digest_jobs)
+    }
+
+    /// The verification codes are sent once, a code which does not arrive is sent again until it expires. The contact
+    /// replaces the subscription to get a new one.
+    async fn dispatch_verifications(&self, channels: &[NotificationChannel]) -> Result<usize, String> {
+        let verifications: Vec<Verification> = sqlx::query_as(CLAIM_VERIFICATIONS_QUERY)
+            .bind(channels)
+            .bind(self.configuration.batch_size as i64)
+            .bind(CLAIM_LEASE_SECS)
+            .bind(SubscriptionState::Disabled)
+            .bind(MAX_VERIFICATION_FAILURES)
+            .bind(VERIFICATION_VALIDITY_HOURS)
+            .fetch_all(self.pg_pool.deref())
+            .await
+            .map_err(|e| {
+                error!("Could not claim the due verifications: {}", e);
+                e.to_string()
+            })?;
+
+        for verification in verifications.iter() {
+            let id = verification.subscription.id;
+            let Some(notifier) = self.notifiers.get(&verification.subscription.channel) else {
+                continue;
+            };
+            let recorded = match notifier.send_verification(verification).await {
+                Ok(()) => {
+                    sqlx::query(VERIFICATION_SENT_QUERY)
+                        .bind(id)
+                        .execute(self.pg_pool.deref())
+                        .await
+                }
+                Err(DeliveryError::Transient(message)) => {
+                    warn!(
+                        "Could not send the verification code of the subscription {}, retrying in {}s: {}",
+                        id, self.configuration.retry_delay_secs, message
+                    );
+                    sqlx::query(VERIFICATION_RETRY_QUERY)
+                        .bind(id)
+                        .bind(self.configuration.retry_delay_secs as f64)
+                        .execute(self.pg_pool.deref())
+                        .await
+                }
+                Err(DeliveryError::Rejected(message)) => {
+                    error!(
+                        "The verification code of the subscription {} was rejected: {}",
+                        id, message
+                    );
+                    sqlx::query(VERIFICATION_SENT_QUERY)
+                        .bind(id)
+                        .execute(self.pg_pool.deref())
+                        .await
+                }
+            };
+            if let Err(e) = recorded {
+                error!(
+                    "Could not record the verification sent to the subscription {}: {}",
+                    id, e
+                );
+            }
+        }
+        Ok(verifications.len())
+    }
+
+    /// Sends a digest for each subscription with due changes, the changes not worth sending anymore are skipped.
+    async fn dispatch_digests(&self, channels: &[NotificationChannel]) -> Result<usize, String> {
+        let subscription_ids: Vec<i64> = sqlx::query_scalar(DUE_DIGESTS_QUERY)
+            .bind(channels)
+            .bind(self.configuration.batch_size as i64)
+            .bind(JobState::Pending)
+            .fetch_all(self.pg_pool.deref())
+            .await
+            .map_err(|e| {
+                error!("Could not find the due digests: {}", e);
+                e.to_string()
+            })?;
+
+        let mut dispatched = 0;
+        for subscription_id in subscription_ids {
+            let jobs: Vec<ClaimedJob> = sqlx::query_as(CLAIM_DIGEST_QUERY)
+                .bind(subscription_id)
+                .bind(CLAIM_LEASE_SECS)
+                .bind(JobState::Pending)
+                .bind(Uuid::new_v4().simple().to_string())
+                .fetch_all(self.pg_pool.deref())
+                .await
+                .map_err(|e| {
+                    error!(
+                        "Could not claim the digest of the subscription {}: {}",
+                        subscription_id, e
+                    );
+                    e.to_string()
+                })?;
+            if jobs.is_empty() {
+                continue;
+            }
+            dispatched += jobs.len();
+
+            let batch = self.load(&jobs).await?;
+            let now = Utc::now();
+            let mut included = Vec::with_capacity(jobs.len());
+            let mut changes = Vec::with_capacity(jobs.len());
+            for job in jobs.iter() {
+                match deliverable(job, &batch, now) {
+                    Ok((_, incident)) => {
+                        included.push(job);
+                        changes.push((
+                            job.revision,
+                            DigestChange {
+                                reason: job.reason,
+                                incident: incident.clone(),
+                            },
+                        ));
+                    }
+                    Err(reason) => self.record_outcome(job, Outcome::Skipped(reason)).await,
+                }
+            }
+            let Some(subscription) = batch.subscriptions.get(&subscription_id) else {
+                continue;
+            };
+            if included.is_empty() {
+                continue;
+            }
+
+            let digest = Digest::new(subscription.clone(), changes);
+            let attempts = included.iter().map(|job| job.attempts).max().unwrap_or(1);
+            let outcome = self.send_digest(&digest, &batch, now, attempts).await;
+            self.count_failures(subscription, &outcome).await;
+            for job in included {
+                self.record_outcome(job, outcome.clone()).await;
+            }
+        }
+        Ok(dispatched)
+    }
+
+    /// A batch of slow deliveries would outlast the lease, the jobs still waiting keep it for the whole batch.
+    async fn renew_lease(&self, claim_token: &str) {
+        if let Err(e) = sqlx::query(RENEW_QUERY)
+            .bind(claim_token)
+            .bind(CLAIM_LEASE_SECS)
+            .bind(JobState::Pending)
+            .execute(self.pg_pool.deref())
+            .await
+        {
+            warn!("Could not renew the lease of the claimed notifications: {}", e);
+        }
+    }
+
+    async fn load(&self, jobs: &[ClaimedJob]) -> Result<Batch, String> {
+        let subscription_ids: Vec<i64> = jobs.iter().map(|job| job.subscription_id).collect();
+        let subscriptions: Vec<Subscription> = sqlx::query_as(SUBSCRIPTIONS_QUERY)
+            .bind(subscription_ids)
+            .fetch_all(self.pg_pool.deref())
+            .await
+            .map_err(|e| {
+                error!("Could not read the subscriptions to notify: {}", e);
+                e.to_string()
+            })?;
+
+        let incident_ids: Vec<i64> = jobs.iter().map(|job| job.incident_id).collect();
+        let incidents: Vec<Incident> = sqlx::query_as(INCIDENTS_QUERY)
+            .bind(incident_ids)
+            .fetch_all(self.pg_pool.deref())
+            .await
+            .map_err(|e| {
+                error!("Could not read the incidents to notify: {}", e);
+                e.to_string()
+            })?;
+
+        let contacts: Vec<&str> = subscriptions
+            .iter()
+            .map(|subscription| subscription.contact.as_str())
+            .collect();
+        let quiet_hours: Vec<QuietHours> = sqlx::query_as(QUIET_HOURS_QUERY)
+            .bind(contacts)
+            .fetch_all(self.pg_pool.deref())
+            .await
+            .map_err(|e| {
+                error!("Could not read the quiet hours of the contacts to notify: {}", e);
+                e.to_string()
+            })?;
+
+        Ok(Batch {
+            subscriptions: subscriptions
+                .into_iter()
+                .map(|subscription| (subscription.id, subscription))
+                .collect(),
+            incidents: incidents.into_iter().map(|incident| (incident.id, incident)).collect(),
+            quiet_hours: quiet_hours
+                .into_iter()
+                .map(|quiet_hours| ((quiet_hours.channel, quiet_hours.contact.clone()), quiet_hours))
+                .collect(),
+        })
+    }
+
+    /// The notifications are held back during the quiet hours of their contact, unless the outage is about to start.
+    async fn dispatch(&self, job: &ClaimedJob, batch: &Batch, now: DateTime<Utc>) -> Outcome {
+        let (subscription, incident) = match deliverable(job, batch, now) {
+            Ok(found) => found,
+            Err(reason) => return Outcome::Skipped(reason),
+        };
+        let Some(notifier) = self.notifiers.get(&subscription.channel) else {
+            return Outcome::Skipped("The channel has no notifier.");
+        };
+
+        let notification = Notification {
+            subscription: subscription.clone(),
+            reason: job.reason,
+            incident: incident.clone(),
+        };
+        if urgency(&notification, now) < Urgency::Urgent
+            && let Some(until) = batch.quiet_until(subscription, now)
+        {
+            return Outcome::Deferred(until);
+        }
+        self.outcome(notifier.notify(&notification).await, job.attempts)
+    }
+
+    /// The digests wait for the end of the quiet hours, unless one of their outages is about to start.
+    async fn send_digest(&self, digest: &Digest, batch: &Batch, now: DateTime<Utc>, attempts: i32) -> Outcome {
+        let Some(notifier) = self.notifiers.get(&digest.subscription.channel) else {
+            return Outcome::Skipped("The channel has no notifier.");
+        };
+        let urgent = digest
+            .changes
+            .iter()
+            .any(|change| outage_urgency(&change.incident, now) == Urgency::Urgent);
+        if !urgent && let Some(until) = batch.quiet_until(&digest.subscription, now) {
+            return Outcome::Deferred(until);
+        }
+        self.outcome(notifier.notify_digest(digest).await, attempts)
+    }
+
+    fn outcome(&self, delivered: Result<(), DeliveryError>, attempts: i32) -> Outcome {
+        match delivered {
+            Ok(()) => Outcome::Delivered,
+            Err(DeliveryError::Transient(message)) if attempts < self.configuration.max_attempts as i32 => {
+                Outcome::Retry(retry_delay(&self.configuration, attempts as u32), message)
+            }
+            Err(DeliveryError::Transient(message)) | Err(DeliveryError::Rejected(message)) => Outcome::Dead(message),
+        }
+    }
+
+    /// A webhook dead-lettering `max_failures` notifications in a row is disabled, a delivered one starts the count
+    /// again. The retries of a notification are not counted, the receiver may come back before they are over.
+    async fn count_failures(&self, subscription: &Subscription, outcome: &Outcome) {
+        if subscription.channel != NotificationChannel::Webhook {
+            return;
+        }
+        let id = subscription.id;
+        match outcome {
+            Outcome::Delivered if subscription.failure_count > 0 => {
+                if let Err(e) = sqlx::query(RECORD_SUCCESS_QUERY)
+                    .bind(id)
+                    .execute(self.pg_pool.deref())
+                    .await
+                {
+                    error!("Could not reset the failures of the subscription {}: {}", id, e);
+                }
+            }
+            Outcome::Dead(_) => {
+                let max_failures = self.webhook.max_failures.max(1);
+                let state: Result<Option<SubscriptionState>, sqlx::Error> = sqlx::query_scalar(RECORD_FAILURE_QUERY)
+                    .bind(id)
+                    .bind(max_failures as i32)
+                    .bind(SubscriptionState::Disabled)
+                    .fetch_optional(self.pg_pool.deref())
+                    .await;
+                match state {
+                    Ok(Some(SubscriptionState::Disabled)) => warn!(
+                        "Disabled the webhook subscription {} after {} dead-lettered notifications in a row.",
+                        id, max_failures
+                    ),
+                    Ok(_) => {}
+                    Err(e) => error!("Could not count the failure of the subscription {}: {}", id, e),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    async fn record_outcome(&self, job: &ClaimedJob, outcome: Outcome) {
+        let recorded = match &outcome {
+            Outcome::Delivered => {
+                sqlx::query(DELIVERED_QUERY)
+                    .bind(job.id)
+                    .bind(JobState::Delivered)
+                    .bind(&job.claim_token)
+                    .execute(self.pg_pool.deref())
+                    .await
+            }
+            Outcome::Retry(delay, message) => {
+                warn!(
+                    "Notification {} of subscription {} for revision {} of incident {} failed attempt {}, retrying in \
+                     {:?}: {}",
+                    job.id, job.subscription_id, job.revision, job.incident_id, job.attempts, delay, message
+                );
+                sqlx::query(RETRY_QUERY)
+                    .bind(job.id)
+                    .bind(delay.as_secs_f64())
+                    .bind(truncated(message))
+                    .bind(&job.claim_token)
+                    .execute(self.pg_pool.deref())
+                    .await
+            }
+            Outcome::Deferred(until) => {
+                sqlx::query(DEFER_QUERY)
+                    .bind(job.id)
+                    .bind(until)
+                    .bind(&job.claim_token)
+                    .execute(self.pg_pool.deref())
+                    .await
+            }
+            Outcome::Skipped(reason) => {
+                sqlx::query(CLOSE_QUERY)
+                    .bind(job.id)
+                    .bind(JobState::Skipped)
+                    .bind(*reason)
+                    .bind(&job.claim_token)
+                    .execute(self.pg_pool.deref())
+                    .await
+            }
+            Outcome::Dead(message) => {
+                error!(
+                    "Notification {} of subscription {} for revision {} of incident {} dead-lettered after {} \
+                     attempts: {}",
+                    job.id, job.subscription_id, job.revision, job.incident_id, job.attempts, message
+                );
+                sqlx::query(CLOSE_QUERY)
+                    .bind(job.id)
+                    .bind(JobState::Dead)
+                    .bind(truncated(message))
+                    .bind(&job.claim_token)
+                    .execute(self.pg_pool.deref())
+                    .await
+            }
+        };
+
+        // The job is claimed again once the lease is over, a delivered notification may then be sent twice.
+        match recorded {
+            Ok(result) if result.rows_affected() == 0 => warn!(
+                "The claim of the notification {} was lost before its outcome {:?} was recorded, it is left to the \
+                 dispatcher holding it.",
+                job.id, outcome
+            ),
+            Ok(_) => {}
+            Err(e) => error!(
+                "Could not record the outcome {:?} of the notification {}: {}",
+                outcome, job.id, e
+            ),
+        }
+    }
+}
+
+/// The subscription and the incident of a job still worth sending, otherwise why it is skipped.
+fn deliverable<'a>(
+    job: &ClaimedJob,
+    batch: &'a Batch,
+    now: DateTime<Utc>,
+) -> Result<(&'a Subscription, &'a Incident), &'static str> {
+    let (Some(subscription), Some(incident)) = (
+        batch.subscriptions.get(&job.subscription_id),
+        batch.incidents.get(&job.incident_id),
+    ) else {
+        return Err("The subscription or the incident was deleted.");
+    };
+    if subscription.state != SubscriptionState::Active {
+        return Err("The subscription is not active.");
+    }
+    if subscription.verified_at.is_none() {
+        return Err("The contact of the subscription is not verified.");
+    }
+    if incident.duplicate_of.is_some() {
+        return Err("The outage is announced by another incident.");
+    }
+    if incident.day < local_time::to_local(now).date() {
+        return Err("The outage is over.");
+    }
+    if let Some(reminder) = job.reminder
+        && let Some(reason) = obsolete_reminder(reminder, job.revision, subscription, incident, now)
+    {
+        return Err(reason);
+    }
+    Ok((subscription, incident))
+}
+
+/// The notifiers of the configured channels: SMS and e-mail need their gateway, the others are always available. A
+/// notifier which cannot be set up is left out, its notifications wait for the next start.
+pub fn configured_notifiers(configuration: &ServiceConfiguration, templates: Arc<Templates>) -> Vec<Box<dyn Notifier>> {
+    let public_url = public_url(configuration);
+    let allow_private_targets = configuration.allow_private_targets;
+
+    let mut notifiers: Vec<Result<Box<dyn Notifier>, String>> = vec![
+        WebhookNotifier::new(templates.clone(), allow_private_targets)
+            .map(|notifier| Box::new(notifier) as Box<dyn Notifier>),
+        NtfyNotifier::new(public_url.clone(), templates.clone(), allow_private_targets)
+            .map(|notifier| Box::new(notifier) as Box<dyn Notifier>),
+        GotifyNotifier::new(public_url, templates.clone(), allow_private_targets)
+            .map(|notifier| Box::new(notifier) as Box<dyn Notifier>),
+    ];
+    if let Some(sms_gateway) = &configuration.sms_gateway {
+        notifiers.push(
+            SmsNotifier::new(sms_gateway, templates.clone()).map(|notifier| Box::new(notifier) as Box<dyn Notifier>),
+        );
+    }
+    if let Some(smtp) = &configuration.smtp {
+        notifiers.push(EmailNotifier::new(smtp, templates).map(|notifier| Box::new(notifier) as Box<dyn Notifier>));
+    }
+
+    notifiers
+        .into_iter()
+        .filter_map(|notifier| {
+            notifier
+                .inspect_err(|e| error!("Could not set up a notifier: {}", e))
+                .ok()
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+enum Outcome {
+    Delivered,
+    Retry(Duration, String),
+    /// Held back until the end of the quiet hours.
+    Deferred(DateTime<Utc>),
+    Skipped(&'static str),
+    Dead(String),
+}
+
+/// The delay doubles with each failed attempt, starting from the configured one and up to its maximum.
+pub fn retry_delay(configuration: &OutboxConfiguration, failed_attempts: u32) -> Duration {
+    let factor = 2u64.saturating_pow(failed_attempts.saturating_sub(1));
+    let delay = configuration.retry_delay_secs.saturating_mul(factor);
+    Duration::from_secs(delay.min(configuration.max_retry_delay_secs))
+}
+
+fn truncated(message: &str) -> String {
+    message.chars().take(MAX_ERROR_LENGTH).collect()
+}
+
+#[cfg(test)]
+mod dispatcher_tests {
+    use super::retry_delay;
+    use common::configuration::OutboxConfiguration;
+    use std::time::Duration;
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_maximum() {
+        let configuration = OutboxConfiguration {
+            retry_delay_secs: 60,
+            max_retry_delay_secs: 600,
+            ..OutboxConfiguration::default()
+        };
+
+        assert_eq!(Duration::from_secs(60), retry_delay(&configuration, 1));
+        assert_eq!(Duration::from_secs(120), retry_delay(&configuration, 2));
+        assert_eq!(Duration::from_secs(480), retry_delay(&configuration, 4));
+        assert_eq!(Duration::from_secs(600), retry_delay(&configuration, 5));
+        assert_eq!(Duration::from_secs(600), retry_delay(&configuration, 100));
+    }
+}
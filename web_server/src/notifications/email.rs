@@ -0,0 +1,326 @@
+use crate::notifications::consent::Verification;
+use crate::notifications::digest::Digest;
+use crate::notifications::notifier::{DeliveryError, Notification, Notifier};
+use crate::notifications::smtp::SmtpClient;
+use crate::notifications::subscriptions::NotificationChannel;
+use crate::notifications::templates::{Template, Templates};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, Utc};
+use common::configuration::SmtpConfiguration;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Lines of the base64 encoded parts, as recommended for MIME.
+const BASE64_LINE_LENGTH: usize = 76;
+
+/// The text of an encoded word, so that with its `=?UTF-8?B?` envelope it stays within the 75 characters allowed.
+const ENCODED_WORD_BYTES: usize = 45;
+
+/// Sends the notifications as e-mails with an HTML and a plain text alternative.
+pub struct EmailNotifier {
+    client: SmtpClient,
+    from: String,
+    templates: Arc<Templates>,
+}
+
+/// The rendered content of an e-mail notification.
+#[derive(Debug, PartialEq)]
+pub struct Email {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+    /// Sent in the `List-Unsubscribe` headers, for the mail clients to unsubscribe in one click (RFC 8058).
+    pub unsubscribe_url: Option<String>,
+}
+
+impl EmailNotifier {
+    pub fn new(configuration: &SmtpConfiguration, templates: Arc<Templates>) -> Result<EmailNotifier, String> {
+        Ok(EmailNotifier {
+            client: SmtpClient::new(configuration)?,
+            from: configuration.from.clone(),
+            templates,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    fn channel(&self) -> NotificationChannel {
+        NotificationChannel::Email
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), DeliveryError> {
+        let email = render(&self.templates, notification)?;
+        let contact = &notification.subscription.contact;
+        let message = mime_message(&self.from, contact, &email, Utc::now());
+        self.client.send(&self.from, contact, &message).await
+    }
+
+    async fn notify_digest(&self, digest: &Digest) -> Result<(), DeliveryError> {
+        let email = render_digest(&self.templates, digest)?;
+        let contact = &digest.subscription.contact;
+        let message = mime_message(&self.from, contact, &email, Utc::now());
+        self.client.send(&self.from, contact, &message).await
+    }
+
+    async fn send_verification(&self, verification: &Verification) -> Result<(), DeliveryError> {
+        let email = render_verification(&self.templates, verification)?;
+        let contact = &verification.subscription.contact;
+        let message = mime_message(&self.from, contact, &email, Utc::now());
+        self.client.send(&self.from, contact, &message).await
+    }
+}
+
+/// The county, locality, day, time window and description of the incident, as plain text and as HTML.
+pub fn render(templates: &Templates, notification: &Notification) -> Result<Email, DeliveryError> {
+    Ok(Email {
+        subject: templates.render(Template::EmailSubject, notification)?,
+        text: crlf(&templates.render(Template::EmailText, notification)?),
+        html: crlf(&templates.render(Template::EmailHtml, notification)?),
+        unsubscribe_url: Some(templates.links().unsubscribe_url(notification.subscription.id)),
+    })
+}
+
+/// The outages of the digest as a plain text and an HTML list.
+pub fn render_digest(templates: &Templates, digest: &Digest) -> Result<Email, DeliveryError> {
+    Ok(Email {
+        subject: templates.render_digest(Template::DigestTitle, digest)?,
+        text: crlf(&templates.render_digest(Template::DigestText, digest)?),
+        html: crlf(&templates.render_digest(Template::DigestHtml, digest)?),
+        unsubscribe_url: Some(templates.links().unsubscribe_url(digest.subscription.id)),
+    })
+}
+
+/// The code and the link confirming the address, before anything else is sent to it.
+pub fn render_verification(templates: &Templates, verification: &Verification) -> Result<Email, DeliveryError> {
+    Ok(Email {
+        subject: templates.render_verification(Template::VerificationTitle, verification)?,
+        text: crlf(&templates.render_verification(Template::VerificationMessage, verification)?),
+        html: crlf(&templates.render_verification(Template::VerificationHtml, verification)?),
+        unsubscribe_url: None,
+    })
+}
+
+/// The lines of the MIME parts end with CRLF, the last one included.
+fn crlf(content: &str) -> String {
+    content.lines().map(|line| format!("{}\r\n", line)).collect()
+}
+
+/// A `multipart/alternative` message, the plain text first so the clients prefer the HTML part.
+fn mime_message(from: &str, to: &str, email: &Email, date: DateTime<Utc>) -> String {
+    let domain = from.rsplit_once('@').map(|(_, domain)| domain).unwrap_or("localhost");
+    let boundary = format!("=_{}", Uuid::new_v4().simple());
+    let list_unsubscribe = email
+        .unsubscribe_url
+        .as_ref()
+        .map(|url| {
+            format!(
+                "List-Unsubscribe: <{}>\r\nList-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n",
+                url
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        "From: <{from}>\r\nTo: <{to}>\r\nSubject: {subject}\r\nDate: {date}\r\nMessage-ID: <{id}@{domain}>\r\n\
+         {list_unsubscribe}MIME-Version: 1.0\r\nContent-Type: multipart/alternative; boundary=\"{boundary}\"\r\n\r\n\
+         --{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{text}\r\n\
+         --{boundary}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{html}\r\n\
+         --{boundary}--\r\n",
+        subject = encoded_header(&email.subject),
+        date = date.to_rfc2822(),
+        id = Uuid::new_v4(),
+        text = base64_lines(&email.text),
+        html = base64_lines(&email.html),
+    )
+}
+
+/// Header values are ASCII, anything else is sent as RFC 2047 encoded words. Line breaks never make it into a header.
+fn encoded_header(value: &str) -> String {
+    let value: String = value.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    if value.is_ascii() {
+        return value;
+    }
+
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for c in value.chars() {
+        if word.len() + c.len_utf8() > ENCODED_WORD_BYTES {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(c);
+    }
+    words.push(word);
+
+    words
+        .iter()
+        .map(|word| format!("=?UTF-8?B?{}?=", STANDARD.encode(word)))
+        .collect::<Vec<_>>()
+        .join("\r\n ")
+}
+
+fn base64_lines(content: &str) -> String {
+    let encoded = STANDARD.encode(content);
+    encoded
+        .as_bytes()
+        .chunks(BASE64_LINE_LENGTH)
+        .map(|line| String::from_utf8_lossy(line).into_owned())
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+#[cfg(test)]
+mod email_tests {
+    use super::{Email, base64_lines, encoded_header, mime_message, render, render_digest};
+    use crate::notifications::digest::{Digest, DigestChange, DigestSchedule};
+    use crate::notifications::notifier::Notification;
+    use crate::notifications::rules::NotificationReason;
+    use crate::notifications::subscriptions::{NotificationChannel, Subscription, SubscriptionState};
+    use crate::notifications::templates::{Language, Templates};
+    use crate::web_api::Incident;
+    use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+
+    fn notification(hours: Option<(u32, u32)>, description: &str) -> Notification {
+        Notification {
+            subscription: Subscription {
+                id: 3,
+                county_key: "tulcea".to_string(),
+                locality: None,
+                locality_key: None,
+                street_pattern: None,
+                window_start: None,
+                window_end: None,
+                channel: NotificationChannel::Email,
+                contact: "ana@example.ro".to_string(),
+                state: SubscriptionState::Active,
+                reminders: vec![],
+                digest: None,
+                digest_time: None,
+                language: Language::En,
+                signing_secret: None,
+                failure_count: 0,
+                verified_at: Some(Utc::now()),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            },
+            reason: NotificationReason::Rescheduled,
+            incident: Incident {
+                external_id: "134691 - Retele Electrice".to_string(),
+                county: "TULCEA".to_string(),
+                location: "LOC. ISACCEA".to_string(),
+                day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+                start_time: hours.map(|(start, _)| NaiveTime::from_hms_opt(start, 0, 0).unwrap()),
+                end_time: hours.map(|(_, end)| NaiveTime::from_hms_opt(end, 0, 0).unwrap()),
+                description: description.to_string(),
+                id: 7,
+                revision: 2,
+                updated_at: Utc::now(),
+                cancelled_at: None,
+                duplicate_of: None,
+            },
+        }
+    }
+
+    #[test]
+    fn emails_cover_the_place_the_time_and_the_description() {
+        let email = render(
+            &Templates::default(),
+            &notification(Some((9, 13)), "Strada Păcii <nr. 1-10>"),
+        )
+        .unwrap();
+
+        assert_eq!("Rescheduled power outage in LOC. ISACCEA on 08.08.2025", email.subject);
+        assert_eq!(
+            format!(
+                "Rescheduled power outage\r\n\r\nCounty: TULCEA\r\nLocality: LOC. ISACCEA\r\nDay: 08.08.2025\r\n\
+                 Time window: 09:00 - 13:00\r\n\r\nStrada Păcii <nr. 1-10>\r\n\r\nUnsubscribe: {}\r\n",
+                Templates::default().links().unsubscribe_url(3)
+            ),
+            email.text
+        );
+        assert!(
+            email
+                .html
+                .contains("<tr><th align=\"left\">Time window</th><td>09:00 - 13:00</td></tr>")
+        );
+        assert!(email.html.contains("<p>Strada Păcii &lt;nr. 1-10&gt;</p>"));
+    }
+
+    #[test]
+    fn unknown_hours_are_said_so() {
+        let email = render(&Templates::default(), &notification(None, "")).unwrap();
+
+        assert!(email.text.contains("Time window: not announced\r\n"));
+        // Only the unsubscribe link.
+        assert_eq!(1, email.html.matches("<p>").count());
+    }
+
+    #[test]
+    fn digests_list_the_outages() {
+        let Notification {
+            mut subscription,
+            reason,
+            incident,
+        } = notification(Some((9, 13)), "Strada Păcii <nr. 1-10>");
+        subscription.digest = Some(DigestSchedule::Daily);
+        let digest = Digest::new(subscription, vec![(2, DigestChange { reason, incident })]);
+        let email = render_digest(&Templates::default(), &digest).unwrap();
+
+        assert_eq!("Daily power outage digest: 1 outage", email.subject);
+        assert_eq!(
+            format!(
+                "Daily power outage digest: 1 outage\r\n\r\n- Rescheduled power outage in LOC. ISACCEA, TULCEA on \
+                 08.08.2025 09:00-13:00: Strada Păcii <nr. 1-10>\r\n\r\nUnsubscribe: {}\r\n",
+                Templates::default().links().unsubscribe_url(3)
+            ),
+            email.text
+        );
+        assert!(email.html.contains(
+            "<li>Rescheduled power outage in LOC. ISACCEA, TULCEA on 08.08.2025 \
+             09:00-13:00: Strada Păcii &lt;nr. 1-10&gt;</li>"
+        ));
+    }
+
+    #[test]
+    fn non_ascii_headers_are_encoded() {
+        assert_eq!("Plain subject", encoded_header("Plain\nsubject"));
+        assert_eq!("=?UTF-8?B?UMSDY2lp?=", encoded_header("Păcii"));
+        assert_eq!(2, encoded_header(&"ă".repeat(30)).split("\r\n ").count());
+    }
+
+    #[test]
+    fn messages_have_both_alternatives() {
+        let email = Email {
+            subject: "Subject".to_string(),
+            text: "text".to_string(),
+            html: "<p>html</p>".to_string(),
+            unsubscribe_url: Some("https://enel.lab.wicked/api/unsubscribe?token=3.sig".to_string()),
+        };
+        let date = Utc.with_ymd_and_hms(2025, 8, 8, 12, 0, 0).unwrap();
+
+        let message = mime_message("alerte@example.ro", "ana@example.ro", &email, date);
+
+        assert!(message.starts_with("From: <alerte@example.ro>\r\nTo: <ana@example.ro>\r\nSubject: Subject\r\n"));
+        assert!(message.contains("Date: Fri, 8 Aug 2025 12:00:00 +0000\r\n"));
+        assert!(message.contains(
+            "List-Unsubscribe: <https://enel.lab.wicked/api/unsubscribe?token=3.sig>\r\n\
+             List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n"
+        ));
+        assert!(message.contains(&format!(
+            "text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}",
+            base64_lines("text")
+        )));
+        assert!(message.contains(&base64_lines("<p>html</p>")));
+        assert!(message.ends_with("--\r\n"));
+    }
+
+    #[test]
+    fn base64_lines_are_wrapped() {
+        let lines = base64_lines(&"a".repeat(100));
+
+        assert!(lines.split("\r\n").all(|line| line.len() <= 76));
+        assert_eq!(2, lines.split("\r\n").count());
+    }
+}
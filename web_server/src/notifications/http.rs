@@ -0,0 +1,297 @@
+use crate::notifications::notifier::DeliveryError;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Request, StatusCode, Uri};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::connect::dns::Name;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::future::{Future, ready};
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::net::{TcpStream, lookup_host};
+use tokio::time::timeout;
+use tower_service::Service;
+
+/// A gateway which does not answer in time is treated as unavailable, the notification is sent again later.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Only the beginning of an error response is kept for the logs.
+const MAX_ERROR_BODY_LENGTH: usize = 200;
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+pub type HttpClient = Client<HttpsConnector<TargetConnector>, Full<Bytes>>;
+
+/// A client for plain HTTP and for HTTPS verified against the root certificates of the system, for the gateways
+/// configured with the service.
+pub fn http_client() -> Result<HttpClient, String> {
+    client(true)
+}
+
+/// A client for the URLs given by the subscribers. Unless `allow_private_targets`, it only connects to public addresses,
+/// checked once the host is resolved, so that a subscriber cannot reach the network of the service through it.
+pub fn subscriber_http_client(allow_private_targets: bool) -> Result<HttpClient, String> {
+    client(allow_private_targets)
+}
+
+fn client(allow_private_targets: bool) -> Result<HttpClient, String> {
+    let mut http = HttpConnector::new_with_resolver(TargetResolver { allow_private_targets });
+    http.enforce_http(false);
+    let connector = HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .map_err(|e| format!("Could not load the root certificates: {}", e))?
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(TargetConnector {
+            http,
+            allow_private_targets,
+        });
+    Ok(Client::builder(TokioExecutor::new()).build(connector))
+}
+
+/// The host of a URL given by a subscriber is or resolves to an address of the network of the service.
+#[derive(Debug)]
+pub struct InternalTarget {
+    host: String,
+    address: IpAddr,
+}
+
+impl Display for InternalTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` is the internal address {}.", self.host, self.address)
+    }
+}
+
+impl Error for InternalTarget {}
+
+/// Resolves the hosts, refusing the names resolving to any internal address unless they are allowed.
+#[derive(Debug, Clone, Copy)]
+pub struct TargetResolver {
+    allow_private_targets: bool,
+}
+
+impl Service<Name> for TargetResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let allow_private_targets = self.allow_private_targets;
+        Box::pin(async move {
+            // The connector sets the port of the URL.
+            let addresses: Vec<SocketAddr> = lookup_host((name.as_str(), 0)).await?.collect();
+            if !allow_private_targets {
+                check_addresses(name.as_str(), &addresses)?;
+            }
+            Ok(addresses.into_iter())
+        })
+    }
+}
+
+/// Connects through the [`TargetResolver`], and refuses the internal addresses written in the URLs, which are not
+/// resolved.
+#[derive(Debug, Clone)]
+pub struct TargetConnector {
+    http: HttpConnector<TargetResolver>,
+    allow_private_targets: bool,
+}
+
+impl Service<Uri> for TargetConnector {
+    type Response = TokioIo<TcpStream>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        if !self.allow_private_targets
+            && let Some(host) = uri.host()
+            && let Ok(address) = unbracketed(host).parse::<IpAddr>()
+            && let Err(e) = check_addresses(host, &[SocketAddr::new(address, 0)])
+        {
+            return Box::pin(ready(Err(e.into())));
+        }
+        let connecting = self.http.call(uri);
+        Box::pin(async move { connecting.await.map_err(Into::into) })
+    }
+}
+
+/// Checks the host of a URL given by a subscriber when it is saved, the deliveries check it again as it may resolve
+/// differently by then.
+pub async fn check_public_target(url: &str) -> Result<(), String> {
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|e| format!("Invalid URL `{}`: {}", url, e))?;
+    let host = uri.host().map(unbracketed).unwrap_or_default();
+    let addresses: Vec<SocketAddr> = lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("Could not resolve `{}`: {}", host, e))?
+        .collect();
+    check_addresses(host, &addresses).map_err(|e| e.to_string())
+}
+
+fn check_addresses(host: &str, addresses: &[SocketAddr]) -> Result<(), InternalTarget> {
+    match addresses.iter().find(|address| !is_public(address.ip())) {
+        Some(address) => Err(InternalTarget {
+            host: host.to_string(),
+            address: address.ip(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Whether the address can be reached from the internet: the private, shared, loopback, link-local and unspecified
+/// addresses are the ones of the network of the service, also when mapped to IPv6.
+fn is_public(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => {
+            let [first, second, ..] = address.octets();
+            let shared = first == 100 && (64..128).contains(&second);
+            !(address.is_private()
+                || shared
+                || address.is_loopback()
+                || address.is_link_local()
+                || address.is_unspecified()
+                || address.is_broadcast())
+        }
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(mapped) => is_public(IpAddr::V4(mapped)),
+            None => {
+                !(address.is_loopback()
+                    || address.is_unspecified()
+                    || address.is_unique_local()
+                    || address.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+fn unbracketed(host: &str) -> &str {
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+/// The refusal of an internal address, somewhere among the causes of a failed request.
+fn internal_target<'a>(error: &'a (dyn Error + 'static)) -> Option<&'a InternalTarget> {
+    let mut cause = Some(error);
+    while let Some(error) = cause {
+        if let Some(target) = error.downcast_ref::<InternalTarget>() {
+            return Some(target);
+        }
+        cause = error.source();
+    }
+    None
+}
+
+/// Sends the request, any 2xx answer is a delivery. Timeouts, connection errors, throttling and server errors can be
+/// retried, the other answers and the internal addresses are rejections.
+pub async fn deliver(client: &HttpClient, request: Request<Full<Bytes>>) -> Result<(), DeliveryError> {
+    let response = match timeout(REQUEST_TIMEOUT, client.request(request)).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => {
+            return Err(match internal_target(&e) {
+                Some(target) => DeliveryError::Rejected(target.to_string()),
+                None => DeliveryError::Transient(e.to_string()),
+            });
+        }
+        Err(_) => return Err(DeliveryError::Transient(String::from("No answer in time."))),
+    };
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let body = match timeout(REQUEST_TIMEOUT, response.into_body().collect()).await {
+        Ok(Ok(body)) => String::from_utf8_lossy(&body.to_bytes()).into_owned(),
+        _ => String::new(),
+    };
+    let message = format!(
+        "Answered {}: {}",
+        status,
+        body.chars().take(MAX_ERROR_BODY_LENGTH).collect::<String>()
+    );
+    if is_retryable(status) {
+        Err(DeliveryError::Transient(message))
+    } else {
+        Err(DeliveryError::Rejected(message))
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+#[cfg(test)]
+mod http_tests {
+    use super::{check_public_target, deliver, is_public, is_retryable, subscriber_http_client};
+    use crate::notifications::notifier::DeliveryError;
+    use http_body_util::Full;
+    use hyper::{Request, StatusCode};
+    use std::net::IpAddr;
+
+    #[test]
+    fn throttling_and_server_errors_are_retried() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        let public = |address: &str| is_public(address.parse::<IpAddr>().unwrap());
+
+        assert!(public("8.8.8.8"));
+        assert!(public("2a00:1450:4001:82a::200e"));
+        assert!(!public("127.0.0.1"));
+        assert!(!public("10.1.2.3"));
+        assert!(!public("172.16.0.1"));
+        assert!(!public("192.168.1.1"));
+        assert!(!public("100.64.0.1"));
+        assert!(!public("169.254.169.254"));
+        assert!(!public("0.0.0.0"));
+        assert!(!public("::1"));
+        assert!(!public("fd00::1"));
+        assert!(!public("fe80::1"));
+        assert!(!public("::ffff:127.0.0.1"));
+    }
+
+    #[tokio::test]
+    async fn internal_targets_are_refused_once_resolved() {
+        assert!(check_public_target("http://localhost:8080/hooks").await.is_err());
+        assert!(
+            check_public_target("http://169.254.169.254/latest/meta-data")
+                .await
+                .is_err()
+        );
+        assert!(check_public_target("http://[::1]/hooks").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn deliveries_to_internal_targets_are_rejected() {
+        for url in ["http://127.0.0.1:9/hooks", "http://localhost:9/hooks"] {
+            let client = subscriber_http_client(false).unwrap();
+            let request = Request::post(url).body(Full::default()).unwrap();
+
+            let delivered = deliver(&client, request).await;
+
+            assert!(
+                matches!(&delivered, Err(DeliveryError::Rejected(message)) if message.contains("internal address")),
+                "{:?}",
+                delivered
+            );
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use chrono::{DateTime, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Europe::Bucharest;
+
+/// The wall clock time in Romania at the given instant.
+pub fn to_local(instant: DateTime<Utc>) -> NaiveDateTime {
+    instant.with_timezone(&Bucharest).naive_local()
+}
+
+/// The instant a wall clock time in Romania stands for. A time in the hour skipped in spring is moved an hour later,
+/// and one in the hour repeated in autumn is taken as its first occurrence.
+pub fn to_utc(local: NaiveDateTime) -> DateTime<Utc> {
+    Bucharest
+        .from_local_datetime(&local)
+        .earliest()
+        .or_else(|| Bucharest.from_local_datetime(&(local + TimeDelta::hours(1))).earliest())
+        .expect("Only an hour is skipped when the clocks change.")
+        .to_utc()
+}
+
+#[cfg(test)]
+mod local_time_tests {
+    use super::{to_local, to_utc};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    #[test]
+    fn summer_and_winter_time() {
+        let summer = Utc.with_ymd_and_hms(2025, 8, 8, 6, 0, 0).unwrap();
+        let winter = Utc.with_ymd_and_hms(2025, 12, 8, 6, 0, 0).unwrap();
+
+        assert_eq!(summer.naive_utc() + chrono::TimeDelta::hours(3), to_local(summer));
+        assert_eq!(winter.naive_utc() + chrono::TimeDelta::hours(2), to_local(winter));
+        assert_eq!(summer, to_utc(to_local(summer)));
+        assert_eq!(winter, to_utc(to_local(winter)));
+    }
+
+    #[test]
+    fn switching_nights() {
+        let before_spring_switch = Utc.with_ymd_and_hms(2025, 3, 30, 0, 59, 0).unwrap();
+        let after_spring_switch = Utc.with_ymd_and_hms(2025, 3, 30, 1, 0, 0).unwrap();
+        assert_eq!("02:59", to_local(before_spring_switch).format("%H:%M").to_string());
+        assert_eq!("04:00", to_local(after_spring_switch).format("%H:%M").to_string());
+        let skipped = NaiveDate::from_ymd_opt(2025, 3, 30)
+            .unwrap()
+            .and_hms_opt(3, 30, 0)
+            .unwrap();
+        assert_eq!(Utc.with_ymd_and_hms(2025, 3, 30, 1, 30, 0).unwrap(), to_utc(skipped));
+
+        let repeated = NaiveDate::from_ymd_opt(2025, 10, 26)
+            .unwrap()
+            .and_hms_opt(3, 30, 0)
+            .unwrap();
+        assert_eq!(Utc.with_ymd_and_hms(2025, 10, 26, 0, 30, 0).unwrap(), to_utc(repeated));
+    }
+}
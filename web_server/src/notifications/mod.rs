@@ -0,0 +1,16 @@
+pub mod consent;
+pub mod digest;
+pub mod dispatcher;
+pub mod email;
+pub mod http;
+pub mod local_time;
+pub mod notifier;
+pub mod push;
+pub mod quiet_hours;
+pub mod reminders;
+pub mod rules;
+pub mod sms;
+pub mod smtp;
+pub mod subscriptions;
+pub mod templates;
+pub mod webhook;
@@ -0,0 +1,175 @@
+use crate::notifications::consent::Verification;
+use crate::notifications::digest::Digest;
+use crate::notifications::local_time;
+use crate::notifications::rules::NotificationReason;
+use crate::notifications::subscriptions::{NotificationChannel, Subscription};
+use crate::web_api::Incident;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveTime, Utc};
+use common::configuration::ServiceConfiguration;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// An incident change about to be delivered to one subscriber.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    /// The subscription matching the incident, its contact is already normalized for its channel.
+    pub subscription: Subscription,
+    pub reason: NotificationReason,
+    pub incident: Incident,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeliveryError {
+    /// The network or the receiving service failed, the notification can be sent again later.
+    Transient(String),
+    /// The receiving service refused the notification, sending it again would fail the same way.
+    Rejected(String),
+}
+
+impl Display for DeliveryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeliveryError::Transient(message) => write!(f, "Delivery failed: {}", message),
+            DeliveryError::Rejected(message) => write!(f, "Delivery rejected: {}", message),
+        }
+    }
+}
+
+impl Error for DeliveryError {}
+
+/// How pressing a notification is, the push channels map it to the priorities of their apps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Urgency {
+    /// Outages days away.
+    Low,
+    /// Outages within the next three days.
+    Normal,
+    /// Outages within the next day.
+    High,
+    /// Outages starting within a few hours or already started.
+    Urgent,
+}
+
+/// Delivers the notifications of the subscriptions of one channel.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn channel(&self) -> NotificationChannel;
+
+    async fn notify(&self, notification: &Notification) -> Result<(), DeliveryError>;
+
+    /// Sends the changes collected for a subscription with a digest as a single message.
+    async fn notify_digest(&self, digest: &Digest) -> Result<(), DeliveryError>;
+
+    /// Sends the code confirming the contact of a new subscription, nothing else is sent to it before.
+    async fn send_verification(&self, verification: &Verification) -> Result<(), DeliveryError>;
+}
+
+/// The sooner the outage starts the more urgent its notification is, the ones without announced hours start with the
+/// day. A cancellation is as urgent as the outage it cancels, the contact may be getting ready for it.
+pub fn urgency(notification: &Notification, now: DateTime<Utc>) -> Urgency {
+    outage_urgency(&notification.incident, now)
+}
+
+/// How soon the outage starts, see [`urgency`].
+pub fn outage_urgency(incident: &Incident, now: DateTime<Utc>) -> Urgency {
+    let start = local_time::to_utc(incident.day.and_time(incident.start_time.unwrap_or(NaiveTime::MIN)));
+    match (start - now).num_hours() {
+        ..3 => Urgency::Urgent,
+        3..24 => Urgency::High,
+        24..72 => Urgency::Normal,
+        _ => Urgency::Low,
+    }
+}
+
+/// The address the links of the messages point to, the first OpenAPI server when it is not configured.
+pub fn public_url(configuration: &ServiceConfiguration) -> String {
+    configuration
+        .public_url
+        .clone()
+        .or_else(|| configuration.openapi_servers.first().cloned())
+        .unwrap_or_default()
+}
+
+/// The page of the incident in the webapp, served under `/web` on the public address of the service.
+pub fn incident_url(public_url: &str, incident: &Incident) -> String {
+    format!("{}/web/incidents/{}", public_url.trim_end_matches('/'), incident.id)
+}
+
+#[cfg(test)]
+mod notifier_tests {
+    use super::{Notification, Urgency, urgency};
+    use crate::notifications::rules::NotificationReason;
+    use crate::notifications::subscriptions::{NotificationChannel, Subscription, SubscriptionState};
+    use crate::notifications::templates::Language;
+    use crate::web_api::Incident;
+    use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+
+    fn notification(reason: NotificationReason, hours: Option<(u32, u32)>) -> Notification {
+        Notification {
+            subscription: Subscription {
+                id: 3,
+                county_key: "tulcea".to_string(),
+                locality: None,
+                locality_key: None,
+                street_pattern: None,
+                window_start: None,
+                window_end: None,
+                channel: NotificationChannel::Sms,
+                contact: "+40722123456".to_string(),
+                state: SubscriptionState::Active,
+                reminders: vec![],
+                digest: None,
+                digest_time: None,
+                language: Language::En,
+                signing_secret: None,
+                failure_count: 0,
+                verified_at: Some(Utc::now()),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            },
+            reason,
+            incident: Incident {
+                external_id: "134691 - Retele Electrice".to_string(),
+                county: "TULCEA".to_string(),
+                location: "LOC. ISACCEA".to_string(),
+                day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+                start_time: hours.map(|(start, _)| NaiveTime::from_hms_opt(start, 0, 0).unwrap()),
+                end_time: hours.map(|(_, end)| NaiveTime::from_hms_opt(end, 0, 0).unwrap()),
+                description: String::new(),
+                id: 7,
+                revision: 1,
+                updated_at: Utc::now(),
+                cancelled_at: None,
+                duplicate_of: None,
+            },
+        }
+    }
+
+    #[test]
+    fn urgency_grows_as_the_outage_gets_closer() {
+        // 09:00 in Romania on 08.08.2025 is 06:00 UTC.
+        let new = notification(NotificationReason::New, Some((9, 13)));
+        let at = |day, hour| Utc.with_ymd_and_hms(2025, 8, day, hour, 0, 0).unwrap();
+
+        assert_eq!(Urgency::Low, urgency(&new, at(4, 6)));
+        assert_eq!(Urgency::Normal, urgency(&new, at(6, 6)));
+        assert_eq!(Urgency::High, urgency(&new, at(7, 18)));
+        assert_eq!(Urgency::Urgent, urgency(&new, at(8, 4)));
+        assert_eq!(Urgency::Urgent, urgency(&new, at(8, 8)));
+
+        let cancelled = notification(NotificationReason::Cancelled, Some((9, 13)));
+        assert_eq!(Urgency::Low, urgency(&cancelled, at(4, 6)));
+        assert_eq!(Urgency::Urgent, urgency(&cancelled, at(8, 4)));
+    }
+
+    #[test]
+    fn outages_without_hours_start_with_the_day() {
+        let new = notification(NotificationReason::New, None);
+
+        assert_eq!(
+            Urgency::Urgent,
+            urgency(&new, Utc.with_ymd_and_hms(2025, 8, 7, 20, 0, 0).unwrap())
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use crate::notifications::consent::Verification;
+use crate::notifications::digest::Digest;
+use crate::notifications::http::{HttpClient, deliver, subscriber_http_client};
+use crate::notifications::notifier::{DeliveryError, Notification, Notifier, Urgency, incident_url, urgency};
+use crate::notifications::rules::NotificationReason;
+use crate::notifications::subscriptions::NotificationChannel;
+use crate::notifications::templates::{Template, Templates};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Request, Uri};
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+/// Publishes the notifications to the ntfy topic of the subscription, the message opening the incident page when
+/// tapped. The digests are sent with the normal priority.
+pub struct NtfyNotifier {
+    client: HttpClient,
+    public_url: String,
+    templates: Arc<Templates>,
+}
+
+/// Pushes the notifications to the Gotify server of the subscription, with the token of its application.
+pub struct GotifyNotifier {
+    client: HttpClient,
+    public_url: String,
+    templates: Arc<Templates>,
+}
+
+impl NtfyNotifier {
+    pub fn new(
+        public_url: String,
+        templates: Arc<Templates>,
+        allow_private_targets: bool,
+    ) -> Result<NtfyNotifier, String> {
+        Ok(NtfyNotifier {
+            client: subscriber_http_client(allow_private_targets)?,
+            public_url,
+            templates,
+        })
+    }
+}
+
+impl GotifyNotifier {
+    pub fn new(
+        public_url: String,
+        templates: Arc<Templates>,
+        allow_private_targets: bool,
+    ) -> Result<GotifyNotifier, String> {
+        Ok(GotifyNotifier {
+            client: subscriber_http_client(allow_private_targets)?,
+            public_url,
+            templates,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for NtfyNotifier {
+    fn channel(&self) -> NotificationChannel {
+        NotificationChannel::Ntfy
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), DeliveryError> {
+        let (url, topic) = ntfy_target(&notification.subscription.contact).map_err(DeliveryError::Rejected)?;
+        let payload = ntfy_payload(&self.templates, notification, &topic, &self.public_url, Utc::now())?;
+        post_json(&self.client, url, &payload).await
+    }
+
+    async fn notify_digest(&self, digest: &Digest) -> Result<(), DeliveryError> {
+        let (url, topic) = ntfy_target(&digest.subscription.contact).map_err(DeliveryError::Rejected)?;
+        let payload = json!({
+            "topic": topic,
+            "title": self.templates.render_digest(Template::DigestTitle, digest)?,
+            "message": digest_message(&self.templates, digest)?,
+            "priority": ntfy_priority(Urgency::Normal),
+            "tags": ["newspaper"],
+        });
+        post_json(&self.client, url, &payload).await
+    }
+
+    async fn send_verification(&self, verification: &Verification) -> Result<(), DeliveryError> {
+        let (url, topic) = ntfy_target(&verification.subscription.contact).map_err(DeliveryError::Rejected)?;
+        let payload = json!({
+            "topic": topic,
+            "title": self.templates.render_verification(Template::VerificationTitle, verification)?,
+            "message": self.templates.render_verification(Template::VerificationMessage, verification)?,
+            "priority": ntfy_priority(Urgency::Normal),
+            "tags": ["key"],
+            "click": verification_url(&self.templates, verification),
+        });
+        post_json(&self.client, url, &payload).await
+    }
+}
+
+#[async_trait]
+impl Notifier for GotifyNotifier {
+    fn channel(&self) -> NotificationChannel {
+        NotificationChannel::Gotify
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), DeliveryError> {
+        let url = gotify_target(&notification.subscription.contact).map_err(DeliveryError::Rejected)?;
+        let payload = gotify_payload(&self.templates, notification, &self.public_url, Utc::now())?;
+        post_json(&self.client, url, &payload).await
+    }
+
+    async fn notify_digest(&self, digest: &Digest) -> Result<(), DeliveryError> {
+        let url = gotify_target(&digest.subscription.contact).map_err(DeliveryError::Rejected)?;
+        let payload = json!({
+            "title": self.templates.render_digest(Template::DigestTitle, digest)?,
+            "message": digest_message(&self.templates, digest)?,
+            "priority": gotify_priority(Urgency::Normal),
+            "extras": { "client::display": { "contentType": "text/plain" } },
+        });
+        post_json(&self.client, url, &payload).await
+    }
+
+    async fn send_verification(&self, verification: &Verification) -> Result<(), DeliveryError> {
+        let url = gotify_target(&verification.subscription.contact).map_err(DeliveryError::Rejected)?;
+        let payload = json!({
+            "title": self.templates.render_verification(Template::VerificationTitle, verification)?,
+            "message": self.templates.render_verification(Template::VerificationMessage, verification)?,
+            "priority": gotify_priority(Urgency::Normal),
+            "extras": {
+                "client::display": { "contentType": "text/plain" },
+                "client::notification": { "click": { "url": verification_url(&self.templates, verification) } },
+            },
+        });
+        post_json(&self.client, url, &payload).await
+    }
+}
+
+async fn post_json(client: &HttpClient, url: Uri, payload: &Value) -> Result<(), DeliveryError> {
+    let request = Request::post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(payload.to_string())))
+        .map_err(|e| DeliveryError::Rejected(e.to_string()))?;
+    deliver(client, request).await
+}
+
+/// The server URL the messages are published to as JSON, with the query of the topic URL kept for the `auth`
+/// parameter, and the name of the topic.
+pub fn ntfy_target(contact: &str) -> Result<(Uri, String), String> {
+    let uri = contact
+        .parse::<Uri>()
+        .map_err(|e| format!("Invalid ntfy topic URL `{}`: {}", contact, e))?;
+    let (Some(scheme), Some(authority)) = (uri.scheme_str(), uri.authority()) else {
+        return Err(format!("The ntfy topic URL `{}` is not absolute.", contact));
+    };
+    let (base, topic) = uri.path().trim_end_matches('/').rsplit_once('/').unwrap_or_default();
+    if topic.is_empty() || !topic.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("The ntfy topic URL `{}` does not end with a topic.", contact));
+    }
+
+    let query = uri.query().map(|query| format!("?{}", query)).unwrap_or_default();
+    let url = format!("{}://{}{}/{}", scheme, authority, base, query)
+        .parse::<Uri>()
+        .map_err(|e| format!("Invalid ntfy server URL: {}", e))?;
+    Ok((url, topic.to_string()))
+}
+
+/// The message endpoint of the Gotify server, the contact being the server URL with the `token` of the application.
+pub fn gotify_target(contact: &str) -> Result<Uri, String> {
+    let uri = contact
+        .parse::<Uri>()
+        .map_err(|e| format!("Invalid Gotify URL `{}`: {}", contact, e))?;
+    let (Some(scheme), Some(authority)) = (uri.scheme_str(), uri.authority()) else {
+        return Err(format!("The Gotify URL `{}` is not absolute.", contact));
+    };
+    let Some(query) = uri.query().filter(|query| {
+        query
+            .split('&')
+            .any(|parameter| parameter.len() > 6 && parameter.starts_with("token="))
+    }) else {
+        return Err(format!("The Gotify URL `{}` has no application token.", contact));
+    };
+
+    let path = uri.path().trim_end_matches('/');
+    let path = path.strip_suffix("/message").unwrap_or(path);
+    format!("{}://{}{}/message?{}", scheme, authority, path, query)
+        .parse::<Uri>()
+        .map_err(|e| format!("Invalid Gotify URL: {}", e))
+}
+
+/// The push messages end with the unsubscribe link of the subscription.
+fn push_message(templates: &Templates, notification: &Notification) -> Result<String, DeliveryError> {
+    Ok(format!(
+        "{}\n{}",
+        templates.render(Template::PushMessage, notification)?,
+        templates.render(Template::Unsubscribe, notification)?
+    ))
+}
+
+fn digest_message(templates: &Templates, digest: &Digest) -> Result<String, DeliveryError> {
+    Ok(format!(
+        "{}\n{}",
+        templates.render_digest(Template::DigestMessage, digest)?,
+        templates.render_digest(Template::Unsubscribe, digest)?
+    ))
+}
+
+fn verification_url(templates: &Templates, verification: &Verification) -> String {
+    templates
+        .links()
+        .verification_url(verification.subscription.id, &verification.code)
+}
+
+/// ntfy priorities go from 1, delivered silently, to 5, breaking through do not disturb.
+fn ntfy_priority(urgency: Urgency) -> u8 {
+    match urgency {
+        Urgency::Low => 2,
+        Urgency::Normal => 3,
+        Urgency::High => 4,
+        Urgency::Urgent => 5,
+    }
+}
+
+/// Gotify priorities go from 0, not shown as a notification, to 10, the clients popping up the ones from 8.
+fn gotify_priority(urgency: Urgency) -> u8 {
+    match urgency {
+        Urgency::Low => 2,
+        Urgency::Normal => 4,
+        Urgency::High => 6,
+        Urgency::Urgent => 8,
+    }
+}
+
+fn ntfy_payload(
+    templates: &Templates,
+    notification: &Notification,
+    topic: &str,
+    public_url: &str,
+    now: DateTime<Utc>,
+) -> Result<Value, DeliveryError> {
+    let tag = match notification.reason {
+        NotificationReason::Cancelled => "white_check_mark",
+        NotificationReason::New | NotificationReason::Rescheduled => "zap",
+        NotificationReason::Reminder => "alarm_clock",
+    };
+    Ok(json!({
+        "topic": topic,
+        "title": templates.render(Template::PushTitle, notification)?,
+        "message": push_message(templates, notification)?,
+        "priority": ntfy_priority(urgency(notification, now)),
+        "tags": [tag],
+        "click": incident_url(public_url, &notification.incident),
+    }))
+}
+
+fn gotify_payload(
+    templates: &Templates,
+    notification: &Notification,
+    public_url: &str,
+    now: DateTime<Utc>,
+) -> Result<Value, DeliveryError> {
+    Ok(json!({
+        "title": templates.render(Template::PushTitle, notification)?,
+        "message": push_message(templates, notification)?,
+        "priority": gotify_priority(urgency(notification, now)),
+        "extras": {
+            "client::display": { "contentType": "text/plain" },
+            "client::notification": { "click": { "url": incident_url(public_url, &notification.incident) } },
+        },
+    }))
+}
+
+#[cfg(test)]
+mod push_tests {
+    use super::{gotify_payload, gotify_target, ntfy_payload, ntfy_target};
+    use crate::notifications::notifier::Notification;
+    use crate::notifications::rules::NotificationReason;
+    use crate::notifications::subscriptions::{NotificationChannel, Subscription, SubscriptionState};
+    use crate::notifications::templates::{Language, Templates};
+    use crate::web_api::Incident;
+    use chrono::{NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+    use serde_json::json;
+
+    fn notification(reason: NotificationReason) -> Notification {
+        Notification {
+            subscription: Subscription {
+                id: 3,
+                county_key: "tulcea".to_string(),
+                locality: None,
+                locality_key: None,
+                street_pattern: None,
+                window_start: None,
+                window_end: None,
+                channel: NotificationChannel::Ntfy,
+                contact: "https://ntfy.sh/isaccea".to_string(),
+                state: SubscriptionState::Active,
+                reminders: vec![],
+                digest: None,
+                digest_time: None,
+                language: Language::En,
+                signing_secret: None,
+                failure_count: 0,
+                verified_at: Some(Utc::now()),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            },
+            reason,
+            incident: Incident {
+                external_id: "134691 - Retele Electrice".to_string(),
+                county: "TULCEA".to_string(),
+                location: "LOC. ISACCEA".to_string(),
+                day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+                start_time: NaiveTime::from_hms_opt(9, 0, 0),
+                end_time: NaiveTime::from_hms_opt(13, 0, 0),
+                description: "Strada Păcii nr. 1-10".to_string(),
+                id: 7,
+                revision: 1,
+                updated_at: Utc::now(),
+                cancelled_at: None,
+                duplicate_of: None,
+            },
+        }
+    }
+
+    #[test]
+    fn ntfy_messages_are_published_to_the_server_of_the_topic() {
+        let (url, topic) = ntfy_target("https://ntfy.example.ro/alerts/isaccea-2?auth=abc").unwrap();
+
+        assert_eq!("https://ntfy.example.ro/alerts/?auth=abc", url.to_string());
+        assert_eq!("isaccea-2", topic);
+        assert!(ntfy_target("https://ntfy.sh/").is_err());
+        assert!(ntfy_target("https://ntfy.sh/a/b%20c").is_err());
+    }
+
+    #[test]
+    fn gotify_messages_need_the_application_token() {
+        assert_eq!(
+            "https://gotify.example.ro/message?token=AbC",
+            gotify_target("https://gotify.example.ro/?token=AbC")
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            "https://example.ro/gotify/message?token=AbC",
+            gotify_target("https://example.ro/gotify/message?token=AbC")
+                .unwrap()
+                .to_string()
+        );
+        assert!(gotify_target("https://gotify.example.ro/").is_err());
+        assert!(gotify_target("https://gotify.example.ro/?token=").is_err());
+    }
+
+    #[test]
+    fn ntfy_payload_links_the_incident() {
+        let now = Utc.with_ymd_and_hms(2025, 8, 7, 18, 0, 0).unwrap();
+
+        assert_eq!(
+            json!({
+                "topic": "isaccea",
+                "title": "Planned power outage in LOC. ISACCEA",
+                "message": format!(
+                    "TULCEA, 08.08.2025 09:00-13:00\nStrada Păcii nr. 1-10\nUnsubscribe: {}",
+                    Templates::default().links().unsubscribe_url(3)
+                ),
+                "priority": 4,
+                "tags": ["zap"],
+                "click": "https://enel.lab.wicked/web/incidents/7",
+            }),
+            ntfy_payload(
+                &Templates::default(),
+                &notification(NotificationReason::New),
+                "isaccea",
+                "https://enel.lab.wicked",
+                now
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn gotify_priority_follows_the_urgency() {
+        let now = Utc.with_ymd_and_hms(2025, 8, 8, 5, 0, 0).unwrap();
+
+        let templates = Templates::default();
+        let new = gotify_payload(
+            &templates,
+            &notification(NotificationReason::New),
+            "https://enel.lab.wicked/",
+            now,
+        )
+        .unwrap();
+        assert_eq!(8, new["priority"]);
+        assert_eq!(
+            "https://enel.lab.wicked/web/incidents/7",
+            new["extras"]["client::notification"]["click"]["url"]
+        );
+
+        let cancelled = gotify_payload(
+            &templates,
+            &notification(NotificationReason::Cancelled),
+            "https://enel.lab.wicked",
+            now,
+        )
+        .unwrap();
+        assert_eq!(8, cancelled["priority"]);
+        let later = gotify_payload(
+            &templates,
+            &notification(NotificationReason::Cancelled),
+            "https://enel.lab.wicked",
+            now - TimeDelta::days(5),
+        )
+        .unwrap();
+        assert_eq!(2, later["priority"]);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::AppState;
+use crate::error::{ApiError, ProblemDetails};
+use crate::extract::{Json, Query};
+use crate::notifications::local_time;
+use crate::notifications::subscriptions::{ManagementToken, NotificationChannel, normalize_contact};
+use axum::extract::State;
+use axum::http::StatusCode;
+use chrono::{DateTime, Days, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::ops::Deref;
+use utoipa::{IntoParams, ToSchema};
+
+const QUIET_HOURS_QUERY: &str = "SELECT * FROM quiet_hours WHERE channel = $1 AND contact = $2";
+
+const UPSERT_QUIET_HOURS_QUERY: &str = "INSERT INTO quiet_hours(channel, contact, start_time, end_time) \
+ VALUES ($1, $2, $3, $4) ON CONFLICT (channel, contact) DO UPDATE \
+ SET start_time = EXCLUDED.start_time, end_time = EXCLUDED.end_time, updated_at = now() RETURNING *";
+
+const DELETE_QUIET_HOURS_QUERY: &str = "DELETE FROM quiet_hours WHERE channel = $1 AND contact = $2";
+
+/// Anyone may subscribe any contact, only the subscriptions whose contact confirmed them speak for it.
+const VERIFIED_SUBSCRIPTION_QUERY: &str = "SELECT EXISTS (SELECT 1 FROM subscriptions \
+ WHERE id = $1 AND channel = $2 AND contact = $3 AND verified_at IS NOT NULL)";
+
+/// Body of the requests setting the quiet hours of a contact.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QuietHoursRequest {
+    pub channel: NotificationChannel,
+    /// Phone number, email address or URL, as in the subscriptions.
+    #[schema(example = "+40722123456")]
+    pub contact: String,
+    /// Start of the quiet hours, Romanian time.
+    #[schema(value_type = String, example = "22:00:00")]
+    pub start_time: NaiveTime,
+    /// End of the quiet hours, the next day when it is before the start.
+    #[schema(value_type = String, example = "07:00:00")]
+    pub end_time: NaiveTime,
+}
+
+/// The hours in which the notifications of a contact are held back, unless the outage starts within a few hours.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, FromRow)]
+pub struct QuietHours {
+    pub id: i64,
+    pub channel: NotificationChannel,
+    pub contact: String,
+    #[schema(value_type = String, example = "22:00:00")]
+    pub start_time: NaiveTime,
+    #[schema(value_type = String, example = "07:00:00")]
+    pub end_time: NaiveTime,
+    #[schema(value_type = String, format = DateTime)]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ContactFiltering {
+    pub channel: NotificationChannel,
+    /// Contact as given in the subscriptions.
+    pub contact: String,
+    /// The `management_token` of a verified subscription of the contact.
+    pub token: String,
+}
+
+impl QuietHours {
+    /// The end of the quiet hours when `now` falls inside them.
+    pub fn until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let local_now = local_time::to_local(now);
+        let (today, time) = (local_now.date(), local_now.time());
+        let end_day = if self.start_time < self.end_time {
+            (self.start_time..self.end_time).contains(&time).then_some(today)
+        } else if time >= self.start_time {
+            today.checked_add_days(Days::new(1))
+        } else {
+            (time < self.end_time).then_some(today)
+        };
+        end_day.map(|day| local_time::to_utc(day.and_time(self.end_time)))
+    }
+}
+
+/// The quiet hours of a contact apply to all its subscriptions, the management token of any of them whose contact is
+/// verified manages them.
+async fn authorize(state: &AppState, channel: NotificationChannel, contact: &str, token: &str) -> Result<(), ApiError> {
+    let verified = match state.links.managed_subscription(token) {
+        Some(subscription_id) => {
+            sqlx::query_scalar(VERIFIED_SUBSCRIPTION_QUERY)
+                .bind(subscription_id)
+                .bind(channel)
+                .bind(contact)
+                .fetch_one(state.pg_pool.deref())
+                .await?
+        }
+        None => false,
+    };
+    if verified {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(format!(
+            "The token does not manage a verified subscription of `{}`.",
+            contact
+        )))
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/quiet-hours",
+    params(ManagementToken),
+    request_body = QuietHoursRequest,
+    responses(
+        (status=200, description = "The quiet hours of the contact were set.", body=QuietHours),
+        (status=400, description = "Invalid contact for the channel, the quiet hours start when they end or the body is not JSON.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=413, description = "The body is too large.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=415, description = "The body is not sent as `application/json`.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=422, description = "The body does not fit the request, a field is missing or has the wrong type.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=403, description = "The token does not manage a verified subscription of the contact.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error storing the quiet hours.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn set_quiet_hours(
+    state: State<AppState>,
+    Query(access): Query<ManagementToken>,
+    Json(request): Json<QuietHoursRequest>,
+) -> Result<Json<QuietHours>, ApiError> {
+    let contact = normalize_contact(request.channel, &request.contact).map_err(ApiError::InvalidParameter)?;
+    if request.start_time == request.end_time {
+        return Err(ApiError::InvalidParameter(String::from(
+            "The quiet hours cannot start when they end.",
+        )));
+    }
+    authorize(&state, request.channel, &contact, &access.token).await?;
+
+    let quiet_hours: QuietHours = sqlx::query_as(UPSERT_QUIET_HOURS_QUERY)
+        .bind(request.channel)
+        .bind(contact)
+        .bind(request.start_time)
+        .bind(request.end_time)
+        .fetch_one(state.pg_pool.deref())
+        .await?;
+
+    Ok(Json(quiet_hours))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/quiet-hours",
+    params(ContactFiltering),
+    responses(
+        (status=200, description = "The quiet hours of the contact.", body=QuietHours),
+        (status=403, description = "The token does not manage a verified subscription of the contact.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=404, description = "The contact has no quiet hours.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error getting the quiet hours.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_quiet_hours(
+    state: State<AppState>,
+    Query(filtering): Query<ContactFiltering>,
+) -> Result<Json<QuietHours>, ApiError> {
+    let contact = normalize_contact(filtering.channel, &filtering.contact).map_err(ApiError::InvalidParameter)?;
+    authorize(&state, filtering.channel, &contact, &filtering.token).await?;
+    let quiet_hours: Option<QuietHours> = sqlx::query_as(QUIET_HOURS_QUERY)
+        .bind(filtering.channel)
+        .bind(&contact)
+        .fetch_optional(state.pg_pool.deref())
+        .await?;
+
+    quiet_hours.map(Json).ok_or_else(|| not_found(&contact))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/quiet-hours",
+    params(ContactFiltering),
+    responses(
+        (status=204, description = "The quiet hours of the contact were removed."),
+        (status=403, description = "The token does not manage a verified subscription of the contact.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=404, description = "The contact has no quiet hours.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error removing the quiet hours.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn delete_quiet_hours(
+    state: State<AppState>,
+    Query(filtering): Query<ContactFiltering>,
+) -> Result<StatusCode, ApiError> {
+    let contact = normalize_contact(filtering.channel, &filtering.contact).map_err(ApiError::InvalidParameter)?;
+    authorize(&state, filtering.channel, &contact, &filtering.token).await?;
+    let deleted = sqlx::query(DELETE_QUIET_HOURS_QUERY)
+        .bind(filtering.channel)
+        .bind(&contact)
+        .execute(state.pg_pool.deref())
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(not_found(&contact));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn not_found(contact: &str) -> ApiError {
+    ApiError::NotFound(format!("The contact `{}` has no quiet hours.", contact))
+}
+
+#[cfg(test)]
+mod quiet_hours_tests {
+    use super::QuietHours;
+    use crate::notifications::subscriptions::NotificationChannel;
+    use chrono::{NaiveTime, TimeZone, Utc};
+
+    fn quiet_hours(start: u32, end: u32) -> QuietHours {
+        QuietHours {
+            id: 1,
+            channel: NotificationChannel::Sms,
+            contact: "+40722123456".to_string(),
+            start_time: NaiveTime::from_hms_opt(start, 0, 0).unwrap(),
+            end_time: NaiveTime::from_hms_opt(end, 0, 0).unwrap(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn overnight_quiet_hours_end_the_next_morning() {
+        let night = quiet_hours(22, 7);
+        let late_evening = Utc.with_ymd_and_hms(2025, 8, 7, 20, 0, 0).unwrap();
+        let early_morning = Utc.with_ymd_and_hms(2025, 8, 8, 2, 0, 0).unwrap();
+        let afternoon = Utc.with_ymd_and_hms(2025, 8, 8, 12, 0, 0).unwrap();
+
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2025, 8, 8, 4, 0, 0).unwrap()),
+            night.until(late_evening)
+        );
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2025, 8, 8, 4, 0, 0).unwrap()),
+            night.until(early_morning)
+        );
+        assert_eq!(None, night.until(afternoon));
+    }
+
+    #[test]
+    fn daytime_quiet_hours_end_the_same_day() {
+        let work = quiet_hours(9, 17);
+
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2025, 8, 8, 14, 0, 0).unwrap()),
+            work.until(Utc.with_ymd_and_hms(2025, 8, 8, 6, 0, 0).unwrap())
+        );
+        assert_eq!(None, work.until(Utc.with_ymd_and_hms(2025, 8, 8, 14, 0, 0).unwrap()));
+    }
+}
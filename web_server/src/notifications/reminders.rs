@@ -0,0 +1,175 @@
+use crate::notifications::local_time;
+use crate::notifications::subscriptions::Subscription;
+use crate::web_api::Incident;
+use chrono::{DateTime, Days, NaiveTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The evening reminders are sent at this hour of the day before the outage, Romanian time.
+const EVENING_HOUR: u32 = 20;
+
+/// A reminder sent some time before the outage starts, besides the notification of its announcement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+pub enum Reminder {
+    /// At 20:00 on the day before the outage.
+    EveningBefore,
+    /// Two hours before the announced start. Not sent for the outages without announced hours.
+    TwoHoursBefore,
+}
+
+impl Reminder {
+    /// When the reminder of the incident is due, `None` when the incident has no time to count from.
+    pub fn due_at(self, incident: &Incident) -> Option<DateTime<Utc>> {
+        let local = match self {
+            Reminder::EveningBefore => incident
+                .day
+                .checked_sub_days(Days::new(1))?
+                .and_hms_opt(EVENING_HOUR, 0, 0)?,
+            Reminder::TwoHoursBefore => incident.day.and_time(incident.start_time?) - TimeDelta::hours(2),
+        };
+        Some(local_time::to_utc(local))
+    }
+}
+
+/// The reminders of the subscription still ahead for the incident, with their due time. The ones already due are
+/// left out, the announcement arriving that late is the reminder.
+pub fn upcoming_reminders(
+    subscription: &Subscription,
+    incident: &Incident,
+    now: DateTime<Utc>,
+) -> Vec<(Reminder, DateTime<Utc>)> {
+    subscription
+        .reminders
+        .iter()
+        .filter_map(|reminder| reminder.due_at(incident).map(|due_at| (*reminder, due_at)))
+        .filter(|(_, due_at)| *due_at > now)
+        .collect()
+}
+
+/// Why a due reminder is not sent anymore: the outage changed or started since it was scheduled, or the subscriber
+/// turned the reminder off. The reminders of the current revision are rescheduled when the incident changes.
+pub fn obsolete_reminder(
+    reminder: Reminder,
+    revision: i32,
+    subscription: &Subscription,
+    incident: &Incident,
+    now: DateTime<Utc>,
+) -> Option<&'static str> {
+    if incident.cancelled_at.is_some() {
+        return Some("The outage was cancelled.");
+    }
+    if incident.revision != revision {
+        return Some("The outage was rescheduled.");
+    }
+    if !subscription.reminders.contains(&reminder) {
+        return Some("The reminder was turned off.");
+    }
+    let start = local_time::to_utc(incident.day.and_time(incident.start_time.unwrap_or(NaiveTime::MIN)));
+    if start <= now {
+        return Some("The outage already started.");
+    }
+    None
+}
+
+#[cfg(test)]
+mod reminders_tests {
+    use super::{Reminder, obsolete_reminder, upcoming_reminders};
+    use crate::notifications::subscriptions::{NotificationChannel, Subscription, SubscriptionState};
+    use crate::notifications::templates::Language;
+    use crate::web_api::Incident;
+    use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+
+    fn incident(start_hour: Option<u32>) -> Incident {
+        Incident {
+            external_id: "134691 - Retele Electrice".to_string(),
+            county: "TULCEA".to_string(),
+            location: "LOC. ISACCEA".to_string(),
+            day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+            start_time: start_hour.map(|hour| NaiveTime::from_hms_opt(hour, 0, 0).unwrap()),
+            end_time: start_hour.map(|hour| NaiveTime::from_hms_opt(hour + 4, 0, 0).unwrap()),
+            description: String::new(),
+            id: 7,
+            revision: 2,
+            updated_at: Utc::now(),
+            cancelled_at: None,
+            duplicate_of: None,
+        }
+    }
+
+    fn subscription(reminders: Vec<Reminder>) -> Subscription {
+        Subscription {
+            id: 3,
+            county_key: "tulcea".to_string(),
+            locality: None,
+            locality_key: None,
+            street_pattern: None,
+            window_start: None,
+            window_end: None,
+            channel: NotificationChannel::Sms,
+            contact: "+40722123456".to_string(),
+            state: SubscriptionState::Active,
+            reminders,
+            digest: None,
+            digest_time: None,
+            language: Language::En,
+            signing_secret: None,
+            failure_count: 0,
+            verified_at: Some(Utc::now()),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn reminders_are_due_in_romanian_time() {
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2025, 8, 7, 17, 0, 0).unwrap()),
+            Reminder::EveningBefore.due_at(&incident(Some(9)))
+        );
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2025, 8, 8, 4, 0, 0).unwrap()),
+            Reminder::TwoHoursBefore.due_at(&incident(Some(9)))
+        );
+        assert_eq!(None, Reminder::TwoHoursBefore.due_at(&incident(None)));
+    }
+
+    #[test]
+    fn past_reminders_are_not_scheduled() {
+        let subscription = subscription(vec![Reminder::EveningBefore, Reminder::TwoHoursBefore]);
+        let evening = Utc.with_ymd_and_hms(2025, 8, 7, 18, 0, 0).unwrap();
+
+        assert_eq!(
+            vec![(
+                Reminder::TwoHoursBefore,
+                Utc.with_ymd_and_hms(2025, 8, 8, 4, 0, 0).unwrap()
+            )],
+            upcoming_reminders(&subscription, &incident(Some(9)), evening)
+        );
+        assert!(upcoming_reminders(&subscription, &incident(None), evening).is_empty());
+    }
+
+    #[test]
+    fn reminders_of_changed_outages_are_obsolete() {
+        let subscription = subscription(vec![Reminder::TwoHoursBefore]);
+        let now = Utc.with_ymd_and_hms(2025, 8, 8, 4, 0, 0).unwrap();
+        let incident = incident(Some(9));
+
+        assert_eq!(
+            None,
+            obsolete_reminder(Reminder::TwoHoursBefore, 2, &subscription, &incident, now)
+        );
+        assert!(obsolete_reminder(Reminder::TwoHoursBefore, 1, &subscription, &incident, now).is_some());
+        assert!(obsolete_reminder(Reminder::EveningBefore, 2, &subscription, &incident, now).is_some());
+
+        let cancelled = Incident {
+            cancelled_at: Some(now),
+            ..incident.clone()
+        };
+        assert!(obsolete_reminder(Reminder::TwoHoursBefore, 2, &subscription, &cancelled, now).is_some());
+
+        let started = Utc.with_ymd_and_hms(2025, 8, 8, 6, 30, 0).unwrap();
+        assert!(obsolete_reminder(Reminder::TwoHoursBefore, 2, &subscription, &incident, started).is_some());
+    }
+}
@@ -0,0 +1,524 @@
+use crate::events::LAST_EVENT_QUERY;
+use crate::lookup::{MIN_STREET_SIMILARITY, is_announced_for, street_similarity, street_words_of};
+use crate::notifications::digest::next_digest_at;
+use crate::notifications::dispatcher::JobState;
+use crate::notifications::local_time;
+use crate::notifications::reminders::{Reminder, upcoming_reminders};
+use crate::notifications::subscriptions::{Subscription, SubscriptionState};
+use crate::web_api::Incident;
+use chrono::{DateTime, NaiveDate, NaiveTime, Timelike, Utc};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgExecutor, Pool, Postgres};
+use std::ops::Deref;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// The duplicates are left out, their subscribers are notified through the canonical incident. So are the incidents
+/// of the past days, which nobody can prepare for anymore.
+const EVENTS_QUERY: &str = "SELECT e.kind, e.revision AS event_revision, i.* FROM incident_events e \
+ JOIN incidents i ON i.id = e.incident_id \
+ WHERE e.id > $1 AND i.day >= $2 AND i.duplicate_of IS NULL AND i.county_key IS NOT NULL ORDER BY e.id";
+
+/// The contacts not verified yet get no notifications, not even the ones of the changes made meanwhile.
+const SUBSCRIPTIONS_QUERY: &str =
+    "SELECT * FROM subscriptions WHERE state = $1 AND county_key = ANY($2) AND verified_at IS NOT NULL";
+
+/// A job already created for the same revision is kept as it is, so evaluating the same events again is harmless. The
+/// reminders and the changes collected into digests are due at their time, the other notifications right away.
+const INSERT_JOBS_QUERY: &str = "INSERT INTO notification_jobs(subscription_id, incident_id, revision, reason, \
+ reminder, next_attempt_at) \
+ SELECT s, i, r, reason, reminder, COALESCE(due_at, now()) \
+ FROM UNNEST($1::BIGINT[], $2::BIGINT[], $3::INTEGER[], $4::VARCHAR[], $5::VARCHAR[], $6::TIMESTAMPTZ[]) \
+ AS j(s, i, r, reason, reminder, due_at) \
+ ON CONFLICT (subscription_id, incident_id, revision, COALESCE(reminder, '')) DO NOTHING";
+
+/// The pending reminders of the earlier revisions of the changed incidents are dropped, the ones of the new revision
+/// replace them.
+const CANCEL_REMINDERS_QUERY: &str = "UPDATE notification_jobs j SET state = $3, last_error = $4 \
+ FROM UNNEST($1::BIGINT[], $2::INTEGER[]) AS c(incident_id, revision) \
+ WHERE j.incident_id = c.incident_id AND j.revision < c.revision AND j.reminder IS NOT NULL AND j.state = $5";
+
+/// Why a subscriber is notified about an incident.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+pub enum NotificationReason {
+    /// The outage was announced.
+    New,
+    /// The provider changed the day, the time window or the description of the outage.
+    Rescheduled,
+    /// The provider withdrew the outage.
+    Cancelled,
+    /// The outage starts soon, see [`Reminder`].
+    Reminder,
+}
+
+impl NotificationReason {
+    fn from_event_kind(kind: &str) -> Option<NotificationReason> {
+        match kind {
+            "created" => Some(NotificationReason::New),
+            "updated" => Some(NotificationReason::Rescheduled),
+            "cancelled" => Some(NotificationReason::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct IncidentChange {
+    kind: String,
+    event_revision: i32,
+    county_key: String,
+    #[sqlx(flatten)]
+    incident: Incident,
+}
+
+/// The latest entry of the incident journal, the changes made after it are the ones of the next ingestion run.
+pub async fn latest_event_id(pg_pool: Arc<Pool<Postgres>>) -> Result<i64, String> {
+    journal_position(pg_pool.deref()).await
+}
+
+/// The latest entry of the incident journal seen by the given connection.
+pub async fn journal_position<'e>(executor: impl PgExecutor<'e>) -> Result<i64, String> {
+    sqlx::query_scalar(LAST_EVENT_QUERY)
+        .fetch_one(executor)
+        .await
+        .map_err(|e| {
+            error!("Could not read the incident journal: {}", e);
+            e.to_string()
+        })
+}
+
+/// Matches the incidents created, changed or cancelled after the event `after_event_id` of the journal against the
+/// active subscriptions and creates a notification job for each match. Returns the number of new jobs, the matches
+/// already notified for the same revision of an incident are not counted.
+pub async fn evaluate_incident_changes(
+    after_event_id: i64,
+    today: NaiveDate,
+    pg_pool: Arc<Pool<Postgres>>,
+) -> Result<u64, String> {
+    let mut connection = pg_pool.acquire().await.map_err(|e| {
+        error!("Could not connect to evaluate the incident changes: {}", e);
+        e.to_string()
+    })?;
+    enqueue_notifications(after_event_id, today, &mut connection).await
+}
+
+/// Evaluates the incident changes with the given connection, within the transaction storing them, and reschedules the
+/// reminders of the changed incidents, so the jobs are the outbox of the notifications: they exist if and only if the
+/// changes were stored.
+pub async fn enqueue_notifications(
+    after_event_id: i64,
+    today: NaiveDate,
+    connection: &mut PgConnection,
+) -> Result<u64, String> {
+    let changes: Vec<IncidentChange> = sqlx::query_as(EVENTS_QUERY)
+        .bind(after_event_id)
+        .bind(today)
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(|e| {
+            error!("Could not read the incident changes: {}", e);
+            e.to_string()
+        })?;
+    if changes.is_empty() {
+        return Ok(0);
+    }
+
+    let cancelled = sqlx::query(CANCEL_REMINDERS_QUERY)
+        .bind(changes.iter().map(|change| change.incident.id).collect::<Vec<i64>>())
+        .bind(changes.iter().map(|change| change.event_revision).collect::<Vec<i32>>())
+        .bind(JobState::Skipped)
+        .bind("The outage was rescheduled or cancelled.")
+        .bind(JobState::Pending)
+        .execute(&mut *connection)
+        .await
+        .map_err(|e| {
+            error!("Could not cancel the reminders of the changed incidents: {}", e);
+            e.to_string()
+        })?;
+    if cancelled.rows_affected() > 0 {
+        info!(
+            "Cancelled {} reminders of changed incidents.",
+            cancelled.rows_affected()
+        );
+    }
+
+    let mut counties: Vec<&str> = changes.iter().map(|change| change.county_key.as_str()).collect();
+    counties.sort();
+    counties.dedup();
+    let subscriptions: Vec<Subscription> = sqlx::query_as(SUBSCRIPTIONS_QUERY)
+        .bind(SubscriptionState::Active)
+        .bind(counties)
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(|e| {
+            error!("Could not read the subscriptions: {}", e);
+            e.to_string()
+        })?;
+
+    let jobs = match_changes(&changes, &subscriptions, Utc::now());
+    if jobs.is_empty() {
+        return Ok(0);
+    }
+
+    let mut subscription_ids = Vec::with_capacity(jobs.len());
+    let mut incident_ids = Vec::with_capacity(jobs.len());
+    let mut revisions = Vec::with_capacity(jobs.len());
+    let mut reasons = Vec::with_capacity(jobs.len());
+    let mut reminders = Vec::with_capacity(jobs.len());
+    let mut due_times = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        subscription_ids.push(job.subscription_id);
+        incident_ids.push(job.incident_id);
+        revisions.push(job.revision);
+        reasons.push(job.reason);
+        reminders.push(job.reminder);
+        due_times.push(job.due_at);
+    }
+
+    let inserted = sqlx::query(INSERT_JOBS_QUERY)
+        .bind(subscription_ids)
+        .bind(incident_ids)
+        .bind(revisions)
+        .bind(reasons)
+        .bind(reminders)
+        .bind(due_times)
+        .execute(connection)
+        .await;
+
+    match inserted {
+        Ok(result) => {
+            info!("Created {} notification jobs.", result.rows_affected());
+            Ok(result.rows_affected())
+        }
+        Err(e) => {
+            error!("Could not create the notification jobs: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct NotificationJob {
+    subscription_id: i64,
+    incident_id: i64,
+    revision: i32,
+    reason: NotificationReason,
+    reminder: Option<Reminder>,
+    due_at: Option<DateTime<Utc>>,
+}
+
+/// The notifications of the matching subscriptions, due with the next digest for the subscriptions with one, followed
+/// by their reminders when the outage is announced or rescheduled. The reminders are only scheduled for the latest
+/// revision of the incident.
+fn match_changes(
+    changes: &[IncidentChange],
+    subscriptions: &[Subscription],
+    now: DateTime<Utc>,
+) -> Vec<NotificationJob> {
+    let mut jobs = Vec::new();
+    for change in changes {
+        let Some(reason) = NotificationReason::from_event_kind(&change.kind) else {
+            continue;
+        };
+        let description_words = street_words_of(&change.incident.description);
+        let is_latest = change.event_revision == change.incident.revision && change.incident.cancelled_at.is_none();
+        for subscription in subscriptions
+            .iter()
+            .filter(|subscription| matches(subscription, &change.county_key, &change.incident, &description_words))
+        {
+            jobs.push(NotificationJob {
+                subscription_id: subscription.id,
+                incident_id: change.incident.id,
+                revision: change.event_revision,
+                reason,
+                reminder: None,
+                due_at: digest_due_at(subscription, &change.incident, now),
+            });
+            if reason == NotificationReason::Cancelled || !is_latest {
+                continue;
+            }
+            for (reminder, due_at) in upcoming_reminders(subscription, &change.incident, now) {
+                jobs.push(NotificationJob {
+                    subscription_id: subscription.id,
+                    incident_id: change.incident.id,
+                    revision: change.event_revision,
+                    reason: NotificationReason::Reminder,
+                    reminder: Some(reminder),
+                    due_at: Some(due_at),
+                });
+            }
+        }
+    }
+    jobs
+}
+
+/// The changes wait for the next digest of the subscription, unless the outage starts before it is sent: those are
+/// due right away, in a digest of their own, since a digest announcing a past outage is of no use.
+fn digest_due_at(subscription: &Subscription, incident: &Incident, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let (schedule, time) = subscription.digest.zip(subscription.digest_time)?;
+    let due_at = next_digest_at(schedule, time, now);
+    let start = local_time::to_utc(incident.day.and_time(incident.start_time.unwrap_or(NaiveTime::MIN)));
+    (start > due_at).then_some(due_at)
+}
+
+/// A subscription matches the incidents of its county, announced for its locality, listing a street similar to its
+/// pattern and overlapping its time window. Descriptions without any street and incidents without a time window are
+/// taken as covering the whole locality and the whole day.
+fn matches(subscription: &Subscription, county_key: &str, incident: &Incident, description_words: &[String]) -> bool {
+    if county_key != subscription.county_key {
+        return false;
+    }
+
+    if let Some(locality_key) = &subscription.locality_key
+        && !is_announced_for(locality_key, incident, description_words)
+    {
+        return false;
+    }
+
+    if let Some(pattern) = &subscription.street_pattern {
+        let street_words = street_words_of(pattern);
+        if !street_words.is_empty()
+            && !description_words.is_empty()
+            && street_similarity(&street_words, description_words) < MIN_STREET_SIMILARITY
+        {
+            return false;
+        }
+    }
+
+    match (
+        subscription.window_start,
+        subscription.window_end,
+        incident.start_time,
+        incident.end_time,
+    ) {
+        (Some(window_start), Some(window_end), Some(start), Some(end)) => {
+            overlaps((window_start, window_end), (start, end))
+        }
+        _ => true,
+    }
+}
+
+/// Whether two times of day overlap, each of them running past midnight when it ends before it starts. The window is
+/// repeated on the day before and after the outage, for the outages and the windows running overnight.
+fn overlaps(window: (NaiveTime, NaiveTime), outage: (NaiveTime, NaiveTime)) -> bool {
+    const DAY: i64 = 24 * 3600;
+    let seconds = |(start, end): (NaiveTime, NaiveTime)| {
+        let start = i64::from(start.num_seconds_from_midnight());
+        let end = i64::from(end.num_seconds_from_midnight());
+        (start, if end <= start { end + DAY } else { end })
+    };
+    let (window_start, window_end) = seconds(window);
+    let (start, end) = seconds(outage);
+
+    [-DAY, 0, DAY]
+        .iter()
+        .any(|shift| start < window_end + shift && window_start + shift < end)
+}
+
+#[cfg(test)]
+mod rules_tests {
+    use super::{IncidentChange, NotificationJob, NotificationReason, digest_due_at, match_changes, matches};
+    use crate::lookup::street_words_of;
+    use crate::notifications::digest::DigestSchedule;
+    use crate::notifications::reminders::Reminder;
+    use crate::notifications::subscriptions::{NotificationChannel, Subscription, SubscriptionState};
+    use crate::notifications::templates::Language;
+    use crate::web_api::Incident;
+    use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+
+    fn incident(location: &str, hours: Option<(u32, u32)>, description: &str) -> Incident {
+        Incident {
+            external_id: "134691 - Retele Electrice".to_string(),
+            county: "TULCEA".to_string(),
+            location: location.to_string(),
+            day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+            start_time: hours.map(|(start, _)| NaiveTime::from_hms_opt(start, 0, 0).unwrap()),
+            end_time: hours.map(|(_, end)| NaiveTime::from_hms_opt(end, 0, 0).unwrap()),
+            description: description.to_string(),
+            id: 7,
+            revision: 2,
+            updated_at: Utc::now(),
+            cancelled_at: None,
+            duplicate_of: None,
+        }
+    }
+
+    fn subscription(
+        locality_key: Option<&str>,
+        street_pattern: Option<&str>,
+        window: Option<(u32, u32)>,
+    ) -> Subscription {
+        Subscription {
+            id: 3,
+            county_key: "tulcea".to_string(),
+            locality: None,
+            locality_key: locality_key.map(str::to_string),
+            street_pattern: street_pattern.map(str::to_string),
+            window_start: window.map(|(start, _)| NaiveTime::from_hms_opt(start, 0, 0).unwrap()),
+            window_end: window.map(|(_, end)| NaiveTime::from_hms_opt(end, 0, 0).unwrap()),
+            channel: NotificationChannel::Sms,
+            contact: "+40722123456".to_string(),
+            state: SubscriptionState::Active,
+            reminders: vec![],
+            digest: None,
+            digest_time: None,
+            language: Language::En,
+            signing_secret: None,
+            failure_count: 0,
+            verified_at: Some(Utc::now()),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn is_match(subscription: &Subscription, incident: &Incident) -> bool {
+        matches(
+            subscription,
+            "tulcea",
+            incident,
+            &street_words_of(&incident.description),
+        )
+    }
+
+    #[test]
+    fn matches_place_street_and_time_window() {
+        let incident = incident("LOC. ISACCEA", Some((9, 13)), "Strada: Pacii nr. 1-10, Viilor");
+
+        assert!(is_match(&subscription(None, None, None), &incident));
+        assert!(is_match(
+            &subscription(Some("isaccea"), Some("str. Păcii"), Some((12, 18))),
+            &incident
+        ));
+        assert!(!is_match(&subscription(Some("macin"), None, None), &incident));
+        assert!(!is_match(
+            &subscription(Some("isaccea"), Some("Dunarii"), None),
+            &incident
+        ));
+        assert!(!is_match(
+            &subscription(Some("isaccea"), None, Some((13, 18))),
+            &incident
+        ));
+    }
+
+    #[test]
+    fn overnight_windows_wrap_around_midnight() {
+        let night = subscription(Some("isaccea"), None, Some((22, 6)));
+
+        assert!(is_match(&night, &incident("LOC. ISACCEA", Some((23, 1)), "")));
+        assert!(is_match(&night, &incident("LOC. ISACCEA", Some((4, 8)), "")));
+        assert!(is_match(&night, &incident("LOC. ISACCEA", Some((20, 23)), "")));
+        assert!(!is_match(&night, &incident("LOC. ISACCEA", Some((9, 13)), "")));
+        assert!(!is_match(&night, &incident("LOC. ISACCEA", Some((6, 22)), "")));
+
+        let evening = subscription(Some("isaccea"), None, Some((18, 21)));
+        assert!(is_match(&evening, &incident("LOC. ISACCEA", Some((20, 2)), "")));
+        assert!(!is_match(&evening, &incident("LOC. ISACCEA", Some((22, 2)), "")));
+    }
+
+    #[test]
+    fn unlisted_streets_and_times_cover_the_whole_locality() {
+        let incident = incident("LOC. ISACCEA", None, "");
+
+        assert!(is_match(
+            &subscription(Some("isaccea"), Some("Dunarii"), Some((13, 18))),
+            &incident
+        ));
+    }
+
+    #[test]
+    fn other_counties_never_match() {
+        let incident = incident("LOC. ISACCEA", None, "");
+
+        assert!(!matches(&subscription(None, None, None), "constanta", &incident, &[]));
+    }
+
+    #[test]
+    fn jobs_carry_the_revision_and_the_reason() {
+        let changes = vec![
+            IncidentChange {
+                kind: "created".to_string(),
+                event_revision: 1,
+                county_key: "tulcea".to_string(),
+                incident: incident("LOC. ISACCEA", None, ""),
+            },
+            IncidentChange {
+                kind: "cancelled".to_string(),
+                event_revision: 2,
+                county_key: "tulcea".to_string(),
+                incident: incident("LOC. ISACCEA", None, ""),
+            },
+        ];
+
+        assert_eq!(
+            vec![
+                NotificationJob {
+                    subscription_id: 3,
+                    incident_id: 7,
+                    revision: 1,
+                    reason: NotificationReason::New,
+                    reminder: None,
+                    due_at: None,
+                },
+                NotificationJob {
+                    subscription_id: 3,
+                    incident_id: 7,
+                    revision: 2,
+                    reason: NotificationReason::Cancelled,
+                    reminder: None,
+                    due_at: None,
+                },
+            ],
+            match_changes(&changes, &[subscription(None, None, None)], Utc::now())
+        );
+    }
+
+    #[test]
+    fn outages_starting_before_the_digest_are_not_held_for_it() {
+        let mut weekly = subscription(None, None, None);
+        weekly.digest = Some(DigestSchedule::Weekly);
+        weekly.digest_time = NaiveTime::from_hms_opt(18, 0, 0);
+        // Tuesday 05.08.2025, the outage is on Friday and the digest on Monday.
+        let now = Utc.with_ymd_and_hms(2025, 8, 5, 9, 0, 0).unwrap();
+
+        assert_eq!(
+            None,
+            digest_due_at(&weekly, &incident("LOC. ISACCEA", Some((9, 13)), ""), now)
+        );
+        let mut later = incident("LOC. ISACCEA", Some((9, 13)), "");
+        later.day = NaiveDate::from_ymd_opt(2025, 8, 20).unwrap();
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2025, 8, 11, 15, 0, 0).unwrap()),
+            digest_due_at(&weekly, &later, now)
+        );
+    }
+
+    #[test]
+    fn reminders_follow_the_latest_revision() {
+        let mut subscription = subscription(None, None, None);
+        subscription.reminders = vec![Reminder::EveningBefore, Reminder::TwoHoursBefore];
+        let change = |kind: &str, event_revision: i32| IncidentChange {
+            kind: kind.to_string(),
+            event_revision,
+            county_key: "tulcea".to_string(),
+            incident: incident("LOC. ISACCEA", Some((9, 13)), ""),
+        };
+        let changes = vec![change("created", 1), change("updated", 2)];
+        let now = Utc.with_ymd_and_hms(2025, 8, 7, 18, 0, 0).unwrap();
+
+        let jobs: Vec<(i32, NotificationReason, Option<Reminder>)> = match_changes(&changes, &[subscription], now)
+            .into_iter()
+            .map(|job| (job.revision, job.reason, job.reminder))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, NotificationReason::New, None),
+                (2, NotificationReason::Rescheduled, None),
+                (2, NotificationReason::Reminder, Some(Reminder::TwoHoursBefore)),
+            ],
+            jobs
+        );
+    }
+}
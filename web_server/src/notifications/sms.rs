@@ -0,0 +1,289 @@
+use crate::notifications::consent::Verification;
+use crate::notifications::digest::Digest;
+use crate::notifications::http::{HttpClient, deliver, http_client};
+use crate::notifications::notifier::{DeliveryError, Notification, Notifier};
+use crate::notifications::subscriptions::NotificationChannel;
+use crate::notifications::templates::{Template, Templates};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use common::configuration::SmsGatewayConfiguration;
+use common::normalization::fold_diacritics;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE, HeaderValue};
+use hyper::{Request, Uri};
+use std::sync::Arc;
+
+/// The GSM 03.38 default alphabet, each character is encoded on 7 bits.
+const GSM7_BASIC: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?¡\
+ ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+
+/// The characters of the GSM 03.38 extension table, each is sent as an escape followed by the character itself.
+const GSM7_EXTENDED: &str = "^{}\\[~]|€\u{000C}";
+
+/// A concatenated message loses room in each segment to the header joining them, so two segments hold less than
+/// twice a single one.
+const GSM7_SINGLE_SEGMENT: usize = 160;
+const GSM7_CONCATENATED_SEGMENT: usize = 153;
+const UCS2_SINGLE_SEGMENT: usize = 70;
+const UCS2_CONCATENATED_SEGMENT: usize = 67;
+
+/// Sends the notifications as SMS through a generic HTTP gateway, see [`SmsGatewayConfiguration`].
+pub struct SmsNotifier {
+    client: HttpClient,
+    url: Uri,
+    authorization: Option<HeaderValue>,
+    sender: String,
+    content_type: String,
+    payload_template: String,
+    max_segments: usize,
+    templates: Arc<Templates>,
+}
+
+impl SmsNotifier {
+    pub fn new(configuration: &SmsGatewayConfiguration, templates: Arc<Templates>) -> Result<SmsNotifier, String> {
+        let url = configuration
+            .url
+            .parse::<Uri>()
+            .map_err(|e| format!("Invalid SMS gateway URL `{}`: {}", configuration.url, e))?;
+
+        let authorization = match (&configuration.token, &configuration.username) {
+            (Some(token), _) => Some(format!("Bearer {}", token)),
+            (None, Some(username)) => Some(format!(
+                "Basic {}",
+                STANDARD.encode(format!(
+                    "{}:{}",
+                    username,
+                    configuration.password.as_deref().unwrap_or_default()
+                ))
+            )),
+            (None, None) => None,
+        };
+        let authorization = authorization
+            .map(|value| {
+                HeaderValue::from_str(&value).map(|mut value| {
+                    value.set_sensitive(true);
+                    value
+                })
+            })
+            .transpose()
+            .map_err(|_| String::from("Invalid SMS gateway credentials."))?;
+
+        Ok(SmsNotifier {
+            client: http_client()?,
+            url,
+            authorization,
+            sender: configuration.sender.clone().unwrap_or_default(),
+            content_type: configuration.content_type.clone(),
+            payload_template: configuration.payload_template.clone(),
+            max_segments: configuration.max_segments.max(1) as usize,
+            templates,
+        })
+    }
+
+    fn payload(&self, to: &str, message: &str) -> String {
+        let escape = |value: &str| escape_for(&self.content_type, value);
+        self.payload_template
+            .replace("{{from}}", &escape(&self.sender))
+            .replace("{{to}}", &escape(to))
+            .replace("{{message}}", &escape(message))
+    }
+
+    async fn send(&self, to: &str, message: &str, footer: &str) -> Result<(), DeliveryError> {
+        let message = fit_message(message, footer, self.max_segments);
+        let payload = self.payload(to, &message);
+
+        let mut request = Request::post(self.url.clone()).header(CONTENT_TYPE, &self.content_type);
+        if let Some(authorization) = &self.authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        let request = request
+            .body(Full::new(Bytes::from(payload)))
+            .map_err(|e| DeliveryError::Rejected(e.to_string()))?;
+
+        deliver(&self.client, request).await
+    }
+}
+
+#[async_trait]
+impl Notifier for SmsNotifier {
+    fn channel(&self) -> NotificationChannel {
+        NotificationChannel::Sms
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), DeliveryError> {
+        let message = self.templates.render(Template::Summary, notification)?;
+        let unsubscribe = self.templates.render(Template::Unsubscribe, notification)?;
+        self.send(&notification.subscription.contact, &message, &unsubscribe)
+            .await
+    }
+
+    /// The title and a line per outage, the ones not fitting the segments are cut.
+    async fn notify_digest(&self, digest: &Digest) -> Result<(), DeliveryError> {
+        let message = format!(
+            "{}\n{}",
+            self.templates.render_digest(Template::DigestTitle, digest)?,
+            self.templates.render_digest(Template::DigestMessage, digest)?
+        );
+        let unsubscribe = self.templates.render_digest(Template::Unsubscribe, digest)?;
+        self.send(&digest.subscription.contact, &message, &unsubscribe).await
+    }
+
+    async fn send_verification(&self, verification: &Verification) -> Result<(), DeliveryError> {
+        let message = self
+            .templates
+            .render_verification(Template::VerificationMessage, verification)?;
+        self.send(&verification.subscription.contact, &message, "").await
+    }
+}
+
+/// Escapes a placeholder value for the body of the gateway request, JSON values are rendered inside an existing
+/// string literal of the template.
+fn escape_for(content_type: &str, value: &str) -> String {
+    let mime_type = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+    if mime_type == "application/json" || mime_type.ends_with("+json") {
+        let quoted = serde_json::Value::String(value.to_string()).to_string();
+        quoted[1..quoted.len() - 1].to_string()
+    } else if mime_type == "application/x-www-form-urlencoded" {
+        form_encode(value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn form_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'*' | b'-' | b'.' | b'_' => encoded.push(byte as char),
+            b' ' => encoded.push('+'),
+            other => encoded.push_str(&format!("%{:02X}", other)),
+        }
+    }
+    encoded
+}
+
+/// The septets a character takes in the GSM 7-bit encoding, `None` for the characters only UCS-2 can encode.
+fn gsm7_septets(c: char) -> Option<usize> {
+    if GSM7_BASIC.contains(c) {
+        Some(1)
+    } else if GSM7_EXTENDED.contains(c) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Shortens the message to the given number of segments, the footer, e.g. the unsubscribe link, being kept whole on
+/// its own last line. The Romanian diacritics are replaced first, they are not part of the GSM alphabet and a single
+/// one would switch the whole message to UCS-2, which fits less than half the characters in a segment.
+fn fit_message(message: &str, footer: &str, max_segments: usize) -> String {
+    let message = fold_diacritics(message);
+    let footer = if footer.is_empty() {
+        String::new()
+    } else {
+        format!("\n{}", fold_diacritics(footer))
+    };
+
+    let (single_segment, concatenated_segment, ellipsis, length): (usize, usize, &str, fn(char) -> usize) =
+        if message.chars().chain(footer.chars()).all(|c| gsm7_septets(c).is_some()) {
+            (GSM7_SINGLE_SEGMENT, GSM7_CONCATENATED_SEGMENT, "...", |c| {
+                gsm7_septets(c).unwrap_or(1)
+            })
+        } else {
+            (UCS2_SINGLE_SEGMENT, UCS2_CONCATENATED_SEGMENT, "…", char::len_utf16)
+        };
+
+    let capacity = if max_segments > 1 {
+        concatenated_segment * max_segments
+    } else {
+        single_segment
+    };
+    let footer_length: usize = footer.chars().map(length).sum();
+    if message.chars().map(length).sum::<usize>() + footer_length <= capacity {
+        return message + &footer;
+    }
+
+    let budget = capacity.saturating_sub(footer_length + ellipsis.chars().map(length).sum::<usize>());
+    let mut used = 0;
+    let mut truncated: String = message
+        .chars()
+        .take_while(|c| {
+            used += length(*c);
+            used <= budget
+        })
+        .collect();
+    truncated.truncate(truncated.trim_end().len());
+    truncated.push_str(ellipsis);
+    truncated.push_str(&footer);
+    truncated
+}
+
+#[cfg(test)]
+mod sms_tests {
+    use super::{escape_for, fit_message, gsm7_septets};
+
+    #[test]
+    fn extended_characters_take_two_septets() {
+        assert_eq!(Some(1), gsm7_septets('a'));
+        assert_eq!(Some(2), gsm7_septets('€'));
+        assert_eq!(None, gsm7_septets('ș'));
+    }
+
+    #[test]
+    fn short_messages_are_kept() {
+        assert_eq!("Intrerupere in Isaccea", fit_message("Întrerupere în Isaccea", "", 1));
+    }
+
+    #[test]
+    fn gsm7_messages_are_truncated_to_the_segments() {
+        let message = "a".repeat(200);
+
+        let single = fit_message(&message, "", 1);
+        assert_eq!(160, single.len());
+        assert!(single.ends_with("..."));
+
+        assert_eq!(message, fit_message(&message, "", 2));
+        assert_eq!(306, fit_message(&"a".repeat(400), "", 2).len());
+    }
+
+    #[test]
+    fn extended_characters_count_twice() {
+        let fitted = fit_message(&"€".repeat(100), "", 1);
+
+        assert_eq!(format!("{}...", "€".repeat(78)), fitted);
+    }
+
+    #[test]
+    fn ucs2_messages_fit_fewer_characters() {
+        let fitted = fit_message(&"Ж".repeat(100), "", 1);
+
+        assert_eq!(70, fitted.chars().count());
+        assert!(fitted.ends_with('…'));
+    }
+
+    #[test]
+    fn footers_are_kept_whole() {
+        let footer = "Dezabonare: https://enel.lab.wicked/api/unsubscribe?token=7.Xq3vO0hmLw2RUm5J";
+
+        let fitted = fit_message(&"a".repeat(200), footer, 1);
+
+        assert_eq!(160, fitted.len());
+        assert!(fitted.ends_with(&format!("...\n{}", footer)));
+        assert_eq!("Intrerupere\nStop", fit_message("Întrerupere", "Stop", 1));
+    }
+
+    #[test]
+    fn placeholder_values_are_escaped_for_the_content_type() {
+        assert_eq!(
+            r#"say \"hi\"\n"#,
+            escape_for("application/json; charset=utf-8", "say \"hi\"\n")
+        );
+        assert_eq!(
+            "a+%26+b%3D%C3%A9",
+            escape_for("application/x-www-form-urlencoded", "a & b=é")
+        );
+        assert_eq!("a & b", escape_for("text/plain", "a & b"));
+    }
+}
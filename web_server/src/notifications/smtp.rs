@@ -0,0 +1,315 @@
+use crate::notifications::notifier::DeliveryError;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use common::configuration::SmtpConfiguration;
+use log::warn;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+/// A relay which does not complete the session in time is treated as unavailable, the message is sent again later.
+const SESSION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Replies are a few lines long, a longer one means the peer does not speak SMTP.
+const MAX_REPLY_LINES: usize = 100;
+
+/// Submits messages to an SMTP relay, a session per message.
+pub struct SmtpClient {
+    host: String,
+    port: u16,
+    credentials: Option<(String, String)>,
+    tls: Option<(TlsConnector, ServerName<'static>)>,
+    hello_name: String,
+}
+
+/// A reply of the relay: the status code and the text of its lines.
+#[derive(Debug)]
+struct Reply {
+    code: u16,
+    lines: Vec<String>,
+}
+
+impl Reply {
+    /// The extensions announced in the reply to `EHLO`, one per line after the greeting.
+    fn has_extension(&self, extension: &str) -> bool {
+        self.lines.iter().skip(1).any(|line| {
+            line.split_whitespace()
+                .next()
+                .is_some_and(|keyword| keyword.eq_ignore_ascii_case(extension))
+        })
+    }
+}
+
+impl SmtpClient {
+    pub fn new(configuration: &SmtpConfiguration) -> Result<SmtpClient, String> {
+        if configuration.username.is_some() && !configuration.starttls {
+            return Err(String::from(
+                "The SMTP credentials are only sent over STARTTLS, enable it or drop the username.",
+            ));
+        }
+        let tls = if configuration.starttls {
+            let server_name = ServerName::try_from(configuration.host.clone())
+                .map_err(|e| format!("Invalid SMTP host `{}`: {}", configuration.host, e))?;
+            let connector = TlsConnector::from(Arc::new(tls_config(configuration.ca_file.as_deref())?));
+            Some((connector, server_name))
+        } else {
+            None
+        };
+
+        let credentials = configuration
+            .username
+            .as_ref()
+            .map(|username| (username.clone(), configuration.password.clone().unwrap_or_default()));
+
+        Ok(SmtpClient {
+            host: configuration.host.clone(),
+            port: configuration.port,
+            credentials,
+            tls,
+            hello_name: configuration
+                .from
+                .rsplit_once('@')
+                .map(|(_, domain)| domain.to_string())
+                .unwrap_or_else(|| String::from("localhost")),
+        })
+    }
+
+    /// Sends `message`, a complete RFC 5322 message with CRLF line endings, from `from` to `to`.
+    pub async fn send(&self, from: &str, to: &str, message: &str) -> Result<(), DeliveryError> {
+        timeout(SESSION_TIMEOUT, self.session(from, to, message))
+            .await
+            .unwrap_or_else(|_| Err(DeliveryError::Transient(String::from("The SMTP session timed out."))))
+    }
+
+    async fn session(&self, from: &str, to: &str, message: &str) -> Result<(), DeliveryError> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| DeliveryError::Transient(format!("Could not connect to the SMTP relay: {}", e)))?;
+        let mut connection = Connection::new(stream);
+        connection.expect(220).await?;
+        let extensions = connection.command(&format!("EHLO {}", self.hello_name), 250).await?;
+
+        let Some((connector, server_name)) = &self.tls else {
+            return self.transaction(&mut connection, None, from, to, message).await;
+        };
+        if !extensions.has_extension("STARTTLS") {
+            return Err(DeliveryError::Transient(String::from(
+                "The SMTP relay does not offer STARTTLS.",
+            )));
+        }
+        connection.command("STARTTLS", 220).await?;
+        let stream = connector
+            .connect(server_name.clone(), connection.into_inner())
+            .await
+            .map_err(|e| DeliveryError::Transient(format!("The TLS handshake failed: {}", e)))?;
+
+        let mut connection = Connection::new(stream);
+        connection.command(&format!("EHLO {}", self.hello_name), 250).await?;
+        self.transaction(&mut connection, self.credentials.as_ref(), from, to, message)
+            .await
+    }
+
+    /// Submits the message, authenticating with `credentials` which are only given over TLS.
+    async fn transaction<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        connection: &mut Connection<S>,
+        credentials: Option<&(String, String)>,
+        from: &str,
+        to: &str,
+        message: &str,
+    ) -> Result<(), DeliveryError> {
+        if let Some((username, password)) = credentials {
+            let token = STANDARD.encode(format!("\0{}\0{}", username, password));
+            connection.command(&format!("AUTH PLAIN {}", token), 235).await?;
+        }
+
+        connection.command(&format!("MAIL FROM:<{}>", from), 250).await?;
+        connection.command(&format!("RCPT TO:<{}>", to), 250).await?;
+        connection.command("DATA", 354).await?;
+        connection.write(&dot_stuffed(message)).await?;
+        connection.command(".", 250).await?;
+
+        // The message is accepted at this point, a relay closing the connection without answering is not a failure.
+        if let Err(e) = connection.command("QUIT", 221).await {
+            warn!("The SMTP relay did not end the session cleanly: {}", e);
+        }
+        Ok(())
+    }
+}
+
+/// The authorities of the system, plus the ones of `ca_file` when given.
+fn tls_config(ca_file: Option<&str>) -> Result<ClientConfig, String> {
+    let mut roots = RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs();
+    for error in native.errors {
+        warn!("Could not load a root certificate of the system: {}", error);
+    }
+    roots.add_parsable_certificates(native.certs);
+
+    if let Some(ca_file) = ca_file {
+        let certificates = CertificateDer::pem_file_iter(ca_file)
+            .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Could not read the certificates of `{}`: {}", ca_file, e))?;
+        for certificate in certificates {
+            roots
+                .add(certificate)
+                .map_err(|e| format!("Invalid certificate in `{}`: {}", ca_file, e))?;
+        }
+    }
+
+    Ok(ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
+}
+
+/// Lines starting with a dot get another one, so none of them is taken for the end of the data.
+fn dot_stuffed(message: &str) -> String {
+    let mut stuffed = String::with_capacity(message.len() + 8);
+    for line in message.split_inclusive("\r\n") {
+        if line.starts_with('.') {
+            stuffed.push('.');
+        }
+        stuffed.push_str(line);
+    }
+    if !stuffed.ends_with("\r\n") {
+        stuffed.push_str("\r\n");
+    }
+    stuffed
+}
+
+struct Connection<S> {
+    stream: BufReader<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    fn new(stream: S) -> Connection<S> {
+        Connection {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    async fn write(&mut self, data: &str) -> Result<(), DeliveryError> {
+        self.stream
+            .get_mut()
+            .write_all(data.as_bytes())
+            .await
+            .map_err(|e| DeliveryError::Transient(format!("Could not write to the SMTP relay: {}", e)))
+    }
+
+    async fn command(&mut self, command: &str, expected: u16) -> Result<Reply, DeliveryError> {
+        self.write(&format!("{}\r\n", command)).await?;
+        self.stream
+            .get_mut()
+            .flush()
+            .await
+            .map_err(|e| DeliveryError::Transient(format!("Could not write to the SMTP relay: {}", e)))?;
+        self.expect(expected).await
+    }
+
+    /// Reads a reply, the relay refusing with a 4xx code can be tried again later, a 5xx code is final.
+    async fn expect(&mut self, expected: u16) -> Result<Reply, DeliveryError> {
+        let reply = self.reply().await?;
+        match reply.code {
+            code if code == expected => Ok(reply),
+            400..=499 => Err(DeliveryError::Transient(reply.lines.join(" "))),
+            _ => Err(DeliveryError::Rejected(reply.lines.join(" "))),
+        }
+    }
+
+    async fn reply(&mut self) -> Result<Reply, DeliveryError> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read = self
+                .stream
+                .read_line(&mut line)
+                .await
+                .map_err(|e| DeliveryError::Transient(format!("Could not read from the SMTP relay: {}", e)))?;
+            if read == 0 {
+                return Err(DeliveryError::Transient(String::from(
+                    "The SMTP relay closed the connection.",
+                )));
+            }
+
+            let line = line.trim_end();
+            let code = line.get(..3).and_then(|code| code.parse::<u16>().ok());
+            let Some(code) = code else {
+                return Err(DeliveryError::Transient(format!("Invalid SMTP reply `{}`.", line)));
+            };
+            lines.push(line.get(4..).unwrap_or_default().to_string());
+
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(Reply { code, lines });
+            }
+            if lines.len() >= MAX_REPLY_LINES {
+                return Err(DeliveryError::Transient(String::from("The SMTP reply is too long.")));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod smtp_tests {
+    use super::{Connection, SmtpClient, dot_stuffed};
+    use crate::notifications::notifier::DeliveryError;
+    use common::configuration::SmtpConfiguration;
+    use tokio::io::{AsyncWriteExt, duplex};
+
+    #[test]
+    fn lines_starting_with_a_dot_are_stuffed() {
+        assert_eq!("a\r\n..b\r\nc.\r\n", dot_stuffed("a\r\n.b\r\nc."));
+    }
+
+    #[test]
+    fn credentials_need_starttls() {
+        let mut configuration = SmtpConfiguration::new(String::from("localhost"), String::from("alerte@example.ro"));
+        configuration.username = Some(String::from("alerte"));
+        configuration.starttls = false;
+
+        assert!(SmtpClient::new(&configuration).is_err());
+    }
+
+    #[tokio::test]
+    async fn multiline_replies_list_the_extensions() {
+        let (client, mut server) = duplex(1024);
+        server
+            .write_all(b"250-smtp.example.ro\r\n250-STARTTLS\r\n250 AUTH PLAIN LOGIN\r\n")
+            .await
+            .unwrap();
+
+        let reply = Connection::new(client).expect(250).await.unwrap();
+
+        assert!(reply.has_extension("starttls"));
+        assert!(reply.has_extension("AUTH"));
+        assert!(!reply.has_extension("smtp.example.ro"));
+    }
+
+    #[tokio::test]
+    async fn temporary_failures_can_be_retried() {
+        let (client, mut server) = duplex(1024);
+        server
+            .write_all(b"451 Try again later\r\n550 No such user\r\n")
+            .await
+            .unwrap();
+        let mut connection = Connection::new(client);
+
+        assert_eq!(
+            DeliveryError::Transient(String::from("Try again later")),
+            connection.expect(250).await.unwrap_err()
+        );
+        assert_eq!(
+            DeliveryError::Rejected(String::from("No such user")),
+            connection.expect(250).await.unwrap_err()
+        );
+    }
+}
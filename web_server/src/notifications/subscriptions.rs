@@ -0,0 +1,624 @@
+use crate::AppState;
+use crate::error::{ApiError, ProblemDetails};
+use crate::extract::{Json, Path, Query};
+use crate::notifications::consent::{Links, new_verification_code};
+use crate::notifications::digest::DigestSchedule;
+use crate::notifications::http::check_public_target;
+use crate::notifications::push::{gotify_target, ntfy_target};
+use crate::notifications::reminders::Reminder;
+use crate::notifications::templates::Language;
+use axum::extract::State;
+use axum::http::StatusCode;
+use chrono::{DateTime, NaiveTime, Utc};
+use common::normalization::{normalize_county, normalize_locality};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::ops::Deref;
+use std::sync::LazyLock;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+const MAX_STREET_PATTERN_LENGTH: usize = 255;
+const MAX_CONTACT_LENGTH: usize = 512;
+
+static PHONE_NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\+?[0-9]{8,15}$").unwrap());
+static EMAIL_ADDRESS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap());
+static HTTP_URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^https?://[^/\s]+\S*$").unwrap());
+
+const COUNTY_EXISTS_QUERY: &str = "SELECT EXISTS (SELECT 1 FROM counties WHERE key = $1)";
+
+const SUBSCRIPTION_QUERY: &str = "SELECT * FROM subscriptions WHERE id = $1";
+
+/// The subscriptions sending their codes to a contact lately, counted so that nobody floods a contact with them. The
+/// subscriptions replaced with the contact count from then on.
+const PENDING_SUBSCRIPTIONS_QUERY: &str = "SELECT count(*) FROM subscriptions WHERE channel = $1 AND contact = $2 \
+ AND verified_at IS NULL AND updated_at > now() - interval '1 hour'";
+
+/// The verification code is due to be sent right away.
+const INSERT_SUBSCRIPTION_QUERY: &str = "INSERT INTO subscriptions(county_key, locality, locality_key, \
+ street_pattern, window_start, window_end, channel, contact, state, signing_secret, reminders, digest, digest_time, \
+ language, verification_code, verification_due_at) \
+ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, now()) RETURNING *";
+
+/// Only run with the management token of the subscription. The failures are counted again from zero, a webhook keeps
+/// its secret as long as it stays a webhook. Only a new contact gets a new code, counting its wrong codes from zero:
+/// the same contact keeps its verification, or its code and the wrong codes given so far.
+const UPDATE_SUBSCRIPTION_QUERY: &str = "UPDATE subscriptions SET county_key = $2, locality = $3, \
+ locality_key = $4, street_pattern = $5, window_start = $6, window_end = $7, channel = $8, contact = $9, \
+ state = $10, signing_secret = CASE WHEN $8 = 'webhook' THEN COALESCE(signing_secret, $11) END, \
+ reminders = $12, digest = $13, digest_time = $14, language = $15, failure_count = 0, \
+ verification_code = CASE WHEN channel = $8 AND contact = $9 THEN verification_code ELSE $16 END, \
+ verification_due_at = CASE WHEN channel = $8 AND contact = $9 THEN verification_due_at ELSE now() END, \
+ verification_failures = CASE WHEN channel = $8 AND contact = $9 THEN verification_failures ELSE 0 END, \
+ verified_at = CASE WHEN channel = $8 AND contact = $9 THEN verified_at END, \
+ updated_at = now() WHERE id = $1 RETURNING *";
+
+const DELETE_SUBSCRIPTION_QUERY: &str = "DELETE FROM subscriptions WHERE id = $1";
+
+/// How the notifications of a subscription are delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+pub enum NotificationChannel {
+    /// The contact is a phone number.
+    Sms,
+    /// The contact is an email address.
+    Email,
+    /// The contact is the URL receiving the notifications.
+    Webhook,
+    /// The contact is the URL of the ntfy topic, e.g. `https://ntfy.sh/outages-isaccea`.
+    Ntfy,
+    /// The contact is the URL of the Gotify server with the token of the application, e.g.
+    /// `https://gotify.example.ro/?token=AbCdEf`.
+    Gotify,
+}
+
+impl NotificationChannel {
+    /// The contact is a URL the service sends requests to.
+    pub fn takes_url(self) -> bool {
+        matches!(
+            self,
+            NotificationChannel::Webhook | NotificationChannel::Ntfy | NotificationChannel::Gotify
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+pub enum SubscriptionState {
+    #[default]
+    Active,
+    /// Kept, but nothing is sent until it is active again.
+    Paused,
+    /// Set by the service after repeated delivery failures, replacing the subscription as active enables it again.
+    Disabled,
+}
+
+/// Body of the requests creating or replacing a subscription.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SubscriptionRequest {
+    /// County name or key, matched regardless of diacritics and case.
+    #[schema(example = "Timiș")]
+    pub county: String,
+    /// The whole county is watched when missing.
+    #[schema(example = "Timișoara")]
+    pub locality: Option<String>,
+    /// Street or landmark looked for in the descriptions of the incidents, e.g. `Pacii`.
+    pub street_pattern: Option<String>,
+    /// Only the outages overlapping this time of day are notified. Set together with `window_end`, the window runs
+    /// overnight when it ends before it starts.
+    #[schema(value_type = Option<String>, example = "08:00:00")]
+    pub window_start: Option<NaiveTime>,
+    #[schema(value_type = Option<String>, example = "18:00:00")]
+    pub window_end: Option<NaiveTime>,
+    pub channel: NotificationChannel,
+    /// Phone number, email address or URL, depending on the channel.
+    #[schema(example = "+40722123456")]
+    pub contact: String,
+    /// Active when missing.
+    pub state: Option<SubscriptionState>,
+    /// Sent before the outages besides their announcement, none when missing. Changing them applies to the outages
+    /// announced or changed afterwards.
+    #[serde(default)]
+    pub reminders: Vec<Reminder>,
+    /// Collects the changes into a digest instead of notifying each of them. The reminders are still sent on time.
+    pub digest: Option<DigestSchedule>,
+    /// When the digest is sent, Romanian time. Set together with `digest`.
+    #[schema(value_type = Option<String>, example = "18:00:00")]
+    pub digest_time: Option<NaiveTime>,
+    /// Of the messages, English when missing.
+    #[serde(default)]
+    pub language: Language,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, FromRow)]
+pub struct Subscription {
+    pub id: i64,
+    #[schema(example = "timis")]
+    pub county_key: String,
+    pub locality: Option<String>,
+    #[schema(example = "timisoara")]
+    pub locality_key: Option<String>,
+    pub street_pattern: Option<String>,
+    #[schema(value_type = Option<String>, example = "08:00:00")]
+    pub window_start: Option<NaiveTime>,
+    #[schema(value_type = Option<String>, example = "18:00:00")]
+    pub window_end: Option<NaiveTime>,
+    pub channel: NotificationChannel,
+    pub contact: String,
+    pub state: SubscriptionState,
+    pub reminders: Vec<Reminder>,
+    pub digest: Option<DigestSchedule>,
+    #[schema(value_type = Option<String>, example = "18:00:00")]
+    pub digest_time: Option<NaiveTime>,
+    pub language: Language,
+    /// Only returned when the subscription is saved, see [`SavedSubscription`].
+    #[serde(skip)]
+    pub signing_secret: Option<String>,
+    /// Deliveries failed in a row, the subscription is disabled when there are too many.
+    pub failure_count: i32,
+    /// Nothing is sent before the contact confirms the code sent to it, missing until then.
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub verified_at: Option<DateTime<Utc>>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = String, format = DateTime)]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A subscription as it was created or replaced.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SavedSubscription {
+    #[serde(flatten)]
+    pub subscription: Subscription,
+    /// The key of the HMAC-SHA256 signature of the webhook deliveries, only shown here.
+    pub signing_secret: Option<String>,
+    /// Reads, replaces and deletes the subscription, only shown here.
+    #[schema(example = "7.Xq3vO0hmLw2RUm5J")]
+    pub management_token: String,
+}
+
+impl SavedSubscription {
+    pub fn new(subscription: Subscription, links: &Links) -> SavedSubscription {
+        SavedSubscription {
+            signing_secret: subscription.signing_secret.clone(),
+            management_token: links.management_token(subscription.id),
+            subscription,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ManagementToken {
+    /// The `management_token` returned when the subscription was created.
+    pub token: String,
+}
+
+/// A request which passed the validation, with the normalized keys.
+#[derive(Debug, PartialEq)]
+struct ValidSubscription {
+    county_key: String,
+    locality: Option<String>,
+    locality_key: Option<String>,
+    street_pattern: Option<String>,
+    window_start: Option<NaiveTime>,
+    window_end: Option<NaiveTime>,
+    channel: NotificationChannel,
+    contact: String,
+    state: SubscriptionState,
+    reminders: Vec<Reminder>,
+    digest: Option<DigestSchedule>,
+    digest_time: Option<NaiveTime>,
+    language: Language,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/subscriptions",
+    request_body = SubscriptionRequest,
+    responses(
+        (status=201, description = "The subscription was created, its contact gets a code verifying it before any notification.", body=SavedSubscription),
+        (status=400, description = "Unknown county, invalid contact for the channel, invalid time window or a body which is not JSON.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=413, description = "The body is too large.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=415, description = "The body is not sent as `application/json`.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=422, description = "The body does not fit the request, a field is missing or has the wrong type.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=429, description = "The client created too many subscriptions in the last hour, or the contact has too many waiting for their verification.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error storing the subscription.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn create_subscription(
+    state: State<AppState>,
+    Json(request): Json<SubscriptionRequest>,
+) -> Result<(StatusCode, Json<SavedSubscription>), ApiError> {
+    let valid = validate_subscription(&state, &request).await?;
+    check_pending_subscriptions(&state, valid.channel, &valid.contact).await?;
+    let signing_secret = new_signing_secret(valid.channel);
+
+    let subscription: Subscription = sqlx::query_as(INSERT_SUBSCRIPTION_QUERY)
+        .bind(valid.county_key)
+        .bind(valid.locality)
+        .bind(valid.locality_key)
+        .bind(valid.street_pattern)
+        .bind(valid.window_start)
+        .bind(valid.window_end)
+        .bind(valid.channel)
+        .bind(valid.contact)
+        .bind(valid.state)
+        .bind(signing_secret)
+        .bind(valid.reminders)
+        .bind(valid.digest)
+        .bind(valid.digest_time)
+        .bind(valid.language)
+        .bind(new_verification_code())
+        .fetch_one(state.pg_pool.deref())
+        .await?;
+
+    let saved = SavedSubscription::new(subscription, &state.links);
+    Ok((StatusCode::CREATED, Json(saved)))
+}
+
+/// Refuses a contact having too many subscriptions waiting for their verification, each of them sent a code.
+async fn check_pending_subscriptions(
+    state: &AppState,
+    channel: NotificationChannel,
+    contact: &str,
+) -> Result<(), ApiError> {
+    let pending: i64 = sqlx::query_scalar(PENDING_SUBSCRIPTIONS_QUERY)
+        .bind(channel)
+        .bind(contact)
+        .fetch_one(state.pg_pool.deref())
+        .await?;
+    if pending >= state.subscription_limits.max_pending_per_contact.into() {
+        return Err(ApiError::TooManyRequests(format!(
+            "The contact `{}` has too many subscriptions waiting for their verification, try again later.",
+            contact
+        )));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/subscriptions/{id}",
+    params(("id" = i64, Path, description = "Id of the subscription."), ManagementToken),
+    responses(
+        (status=200, description = "The subscription.", body=Subscription),
+        (status=403, description = "The token does not manage this subscription.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=404, description = "No subscription has this id.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error getting the subscription.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_subscription(
+    state: State<AppState>,
+    Path(id): Path<i64>,
+    Query(access): Query<ManagementToken>,
+) -> Result<Json<Subscription>, ApiError> {
+    authorize(&state.links, id, &access)?;
+    let subscription: Option<Subscription> = sqlx::query_as(SUBSCRIPTION_QUERY)
+        .bind(id)
+        .fetch_optional(state.pg_pool.deref())
+        .await?;
+
+    subscription.map(Json).ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/subscriptions/{id}",
+    params(("id" = i64, Path, description = "Id of the subscription."), ManagementToken),
+    request_body = SubscriptionRequest,
+    responses(
+        (status=200, description = "The subscription was replaced, e.g. to pause it. A new contact gets a new code verifying it.", body=SavedSubscription),
+        (status=400, description = "Unknown county, invalid contact for the channel, invalid time window or a body which is not JSON.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=413, description = "The body is too large.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=415, description = "The body is not sent as `application/json`.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=422, description = "The body does not fit the request, a field is missing or has the wrong type.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=403, description = "The token does not manage this subscription.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=404, description = "No subscription has this id.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=429, description = "The client replaced too many subscriptions in the last hour, or the new contact has too many waiting for their verification.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error storing the subscription.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn update_subscription(
+    state: State<AppState>,
+    Path(id): Path<i64>,
+    Query(access): Query<ManagementToken>,
+    Json(request): Json<SubscriptionRequest>,
+) -> Result<Json<SavedSubscription>, ApiError> {
+    authorize(&state.links, id, &access)?;
+    let valid = validate_subscription(&state, &request).await?;
+    let current: Option<Subscription> = sqlx::query_as(SUBSCRIPTION_QUERY)
+        .bind(id)
+        .fetch_optional(state.pg_pool.deref())
+        .await?;
+    let current = current.ok_or_else(|| not_found(id))?;
+    if current.channel != valid.channel || current.contact != valid.contact {
+        check_pending_subscriptions(&state, valid.channel, &valid.contact).await?;
+    }
+    let signing_secret = new_signing_secret(valid.channel);
+
+    let subscription: Option<Subscription> = sqlx::query_as(UPDATE_SUBSCRIPTION_QUERY)
+        .bind(id)
+        .bind(valid.county_key)
+        .bind(valid.locality)
+        .bind(valid.locality_key)
+        .bind(valid.street_pattern)
+        .bind(valid.window_start)
+        .bind(valid.window_end)
+        .bind(valid.channel)
+        .bind(valid.contact)
+        .bind(valid.state)
+        .bind(signing_secret)
+        .bind(valid.reminders)
+        .bind(valid.digest)
+        .bind(valid.digest_time)
+        .bind(valid.language)
+        .bind(new_verification_code())
+        .fetch_optional(state.pg_pool.deref())
+        .await?;
+
+    subscription
+        .map(|subscription| Json(SavedSubscription::new(subscription, &state.links)))
+        .ok_or_else(|| not_found(id))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/subscriptions/{id}",
+    params(("id" = i64, Path, description = "Id of the subscription."), ManagementToken),
+    responses(
+        (status=204, description = "The subscription was deleted."),
+        (status=403, description = "The token does not manage this subscription.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=404, description = "No subscription has this id.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error deleting the subscription.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn delete_subscription(
+    state: State<AppState>,
+    Path(id): Path<i64>,
+    Query(access): Query<ManagementToken>,
+) -> Result<StatusCode, ApiError> {
+    authorize(&state.links, id, &access)?;
+    let deleted = sqlx::query(DELETE_SUBSCRIPTION_QUERY)
+        .bind(id)
+        .execute(state.pg_pool.deref())
+        .await?;
+
+    if deleted.rows_affected() == 0 {
+        return Err(not_found(id));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Only the holders of the management token of a subscription see or change it, its id being easy to guess.
+fn authorize(links: &Links, id: i64, access: &ManagementToken) -> Result<(), ApiError> {
+    if links.managed_subscription(&access.token) == Some(id) {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(format!(
+            "The token does not manage the subscription {}.",
+            id
+        )))
+    }
+}
+
+fn not_found(id: i64) -> ApiError {
+    ApiError::NotFound(format!("No subscription has the id {}.", id))
+}
+
+async fn validate_subscription(state: &AppState, request: &SubscriptionRequest) -> Result<ValidSubscription, ApiError> {
+    let valid = check_subscription(request).map_err(ApiError::InvalidParameter)?;
+
+    if valid.channel.takes_url() && !state.allow_private_targets {
+        check_public_target(&valid.contact)
+            .await
+            .map_err(|e| ApiError::InvalidParameter(format!("Invalid contact: {}", e)))?;
+    }
+
+    let county_exists: bool = sqlx::query_scalar(COUNTY_EXISTS_QUERY)
+        .bind(&valid.county_key)
+        .fetch_one(state.pg_pool.deref())
+        .await?;
+    if !county_exists {
+        return Err(ApiError::InvalidParameter(format!(
+            "Unknown county `{}`.",
+            request.county
+        )));
+    }
+
+    Ok(valid)
+}
+
+/// Checks what can be checked without the database and normalizes the request.
+fn check_subscription(request: &SubscriptionRequest) -> Result<ValidSubscription, String> {
+    let county_key = normalize_county(&request.county);
+    if county_key.is_empty() {
+        return Err(String::from("The county is mandatory."));
+    }
+
+    let locality = non_blank(&request.locality);
+    let locality_key = locality.as_deref().map(normalize_locality);
+    if locality_key.as_deref() == Some("") {
+        return Err(format!("Invalid locality `{}`.", locality.unwrap_or_default()));
+    }
+
+    let street_pattern = non_blank(&request.street_pattern);
+    if street_pattern
+        .as_ref()
+        .is_some_and(|pattern| pattern.chars().count() > MAX_STREET_PATTERN_LENGTH)
+    {
+        return Err(format!(
+            "The street pattern is longer than {} characters.",
+            MAX_STREET_PATTERN_LENGTH
+        ));
+    }
+
+    match (request.window_start, request.window_end) {
+        (Some(start), Some(end)) if start == end => {
+            return Err(String::from("`window_start` and `window_end` must differ."));
+        }
+        (Some(_), None) | (None, Some(_)) => {
+            return Err(String::from("`window_start` and `window_end` go together."));
+        }
+        _ => {}
+    }
+
+    if request.digest.is_some() != request.digest_time.is_some() {
+        return Err(String::from("`digest` and `digest_time` go together."));
+    }
+
+    let contact = normalize_contact(request.channel, &request.contact)?;
+
+    let mut reminders = request.reminders.clone();
+    reminders.sort();
+    reminders.dedup();
+
+    Ok(ValidSubscription {
+        county_key,
+        locality,
+        locality_key,
+        street_pattern,
+        window_start: request.window_start,
+        window_end: request.window_end,
+        channel: request.channel,
+        contact,
+        state: request.state.unwrap_or_default(),
+        reminders,
+        digest: request.digest,
+        digest_time: request.digest_time,
+        language: request.language,
+    })
+}
+
+/// Phone numbers are stored without separators, the other contacts as they were given.
+pub(crate) fn normalize_contact(channel: NotificationChannel, contact: &str) -> Result<String, String> {
+    let contact = contact.trim();
+    if contact.len() > MAX_CONTACT_LENGTH {
+        return Err(format!("The contact is longer than {} characters.", MAX_CONTACT_LENGTH));
+    }
+
+    let (normalized, valid) = match channel {
+        NotificationChannel::Sms => {
+            let number: String = contact
+                .chars()
+                .filter(|c| !c.is_whitespace() && *c != '-' && *c != '.')
+                .collect();
+            let valid = PHONE_NUMBER.is_match(&number);
+            (number, valid)
+        }
+        NotificationChannel::Email => (contact.to_string(), EMAIL_ADDRESS.is_match(contact)),
+        NotificationChannel::Webhook => (contact.to_string(), HTTP_URL.is_match(contact)),
+        NotificationChannel::Ntfy => (
+            contact.to_string(),
+            HTTP_URL.is_match(contact) && ntfy_target(contact).is_ok(),
+        ),
+        NotificationChannel::Gotify => (
+            contact.to_string(),
+            HTTP_URL.is_match(contact) && gotify_target(contact).is_ok(),
+        ),
+    };
+
+    if valid {
+        Ok(normalized)
+    } else {
+        Err(format!("Invalid contact `{}` for the {:?} channel.", contact, channel))
+    }
+}
+
+/// Only the webhooks sign their deliveries, with a key made of two random UUIDs, 244 random bits.
+fn new_signing_secret(channel: NotificationChannel) -> Option<String> {
+    (channel == NotificationChannel::Webhook).then(|| format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()))
+}
+
+fn non_blank(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod subscriptions_tests {
+    use super::{
+        DigestSchedule, Language, NotificationChannel, Reminder, SubscriptionRequest, SubscriptionState,
+        check_subscription, normalize_contact,
+    };
+    use chrono::NaiveTime;
+
+    fn request() -> SubscriptionRequest {
+        SubscriptionRequest {
+            county: "Jud. TIMIȘ".to_string(),
+            locality: Some(" Timișoara ".to_string()),
+            street_pattern: Some("  ".to_string()),
+            window_start: NaiveTime::from_hms_opt(8, 0, 0),
+            window_end: NaiveTime::from_hms_opt(18, 0, 0),
+            channel: NotificationChannel::Sms,
+            contact: "+40 722 123 456".to_string(),
+            state: None,
+            reminders: vec![
+                Reminder::TwoHoursBefore,
+                Reminder::EveningBefore,
+                Reminder::TwoHoursBefore,
+            ],
+            digest: None,
+            digest_time: None,
+            language: Language::Ro,
+        }
+    }
+
+    #[test]
+    fn requests_are_normalized() {
+        let valid = check_subscription(&request()).unwrap();
+
+        assert_eq!("timis", valid.county_key);
+        assert_eq!(Some("Timișoara".to_string()), valid.locality);
+        assert_eq!(Some("timisoara".to_string()), valid.locality_key);
+        assert_eq!(None, valid.street_pattern);
+        assert_eq!("+40722123456", valid.contact);
+        assert_eq!(SubscriptionState::Active, valid.state);
+        assert_eq!(vec![Reminder::EveningBefore, Reminder::TwoHoursBefore], valid.reminders);
+        assert_eq!(Language::Ro, valid.language);
+    }
+
+    #[test]
+    fn invalid_time_windows() {
+        let mut empty = request();
+        empty.window_start = empty.window_end;
+        assert!(check_subscription(&empty).is_err());
+
+        let mut overnight = request();
+        overnight.window_start = NaiveTime::from_hms_opt(22, 0, 0);
+        overnight.window_end = NaiveTime::from_hms_opt(6, 0, 0);
+        assert!(check_subscription(&overnight).is_ok());
+
+        let mut open = request();
+        open.window_end = None;
+        assert!(check_subscription(&open).is_err());
+    }
+
+    #[test]
+    fn digests_need_their_time() {
+        let mut untimed = request();
+        untimed.digest = Some(DigestSchedule::Daily);
+        assert!(check_subscription(&untimed).is_err());
+
+        untimed.digest_time = NaiveTime::from_hms_opt(18, 0, 0);
+        assert!(check_subscription(&untimed).is_ok());
+    }
+
+    #[test]
+    fn contacts_match_their_channel() {
+        assert!(normalize_contact(NotificationChannel::Sms, "0722-123-456").is_ok());
+        assert!(normalize_contact(NotificationChannel::Sms, "call me").is_err());
+        assert!(normalize_contact(NotificationChannel::Email, "ana@example.ro").is_ok());
+        assert!(normalize_contact(NotificationChannel::Email, "+40722123456").is_err());
+        assert!(normalize_contact(NotificationChannel::Ntfy, "https://ntfy.sh/outages").is_ok());
+        assert!(normalize_contact(NotificationChannel::Ntfy, "https://ntfy.sh/").is_err());
+        assert!(normalize_contact(NotificationChannel::Gotify, "https://gotify.example.ro/?token=AbC").is_ok());
+        assert!(normalize_contact(NotificationChannel::Gotify, "https://gotify.example.ro/").is_err());
+        assert!(normalize_contact(NotificationChannel::Webhook, "ana@example.ro").is_err());
+    }
+}
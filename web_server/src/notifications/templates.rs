@@ -0,0 +1,659 @@
+use crate::notifications::consent::{Links, Verification};
+use crate::notifications::digest::{Digest, DigestChange, DigestSchedule};
+use crate::notifications::notifier::{DeliveryError, Notification};
+use crate::notifications::rules::NotificationReason;
+use crate::web_api::Incident;
+use chrono::{Datelike, NaiveDate, NaiveTime, Utc};
+use common::configuration::TemplatesConfiguration;
+use handlebars::{Handlebars, handlebars_helper, html_escape, no_escape};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fs;
+use utoipa::ToSchema;
+
+const RO_WEEKDAYS: [&str; 7] = ["luni", "marți", "miercuri", "joi", "vineri", "sâmbătă", "duminică"];
+
+const RO_MONTHS: [&str; 12] = [
+    "ianuarie",
+    "februarie",
+    "martie",
+    "aprilie",
+    "mai",
+    "iunie",
+    "iulie",
+    "august",
+    "septembrie",
+    "octombrie",
+    "noiembrie",
+    "decembrie",
+];
+
+/// The language of the notifications of a subscription.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+pub enum Language {
+    /// Romanian, the days and the months with their Romanian names.
+    Ro,
+    #[default]
+    En,
+}
+
+const LANGUAGES: [Language; 2] = [Language::Ro, Language::En];
+
+/// A part of the notification messages, rendered from the template of its name, e.g. `ro.summary`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    /// What happened to the outage, the other templates of a change get it as `headline`.
+    Headline,
+    /// A single line with the place and the time of the outage, sent by SMS and listed by the digests.
+    Summary,
+    /// The unsubscribe link, appended to the SMS and push messages. The other templates get it as `unsubscribe`.
+    Unsubscribe,
+    PushTitle,
+    PushMessage,
+    EmailSubject,
+    EmailText,
+    EmailHtml,
+    /// The opening words of a digest, the other digest templates get it as `title`.
+    DigestTitle,
+    /// The summaries of the outages of a digest, a line each, sent after the title by SMS and push.
+    DigestMessage,
+    DigestText,
+    DigestHtml,
+    /// The templates of the message asking to confirm a new contact, the title being the subject of the e-mail.
+    VerificationTitle,
+    VerificationMessage,
+    VerificationHtml,
+}
+
+/// What the values of a template are about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Change,
+    Digest,
+    Verification,
+}
+
+const TEMPLATES: [Template; 15] = [
+    Template::Headline,
+    Template::Summary,
+    Template::Unsubscribe,
+    Template::PushTitle,
+    Template::PushMessage,
+    Template::EmailSubject,
+    Template::EmailText,
+    Template::EmailHtml,
+    Template::DigestTitle,
+    Template::DigestMessage,
+    Template::DigestText,
+    Template::DigestHtml,
+    Template::VerificationTitle,
+    Template::VerificationMessage,
+    Template::VerificationHtml,
+];
+
+/// The Romanian and the English built-in template of the given name.
+macro_rules! built_in {
+    ($name:literal) => {
+        (
+            $name,
+            [
+                include_str!(concat!("../../templates/ro/", $name, ".hbs")),
+                include_str!(concat!("../../templates/en/", $name, ".hbs")),
+            ],
+        )
+    };
+}
+
+handlebars_helper!(plural: |count: u64, one: str, other: str, { many: str = "" }| {
+    // Romanian puts a "de" between the count and the noun from 20 on, except for the ones ending in 01 to 19.
+    match count {
+        1 => format!("1 {}", one),
+        _ if !many.is_empty() && (count % 100 >= 20 || (count > 0 && count % 100 == 0)) => {
+            format!("{} {}", count, many)
+        }
+        _ => format!("{} {}", count, other),
+    }
+});
+
+impl Language {
+    fn code(self) -> &'static str {
+        match self {
+            Language::Ro => "ro",
+            Language::En => "en",
+        }
+    }
+}
+
+impl Template {
+    fn source(self) -> (&'static str, [&'static str; 2]) {
+        match self {
+            Template::Headline => built_in!("headline"),
+            Template::Summary => built_in!("summary"),
+            Template::Unsubscribe => built_in!("unsubscribe"),
+            Template::PushTitle => built_in!("push_title"),
+            Template::PushMessage => built_in!("push_message"),
+            Template::EmailSubject => built_in!("email_subject"),
+            Template::EmailText => built_in!("email_text"),
+            Template::EmailHtml => built_in!("email_html"),
+            Template::DigestTitle => built_in!("digest_title"),
+            Template::DigestMessage => built_in!("digest_message"),
+            Template::DigestText => built_in!("digest_text"),
+            Template::DigestHtml => built_in!("digest_html"),
+            Template::VerificationTitle => built_in!("verification_title"),
+            Template::VerificationMessage => built_in!("verification_message"),
+            Template::VerificationHtml => built_in!("verification_html"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        self.source().0
+    }
+
+    fn built_in(self, language: Language) -> &'static str {
+        let [ro, en] = self.source().1;
+        match language {
+            Language::Ro => ro,
+            Language::En => en,
+        }
+    }
+
+    fn is_html(self) -> bool {
+        matches!(
+            self,
+            Template::EmailHtml | Template::DigestHtml | Template::VerificationHtml
+        )
+    }
+
+    fn scope(self) -> Scope {
+        match self {
+            Template::DigestTitle | Template::DigestMessage | Template::DigestText | Template::DigestHtml => {
+                Scope::Digest
+            }
+            Template::VerificationTitle | Template::VerificationMessage | Template::VerificationHtml => {
+                Scope::Verification
+            }
+            _ => Scope::Change,
+        }
+    }
+}
+
+/// The Handlebars templates of the notifications in every language, the built-in ones replaced by the files of the
+/// configuration. The HTML templates escape their values, the others render them as they are.
+///
+/// The templates of a change get its `language`, `change`, `headline`, `county`, `location`, `day` (`08.08.2025`),
+/// `weekday` and `long_day` in the language of the subscription, `start` and `end` (`09:00`, both missing when the
+/// hours were not announced), `description` and its `description_lines`. The digest templates get their `schedule`,
+/// `title`, the `count` of outages and the `changes`, each with its `summary`. Both get the `unsubscribe_url` of the
+/// subscription and the rendered `unsubscribe` line. The verification templates get the `code` and the
+/// `verification_url` confirming the contact.
+pub struct Templates {
+    text: Handlebars<'static>,
+    html: Handlebars<'static>,
+    links: Links,
+}
+
+impl Templates {
+    /// Loads the template files over the built-in templates and renders all of them for a sample outage, a broken
+    /// template stops the service at startup instead of failing its notifications.
+    pub fn new(configuration: &TemplatesConfiguration, links: Links) -> Result<Templates, String> {
+        let mut templates = Templates {
+            text: registry(no_escape),
+            html: registry(html_escape),
+            links,
+        };
+        for language in LANGUAGES {
+            for template in TEMPLATES {
+                templates.register(language, template, template.built_in(language))?;
+            }
+        }
+
+        for (key, path) in &configuration.files {
+            let (language, template) = key
+                .split_once('.')
+                .and_then(|(language, name)| {
+                    let language = LANGUAGES.into_iter().find(|candidate| candidate.code() == language)?;
+                    let template = TEMPLATES.into_iter().find(|candidate| candidate.name() == name)?;
+                    Some((language, template))
+                })
+                .ok_or_else(|| format!("Unknown template `{}`.", key))?;
+            let source = fs::read_to_string(path)
+                .map_err(|e| format!("Could not read the template `{}` from {}: {}", key, path, e))?;
+            templates.register(language, template, &source)?;
+        }
+
+        templates.validate()?;
+        Ok(templates)
+    }
+
+    /// The links the messages point to.
+    pub fn links(&self) -> &Links {
+        &self.links
+    }
+
+    /// Renders a template of a single change in the language of the subscription.
+    pub fn render(&self, template: Template, notification: &Notification) -> Result<String, DeliveryError> {
+        let subscription = &notification.subscription;
+        self.change_context(
+            subscription.language,
+            subscription.id,
+            notification.reason,
+            &notification.incident,
+        )
+        .and_then(|context| self.render_context(subscription.language, template, &context))
+        .map_err(DeliveryError::Rejected)
+    }
+
+    /// Renders a digest template in the language of the subscription.
+    pub fn render_digest(&self, template: Template, digest: &Digest) -> Result<String, DeliveryError> {
+        let subscription = &digest.subscription;
+        let schedule = subscription.digest.unwrap_or(DigestSchedule::Daily);
+        self.digest_context(subscription.language, subscription.id, schedule, &digest.changes)
+            .and_then(|context| self.render_context(subscription.language, template, &context))
+            .map_err(DeliveryError::Rejected)
+    }
+
+    /// Renders a verification template in the language of the subscription.
+    pub fn render_verification(
+        &self,
+        template: Template,
+        verification: &Verification,
+    ) -> Result<String, DeliveryError> {
+        let language = verification.subscription.language;
+        let context = self.verification_context(language, verification.subscription.id, &verification.code);
+        self.render_context(language, template, &context)
+            .map_err(DeliveryError::Rejected)
+    }
+
+    fn register(&mut self, language: Language, template: Template, source: &str) -> Result<(), String> {
+        let key = key(language, template);
+        self.registry(template)
+            .register_template_string(&key, source)
+            .map_err(|e| format!("Invalid template `{}`: {}", key, e))
+    }
+
+    fn registry(&mut self, template: Template) -> &mut Handlebars<'static> {
+        if template.is_html() {
+            &mut self.html
+        } else {
+            &mut self.text
+        }
+    }
+
+    /// The rendered template without the trailing line breaks of its file.
+    fn render_context(&self, language: Language, template: Template, context: &Value) -> Result<String, String> {
+        let key = key(language, template);
+        let registry = if template.is_html() { &self.html } else { &self.text };
+        registry
+            .render(&key, context)
+            .map(|rendered| rendered.trim_end().to_string())
+            .map_err(|e| format!("Could not render the template `{}`: {}", key, e))
+    }
+
+    fn change_context(
+        &self,
+        language: Language,
+        subscription_id: i64,
+        reason: NotificationReason,
+        incident: &Incident,
+    ) -> Result<Value, String> {
+        let hours = incident.start_time.zip(incident.end_time);
+        let description = incident.description.trim();
+        let mut context = json!({
+            "language": language,
+            "change": reason,
+            "headline": "",
+            "county": incident.county.trim(),
+            "location": incident.location.trim(),
+            "day": incident.day.format("%d.%m.%Y").to_string(),
+            "weekday": weekday(language, incident.day),
+            "long_day": long_day(language, incident.day),
+            "start": hours.map(|(start, _)| start.format("%H:%M").to_string()),
+            "end": hours.map(|(_, end)| end.format("%H:%M").to_string()),
+            "description": description,
+            "description_lines": description.lines().collect::<Vec<_>>(),
+            "unsubscribe_url": self.links.unsubscribe_url(subscription_id),
+            "unsubscribe": "",
+        });
+        context["headline"] = Value::String(self.render_context(language, Template::Headline, &context)?);
+        context["unsubscribe"] = Value::String(self.render_context(language, Template::Unsubscribe, &context)?);
+        Ok(context)
+    }
+
+    fn digest_context(
+        &self,
+        language: Language,
+        subscription_id: i64,
+        schedule: DigestSchedule,
+        changes: &[DigestChange],
+    ) -> Result<Value, String> {
+        let changes = changes
+            .iter()
+            .map(|change| {
+                let mut context = self.change_context(language, subscription_id, change.reason, &change.incident)?;
+                context["summary"] = Value::String(self.render_context(language, Template::Summary, &context)?);
+                Ok(context)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let mut context = json!({
+            "language": language,
+            "schedule": schedule,
+            "title": "",
+            "count": changes.len(),
+            "changes": changes,
+            "unsubscribe_url": self.links.unsubscribe_url(subscription_id),
+            "unsubscribe": "",
+        });
+        context["title"] = Value::String(self.render_context(language, Template::DigestTitle, &context)?);
+        context["unsubscribe"] = Value::String(self.render_context(language, Template::Unsubscribe, &context)?);
+        Ok(context)
+    }
+
+    fn verification_context(&self, language: Language, subscription_id: i64, code: &str) -> Value {
+        json!({
+            "language": language,
+            "code": code,
+            "verification_url": self.links.verification_url(subscription_id, code),
+        })
+    }
+
+    /// Renders every template for each kind of change of a sample outage, the digests listing them and the
+    /// verification of a sample code.
+    fn validate(&self) -> Result<(), String> {
+        let incident = sample_incident();
+        let reasons = [
+            NotificationReason::New,
+            NotificationReason::Rescheduled,
+            NotificationReason::Cancelled,
+            NotificationReason::Reminder,
+        ];
+        let changes: Vec<DigestChange> = reasons
+            .iter()
+            .map(|reason| DigestChange {
+                reason: *reason,
+                incident: incident.clone(),
+            })
+            .collect();
+
+        for language in LANGUAGES {
+            for reason in reasons {
+                let context = self.change_context(language, 1, reason, &incident)?;
+                self.render_scope(language, Scope::Change, &context)?;
+            }
+            for schedule in [DigestSchedule::Daily, DigestSchedule::Weekly] {
+                let context = self.digest_context(language, 1, schedule, &changes)?;
+                self.render_scope(language, Scope::Digest, &context)?;
+            }
+            let context = self.verification_context(language, 1, "042517");
+            self.render_scope(language, Scope::Verification, &context)?;
+        }
+        Ok(())
+    }
+
+    fn render_scope(&self, language: Language, scope: Scope, context: &Value) -> Result<(), String> {
+        for template in TEMPLATES.into_iter().filter(|template| template.scope() == scope) {
+            self.render_context(language, template, context)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Templates {
+    /// The built-in templates, linking to a service on the local host and signing with an empty key. Only meant for
+    /// the tests.
+    fn default() -> Self {
+        Templates::new(
+            &TemplatesConfiguration::default(),
+            Links::new("http://localhost:8080", ""),
+        )
+        .expect("The built-in templates are valid.")
+    }
+}
+
+/// Strict, so that a misspelled value fails the validation instead of rendering as nothing.
+fn registry(escape: fn(&str) -> String) -> Handlebars<'static> {
+    let mut registry = Handlebars::new();
+    registry.set_strict_mode(true);
+    registry.register_escape_fn(escape);
+    registry.register_helper("plural", Box::new(plural));
+    registry
+}
+
+fn key(language: Language, template: Template) -> String {
+    format!("{}.{}", language.code(), template.name())
+}
+
+fn weekday(language: Language, day: NaiveDate) -> String {
+    match language {
+        Language::Ro => RO_WEEKDAYS[day.weekday().num_days_from_monday() as usize].to_string(),
+        Language::En => day.format("%A").to_string(),
+    }
+}
+
+/// The day as it is written in the language, e.g. `vineri, 8 august 2025`.
+fn long_day(language: Language, day: NaiveDate) -> String {
+    match language {
+        Language::Ro => format!(
+            "{}, {} {} {}",
+            weekday(language, day),
+            day.day(),
+            RO_MONTHS[day.month0() as usize],
+            day.year()
+        ),
+        Language::En => day.format("%A, %-d %B %Y").to_string(),
+    }
+}
+
+fn sample_incident() -> Incident {
+    Incident {
+        external_id: "134691 - Retele Electrice".to_string(),
+        county: "TULCEA".to_string(),
+        location: "LOC. ISACCEA".to_string(),
+        day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+        start_time: NaiveTime::from_hms_opt(9, 0, 0),
+        end_time: NaiveTime::from_hms_opt(13, 0, 0),
+        description: "Strada Păcii nr. 1-10\nStrada Morilor".to_string(),
+        id: 1,
+        revision: 1,
+        updated_at: Utc::now(),
+        cancelled_at: None,
+        duplicate_of: None,
+    }
+}
+
+#[cfg(test)]
+mod templates_tests {
+    use super::{Language, Template, Templates};
+    use crate::notifications::consent::Links;
+    use crate::notifications::digest::{Digest, DigestChange, DigestSchedule};
+    use crate::notifications::notifier::Notification;
+    use crate::notifications::rules::NotificationReason;
+    use crate::notifications::subscriptions::{NotificationChannel, Subscription, SubscriptionState};
+    use crate::web_api::Incident;
+    use chrono::{NaiveDate, NaiveTime, Utc};
+    use common::configuration::TemplatesConfiguration;
+    use std::fs;
+
+    fn notification(language: Language, hours: Option<(u32, u32)>, description: &str) -> Notification {
+        Notification {
+            subscription: Subscription {
+                id: 3,
+                county_key: "tulcea".to_string(),
+                locality: None,
+                locality_key: None,
+                street_pattern: None,
+                window_start: None,
+                window_end: None,
+                channel: NotificationChannel::Sms,
+                contact: "+40722123456".to_string(),
+                state: SubscriptionState::Active,
+                reminders: vec![],
+                digest: Some(DigestSchedule::Weekly),
+                digest_time: NaiveTime::from_hms_opt(18, 0, 0),
+                language,
+                signing_secret: None,
+                failure_count: 0,
+                verified_at: Some(Utc::now()),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            },
+            reason: NotificationReason::New,
+            incident: Incident {
+                external_id: "134691 - Retele Electrice".to_string(),
+                county: "TULCEA".to_string(),
+                location: "LOC. ISACCEA".to_string(),
+                day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+                start_time: hours.map(|(start, _)| NaiveTime::from_hms_opt(start, 0, 0).unwrap()),
+                end_time: hours.map(|(_, end)| NaiveTime::from_hms_opt(end, 0, 0).unwrap()),
+                description: description.to_string(),
+                id: 7,
+                revision: 1,
+                updated_at: Utc::now(),
+                cancelled_at: None,
+                duplicate_of: None,
+            },
+        }
+    }
+
+    fn digest(language: Language, count: usize) -> Digest {
+        let Notification {
+            subscription, incident, ..
+        } = notification(language, Some((9, 13)), "");
+        let changes = (0..count)
+            .map(|id| {
+                let change = DigestChange {
+                    reason: NotificationReason::Cancelled,
+                    incident: Incident {
+                        id: id as i64,
+                        ..incident.clone()
+                    },
+                };
+                (1, change)
+            })
+            .collect();
+        Digest::new(subscription, changes)
+    }
+
+    #[test]
+    fn english_summaries() {
+        let templates = Templates::default();
+
+        assert_eq!(
+            "Planned power outage in LOC. ISACCEA, TULCEA on 08.08.2025 09:00-13:00: Strada Pacii nr. 1-10",
+            templates
+                .render(
+                    Template::Summary,
+                    &notification(Language::En, Some((9, 13)), " Strada Pacii nr. 1-10 ")
+                )
+                .unwrap()
+        );
+        assert_eq!(
+            "Planned power outage in LOC. ISACCEA, TULCEA on 08.08.2025",
+            templates
+                .render(Template::Summary, &notification(Language::En, None, ""))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn romanian_messages_name_the_days_in_romanian() {
+        let templates = Templates::default();
+        let notification = notification(Language::Ro, Some((9, 13)), "Strada Păcii nr. 1-10");
+
+        assert_eq!(
+            "Întrerupere de curent programată în LOC. ISACCEA, TULCEA, vineri 08.08.2025, 09:00-13:00: Strada Păcii nr. \
+             1-10",
+            templates.render(Template::Summary, &notification).unwrap()
+        );
+        assert_eq!(
+            "Întrerupere de curent programată în LOC. ISACCEA, vineri, 8 august 2025",
+            templates.render(Template::EmailSubject, &notification).unwrap()
+        );
+        assert!(
+            templates
+                .render(Template::EmailHtml, &notification)
+                .unwrap()
+                .contains("<tr><th align=\"left\">Interval orar</th><td>09:00 - 13:00</td></tr>")
+        );
+    }
+
+    #[test]
+    fn digest_titles_count_the_outages() {
+        let templates = Templates::default();
+        let title = |language, count| {
+            templates
+                .render_digest(Template::DigestTitle, &digest(language, count))
+                .unwrap()
+        };
+
+        assert_eq!("Weekly power outage digest: 1 outage", title(Language::En, 1));
+        assert_eq!("Weekly power outage digest: 2 outages", title(Language::En, 2));
+        assert_eq!(
+            "Rezumatul săptămânal al întreruperilor de curent: 1 întrerupere",
+            title(Language::Ro, 1)
+        );
+        assert_eq!(
+            "Rezumatul săptămânal al întreruperilor de curent: 19 întreruperi",
+            title(Language::Ro, 19)
+        );
+        assert_eq!(
+            "Rezumatul săptămânal al întreruperilor de curent: 20 de întreruperi",
+            title(Language::Ro, 20)
+        );
+        assert_eq!(
+            3,
+            templates
+                .render_digest(Template::DigestMessage, &digest(Language::Ro, 3))
+                .unwrap()
+                .lines()
+                .count()
+        );
+    }
+
+    #[test]
+    fn template_files_replace_the_built_in_ones() {
+        let path = std::env::temp_dir().join(format!("push_title.ro.{}.hbs", std::process::id()));
+        fs::write(&path, "{{headline}}: {{location}} ({{long_day}})\n").unwrap();
+        let configuration = TemplatesConfiguration {
+            files: [("ro.push_title".to_string(), path.to_string_lossy().into_owned())].into(),
+        };
+
+        let templates = Templates::new(&configuration, Links::new("http://localhost:8080", "")).unwrap();
+
+        assert_eq!(
+            "Întrerupere de curent programată: LOC. ISACCEA (vineri, 8 august 2025)",
+            templates
+                .render(Template::PushTitle, &notification(Language::Ro, Some((9, 13)), ""))
+                .unwrap()
+        );
+        assert_eq!(
+            "Planned power outage in LOC. ISACCEA",
+            templates
+                .render(Template::PushTitle, &notification(Language::En, Some((9, 13)), ""))
+                .unwrap()
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn broken_templates_are_refused_at_startup() {
+        let path = std::env::temp_dir().join(format!("summary.en.{}.hbs", std::process::id()));
+        let configuration = |key: &str| TemplatesConfiguration {
+            files: [(key.to_string(), path.to_string_lossy().into_owned())].into(),
+        };
+        let with = |key: &str, source: &str| {
+            fs::write(&path, source).unwrap();
+            Templates::new(&configuration(key), Links::new("http://localhost:8080", ""))
+        };
+
+        assert!(with("en.summary", "{{headline}} in {{locality}}").is_err());
+        assert!(with("en.summary", "{{#if start}}{{start}}").is_err());
+        assert!(with("de.summary", "{{headline}}").is_err());
+        assert!(with("en.sms", "{{headline}}").is_err());
+        assert!(with("en.summary", "{{headline}}").is_ok());
+
+        fs::remove_file(&path).unwrap();
+        assert!(Templates::new(&configuration("en.summary"), Links::new("http://localhost:8080", "")).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::notifications::consent::Verification;
+use crate::notifications::digest::{Digest, DigestSchedule};
+use crate::notifications::http::{HttpClient, deliver, subscriber_http_client};
+use crate::notifications::notifier::{DeliveryError, Notification, Notifier};
+use crate::notifications::rules::NotificationReason;
+use crate::notifications::subscriptions::{NotificationChannel, Subscription};
+use crate::notifications::templates::{Template, Templates};
+use crate::web_api::Incident;
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Request, Uri};
+use serde::Serialize;
+use sha2::Sha256;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// The Unix time the delivery was signed at, receivers should refuse the old ones to prevent replays.
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+
+/// `sha256=` followed by the hex encoded HMAC-SHA256 of the timestamp, a dot and the body, keyed with the signing
+/// secret of the subscription.
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+/// The body of the webhook deliveries.
+#[derive(Debug, Serialize)]
+pub struct WebhookPayload<'a> {
+    pub subscription_id: i64,
+    pub change: NotificationReason,
+    /// The summary of the change in the language of the subscription.
+    pub message: String,
+    pub incident: &'a Incident,
+    /// Deletes the subscription, without logging in.
+    pub unsubscribe_url: String,
+}
+
+/// The body of the digest deliveries, the changes being the ones of the single deliveries.
+#[derive(Debug, Serialize)]
+pub struct WebhookDigestPayload<'a> {
+    pub subscription_id: i64,
+    pub digest: Option<DigestSchedule>,
+    /// The title of the digest in the language of the subscription.
+    pub title: String,
+    pub changes: Vec<WebhookChange<'a>>,
+    pub unsubscribe_url: String,
+}
+
+/// The body of the delivery verifying the webhook, its code being sent back through the `verification_url`.
+#[derive(Debug, Serialize)]
+pub struct WebhookVerificationPayload {
+    pub subscription_id: i64,
+    pub verification_code: String,
+    pub verification_url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookChange<'a> {
+    pub change: NotificationReason,
+    pub incident: &'a Incident,
+}
+
+/// POSTs the notifications as signed JSON to the URL of the subscription, once per attempt. The failed deliveries are
+/// retried by the outbox, which also disables the webhooks failing too often.
+pub struct WebhookNotifier {
+    client: HttpClient,
+    templates: Arc<Templates>,
+}
+
+impl WebhookNotifier {
+    pub fn new(templates: Arc<Templates>, allow_private_targets: bool) -> Result<WebhookNotifier, String> {
+        Ok(WebhookNotifier {
+            client: subscriber_http_client(allow_private_targets)?,
+            templates,
+        })
+    }
+
+    async fn post(&self, subscription: &Subscription, payload: &impl Serialize) -> Result<(), DeliveryError> {
+        let Some(secret) = &subscription.signing_secret else {
+            return Err(DeliveryError::Rejected(String::from(
+                "The webhook has no signing secret.",
+            )));
+        };
+        let url = subscription
+            .contact
+            .parse::<Uri>()
+            .map_err(|e| DeliveryError::Rejected(format!("Invalid webhook URL: {}", e)))?;
+        let body = serde_json::to_vec(payload).map_err(|e| DeliveryError::Rejected(e.to_string()))?;
+
+        let timestamp = Utc::now().timestamp();
+        let request = Request::post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, signature(secret, timestamp, &body))
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|e| DeliveryError::Rejected(e.to_string()))?;
+        deliver(&self.client, request).await
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn channel(&self) -> NotificationChannel {
+        NotificationChannel::Webhook
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<(), DeliveryError> {
+        let payload = WebhookPayload {
+            subscription_id: notification.subscription.id,
+            change: notification.reason,
+            message: self.templates.render(Template::Summary, notification)?,
+            incident: &notification.incident,
+            unsubscribe_url: self.templates.links().unsubscribe_url(notification.subscription.id),
+        };
+        self.post(&notification.subscription, &payload).await
+    }
+
+    async fn notify_digest(&self, digest: &Digest) -> Result<(), DeliveryError> {
+        let payload = WebhookDigestPayload {
+            subscription_id: digest.subscription.id,
+            digest: digest.subscription.digest,
+            title: self.templates.render_digest(Template::DigestTitle, digest)?,
+            changes: digest
+                .changes
+                .iter()
+                .map(|change| WebhookChange {
+                    change: change.reason,
+                    incident: &change.incident,
+                })
+                .collect(),
+            unsubscribe_url: self.templates.links().unsubscribe_url(digest.subscription.id),
+        };
+        self.post(&digest.subscription, &payload).await
+    }
+
+    async fn send_verification(&self, verification: &Verification) -> Result<(), DeliveryError> {
+        let subscription = &verification.subscription;
+        let payload = WebhookVerificationPayload {
+            subscription_id: subscription.id,
+            verification_code: verification.code.clone(),
+            verification_url: self
+                .templates
+                .links()
+                .verification_url(subscription.id, &verification.code),
+        };
+        self.post(subscription, &payload).await
+    }
+}
+
+/// The value of the [`SIGNATURE_HEADER`].
+pub fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size.");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    let mut signature = String::from("sha256=");
+    for byte in mac.finalize().into_bytes() {
+        let _ = write!(signature, "{:02x}", byte);
+    }
+    signature
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use super::signature;
+
+    #[test]
+    fn signature_covers_the_timestamp_and_the_body() {
+        assert_eq!(
+            "sha256=df79645f594c78e81d6d5ff1368a100c14a5588672aac8ea7f9d80b2968e3217",
+            signature("secret", 1754640000, br#"{"change":"new"}"#)
+        );
+        assert_ne!(
+            signature("secret", 1754640000, br#"{"change":"new"}"#),
+            signature("secret", 1754640001, br#"{"change":"new"}"#)
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::AppState;
+use crate::error::{ApiError, ProblemDetails};
+use crate::web_api::ApiDoc;
+use axum::Json;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use utoipa::OpenApi;
+use utoipa::openapi::OpenApi as OpenApiDocument;
+use utoipa::openapi::server::Server;
+
+const YAML_CONTENT_TYPE: &str = "application/yaml";
+
+/// The OpenAPI document of the service, advertising the given servers. The paths are absolute, so the servers are
+/// the roots of the deployments, e.g. `https://enel.lab.wicked`.
+pub fn api_doc(servers: &[String]) -> OpenApiDocument {
+    let mut api_doc = ApiDoc::openapi();
+    #[cfg(feature = "graphql")]
+    api_doc.merge(crate::graphql::GraphqlApiDoc::openapi());
+    api_doc.servers = if servers.is_empty() {
+        None
+    } else {
+        Some(servers.iter().map(Server::new).collect())
+    };
+    api_doc
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    responses(
+        (status=200, description = "This OpenAPI document, as JSON.", body=Object)
+    )
+)]
+pub async fn get_openapi_json(state: State<AppState>) -> Json<OpenApiDocument> {
+    Json(api_doc(&state.openapi_servers))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/openapi.yaml",
+    responses(
+        (status=200, description = "This OpenAPI document, as YAML.", body=String, content_type = "application/yaml"),
+        (status=500, description = "Error serializing the document.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_openapi_yaml(state: State<AppState>) -> Result<impl IntoResponse, ApiError> {
+    let yaml = api_doc(&state.openapi_servers)
+        .to_yaml()
+        .map_err(|err| ApiError::Internal(format!("Could not serialize the OpenAPI document: {}", err)))?;
+
+    Ok(([(CONTENT_TYPE, YAML_CONTENT_TYPE)], yaml))
+}
+
+#[cfg(test)]
+mod openapi_tests {
+    use super::api_doc;
+
+    #[test]
+    fn servers_from_configuration() {
+        let servers = vec![
+            "https://enel.lab.wicked".to_string(),
+            "http://localhost:8080".to_string(),
+        ];
+
+        let urls: Vec<String> = api_doc(&servers)
+            .servers
+            .unwrap()
+            .into_iter()
+            .map(|server| server.url)
+            .collect();
+
+        assert_eq!(servers, urls);
+        assert!(api_doc(&[]).servers.is_none());
+    }
+}
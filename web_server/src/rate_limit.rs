@@ -0,0 +1,118 @@
+use crate::error::ApiError;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{HeaderMap, HeaderName};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use common::configuration::SubscriptionLimits;
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+/// The clients seen lately are forgotten once their requests are allowed again, past this many of them.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Limits the requests of every client to the routes it is layered on, kept in memory by each replica.
+#[derive(Clone)]
+pub struct ClientLimiter {
+    limiter: Arc<DefaultKeyedRateLimiter<IpAddr>>,
+    address_header: Option<HeaderName>,
+}
+
+impl ClientLimiter {
+    /// Limits the creation and the replacement of the subscriptions by the clients.
+    pub fn subscriptions(limits: &SubscriptionLimits) -> ClientLimiter {
+        let max_per_hour = NonZeroU32::new(limits.max_per_client_per_hour).unwrap_or(NonZeroU32::MIN);
+        ClientLimiter {
+            limiter: Arc::new(RateLimiter::keyed(Quota::per_hour(max_per_hour))),
+            address_header: limits
+                .client_address_header
+                .as_deref()
+                .map(|name| HeaderName::try_from(name).expect("The configuration checks the header names.")),
+        }
+    }
+
+    /// The last address of the header set by the proxy, the one it received the request from, falling back to the
+    /// address of the connection.
+    pub fn client_address(&self, headers: &HeaderMap, connection: Option<SocketAddr>) -> Option<IpAddr> {
+        self.address_header
+            .as_ref()
+            .and_then(|name| headers.get(name))
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|address| address.trim().parse().ok())
+            .or(connection.map(|connection| connection.ip()))
+    }
+
+    /// Counts a request of the client, false once it made too many.
+    pub fn allows(&self, client: IpAddr) -> bool {
+        let allowed = self.limiter.check_key(&client).is_ok();
+        if self.limiter.len() > MAX_TRACKED_CLIENTS {
+            self.limiter.retain_recent();
+        }
+        allowed
+    }
+}
+
+/// Refuses the requests of the clients over their limit. The requests whose client is not known, as the ones made by
+/// the tests, are not limited.
+pub async fn limit_clients(State(limiter): State<ClientLimiter>, request: Request, next: Next) -> Response {
+    let connection = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| *address);
+    if let Some(client) = limiter.client_address(request.headers(), connection)
+        && !limiter.allows(client)
+    {
+        return ApiError::TooManyRequests(String::from("Too many requests, try again later.")).into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod rate_limit_tests {
+    use super::ClientLimiter;
+    use axum::http::{HeaderMap, HeaderValue};
+    use common::configuration::SubscriptionLimits;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    fn limiter(client_address_header: Option<&str>) -> ClientLimiter {
+        ClientLimiter::subscriptions(&SubscriptionLimits {
+            max_per_client_per_hour: 2,
+            client_address_header: client_address_header.map(String::from),
+            ..SubscriptionLimits::default()
+        })
+    }
+
+    #[test]
+    fn clients_are_limited_separately() {
+        let limiter = limiter(None);
+        let client = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        let other = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+        assert!(limiter.allows(client));
+        assert!(limiter.allows(client));
+        assert!(!limiter.allows(client));
+        assert!(limiter.allows(other));
+    }
+
+    #[test]
+    fn the_proxy_header_names_the_client() {
+        let connection = Some(SocketAddr::from(([10, 0, 0, 7], 41000)));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("203.0.113.9, 198.51.100.4"));
+
+        assert_eq!(
+            Some(IpAddr::V4(Ipv4Addr::new(198, 51, 100, 4))),
+            limiter(Some("X-Forwarded-For")).client_address(&headers, connection)
+        );
+        assert_eq!(
+            Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7))),
+            limiter(None).client_address(&headers, connection)
+        );
+        assert_eq!(
+            Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7))),
+            limiter(Some("X-Real-Ip")).client_address(&headers, connection)
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use crate::analytics::OutageAnalyticsParams;
+use crate::caching::{CachedParameters, conditional_get};
+use crate::calendar::CalendarFiltering;
+use crate::catalogue::LocalitiesFiltering;
+use crate::export::ExportParams;
+use crate::extract::{Path, Query};
+use crate::lookup::AddressLookupParams;
+use crate::metrics::{self, monitor_endpoint};
+use crate::notifications::{consent, quiet_hours, subscriptions};
+use crate::rate_limit::{ClientLimiter, limit_clients};
+use crate::web_api::{FieldSelection, IncidentsFiltering};
+use crate::{
+    AppState, analytics, calendar, catalogue, error, events, export, geojson, health, lookup, openapi, scraper, web_api,
+};
+use axum::{Router, middleware};
+use utoipa_axum::router::{OpenApiRouter, UtoipaMethodRouter, UtoipaMethodRouterExt};
+use utoipa_axum::routes;
+use utoipa_swagger_ui::{Config, SwaggerUi};
+
+/// The application with all its routes, middlewares and state.
+pub fn create_app(state: AppState) -> Router {
+    let (app, _) = documented_routes(&state).split_for_parts();
+
+    // The document is referenced relative to the page, so it works behind any path prefix.
+    let swagger_ui = SwaggerUi::new("/api/docs").config(Config::from("../openapi.json"));
+
+    app.merge(swagger_ui)
+        .route_layer(middleware::from_fn_with_state(state.clone(), monitor_endpoint))
+        .layer(middleware::from_fn(error::correlation_id))
+        .with_state(state)
+}
+
+/// The routes of the handlers, each one registered together with its OpenAPI operation.
+fn documented_routes(state: &AppState) -> OpenApiRouter<AppState> {
+    let routes = OpenApiRouter::new()
+        .routes(cached::<()>(state, routes!(web_api::count_incidents)))
+        .routes(cached::<(Query<IncidentsFiltering>, Query<FieldSelection>)>(
+            state,
+            routes!(web_api::get_all_incidents),
+        ))
+        .routes(cached::<Query<IncidentsFiltering>>(
+            state,
+            routes!(geojson::get_incidents_geojson),
+        ))
+        .routes(cached::<Query<ExportParams>>(state, routes!(export::export_incidents)))
+        .routes(cached::<Query<AddressLookupParams>>(
+            state,
+            routes!(lookup::lookup_address),
+        ))
+        .routes(cached::<Path<i64>>(state, routes!(web_api::get_incident)))
+        .routes(cached::<Query<CalendarFiltering>>(
+            state,
+            routes!(calendar::get_calendar),
+        ))
+        .routes(cached::<()>(state, routes!(catalogue::get_counties)))
+        .routes(cached::<Query<LocalitiesFiltering>>(
+            state,
+            routes!(catalogue::get_localities),
+        ))
+        .routes(cached::<Query<OutageAnalyticsParams>>(
+            state,
+            routes!(analytics::get_outage_analytics),
+        ))
+        .routes(routes!(web_api::ping))
+        .routes(routes!(health::get_liveness))
+        .routes(routes!(health::get_readiness))
+        .routes(routes!(health::get_status))
+        .routes(routes!(
+            subscriptions::get_subscription,
+            subscriptions::delete_subscription
+        ))
+        .routes(routes!(consent::verification_page, consent::confirm_verification))
+        .routes(routes!(consent::unsubscribe_page, consent::confirm_unsubscribe))
+        .routes(routes!(
+            quiet_hours::set_quiet_hours,
+            quiet_hours::get_quiet_hours,
+            quiet_hours::delete_quiet_hours
+        ))
+        .routes(routes!(openapi::get_openapi_json))
+        .routes(routes!(openapi::get_openapi_yaml))
+        .routes(routes!(events::stream_incidents))
+        .routes(routes!(scraper::scraper_api::submit_rss))
+        .routes(routes!(metrics::serve_metrics));
+
+    // Creating and replacing the subscriptions sends codes to their contacts.
+    let limiter = ClientLimiter::subscriptions(&state.subscription_limits);
+    let routes = routes.merge(
+        OpenApiRouter::new()
+            .routes(routes!(subscriptions::create_subscription))
+            .routes(routes!(subscriptions::update_subscription))
+            .route_layer(middleware::from_fn_with_state(limiter, limit_clients)),
+    );
+
+    #[cfg(feature = "graphql")]
+    let routes = routes.routes(routes!(crate::graphql::get_graphiql, crate::graphql::execute_graphql));
+
+    routes
+}
+
+/// Answers the conditional requests of a route reading the incidents, whose handler extracts the parameters `P`.
+fn cached<P: CachedParameters + 'static>(
+    state: &AppState,
+    routes: UtoipaMethodRouter<AppState>,
+) -> UtoipaMethodRouter<AppState> {
+    routes.map(|method_router| {
+        method_router.route_layer(middleware::from_fn_with_state(state.clone(), conditional_get::<P>))
+    })
+}
+
+#[cfg(test)]
+mod routes_tests {
+    use super::documented_routes;
+    use crate::AppState;
+    use crate::metrics::Metrics;
+    use crate::notifications::consent::Links;
+    use crate::openapi::api_doc;
+    use common::configuration::SubscriptionLimits;
+    use sqlx::postgres::PgPoolOptions;
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
+    use tokio::sync::{RwLock, broadcast};
+    use utoipa::openapi::OpenApi;
+
+    fn operations(api_doc: &OpenApi) -> BTreeSet<String> {
+        let mut operations = BTreeSet::new();
+        for (path, item) in api_doc.paths.paths.iter() {
+            let methods = [
+                ("get", item.get.is_some()),
+                ("post", item.post.is_some()),
+                ("put", item.put.is_some()),
+                ("delete", item.delete.is_some()),
+            ];
+            for (method, _) in methods.iter().filter(|(_, present)| *present) {
+                operations.insert(format!("{} {}", method, path));
+            }
+        }
+        operations
+    }
+
+    #[tokio::test]
+    async fn every_route_is_documented() {
+        let state = AppState {
+            ping_msg: String::new(),
+            pg_pool: Arc::new(PgPoolOptions::new().connect_lazy("postgres://localhost/enel").unwrap()),
+            categories: vec![],
+            metrics: Arc::new(RwLock::new(Metrics::default())),
+            incident_notifications: broadcast::channel(1).0,
+            openapi_servers: vec![],
+            max_data_age: None,
+            links: Links::new("http://localhost:8080", "secret"),
+            allow_private_targets: false,
+            subscription_limits: SubscriptionLimits::default(),
+        };
+
+        let routed = operations(&documented_routes(&state).into_openapi());
+
+        assert!(routed.len() > 10);
+        assert_eq!(operations(&api_doc(&[])), routed);
+    }
+}
@@ -0,0 +1,186 @@
+use chrono::{NaiveDate, NaiveTime};
+use common::normalization::to_key;
+use log::{error, info};
+use sqlx::{FromRow, PgConnection, Pool, Postgres};
+use std::collections::HashMap;
+use std::sync::Arc;
+use strsim::sorensen_dice;
+
+/// Similarity of the normalized descriptions above which two announcements of the same place, day and time are taken
+/// as the same works.
+const MIN_DESCRIPTION_SIMILARITY: f64 = 0.8;
+
+const CANDIDATES_QUERY: &str = "SELECT id, day, county_key, location_key, start_time, end_time, description, \
+ duplicate_of FROM incidents WHERE day = ANY($1) AND cancelled_at IS NULL ORDER BY id";
+
+/// Only the links which changed are written, so re-running the pass is cheap. They are journaled, the responses
+/// leaving out the duplicates change with them.
+const UPDATE_LINKS_QUERY: &str = "WITH linked AS (\
+ UPDATE incidents SET duplicate_of = links.canonical \
+ FROM UNNEST($1::BIGINT[], $2::BIGINT[]) AS links(id, canonical) \
+ WHERE incidents.id = links.id AND incidents.duplicate_of IS DISTINCT FROM links.canonical \
+ RETURNING incidents.id, incidents.duplicate_of) \
+ INSERT INTO incident_link_events(incident_id, duplicate_of) SELECT id, duplicate_of FROM linked";
+
+#[derive(Debug, FromRow)]
+struct Candidate {
+    id: i64,
+    day: NaiveDate,
+    county_key: Option<String>,
+    location_key: Option<String>,
+    start_time: Option<NaiveTime>,
+    end_time: Option<NaiveTime>,
+    description: String,
+    duplicate_of: Option<i64>,
+}
+
+impl Candidate {
+    /// Incidents without a time window are announced for the whole day.
+    fn overlaps(&self, other: &Candidate) -> bool {
+        match (self.start_time, self.end_time, other.start_time, other.end_time) {
+            (Some(start), Some(end), Some(other_start), Some(other_end)) => start < other_end && other_start < end,
+            _ => true,
+        }
+    }
+
+    fn duplicates(&self, canonical: &Candidate) -> bool {
+        self.overlaps(canonical)
+            && sorensen_dice(&to_key(&self.description), &to_key(&canonical.description)) >= MIN_DESCRIPTION_SIMILARITY
+    }
+}
+
+/// Links the incidents of the given days republished by the provider under another id to the first announcement of
+/// the same works: same county, locality and day, overlapping time windows and similar descriptions. Cancelled
+/// incidents are not considered, so the duplicates of a withdrawn incident get a new canonical one. Returns the number
+/// of incidents whose link changed.
+pub async fn deduplicate_incidents(days: &[NaiveDate], pg_pool: Arc<Pool<Postgres>>) -> Result<u64, String> {
+    let mut connection = pg_pool.acquire().await.map_err(|e| {
+        error!("Could not connect to deduplicate the incidents: {}", e);
+        e.to_string()
+    })?;
+    link_duplicates(days, &mut connection).await
+}
+
+/// Links the duplicates with the given connection.
+pub async fn link_duplicates(days: &[NaiveDate], connection: &mut PgConnection) -> Result<u64, String> {
+    if days.is_empty() {
+        return Ok(0);
+    }
+
+    let candidates: Vec<Candidate> = sqlx::query_as(CANDIDATES_QUERY)
+        .bind(days)
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(|e| {
+            error!("Could not read the incidents to deduplicate: {}", e);
+            e.to_string()
+        })?;
+
+    let (ids, canonicals): (Vec<i64>, Vec<Option<i64>>) = canonical_links(&candidates)
+        .into_iter()
+        .zip(&candidates)
+        .filter(|((_, canonical), candidate)| candidate.duplicate_of != *canonical)
+        .map(|(link, _)| link)
+        .unzip();
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    let updated = sqlx::query(UPDATE_LINKS_QUERY)
+        .bind(ids)
+        .bind(canonicals)
+        .execute(connection)
+        .await;
+
+    match updated {
+        Ok(result) => {
+            info!("Updated the duplicate links of {} incidents.", result.rows_affected());
+            Ok(result.rows_affected())
+        }
+        Err(e) => {
+            error!("Could not link the duplicate incidents: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+/// The canonical incident of each candidate, in the same order, `None` for the canonical ones. Candidates have to be
+/// ordered by id, so the oldest announcement is the canonical one. Incidents without normalized keys cannot be
+/// compared.
+fn canonical_links(candidates: &[Candidate]) -> Vec<(i64, Option<i64>)> {
+    let mut canonicals: HashMap<(&str, &str, NaiveDate), Vec<&Candidate>> = HashMap::new();
+
+    candidates
+        .iter()
+        .map(|candidate| {
+            let (Some(county_key), Some(location_key)) = (&candidate.county_key, &candidate.location_key) else {
+                return (candidate.id, None);
+            };
+            if location_key.is_empty() {
+                return (candidate.id, None);
+            }
+
+            let place = canonicals
+                .entry((county_key.as_str(), location_key.as_str(), candidate.day))
+                .or_default();
+            match place.iter().find(|canonical| candidate.duplicates(canonical)) {
+                Some(canonical) => (candidate.id, Some(canonical.id)),
+                None => {
+                    place.push(candidate);
+                    (candidate.id, None)
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod deduplication_tests {
+    use super::{Candidate, canonical_links};
+    use chrono::{NaiveDate, NaiveTime};
+
+    fn candidate(id: i64, location_key: &str, hours: Option<(u32, u32)>, description: &str) -> Candidate {
+        Candidate {
+            id,
+            day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+            county_key: Some("tulcea".to_string()),
+            location_key: Some(location_key.to_string()),
+            start_time: hours.map(|(start, _)| NaiveTime::from_hms_opt(start, 0, 0).unwrap()),
+            end_time: hours.map(|(_, end)| NaiveTime::from_hms_opt(end, 0, 0).unwrap()),
+            description: description.to_string(),
+            duplicate_of: None,
+        }
+    }
+
+    #[test]
+    fn republished_works_link_to_the_first_announcement() {
+        let candidates = vec![
+            candidate(1, "isaccea", Some((9, 13)), "Strada: Pacii nr. 1-10, Viilor"),
+            candidate(2, "isaccea", Some((10, 14)), "Strada: PACII NR 1-10; Viilor"),
+            candidate(3, "isaccea", None, "Strada: Pacii nr. 1-10, Viilor"),
+        ];
+
+        assert_eq!(
+            vec![(1, None), (2, Some(1)), (3, Some(1))],
+            canonical_links(&candidates)
+        );
+    }
+
+    #[test]
+    fn different_works_are_kept() {
+        let candidates = vec![
+            candidate(1, "isaccea", Some((9, 13)), "Strada: Pacii nr. 1-10"),
+            candidate(2, "isaccea", Some((13, 17)), "Strada: Pacii nr. 1-10"),
+            candidate(3, "isaccea", Some((9, 13)), "Strada: Dunarii, Portului"),
+            candidate(4, "macin", Some((9, 13)), "Strada: Pacii nr. 1-10"),
+            candidate(5, "", Some((9, 13)), "Strada: Pacii nr. 1-10"),
+            candidate(6, "", Some((9, 13)), "Strada: Pacii nr. 1-10"),
+        ];
+
+        assert!(
+            canonical_links(&candidates)
+                .into_iter()
+                .all(|(_, canonical)| canonical.is_none())
+        );
+    }
+}
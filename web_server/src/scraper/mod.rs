@@ -1,3 +1,4 @@
+pub mod deduplication;
 pub mod persistence;
 mod rss_reader;
-pub mod scraper_api;
\ No newline at end of file
+pub mod scraper_api;
@@ -1,22 +1,68 @@
+use chrono::NaiveDate;
 use common::Record;
-use log::error;
-use sqlx::{Pool, Postgres};
+use common::normalization::{normalize_county, normalize_locality};
+use log::{error, info, warn};
+use sqlx::{PgConnection, PgExecutor, Pool, Postgres, Row};
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::sync::Arc;
 
-const INSERT_QUERY: &str = "INSERT INTO incidents(external_id, day, county, location, description) \
- VALUES ($1, $2, $3, $4, $5) \
+/// The revision is only increased when the provider actually changed the incident, so re-submitting the same feed
+/// leaves the stored incidents untouched. An incident which shows up again in the feed is no longer cancelled.
+/// Every stored change is journaled in `incident_events` by the same statement.
+const INSERT_QUERY: &str = "WITH stored AS (\
+ INSERT INTO incidents(external_id, day, county, location, description, county_key, location_key, \
+ start_time, end_time) \
+ VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) \
  ON CONFLICT (external_id) DO \
- UPDATE SET day = $2, county = $3, location = $4, description = $5";
+ UPDATE SET day = $2, county = $3, location = $4, description = $5, county_key = $6, location_key = $7, \
+ start_time = $8, end_time = $9, revision = incidents.revision + 1, updated_at = now(), cancelled_at = NULL \
+ WHERE incidents.cancelled_at IS NOT NULL \
+ OR (incidents.day, incidents.county, incidents.location, incidents.description, incidents.start_time, incidents.end_time) \
+ IS DISTINCT FROM ($2, $3, $4, $5, $8, $9) \
+ RETURNING id, revision, xmax = 0 AS inserted) \
+ INSERT INTO incident_events(incident_id, kind, revision) \
+ SELECT id, CASE WHEN inserted THEN 'created' ELSE 'updated' END, revision FROM stored";
+
+/// The upcoming incidents of the counties of the feed, to tell whether the feed covers them completely.
+const UPCOMING_COUNTS_QUERY: &str = "SELECT county, count(*) FROM incidents \
+ WHERE cancelled_at IS NULL AND day > $1 AND county = ANY($2) GROUP BY county";
+
+/// Upcoming incidents of the counties present in the feed which are no longer announced by the provider.
+const CANCEL_WITHDRAWN_QUERY: &str = "WITH cancelled AS (\
+ UPDATE incidents \
+ SET cancelled_at = now(), updated_at = now(), revision = revision + 1 \
+ WHERE cancelled_at IS NULL AND day > $1 AND county = ANY($2) AND NOT (external_id = ANY($3)) \
+ RETURNING id, revision) \
+ INSERT INTO incident_events(incident_id, kind, revision) \
+ SELECT id, 'cancelled', revision FROM cancelled";
+
+const MISSING_KEYS_QUERY: &str =
+    "SELECT id, county, location FROM incidents WHERE county_key IS NULL OR location_key IS NULL";
+
+const UPDATE_KEYS_QUERY: &str = "UPDATE incidents SET county_key = $2, location_key = $3 WHERE id = $1";
+
+const INSERT_INGESTION_QUERY: &str =
+    "INSERT INTO ingestions(received_count, stored_count, cancelled_count) VALUES ($1, $2, $3)";
 
 pub async fn new_store_record(record: &Record, pg_pool: Arc<Pool<Postgres>>) -> Result<u64, String> {
+    store_record(record, pg_pool.deref()).await
+}
+
+/// Stores the record with the given connection, the feeds are stored within a transaction so that the notifications
+/// owed for their changes are enqueued together with them.
+pub async fn store_record<'e>(record: &Record, executor: impl PgExecutor<'e>) -> Result<u64, String> {
     let pg_incident = sqlx::query(INSERT_QUERY)
         .bind(&record.id)
-        .bind(&record.date)
+        .bind(record.date)
         .bind(&record.county)
         .bind(&record.location)
         .bind(&record.description)
-        .execute(pg_pool.deref())
+        .bind(normalize_county(&record.county))
+        .bind(normalize_locality(&record.location))
+        .bind(record.start_time)
+        .bind(record.end_time)
+        .execute(executor)
         .await;
 
     match pg_incident {
@@ -27,3 +73,122 @@ pub async fn new_store_record(record: &Record, pg_pool: Arc<Pool<Postgres>>) ->
         }
     }
 }
+
+/// Marks as cancelled the incidents scheduled after `today` which are missing from the feed, even though the feed
+/// announces other incidents in their county. Returns the number of cancelled incidents.
+pub async fn cancel_withdrawn_incidents(
+    records: &[Record],
+    today: NaiveDate,
+    pg_pool: Arc<Pool<Postgres>>,
+) -> Result<u64, String> {
+    let mut connection = pg_pool.acquire().await.map_err(|e| e.to_string())?;
+    cancel_withdrawn(records, today, &mut connection).await
+}
+
+/// Cancels the withdrawn incidents with the given connection. A county is only taken as completely covered by the
+/// feed when the feed announces at least half as many upcoming incidents there as are stored, a truncated or partial
+/// feed leaves the incidents of the county as they are.
+pub async fn cancel_withdrawn(
+    records: &[Record],
+    today: NaiveDate,
+    connection: &mut PgConnection,
+) -> Result<u64, String> {
+    let mut announced: HashMap<&str, i64> = HashMap::new();
+    for record in records.iter().filter(|record| record.date > today) {
+        *announced.entry(record.county.as_str()).or_default() += 1;
+    }
+    if announced.is_empty() {
+        return Ok(0);
+    }
+
+    let counties: Vec<&str> = announced.keys().copied().collect();
+    let stored: Vec<(String, i64)> = sqlx::query_as(UPCOMING_COUNTS_QUERY)
+        .bind(today)
+        .bind(&counties)
+        .fetch_all(&mut *connection)
+        .await
+        .map_err(|e| {
+            error!("Could not count the upcoming incidents of the feed counties: {}", e);
+            e.to_string()
+        })?;
+    let mut complete = Vec::with_capacity(counties.len());
+    for (county, count) in stored {
+        if announced.get(county.as_str()).copied().unwrap_or_default() * 2 >= count {
+            complete.push(county);
+        } else {
+            warn!(
+                "The feed announces only {} of the {} upcoming incidents of {}, none of them is cancelled.",
+                announced[county.as_str()],
+                count,
+                county
+            );
+        }
+    }
+    if complete.is_empty() {
+        return Ok(0);
+    }
+    let external_ids: Vec<&str> = records.iter().map(|r| r.id.as_str()).collect();
+
+    let cancelled = sqlx::query(CANCEL_WITHDRAWN_QUERY)
+        .bind(today)
+        .bind(complete)
+        .bind(external_ids)
+        .execute(&mut *connection)
+        .await;
+
+    match cancelled {
+        Ok(result) => Ok(result.rows_affected()),
+        Err(e) => {
+            error!("Could not cancel the withdrawn incidents: {}", e);
+            Err(e.to_string())
+        }
+    }
+}
+
+/// Records a feed stored by the scraper endpoint, the latest one tells how fresh the incidents are. It is recorded in
+/// the transaction of the feed, so that a feed is recorded exactly when it is stored.
+pub async fn record_ingestion<'e>(
+    received: usize,
+    stored: usize,
+    cancelled: u64,
+    executor: impl PgExecutor<'e>,
+) -> Result<(), String> {
+    sqlx::query(INSERT_INGESTION_QUERY)
+        .bind(received as i32)
+        .bind(stored as i32)
+        .bind(cancelled as i32)
+        .execute(executor)
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Could not record the ingestion: {}", e);
+            e.to_string()
+        })
+}
+
+/// Fills in the normalized county and location keys for the incidents stored before the keys were introduced.
+pub async fn backfill_normalized_keys(pg_pool: Arc<Pool<Postgres>>) -> Result<u64, String> {
+    let rows = sqlx::query(MISSING_KEYS_QUERY)
+        .fetch_all(pg_pool.deref())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut updated = 0;
+    for row in rows.iter() {
+        let id: i64 = row.get("id");
+        let county: String = row.get("county");
+        let location: String = row.get("location");
+
+        sqlx::query(UPDATE_KEYS_QUERY)
+            .bind(id)
+            .bind(normalize_county(&county))
+            .bind(normalize_locality(&location))
+            .execute(pg_pool.deref())
+            .await
+            .map_err(|e| e.to_string())?;
+        updated += 1;
+    }
+
+    info!("Backfilled the normalized keys of {} incidents.", updated);
+    Ok(updated)
+}
@@ -1,4 +1,4 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveTime};
 use common::Record;
 use log::{debug, error, info};
 use regex::Regex;
@@ -46,17 +46,20 @@ fn convert_item(rss_item: &rss::Item, location_extractor: &Regex) -> Option<Reco
 
         let title_parsing_result = NaiveDate::parse_and_remainder(title, "%d.%m.%Y");
 
-        let incident_datetime = match title_parsing_result {
-            Ok((incident_datetime, _remaining)) => Some(incident_datetime),
+        let (incident_datetime, remaining) = match title_parsing_result {
+            Ok(parsed) => Some(parsed),
             Err(e) => {
                 error!("Error when parsing the date from the title({}): {}", title, e);
                 None
             }
         }?;
+        let (start_time, end_time) = parse_time_window(remaining);
 
         Some(Record {
             id: id.value.to_string(),
             date: incident_datetime,
+            start_time,
+            end_time,
             county: judet.to_string(),
             location: localitate.to_string(),
             title: rss_item.title.as_ref()?.to_string(),
@@ -65,6 +68,22 @@ fn convert_item(rss_item: &rss::Item, location_extractor: &Regex) -> Option<Reco
     })
 }
 
+/// Parses the time window following the date in the title (e.g. ` 09:00 - 17:00  Judet: ...`). Any of the two ends
+/// is missing when it can not be parsed.
+fn parse_time_window(title_remainder: &str) -> (Option<NaiveTime>, Option<NaiveTime>) {
+    let Ok((start_time, remaining)) = NaiveTime::parse_and_remainder(title_remainder.trim_start(), "%H:%M") else {
+        return (None, None);
+    };
+
+    let end_time = remaining
+        .trim_start()
+        .strip_prefix('-')
+        .and_then(|remaining| NaiveTime::parse_and_remainder(remaining.trim_start(), "%H:%M").ok())
+        .map(|(end_time, _remaining)| end_time);
+
+    (Some(start_time), end_time)
+}
+
 /// Convert the categories from the configuration into RSS categories.
 fn convert_config_categs(config_categs: &[String]) -> Vec<Category> {
     config_categs
@@ -78,12 +97,14 @@ fn convert_config_categs(config_categs: &[String]) -> Vec<Category> {
 
 #[cfg(test)]
 mod rss_reader_tests {
-    use chrono::NaiveDate;
+    use chrono::{NaiveDate, NaiveTime};
     use common::Record;
     use regex::Regex;
     use rss::{Category, Guid, ItemBuilder};
 
-    use super::{check_categories, convert_config_categs, convert_item, filter_items, LOCATION_PATTERN};
+    use super::{
+        LOCATION_PATTERN, check_categories, convert_config_categs, convert_item, filter_items, parse_time_window,
+    };
 
     const FILTER_CATEG_1: &str = "one";
     const FILTER_CATEG_2: &str = "two";
@@ -153,6 +174,8 @@ mod rss_reader_tests {
             id,
             // date: "1985-02-21".to_string(),
             date: NaiveDate::parse_from_str("1985-02-21", "%Y-%m-%d").unwrap(),
+            start_time: NaiveTime::from_hms_opt(6, 0, 0),
+            end_time: NaiveTime::from_hms_opt(8, 0, 0),
             county: "X".to_string(),
             location: "Y".to_string(),
             description,
@@ -213,6 +236,28 @@ mod rss_reader_tests {
 
         assert_eq!(None, result);
     }
+    #[test]
+    fn parse_time_window_both_ends() {
+        let result = parse_time_window(" 09:00 - 17:00  Judet: TULCEA Localitate: LOC. TURCOAIA");
+
+        assert_eq!(
+            (NaiveTime::from_hms_opt(9, 0, 0), NaiveTime::from_hms_opt(17, 0, 0)),
+            result
+        );
+    }
+
+    #[test]
+    fn parse_time_window_missing() {
+        assert_eq!(
+            (None, None),
+            parse_time_window(" Judet: TULCEA Localitate: LOC. TURCOAIA")
+        );
+        assert_eq!(
+            (NaiveTime::from_hms_opt(9, 30, 0), None),
+            parse_time_window(" 09:30 Judet: TULCEA Localitate: LOC. TURCOAIA")
+        );
+    }
+
     #[test]
     fn convert_config_categs_works() {
         let config_categs = ["one".to_string(), "two".to_string()];
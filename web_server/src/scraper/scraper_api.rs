@@ -1,59 +1,109 @@
+use crate::AppState;
+use crate::error::{ApiError, ProblemDetails};
 use crate::metrics::AppMetrics;
-use crate::scraper::persistence::new_store_record;
+use crate::notifications::rules::{enqueue_notifications, journal_position};
+use crate::scraper::deduplication::link_duplicates;
+use crate::scraper::persistence::{cancel_withdrawn, record_ingestion, store_record};
 use crate::scraper::rss_reader::parse_rss;
 use crate::web_api::Ping;
-use crate::AppState;
-use axum::extract::State;
-use axum::http::StatusCode;
 use axum::Json;
-use log::{debug, error, info};
-
-// #[utoipa::path(
-//     get,
-//     path = "/ping",
-//     responses(
-//             (status=200, description = "Respond with a pong.", body=Ping),
-//             (status=500, description = "Server is not ready to serve."),
-//     )
-// )]
-pub async fn submit_rss(State(state): State<AppState>, body: String) -> Result<Json<Ping>, (StatusCode, String)> {
-    let incidents = parse_rss(&body, &state.categories);
-    match incidents.await {
-        Ok(incidents) => {
-            debug!("Incidents: {:?}", incidents);
-
-            let mut stored_incidents = 0;
-            for incident in incidents.iter() {
-                new_store_record(incident, state.pg_pool.clone())
-                    .await
-                    .map(|_t| {
-                        stored_incidents += 1;
-                    })
-                    .expect("Counting stored incidents.");
-            }
-
-            let labels = vec![];
-            state
-                .metrics
-                .read()
-                .await
-                .get_gauge(AppMetrics::RssIncidentsCount)
-                .inspect(|gauge| {
-                    gauge.get_or_create(&labels).set(stored_incidents);
-                });
-
-            info!(
-                "Stored {} incidents out of {} received.",
-                stored_incidents,
-                incidents.len()
-            );
-        }
-        Err(err) => {
-            // state.metrics.failures_counter.inc();
-            error!("{}", err);
-        }
+use axum::extract::State;
+use chrono::Utc;
+use log::{debug, info};
+
+#[utoipa::path(
+    post,
+    path = "/scraper",
+    request_body(content = String, description = "RSS feed of the provider, as downloaded by the scraper.", content_type = "application/rss+xml"),
+    responses(
+            (status=200, description = "The incidents of the feed were stored.", body=Ping),
+            (status=400, description = "The feed could not be parsed.", body=ProblemDetails, content_type = "application/problem+json"),
+            (status=500, description = "The incidents could not be stored.", body=ProblemDetails, content_type = "application/problem+json"),
+    )
+)]
+pub async fn submit_rss(State(state): State<AppState>, body: String) -> Result<Json<Ping>, ApiError> {
+    let incidents = parse_rss(&body, &state.categories)
+        .await
+        .map_err(ApiError::InvalidFeed)?;
+    debug!("Incidents: {:?}", incidents);
+
+    // The incidents, their links and the notifications owed for their changes are stored all or nothing, a feed
+    // failing midway is submitted again by the scraper without any notification lost or sent twice.
+    let mut transaction = state
+        .pg_pool
+        .begin()
+        .await
+        .map_err(|err| ApiError::Internal(format!("Could not start storing the feed: {}", err)))?;
+
+    // The changes journaled from here on are the ones of this feed.
+    let last_event_id = journal_position(&mut *transaction).await.map_err(ApiError::Internal)?;
+
+    let mut stored_incidents = 0;
+    for incident in incidents.iter() {
+        store_record(incident, &mut *transaction)
+            .await
+            .map_err(|err| ApiError::Internal(format!("Could not store incident {}: {}", incident.id, err)))?;
+        stored_incidents += 1;
     }
 
+    let today = Utc::now().date_naive();
+    // A feed narrowed to some categories does not tell which incidents the provider withdrew.
+    let cancelled_incidents = if state.categories.is_empty() {
+        cancel_withdrawn(&incidents, today, &mut transaction)
+            .await
+            .map_err(ApiError::Internal)?
+    } else {
+        0
+    };
+
+    let mut days: Vec<_> = incidents.iter().map(|incident| incident.date).collect();
+    days.sort();
+    days.dedup();
+    let linked_duplicates = link_duplicates(&days, &mut transaction)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    let notification_jobs = enqueue_notifications(last_event_id, today, &mut transaction)
+        .await
+        .map_err(ApiError::Internal)?;
+
+    record_ingestion(
+        incidents.len(),
+        stored_incidents as usize,
+        cancelled_incidents,
+        &mut *transaction,
+    )
+    .await
+    .map_err(ApiError::Internal)?;
+
+    transaction
+        .commit()
+        .await
+        .map_err(|err| ApiError::Internal(format!("Could not store the feed: {}", err)))?;
+
+    // Nobody listening to the incident streams is not an error. The notification dispatcher is woken up as well.
+    let _ = state.incident_notifications.send(());
+
+    let labels = vec![];
+    state
+        .metrics
+        .read()
+        .await
+        .get_gauge(AppMetrics::RssIncidentsCount)
+        .inspect(|gauge| {
+            gauge.get_or_create(&labels).set(stored_incidents);
+        });
+
+    info!(
+        "Stored {} incidents out of {} received, {} withdrawn incidents cancelled, {} duplicate links updated, {} \
+         notification jobs created.",
+        stored_incidents,
+        incidents.len(),
+        cancelled_incidents,
+        linked_duplicates,
+        notification_jobs
+    );
+
     Ok(Json(Ping {
         ping: "response".to_string(),
     }))
@@ -1,26 +1,111 @@
 use crate::AppState;
+use crate::analytics::{AnalyticsGrouping, AnalyticsSort, OutageAnalyticsResponse, OutageStatistics};
+use crate::caching::CachedParameters;
+use crate::catalogue::{CountySummary, LocalitySuggestion};
+use crate::error::{ApiError, ErrorCode, ProblemDetails};
+use crate::events::IncidentEvent;
+use crate::export::ExportFormat;
+use crate::extract::{Path, Query};
+use crate::geojson::IncidentFeatureCollection;
+use crate::health::{IncidentCounts, Ingestion, Liveness, Readiness, ServiceStatus};
+use crate::lookup::AddressLookupResponse;
+use crate::notifications::digest::DigestSchedule;
+use crate::notifications::quiet_hours::{QuietHours, QuietHoursRequest};
+use crate::notifications::reminders::Reminder;
+use crate::notifications::subscriptions::{
+    NotificationChannel, SavedSubscription, Subscription, SubscriptionRequest, SubscriptionState,
+};
+use crate::notifications::templates::Language;
 use axum::Json;
-use axum::extract::{Query, State};
-use axum::http::StatusCode;
-use chrono::NaiveDate;
-use log::error;
-use serde::{Deserialize, Serialize};
-use sqlx::{Error, FromRow, QueryBuilder, Row};
+use axum::extract::State;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::de::IntoDeserializer;
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Deserialize, Serialize, Serializer};
+use sqlx::{FromRow, Postgres, QueryBuilder, Row};
 use std::ops::Deref;
 use utoipa::{IntoParams, OpenApi, ToSchema};
 
 #[derive(OpenApi)]
 #[openapi(
-    paths(ping, count_incidents, get_all_incidents),
-    components(schemas(RecordCount, Ping, Incident)),
-    servers(
-        (url="https://enel.lab.wicked/api", description="homelab"),
-        (url="http://localhost:8080/api", description="localhost")
+    paths(
+        ping,
+        crate::health::get_liveness,
+        crate::health::get_readiness,
+        crate::health::get_status,
+        count_incidents,
+        get_all_incidents,
+        get_incident,
+        crate::geojson::get_incidents_geojson,
+        crate::export::export_incidents,
+        crate::calendar::get_calendar,
+        crate::events::stream_incidents,
+        crate::lookup::lookup_address,
+        crate::catalogue::get_counties,
+        crate::catalogue::get_localities,
+        crate::analytics::get_outage_analytics,
+        crate::notifications::subscriptions::create_subscription,
+        crate::notifications::subscriptions::get_subscription,
+        crate::notifications::subscriptions::update_subscription,
+        crate::notifications::subscriptions::delete_subscription,
+        crate::notifications::consent::verification_page,
+        crate::notifications::consent::confirm_verification,
+        crate::notifications::consent::unsubscribe_page,
+        crate::notifications::consent::confirm_unsubscribe,
+        crate::notifications::quiet_hours::set_quiet_hours,
+        crate::notifications::quiet_hours::get_quiet_hours,
+        crate::notifications::quiet_hours::delete_quiet_hours,
+        crate::scraper::scraper_api::submit_rss,
+        crate::metrics::serve_metrics,
+        crate::openapi::get_openapi_json,
+        crate::openapi::get_openapi_yaml
     ),
+    components(schemas(
+        ProblemDetails,
+        ErrorCode,
+        RecordCount,
+        Ping,
+        Incident,
+        IncidentWithDuplicates,
+        IncidentSort,
+        SortDirection,
+        IncidentField,
+        IncidentFeatureCollection,
+        ExportFormat,
+        IncidentEvent,
+        AddressLookupResponse,
+        CountySummary,
+        LocalitySuggestion,
+        AnalyticsGrouping,
+        AnalyticsSort,
+        OutageAnalyticsResponse,
+        OutageStatistics,
+        Liveness,
+        Readiness,
+        ServiceStatus,
+        Ingestion,
+        IncidentCounts,
+        Subscription,
+        SavedSubscription,
+        SubscriptionRequest,
+        SubscriptionState,
+        NotificationChannel,
+        Reminder,
+        DigestSchedule,
+        Language,
+        QuietHours,
+        QuietHoursRequest
+    )),
     info(title = "Test API", license(name = "hey", identifier = "CC-BY-ND-4.0"))
 )]
 pub struct ApiDoc;
 
+/// The incident a duplicate was linked to, or the incident itself when it is not a duplicate.
+const CANONICAL_INCIDENT_QUERY: &str =
+    "SELECT * FROM incidents WHERE id = (SELECT COALESCE(duplicate_of, id) FROM incidents WHERE id = $1)";
+
+pub(crate) const DUPLICATES_QUERY: &str = "SELECT * FROM incidents WHERE duplicate_of = $1 ORDER BY id";
+
 #[derive(Debug, Serialize, Clone, ToSchema)]
 pub struct RecordCount {
     pub total_count: i64,
@@ -36,38 +121,59 @@ pub struct Ping {
 }
 
 #[derive(Debug, Serialize, Clone, ToSchema, FromRow)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject), graphql(complex))]
 pub struct Incident {
     pub external_id: String,
     pub county: String,
     pub location: String,
     #[schema(value_type = String, format = Date)]
     pub day: NaiveDate,
+    #[schema(value_type = Option<String>, example = "09:00:00")]
+    pub start_time: Option<NaiveTime>,
+    #[schema(value_type = Option<String>, example = "17:00:00")]
+    pub end_time: Option<NaiveTime>,
     pub description: String,
     pub id: i64,
+    /// Starts at 1 and is increased every time the provider changes the incident.
+    pub revision: i32,
+    #[schema(value_type = String, format = DateTime)]
+    pub updated_at: DateTime<Utc>,
+    /// Set when the incident disappeared from the provider feed before its day.
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub cancelled_at: Option<DateTime<Utc>>,
+    /// Id of the canonical incident when the provider republished the same works under another id.
+    pub duplicate_of: Option<i64>,
 }
 
 #[utoipa::path(
     get,
-    path = "/incidents/count",
+    path = "/api/incidents/count",
     responses(
             (status=200, description = "Count the number of records in the DB.", body=RecordCount),
-            (status=500, description = "Error counting the number of records in the DB."),
+            (status=500, description = "Error counting the number of records in the DB.", body=ProblemDetails, content_type = "application/problem+json"),
     )
 )]
-pub async fn count_incidents(state: State<AppState>) -> Result<Json<RecordCount>, (StatusCode, String)> {
-    let row =
-        sqlx::query("SELECT COUNT(*) as total_count, MIN(day) as start_date, MAX(day) as end_date FROM incidents")
-            .fetch_one(state.pg_pool.deref())
-            .await;
-
-    match row {
-        Ok(row) => Ok(Json(RecordCount {
-            total_count: row.get("total_count"),
-            start_date: row.get("start_date"),
-            end_date: row.get("end_date"),
-        })),
-        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
-    }
+pub async fn count_incidents(state: State<AppState>) -> Result<Json<RecordCount>, ApiError> {
+    let row = sqlx::query(
+        "SELECT COUNT(*) as total_count, MIN(day) as start_date, MAX(day) as end_date FROM incidents \
+         WHERE duplicate_of IS NULL",
+    )
+    .fetch_one(state.pg_pool.deref())
+    .await?;
+
+    Ok(Json(RecordCount {
+        total_count: row.get("total_count"),
+        start_date: row.get("start_date"),
+        end_date: row.get("end_date"),
+    }))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IncidentWithDuplicates {
+    /// The canonical incident, also when the id of one of its duplicates was requested.
+    pub incident: Incident,
+    /// The announcements of the same works republished by the provider under other ids.
+    pub duplicates: Vec<Incident>,
 }
 
 #[derive(Deserialize, IntoParams, Default)]
@@ -75,88 +181,416 @@ pub struct IncidentsFiltering {
     pub county: Option<String>,
     pub offset: Option<u64>,
     pub count: Option<u64>,
-    pub day: Option<String>,
+    #[param(value_type = Option<String>, format = Date)]
+    pub day: Option<NaiveDate>,
     // datetime: Option<String>,
+    /// Sort key, `day` by default.
+    pub sort: Option<IncidentSort>,
+    /// Sort direction, descending by default.
+    pub direction: Option<SortDirection>,
+    /// Also lists the incidents republished under another id, which are left out by default.
+    pub include_duplicates: Option<bool>,
 }
 
-#[derive(Serialize, ToSchema)]
-pub struct GetIncidentsResponse {
-    pub incidents: Vec<Incident>,
-    pub total_count: u64,
+/// The GeoJSON incidents.
+impl CachedParameters for Query<IncidentsFiltering> {}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentSort {
+    #[default]
+    Day,
+    County,
+    Location,
+    StartTime,
+    UpdatedAt,
 }
 
-#[utoipa::path(
-    get,
-    path = "/incidents/all",
-    params(
-        IncidentsFiltering
-    ),
-    responses(
-        (status=200, description = "All incidents.", body=GetIncidentsResponse),
-        (status=500, description = "Error getting all incidents.")
-    )
-)]
-pub async fn get_all_incidents(
-    state: State<AppState>,
-    filtering: Query<IncidentsFiltering>,
-) -> Result<Json<GetIncidentsResponse>, (StatusCode, String)> {
+impl IncidentSort {
+    fn column(&self) -> &'static str {
+        match self {
+            IncidentSort::Day => "day",
+            IncidentSort::County => "county",
+            IncidentSort::Location => "location",
+            IncidentSort::StartTime => "start_time",
+            IncidentSort::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl SortDirection {
+    fn keyword(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+}
+
+/// Columns of an incident a client can select with the `fields` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentField {
+    Id,
+    ExternalId,
+    County,
+    Location,
+    Day,
+    StartTime,
+    EndTime,
+    Description,
+    Revision,
+    UpdatedAt,
+    CancelledAt,
+    DuplicateOf,
+}
+
+#[derive(Deserialize, IntoParams, Default)]
+pub struct FieldSelection {
+    /// Comma separated columns returned for each incident, e.g. `id,day,location`. All of them by default.
+    #[param(value_type = Option<Vec<IncidentField>>, style = Form, explode = false)]
+    pub fields: Option<String>,
+}
+
+/// The incidents listing.
+impl CachedParameters for (Query<IncidentsFiltering>, Query<FieldSelection>) {
+    fn check(&self) -> Result<(), ApiError> {
+        self.1.parse().map(|_| ()).map_err(ApiError::InvalidParameter)
+    }
+}
+
+/// An incident.
+impl CachedParameters for Path<i64> {}
+
+impl FieldSelection {
+    /// Parses the requested fields, failing on the first unknown one.
+    pub fn parse(&self) -> Result<Option<Vec<IncidentField>>, String> {
+        let Some(fields) = &self.fields else {
+            return Ok(None);
+        };
+
+        fields
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(|field| {
+                IncidentField::deserialize(field.into_deserializer())
+                    .map_err(|_: serde::de::value::Error| format!("Unknown field `{}`.", field))
+            })
+            .collect::<Result<Vec<IncidentField>, String>>()
+            .map(Some)
+    }
+}
+
+/// Pushes the `FROM` clause of the incidents listing: the filters, the ordering and the page selection. Endpoints
+/// serving incidents in other formats use it so they stay consistent with `/incidents/all`.
+pub(crate) fn push_incidents_selection<'a>(
+    query_builder: &mut QueryBuilder<'a, Postgres>,
+    filtering: &'a IncidentsFiltering,
+) {
     let offset = filtering.offset;
     let count = filtering.count.unwrap_or(50);
 
-    let mut query_builder = QueryBuilder::new("SELECT * FROM incidents");
+    query_builder.push(" FROM incidents");
+    push_incidents_filters(query_builder, filtering);
+    push_incidents_order(query_builder, filtering, "");
 
-    if filtering.county.is_some() || filtering.day.is_some() {
+    query_builder.push(" LIMIT ").push(count);
+
+    if let Some(offset) = offset {
+        query_builder.push(" OFFSET ").push_bind(offset as i64);
+    }
+}
+
+/// Pushes the `ORDER BY` clause of the requested sort, the columns being prefixed with `qualifier`. Incidents are
+/// additionally ordered by id, so the pages are stable, and the ones without a value come last.
+pub(crate) fn push_incidents_order(
+    query_builder: &mut QueryBuilder<'_, Postgres>,
+    filtering: &IncidentsFiltering,
+    qualifier: &str,
+) {
+    let direction = filtering.direction.unwrap_or_default().keyword();
+
+    query_builder.push(format!(
+        " ORDER BY {qualifier}{} {direction} NULLS LAST, {qualifier}id {direction}",
+        filtering.sort.unwrap_or_default().column()
+    ));
+}
+
+/// Pushes the `WHERE` clause matching the county and day filters, leaving out the duplicates unless they are
+/// requested, without any paging.
+pub(crate) fn push_incidents_filters<'a>(
+    query_builder: &mut QueryBuilder<'a, Postgres>,
+    filtering: &'a IncidentsFiltering,
+) {
+    let include_duplicates = filtering.include_duplicates.unwrap_or(false);
+
+    if filtering.county.is_some() || filtering.day.is_some() || !include_duplicates {
         query_builder.push(" WHERE ");
 
         let mut separated = query_builder.separated(" AND ");
 
+        if !include_duplicates {
+            separated.push("duplicate_of IS NULL");
+        }
+
         if let Some(county) = &filtering.county {
             separated.push("county = ").push_bind_unseparated(county);
         }
 
-        if let Some(day) = &filtering.day {
-            separated
-                .push("day = ")
-                .push_bind_unseparated(NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap());
+        if let Some(day) = filtering.day {
+            separated.push("day = ").push_bind_unseparated(day);
         }
     }
+}
 
-    query_builder.push(" ORDER BY day DESC");
-
-    query_builder.push(" LIMIT ").push(count);
+#[derive(ToSchema)]
+pub struct GetIncidentsResponse {
+    /// Only the requested `fields` of each incident are present when `fields` is set.
+    pub incidents: Vec<Incident>,
+    pub total_count: u64,
+    #[schema(ignore)]
+    pub fields: Option<Vec<IncidentField>>,
+}
 
-    if let Some(offset) = offset {
-        query_builder.push(" OFFSET ").push_bind(offset as i64);
+impl Serialize for GetIncidentsResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut response = serializer.serialize_struct("GetIncidentsResponse", 2)?;
+        match &self.fields {
+            Some(fields) => {
+                let incidents: Vec<SparseIncident> = self
+                    .incidents
+                    .iter()
+                    .map(|incident| SparseIncident { incident, fields })
+                    .collect();
+                response.serialize_field("incidents", &incidents)?;
+            }
+            None => response.serialize_field("incidents", &self.incidents)?,
+        }
+        response.serialize_field("total_count", &self.total_count)?;
+        response.end()
     }
+}
 
-    let incidents_query_result: Result<Vec<Incident>, Error> =
-        query_builder.build_query_as().fetch_all(state.pg_pool.deref()).await;
+/// Serializes only the selected fields of an incident.
+struct SparseIncident<'a> {
+    incident: &'a Incident,
+    fields: &'a [IncidentField],
+}
 
-    match incidents_query_result {
-        Ok(incidents) => {
-            let incidents_count = incidents.len() as u64;
-            Ok(Json(GetIncidentsResponse {
-                incidents,
-                total_count: incidents_count,
-            }))
-        }
-        Err(err) => {
-            error!("{}", err);
-            Err((StatusCode::INTERNAL_SERVER_ERROR, String::from("Internal Server Error")))
+impl Serialize for SparseIncident<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let incident = self.incident;
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for field in self.fields {
+            match field {
+                IncidentField::Id => map.serialize_entry(field, &incident.id)?,
+                IncidentField::ExternalId => map.serialize_entry(field, &incident.external_id)?,
+                IncidentField::County => map.serialize_entry(field, &incident.county)?,
+                IncidentField::Location => map.serialize_entry(field, &incident.location)?,
+                IncidentField::Day => map.serialize_entry(field, &incident.day)?,
+                IncidentField::StartTime => map.serialize_entry(field, &incident.start_time)?,
+                IncidentField::EndTime => map.serialize_entry(field, &incident.end_time)?,
+                IncidentField::Description => map.serialize_entry(field, &incident.description)?,
+                IncidentField::Revision => map.serialize_entry(field, &incident.revision)?,
+                IncidentField::UpdatedAt => map.serialize_entry(field, &incident.updated_at)?,
+                IncidentField::CancelledAt => map.serialize_entry(field, &incident.cancelled_at)?,
+                IncidentField::DuplicateOf => map.serialize_entry(field, &incident.duplicate_of)?,
+            }
         }
+        map.end()
     }
 }
 
 #[utoipa::path(
     get,
-    path = "/ping",
+    path = "/api/incidents/all",
+    params(
+        IncidentsFiltering,
+        FieldSelection
+    ),
+    responses(
+        (status=200, description = "All incidents.", body=GetIncidentsResponse),
+        (status=400, description = "Invalid day, unknown sort key, direction or field.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error getting all incidents.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_all_incidents(
+    state: State<AppState>,
+    filtering: Query<IncidentsFiltering>,
+    selection: Query<FieldSelection>,
+) -> Result<Json<GetIncidentsResponse>, ApiError> {
+    let fields = selection.parse().map_err(ApiError::InvalidParameter)?;
+
+    let mut query_builder = QueryBuilder::new("SELECT *");
+    push_incidents_selection(&mut query_builder, &filtering);
+
+    let incidents: Vec<Incident> = query_builder.build_query_as().fetch_all(state.pg_pool.deref()).await?;
+    let incidents_count = incidents.len() as u64;
+
+    Ok(Json(GetIncidentsResponse {
+        incidents,
+        total_count: incidents_count,
+        fields,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/incidents/{id}",
+    params(
+        ("id" = i64, Path, description = "Id of the incident or of one of its duplicates.")
+    ),
+    responses(
+        (status=200, description = "The canonical incident with its duplicates.", body=IncidentWithDuplicates),
+        (status=404, description = "No incident has this id.", body=ProblemDetails, content_type = "application/problem+json"),
+        (status=500, description = "Error getting the incident.", body=ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+pub async fn get_incident(
+    state: State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<IncidentWithDuplicates>, ApiError> {
+    let incident: Incident = sqlx::query_as(CANONICAL_INCIDENT_QUERY)
+        .bind(id)
+        .fetch_optional(state.pg_pool.deref())
+        .await?
+        .ok_or_else(|| ApiError::NotFound(String::from("Incident not found.")))?;
+
+    let duplicates: Vec<Incident> = sqlx::query_as(DUPLICATES_QUERY)
+        .bind(incident.id)
+        .fetch_all(state.pg_pool.deref())
+        .await?;
+
+    Ok(Json(IncidentWithDuplicates { incident, duplicates }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/ping",
     responses(
             (status=200, description = "Respond with a pong.", body=Ping),
-            (status=500, description = "Server is not ready to serve."),
+            (status=500, description = "Server is not ready to serve.", body=ProblemDetails, content_type = "application/problem+json"),
     )
 )]
-pub async fn ping(state: State<AppState>) -> Result<Json<Ping>, (StatusCode, String)> {
+pub async fn ping(state: State<AppState>) -> Result<Json<Ping>, ApiError> {
     let a = state.ping_msg.deref();
     let response = format!("Hello {}!", a);
     Ok(Json(Ping { ping: response }))
 }
+
+#[cfg(test)]
+mod web_api_tests {
+    use super::{
+        FieldSelection, GetIncidentsResponse, Incident, IncidentField, IncidentSort, IncidentsFiltering, SortDirection,
+        push_incidents_selection,
+    };
+    use crate::extract::Query;
+    use axum::extract::FromRequestParts;
+    use axum::http::{Request, StatusCode};
+    use chrono::{NaiveDate, Utc};
+    use sqlx::{Postgres, QueryBuilder};
+
+    fn selection(fields: &str) -> FieldSelection {
+        FieldSelection {
+            fields: Some(fields.to_string()),
+        }
+    }
+
+    #[test]
+    fn parse_fields() {
+        assert_eq!(
+            Ok(Some(vec![
+                IncidentField::Id,
+                IncidentField::StartTime,
+                IncidentField::CancelledAt
+            ])),
+            selection("id, start_time,cancelled_at,").parse()
+        );
+        assert_eq!(Ok(None), FieldSelection::default().parse());
+        assert_eq!(
+            Err("Unknown field `county_key`.".to_string()),
+            selection("id,county_key").parse()
+        );
+    }
+
+    #[test]
+    fn sparse_incidents() {
+        let response = GetIncidentsResponse {
+            incidents: vec![Incident {
+                external_id: "134691 - Retele Electrice".to_string(),
+                county: "TULCEA".to_string(),
+                location: "LOC. TURCOAIA".to_string(),
+                day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+                start_time: None,
+                end_time: None,
+                description: "Strada: Pacii".to_string(),
+                id: 7,
+                revision: 1,
+                updated_at: Utc::now(),
+                cancelled_at: None,
+                duplicate_of: None,
+            }],
+            total_count: 1,
+            fields: Some(vec![IncidentField::Id, IncidentField::Day, IncidentField::StartTime]),
+        };
+
+        assert_eq!(
+            r#"{"incidents":[{"id":7,"day":"2025-08-08","start_time":null}],"total_count":1}"#,
+            serde_json::to_string(&response).unwrap()
+        );
+    }
+
+    #[test]
+    fn order_by_requested_sort() {
+        let filtering = IncidentsFiltering {
+            sort: Some(IncidentSort::StartTime),
+            direction: Some(SortDirection::Asc),
+            ..Default::default()
+        };
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT *");
+        push_incidents_selection(&mut query_builder, &filtering);
+
+        assert_eq!(
+            "SELECT * FROM incidents WHERE duplicate_of IS NULL ORDER BY start_time ASC NULLS LAST, id ASC LIMIT 50",
+            query_builder.sql()
+        );
+    }
+
+    #[test]
+    fn order_by_day_descending_by_default() {
+        let filtering = IncidentsFiltering::default();
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT *");
+        push_incidents_selection(&mut query_builder, &filtering);
+
+        assert_eq!(
+            "SELECT * FROM incidents WHERE duplicate_of IS NULL ORDER BY day DESC NULLS LAST, id DESC LIMIT 50",
+            query_builder.sql()
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_days_are_bad_requests() {
+        let (mut parts, _) = Request::get("/api/incidents/all?day=2025-13-01")
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let Err(rejection) = Query::<IncidentsFiltering>::from_request_parts(&mut parts, &()).await else {
+            panic!("The day should be rejected.");
+        };
+
+        assert_eq!(StatusCode::BAD_REQUEST, rejection.status());
+        assert!(rejection.detail().contains("day"));
+    }
+}
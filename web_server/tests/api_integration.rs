@@ -1,10 +1,33 @@
 mod common;
 
 use crate::common::{FILTERING_COUNTY, FILTERING_DAY, TestInfrastructure, create_app_state};
-use axum::extract::{Query, State};
-use chrono::NaiveDate;
+use ::common::Record;
+use ::common::configuration::SubscriptionLimits;
+use axum::body::to_bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::IntoResponse;
+use chrono::{Days, NaiveDate, NaiveTime, Utc};
 use std::collections::HashSet;
-use web_server::web_api::{GetIncidentsResponse, Incident, IncidentsFiltering, RecordCount};
+use tokio_stream::StreamExt;
+use tower_service::Service;
+use web_server::analytics::OutageAnalyticsParams;
+use web_server::caching::data_version;
+use web_server::calendar::CalendarFiltering;
+use web_server::catalogue::LocalitiesFiltering;
+use web_server::error::{ErrorCode, ProblemDetails};
+use web_server::export::{ExportFormat, ExportParams};
+use web_server::extract::{Json, Path, Query};
+use web_server::lookup::AddressLookupParams;
+use web_server::notifications::subscriptions::{
+    self, ManagementToken, NotificationChannel, SubscriptionRequest, SubscriptionState,
+};
+use web_server::notifications::templates::Language;
+use web_server::scraper::deduplication::deduplicate_incidents;
+use web_server::scraper::persistence::new_store_record;
+use web_server::web_api::{
+    FieldSelection, GetIncidentsResponse, Incident, IncidentSort, IncidentsFiltering, RecordCount, SortDirection,
+};
 
 #[tokio::test]
 async fn test_api_count() {
@@ -27,7 +50,8 @@ async fn test_get_all_incidents() {
 
     let filtering = IncidentsFiltering { ..Default::default() };
 
-    let resp = web_server::web_api::get_all_incidents(State(state), Query(filtering)).await;
+    let resp =
+        web_server::web_api::get_all_incidents(State(state), Query(filtering), Query(FieldSelection::default())).await;
     assert!(resp.is_ok());
 
     let json: GetIncidentsResponse = resp.expect("Should be OK").0;
@@ -44,7 +68,8 @@ async fn test_get_all_incidents_filter_county() {
         ..Default::default()
     };
 
-    let resp = web_server::web_api::get_all_incidents(State(state), Query(filtering)).await;
+    let resp =
+        web_server::web_api::get_all_incidents(State(state), Query(filtering), Query(FieldSelection::default())).await;
     assert!(resp.is_ok());
 
     let json: GetIncidentsResponse = resp.expect("Should be OK").0;
@@ -57,11 +82,12 @@ async fn test_get_all_incidents_filter_day() {
     let state = create_app_state(&infra).await;
 
     let filtering = IncidentsFiltering {
-        day: Some(FILTERING_DAY.to_string()),
+        day: Some(FILTERING_DAY),
         ..Default::default()
     };
 
-    let resp = web_server::web_api::get_all_incidents(State(state), Query(filtering)).await;
+    let resp =
+        web_server::web_api::get_all_incidents(State(state), Query(filtering), Query(FieldSelection::default())).await;
     assert!(resp.is_ok());
 
     let json: GetIncidentsResponse = resp.expect("Should be OK").0;
@@ -82,12 +108,13 @@ async fn test_get_all_incidents_filter_day_and_county() {
     let state = create_app_state(&infra).await;
 
     let filtering = IncidentsFiltering {
-        day: Some(FILTERING_DAY.to_string()),
+        day: Some(FILTERING_DAY),
         county: Some(FILTERING_COUNTY.to_string()),
         ..Default::default()
     };
 
-    let resp = web_server::web_api::get_all_incidents(State(state), Query(filtering)).await;
+    let resp =
+        web_server::web_api::get_all_incidents(State(state), Query(filtering), Query(FieldSelection::default())).await;
     assert!(resp.is_ok());
 
     let json: GetIncidentsResponse = resp.expect("Should be OK").0;
@@ -119,7 +146,12 @@ async fn test_get_all_incidents_with_offset() {
     // Get all incidents first to determine their order
     let all_filtering = Default::default();
 
-    let all_resp = web_server::web_api::get_all_incidents(State(state.clone()), Query(all_filtering)).await;
+    let all_resp = web_server::web_api::get_all_incidents(
+        State(state.clone()),
+        Query(all_filtering),
+        Query(FieldSelection::default()),
+    )
+    .await;
     assert!(all_resp.is_ok());
 
     let all_incidents = all_resp.expect("Should be OK").0.incidents;
@@ -131,7 +163,9 @@ async fn test_get_all_incidents_with_offset() {
         ..Default::default()
     };
 
-    let offset_resp = web_server::web_api::get_all_incidents(State(state), Query(offset_filtering)).await;
+    let offset_resp =
+        web_server::web_api::get_all_incidents(State(state), Query(offset_filtering), Query(FieldSelection::default()))
+            .await;
     assert!(offset_resp.is_ok());
 
     let offset_incidents: GetIncidentsResponse = offset_resp.expect("Should be OK").0;
@@ -151,7 +185,12 @@ async fn test_get_all_incidents_with_count() {
     // Get all incidents first to determine their order
     let all_filtering = Default::default();
 
-    let all_resp = web_server::web_api::get_all_incidents(State(state.clone()), Query(all_filtering)).await;
+    let all_resp = web_server::web_api::get_all_incidents(
+        State(state.clone()),
+        Query(all_filtering),
+        Query(FieldSelection::default()),
+    )
+    .await;
     assert!(all_resp.is_ok());
 
     let all_incidents = all_resp.expect("Should be OK").0.incidents;
@@ -163,7 +202,9 @@ async fn test_get_all_incidents_with_count() {
         ..Default::default()
     };
 
-    let count_resp = web_server::web_api::get_all_incidents(State(state), Query(count_filtering)).await;
+    let count_resp =
+        web_server::web_api::get_all_incidents(State(state), Query(count_filtering), Query(FieldSelection::default()))
+            .await;
     assert!(count_resp.is_ok());
 
     let count_incidents: Vec<Incident> = count_resp.expect("Should be OK").0.incidents;
@@ -183,7 +224,12 @@ async fn test_get_all_incidents_with_offset_and_count() {
     // Get all incidents first to determine their order
     let all_filtering = Default::default();
 
-    let all_incidents = web_server::web_api::get_all_incidents(State(state.clone()), Query(all_filtering)).await;
+    let all_incidents = web_server::web_api::get_all_incidents(
+        State(state.clone()),
+        Query(all_filtering),
+        Query(FieldSelection::default()),
+    )
+    .await;
     assert!(all_incidents.is_ok());
 
     let all_incidents: Vec<Incident> = all_incidents.expect("Should be OK").0.incidents;
@@ -196,7 +242,8 @@ async fn test_get_all_incidents_with_offset_and_count() {
         ..Default::default()
     };
 
-    let resp = web_server::web_api::get_all_incidents(State(state), Query(filtering)).await;
+    let resp =
+        web_server::web_api::get_all_incidents(State(state), Query(filtering), Query(FieldSelection::default())).await;
     assert!(resp.is_ok());
 
     let incidents: Vec<Incident> = resp.expect("Should be OK").0.incidents;
@@ -207,3 +254,729 @@ async fn test_get_all_incidents_with_offset_and_count() {
         assert_eq!(format!("{:?}", incident), format!("{:?}", all_incidents[i + 1]));
     }
 }
+
+#[tokio::test]
+async fn test_get_incidents_geojson() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    for (id, location) in [("geo_id1", "LOC. ISACCEA"), ("geo_id2", "LOC. NECUNOSCUTA")] {
+        let record = Record {
+            id: id.to_string(),
+            title: "title".to_string(),
+            description: "description".to_string(),
+            date: FILTERING_DAY,
+            start_time: None,
+            end_time: None,
+            county: "TULCEA".to_string(),
+            location: location.to_string(),
+        };
+        new_store_record(&record, state.pg_pool.clone()).await.unwrap();
+    }
+
+    let filtering = IncidentsFiltering {
+        county: Some("TULCEA".to_string()),
+        ..Default::default()
+    };
+
+    let resp = web_server::geojson::get_incidents_geojson(State(state), Query(filtering)).await;
+    assert!(resp.is_ok());
+
+    let response = resp.expect("Should be OK").into_response();
+    assert_eq!("application/geo+json", response.headers()["content-type"]);
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!("FeatureCollection", json["type"]);
+
+    let features = json["features"].as_array().unwrap();
+    assert_eq!(2, features.len());
+
+    let precisions: HashSet<(&str, &str)> = features
+        .iter()
+        .map(|f| {
+            (
+                f["properties"]["external_id"].as_str().unwrap(),
+                f["properties"]["precision"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        HashSet::from([("geo_id1", "locality"), ("geo_id2", "county")]),
+        precisions
+    );
+}
+
+#[tokio::test]
+async fn test_export_incidents_csv() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let params = ExportParams {
+        format: ExportFormat::Csv,
+        county: Some(FILTERING_COUNTY.to_string()),
+        day: None,
+        include_duplicates: None,
+    };
+
+    let resp = web_server::export::export_incidents(State(state), Query(params)).await;
+    assert!(resp.is_ok());
+
+    let response = resp.expect("Should be OK").into_response();
+    assert_eq!("text/csv; charset=utf-8", response.headers()["content-type"]);
+
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let csv = String::from_utf8(body.to_vec()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+
+    // The header and the two incidents of the filtered county.
+    assert_eq!(3, lines.len());
+    assert_eq!(
+        "id,external_id,county,location,day,start_time,end_time,description,revision,updated_at,cancelled_at",
+        lines[0]
+    );
+    assert!(lines[1].contains(",test_id2,"));
+    assert!(lines[2].contains(",test_id,"));
+}
+
+#[tokio::test]
+async fn test_export_incidents_ndjson() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let params = ExportParams {
+        format: ExportFormat::Ndjson,
+        county: None,
+        day: Some(FILTERING_DAY),
+        include_duplicates: None,
+    };
+
+    let resp = web_server::export::export_incidents(State(state), Query(params)).await;
+    assert!(resp.is_ok());
+
+    let body = to_bytes(resp.expect("Should be OK").into_response().into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let external_ids: HashSet<String> = String::from_utf8(body.to_vec())
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .map(|incident| incident["external_id"].as_str().unwrap().to_string())
+        .collect();
+
+    assert_eq!(
+        HashSet::from(["test_id2".to_string(), "test_id4".to_string(), "test_id5".to_string()]),
+        external_ids
+    );
+}
+
+#[tokio::test]
+async fn test_get_calendar_filter_locality() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let upcoming = Record {
+        id: "test_calendar".to_string(),
+        title: String::new(),
+        description: "Strada: Pacii".to_string(),
+        date: Utc::now().date_naive() + Days::new(2),
+        start_time: NaiveTime::from_hms_opt(9, 0, 0),
+        end_time: NaiveTime::from_hms_opt(17, 0, 0),
+        county: "TULCEA".to_string(),
+        location: "LOC. ISACCEA".to_string(),
+    };
+    new_store_record(&upcoming, state.pg_pool.clone()).await.unwrap();
+
+    let filtering = CalendarFiltering {
+        county: Some("Tulcea".to_string()),
+        locality: Some("Isaccea".to_string()),
+    };
+
+    let resp = web_server::calendar::get_calendar(State(state), Query(filtering)).await;
+    assert!(resp.is_ok());
+
+    let body = to_bytes(resp.expect("Should be OK").into_response().into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let calendar = String::from_utf8(body.to_vec()).unwrap();
+
+    assert_eq!(1, calendar.matches("BEGIN:VEVENT").count());
+    assert!(calendar.contains("SUMMARY:Planned outage: LOC. ISACCEA (TULCEA)"));
+    assert!(calendar.contains("STATUS:CONFIRMED"));
+}
+
+#[tokio::test]
+async fn test_stream_incidents_replays_missed_events() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let filtering = IncidentsFiltering {
+        county: Some(FILTERING_COUNTY.to_string()),
+        ..Default::default()
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert("Last-Event-ID", HeaderValue::from_static("0"));
+
+    let resp = web_server::events::stream_incidents(State(state), Query(filtering), headers).await;
+    assert!(resp.is_ok());
+
+    let mut body = resp
+        .expect("Should be OK")
+        .into_response()
+        .into_body()
+        .into_data_stream();
+    let first_event = String::from_utf8(body.next().await.unwrap().unwrap().to_vec()).unwrap();
+
+    assert!(first_event.starts_with("id: "));
+    assert!(first_event.contains("event: created"));
+    assert!(first_event.contains(&format!("\"county\":\"{}\"", FILTERING_COUNTY)));
+    assert!(first_event.contains("\"revision\":1,"));
+}
+
+#[tokio::test]
+async fn test_stream_incidents_waits_for_the_events_committed_late() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+    let insert_event = "INSERT INTO incident_events(incident_id, kind, revision) \
+     SELECT id, 'updated', revision FROM incidents ORDER BY id LIMIT 1 RETURNING id";
+
+    let mut headers = HeaderMap::new();
+    let last_event_id: i64 = sqlx::query_scalar("SELECT MAX(id) FROM incident_events")
+        .fetch_one(state.pg_pool.as_ref())
+        .await
+        .unwrap();
+    headers.insert("Last-Event-ID", HeaderValue::from(last_event_id));
+    let mut transaction = state.pg_pool.begin().await.unwrap();
+    let late_event_id: i64 = sqlx::query_scalar(insert_event)
+        .fetch_one(&mut *transaction)
+        .await
+        .unwrap();
+    let event_id: i64 = sqlx::query_scalar(insert_event)
+        .fetch_one(state.pg_pool.as_ref())
+        .await
+        .unwrap();
+
+    let resp =
+        web_server::events::stream_incidents(State(state.clone()), Query(IncidentsFiltering::default()), headers)
+            .await
+            .expect("Should be OK");
+    let mut body = resp.into_response().into_body().into_data_stream();
+    let pending = tokio::time::timeout(std::time::Duration::from_millis(500), body.next()).await;
+    assert!(
+        pending.is_err(),
+        "No event is sent before the earlier transaction is finished."
+    );
+
+    transaction.commit().await.unwrap();
+    state.incident_notifications.send(()).unwrap();
+    let first_event = String::from_utf8(body.next().await.unwrap().unwrap().to_vec()).unwrap();
+    let second_event = String::from_utf8(body.next().await.unwrap().unwrap().to_vec()).unwrap();
+
+    assert!(first_event.starts_with(&format!("id: {}\n", late_event_id)));
+    assert!(second_event.starts_with(&format!("id: {}\n", event_id)));
+}
+
+#[tokio::test]
+async fn test_lookup_address_ranks_street_matches() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let upcoming_day = Utc::now().date_naive() + Days::new(3);
+    for (id, description) in [
+        ("test_lookup_street", "Strada: STR OBSERVATORULUI, REPUBLICII Numar: "),
+        ("test_lookup_locality", "Strada:  Numar: "),
+        ("test_lookup_other_street", "Strada: STR MEMORANDUMULUI Numar: "),
+    ] {
+        let record = Record {
+            id: id.to_string(),
+            title: String::new(),
+            description: description.to_string(),
+            date: upcoming_day,
+            start_time: None,
+            end_time: None,
+            county: "CLUJ".to_string(),
+            location: "CLUJ-NAPOCA".to_string(),
+        };
+        new_store_record(&record, state.pg_pool.clone()).await.unwrap();
+    }
+
+    let params = AddressLookupParams {
+        address: "Cluj-Napoca, Str. Observatorului 12".to_string(),
+        count: None,
+    };
+
+    let resp = web_server::lookup::lookup_address(State(state), Query(params)).await;
+    assert!(resp.is_ok());
+
+    let json = resp.expect("Should be OK").0;
+    assert_eq!("cluj", json.county);
+    assert_eq!(Some("cluj-napoca".to_string()), json.locality);
+    let external_ids: Vec<&str> = json.matches.iter().map(|m| m.incident.external_id.as_str()).collect();
+    assert_eq!(vec!["test_lookup_street", "test_lookup_locality"], external_ids);
+}
+
+#[tokio::test]
+async fn test_data_version_changes_when_incidents_change() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let before = data_version(&state.pg_pool).await.unwrap();
+    let unchanged = data_version(&state.pg_pool).await.unwrap();
+    assert_eq!(before.etag(), unchanged.etag());
+
+    let record = Record {
+        id: "test_data_version".to_string(),
+        title: String::new(),
+        description: "Strada: Pacii".to_string(),
+        date: Utc::now().date_naive() + Days::new(1),
+        start_time: None,
+        end_time: None,
+        county: FILTERING_COUNTY.to_string(),
+        location: "test_location".to_string(),
+    };
+    new_store_record(&record, state.pg_pool.clone()).await.unwrap();
+
+    let after = data_version(&state.pg_pool).await.unwrap();
+    assert_ne!(before.etag(), after.etag());
+    assert!(after.modified_at >= before.modified_at);
+}
+
+#[tokio::test]
+async fn test_data_version_changes_when_duplicates_are_linked() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let record = |id: &str| Record {
+        id: id.to_string(),
+        title: String::new(),
+        description: "Strada: Pacii nr. 1-10".to_string(),
+        date: Utc::now().date_naive() + Days::new(2),
+        start_time: NaiveTime::from_hms_opt(9, 0, 0),
+        end_time: NaiveTime::from_hms_opt(13, 0, 0),
+        county: FILTERING_COUNTY.to_string(),
+        location: "test_duplicate_location".to_string(),
+    };
+    let first = record("test_linked_first");
+    new_store_record(&first, state.pg_pool.clone()).await.unwrap();
+    new_store_record(&record("test_linked_second"), state.pg_pool.clone())
+        .await
+        .unwrap();
+
+    // The duplicates leave the responses without any change of the incidents themselves.
+    let before = data_version(&state.pg_pool).await.unwrap();
+    let linked = deduplicate_incidents(&[first.date], state.pg_pool.clone())
+        .await
+        .unwrap();
+    assert_eq!(1, linked);
+
+    let after = data_version(&state.pg_pool).await.unwrap();
+    assert_eq!(before.version, after.version);
+    assert_ne!(before.etag(), after.etag());
+}
+
+#[tokio::test]
+async fn test_get_all_incidents_sorted_with_fields() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let filtering = IncidentsFiltering {
+        sort: Some(IncidentSort::Day),
+        direction: Some(SortDirection::Asc),
+        ..Default::default()
+    };
+    let selection = FieldSelection {
+        fields: Some("external_id,day".to_string()),
+    };
+
+    let resp = web_server::web_api::get_all_incidents(State(state), Query(filtering), Query(selection)).await;
+    assert!(resp.is_ok());
+
+    let json = serde_json::to_value(resp.expect("Should be OK").0).unwrap();
+    let incidents = json["incidents"].as_array().unwrap();
+    assert_eq!(5, incidents.len());
+    assert!(
+        incidents
+            .iter()
+            .all(|incident| incident.as_object().unwrap().len() == 2)
+    );
+
+    let days: Vec<&str> = incidents
+        .iter()
+        .map(|incident| incident["day"].as_str().unwrap())
+        .collect();
+    let mut sorted_days = days.clone();
+    sorted_days.sort();
+    assert_eq!(sorted_days, days);
+    assert_eq!("2023-10-02", days[0]);
+}
+
+#[tokio::test]
+async fn test_get_all_incidents_unknown_field() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let selection = FieldSelection {
+        fields: Some("id,secret".to_string()),
+    };
+
+    let resp =
+        web_server::web_api::get_all_incidents(State(state), Query(IncidentsFiltering::default()), Query(selection))
+            .await;
+
+    let error = resp.err().unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, error.status());
+    assert_eq!(ErrorCode::InvalidParameter, error.code());
+
+    let response = error.into_response();
+    assert_eq!("application/problem+json", response.headers()["content-type"]);
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let problem: ProblemDetails = serde_json::from_slice(&body).unwrap();
+    assert_eq!(400, problem.status);
+    assert!(problem.detail.contains("secret"));
+}
+
+#[tokio::test]
+async fn test_counties_and_localities_catalogue() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let record = Record {
+        id: "test_catalogue".to_string(),
+        title: String::new(),
+        description: "Strada: Pacii".to_string(),
+        date: Utc::now().date_naive() + Days::new(1),
+        start_time: None,
+        end_time: None,
+        county: "TIMIS".to_string(),
+        location: "LOC. TIMISOARA".to_string(),
+    };
+    new_store_record(&record, state.pg_pool.clone()).await.unwrap();
+
+    let counties = web_server::catalogue::get_counties(State(state.clone())).await;
+    assert!(counties.is_ok());
+    let counties = counties.expect("Should be OK").0;
+    assert_eq!(42, counties.len());
+    let timis = counties.iter().find(|county| county.key == "timis").unwrap();
+    assert_eq!(1, timis.incident_count);
+    assert_eq!(1, timis.upcoming_count);
+
+    let filtering = LocalitiesFiltering {
+        county: Some("Timiș".to_string()),
+        prefix: Some("timiș".to_string()),
+        count: None,
+    };
+    let localities = web_server::catalogue::get_localities(State(state), Query(filtering)).await;
+    assert!(localities.is_ok());
+    let localities = localities.expect("Should be OK").0;
+    assert_eq!("timisoara", localities[0].key);
+    assert_eq!(1, localities[0].incident_count);
+}
+
+#[tokio::test]
+async fn test_outage_analytics_per_locality() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    for (id, day, location) in [
+        ("test_analytics1", 1, "LOC. ISACCEA"),
+        ("test_analytics2", 2, "LOC. ISACCEA"),
+        ("test_analytics3", 9, "LOC. ISACCEA"),
+        ("test_analytics4", 3, "LOC. MACIN"),
+    ] {
+        let record = Record {
+            id: id.to_string(),
+            title: String::new(),
+            description: "Strada: Pacii".to_string(),
+            date: NaiveDate::from_ymd_opt(2025, 3, day).unwrap(),
+            start_time: NaiveTime::from_hms_opt(9, 0, 0),
+            end_time: NaiveTime::from_hms_opt(12, 30, 0),
+            county: "TULCEA".to_string(),
+            location: location.to_string(),
+        };
+        new_store_record(&record, state.pg_pool.clone()).await.unwrap();
+    }
+
+    let params = OutageAnalyticsParams {
+        county: Some("Tulcea".to_string()),
+        from: NaiveDate::from_ymd_opt(2025, 1, 1),
+        to: NaiveDate::from_ymd_opt(2025, 12, 31),
+        ..Default::default()
+    };
+    let resp = web_server::analytics::get_outage_analytics(State(state.clone()), Query(params)).await;
+    assert!(resp.is_ok());
+
+    let statistics = resp.expect("Should be OK").0.statistics;
+    assert_eq!(2, statistics.len());
+    assert_eq!(Some("isaccea".to_string()), statistics[0].locality_key);
+    assert_eq!(3, statistics[0].outage_count);
+    assert_eq!(630, statistics[0].total_duration_minutes);
+    assert_eq!(Some(210.0), statistics[0].average_duration_minutes);
+    assert_eq!(2, statistics[0].longest_streak_days);
+    assert_eq!(Some(4.0), statistics[0].median_interval_days);
+
+    let params = OutageAnalyticsParams {
+        from: NaiveDate::from_ymd_opt(2025, 5, 1),
+        to: NaiveDate::from_ymd_opt(2025, 1, 1),
+        ..Default::default()
+    };
+    let resp = web_server::analytics::get_outage_analytics(State(state.clone()), Query(params)).await;
+    assert_eq!(StatusCode::BAD_REQUEST, resp.err().unwrap().status());
+
+    let params = OutageAnalyticsParams {
+        count: Some(1000),
+        ..Default::default()
+    };
+    let resp = web_server::analytics::get_outage_analytics(State(state), Query(params)).await;
+    assert_eq!(StatusCode::BAD_REQUEST, resp.err().unwrap().status());
+}
+
+#[tokio::test]
+async fn test_republished_incidents_are_linked_to_the_canonical_one() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let day = Utc::now().date_naive() + Days::new(3);
+    for (id, description) in [
+        ("test_dedup1", "Strada: Pacii nr. 1-10"),
+        ("test_dedup2", "Strada: PACII NR 1-10"),
+    ] {
+        let record = Record {
+            id: id.to_string(),
+            title: String::new(),
+            description: description.to_string(),
+            date: day,
+            start_time: NaiveTime::from_hms_opt(9, 0, 0),
+            end_time: NaiveTime::from_hms_opt(13, 0, 0),
+            county: "TULCEA".to_string(),
+            location: "LOC. ISACCEA".to_string(),
+        };
+        new_store_record(&record, state.pg_pool.clone()).await.unwrap();
+    }
+    let count_before = web_server::web_api::count_incidents(State(state.clone())).await;
+
+    assert_eq!(Ok(1), deduplicate_incidents(&[day], state.pg_pool.clone()).await);
+    assert_eq!(Ok(0), deduplicate_incidents(&[day], state.pg_pool.clone()).await);
+
+    let count_after = web_server::web_api::count_incidents(State(state.clone())).await;
+    assert_eq!(
+        count_before.expect("Should be OK").0.total_count - 1,
+        count_after.expect("Should be OK").0.total_count
+    );
+
+    let filtering = IncidentsFiltering {
+        county: Some("TULCEA".to_string()),
+        ..Default::default()
+    };
+    let selection = Query(FieldSelection::default());
+    let resp = web_server::web_api::get_all_incidents(State(state.clone()), Query(filtering), selection).await;
+    let incidents = resp.expect("Should be OK").0.incidents;
+    assert_eq!(1, incidents.len());
+    assert_eq!("test_dedup1", incidents[0].external_id);
+
+    let duplicate_id: i64 = sqlx::query_scalar("SELECT id FROM incidents WHERE external_id = 'test_dedup2'")
+        .fetch_one(state.pg_pool.as_ref())
+        .await
+        .unwrap();
+    let resp = web_server::web_api::get_incident(State(state), Path(duplicate_id)).await;
+    let canonical = resp.expect("Should be OK").0;
+    assert_eq!("test_dedup1", canonical.incident.external_id);
+    assert_eq!(1, canonical.duplicates.len());
+    assert_eq!(Some(canonical.incident.id), canonical.duplicates[0].duplicate_of);
+}
+
+#[tokio::test]
+async fn test_subscriptions_crud() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let request = SubscriptionRequest {
+        county: "Timiș".to_string(),
+        locality: Some("Timișoara".to_string()),
+        street_pattern: Some("Pacii".to_string()),
+        window_start: NaiveTime::from_hms_opt(8, 0, 0),
+        window_end: NaiveTime::from_hms_opt(18, 0, 0),
+        channel: NotificationChannel::Sms,
+        contact: "+40 722 123 456".to_string(),
+        state: None,
+        reminders: vec![],
+        digest: None,
+        digest_time: None,
+        language: Language::Ro,
+    };
+    let (status, Json(saved)) = subscriptions::create_subscription(State(state.clone()), Json(request.clone()))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::CREATED, status);
+    assert_eq!(None, saved.signing_secret);
+    let access = || {
+        Query(ManagementToken {
+            token: saved.management_token.clone(),
+        })
+    };
+    let created = saved.subscription.clone();
+    assert_eq!("timis", created.county_key);
+    assert_eq!(Some("timisoara".to_string()), created.locality_key);
+    assert_eq!("+40722123456", created.contact);
+    assert_eq!(SubscriptionState::Active, created.state);
+    assert_eq!(Language::Ro, created.language);
+
+    let unknown_county = SubscriptionRequest {
+        county: "Atlantis".to_string(),
+        ..request.clone()
+    };
+    let resp = subscriptions::create_subscription(State(state.clone()), Json(unknown_county)).await;
+    assert_eq!(StatusCode::BAD_REQUEST, resp.err().unwrap().status());
+
+    let internal_webhook = SubscriptionRequest {
+        channel: NotificationChannel::Webhook,
+        contact: "http://localhost:8080/hooks".to_string(),
+        ..request.clone()
+    };
+    let public_only = web_server::AppState {
+        allow_private_targets: false,
+        ..state.clone()
+    };
+    let resp = subscriptions::create_subscription(State(public_only), Json(internal_webhook)).await;
+    assert_eq!(StatusCode::BAD_REQUEST, resp.err().unwrap().status());
+
+    let paused = SubscriptionRequest {
+        state: Some(SubscriptionState::Paused),
+        ..request
+    };
+    let others = Query(ManagementToken {
+        token: state.links.management_token(created.id + 1),
+    });
+    let resp =
+        subscriptions::update_subscription(State(state.clone()), Path(created.id), others, Json(paused.clone())).await;
+    assert_eq!(StatusCode::FORBIDDEN, resp.err().unwrap().status());
+    let updated = subscriptions::update_subscription(State(state.clone()), Path(created.id), access(), Json(paused))
+        .await
+        .unwrap();
+    assert_eq!(SubscriptionState::Paused, updated.subscription.state);
+    assert_eq!(saved.management_token, updated.management_token);
+
+    let status = subscriptions::delete_subscription(State(state.clone()), Path(created.id), access())
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NO_CONTENT, status);
+    let resp = subscriptions::get_subscription(State(state.clone()), Path(created.id), access()).await;
+    assert_eq!(StatusCode::NOT_FOUND, resp.err().unwrap().status());
+}
+
+#[tokio::test]
+async fn test_contacts_waiting_for_their_verification_are_not_flooded() {
+    let infra = TestInfrastructure::new().await;
+    let state = web_server::AppState {
+        subscription_limits: SubscriptionLimits {
+            max_pending_per_contact: 2,
+            ..SubscriptionLimits::default()
+        },
+        ..create_app_state(&infra).await
+    };
+    let request = |contact: &str| SubscriptionRequest {
+        county: "Timiș".to_string(),
+        locality: None,
+        street_pattern: None,
+        window_start: None,
+        window_end: None,
+        channel: NotificationChannel::Sms,
+        contact: contact.to_string(),
+        state: None,
+        reminders: vec![],
+        digest: None,
+        digest_time: None,
+        language: Language::Ro,
+    };
+
+    for _ in 0..2 {
+        let created = subscriptions::create_subscription(State(state.clone()), Json(request("+40722123456"))).await;
+        assert!(created.is_ok());
+    }
+    let refused = subscriptions::create_subscription(State(state.clone()), Json(request("+40 722 123 456"))).await;
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, refused.err().unwrap().status());
+    let (_, Json(other)) = subscriptions::create_subscription(State(state.clone()), Json(request("+40722654321")))
+        .await
+        .unwrap();
+    let access = || {
+        Query(ManagementToken {
+            token: other.management_token.clone(),
+        })
+    };
+
+    // Replacing a subscription to send its codes elsewhere is limited the same way.
+    let id = other.subscription.id;
+    let moved =
+        subscriptions::update_subscription(State(state.clone()), Path(id), access(), Json(request("+40722123456")))
+            .await;
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, moved.err().unwrap().status());
+
+    // Replacing it with the same contact keeps its code and its wrong codes.
+    let verification = "SELECT verification_code, verification_failures FROM subscriptions WHERE id = $1";
+    sqlx::query("UPDATE subscriptions SET verification_failures = 4 WHERE id = $1")
+        .bind(id)
+        .execute(state.pg_pool.as_ref())
+        .await
+        .unwrap();
+    let before: (String, i32) = sqlx::query_as(verification)
+        .bind(id)
+        .fetch_one(state.pg_pool.as_ref())
+        .await
+        .unwrap();
+    subscriptions::update_subscription(State(state.clone()), Path(id), access(), Json(request("+40722654321")))
+        .await
+        .unwrap();
+    let after: (String, i32) = sqlx::query_as(verification)
+        .bind(id)
+        .fetch_one(state.pg_pool.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(before, after);
+
+    // The verified subscriptions no longer count.
+    sqlx::query("UPDATE subscriptions SET verified_at = now()")
+        .execute(state.pg_pool.as_ref())
+        .await
+        .unwrap();
+    let created = subscriptions::create_subscription(State(state.clone()), Json(request("+40722123456"))).await;
+    assert!(created.is_ok());
+}
+
+#[tokio::test]
+async fn test_conditional_requests_are_answered_before_the_handlers() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+    let mut app = web_server::routes::create_app(state);
+    let get = |uri: &str, etag: Option<&HeaderValue>| {
+        let mut request = axum::http::Request::get(uri);
+        if let Some(etag) = etag {
+            request = request.header(axum::http::header::IF_NONE_MATCH, etag);
+        }
+        request.body(axum::body::Body::empty()).unwrap()
+    };
+
+    let response = app.call(get("/api/counties", None)).await.unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    let etag = response.headers().get(axum::http::header::ETAG).unwrap().clone();
+
+    let response = app.call(get("/api/counties", Some(&etag))).await.unwrap();
+    assert_eq!(StatusCode::NOT_MODIFIED, response.status());
+    let response = app
+        .call(get("/api/analytics/outages?count=10", Some(&etag)))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_MODIFIED, response.status());
+
+    // The invalid requests get their error, whatever their validators.
+    let response = app
+        .call(get("/api/analytics/outages?from=2025-02-01&to=2025-01-01", Some(&etag)))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = app
+        .call(get("/api/incidents/all?fields=colour", Some(&etag)))
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+    let response = app.call(get("/api/incidents/abc", Some(&etag))).await.unwrap();
+    assert_eq!(StatusCode::BAD_REQUEST, response.status());
+}
@@ -1,21 +1,41 @@
-use chrono::NaiveDate;
+#![allow(dead_code)]
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode, Uri};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{NaiveDate, NaiveTime};
 use common::Record;
-use log::{error, info, LevelFilter};
+use common::configuration::SubscriptionLimits;
+use log::{LevelFilter, error, info};
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
 use simple_logger::SimpleLogger;
-use sqlx::postgres::PgPoolOptions;
 use sqlx::Pool;
 use sqlx::Postgres;
+use sqlx::postgres::PgPoolOptions;
 use std::env;
+use std::fs;
 use std::ops::Deref;
 use std::process::{Command, Stdio};
-use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
-use testcontainers::runners::AsyncRunner;
 use testcontainers::ContainerAsync;
+use testcontainers::runners::AsyncRunner;
 use testcontainers_modules::postgres;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
 use tokio::time::sleep;
-use web_server::scraper::persistence::new_store_record;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::pki_types::PrivateKeyDer;
 use web_server::AppState;
+use web_server::events::incident_notifications;
+use web_server::extract::{Json, Path, Query};
+use web_server::notifications::consent::{self, Links, VerificationCode};
+use web_server::notifications::subscriptions::{self, SavedSubscription, SubscriptionRequest};
+use web_server::scraper::persistence::new_store_record;
 
 pub const FILTERING_COUNTY: &str = "test_judet";
 pub const FILTERING_DAY: NaiveDate = NaiveDate::from_ymd_opt(2023, 12, 1).unwrap();
@@ -48,6 +68,225 @@ impl TestInfrastructure {
     }
 }
 
+/// A request received by a [`MockHttpServer`].
+#[derive(Debug, Clone)]
+pub struct CapturedRequest {
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+#[derive(Clone, Default)]
+struct MockHttpState {
+    requests: Arc<Mutex<Vec<CapturedRequest>>>,
+    status: Arc<AtomicU16>,
+}
+
+/// Stands in for the gateways the notifications are sent to: answers every request on a free local port with the
+/// configured status and keeps the requests for the assertions.
+pub struct MockHttpServer {
+    pub url: String,
+    state: MockHttpState,
+}
+
+impl MockHttpServer {
+    pub async fn start() -> MockHttpServer {
+        let state = MockHttpState::default();
+        state.status.store(StatusCode::OK.as_u16(), Ordering::SeqCst);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().fallback(capture_request).with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        MockHttpServer { url, state }
+    }
+
+    pub fn respond_with(&self, status: StatusCode) {
+        self.state.status.store(status.as_u16(), Ordering::SeqCst);
+    }
+
+    pub fn requests(&self) -> Vec<CapturedRequest> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+async fn capture_request(State(state): State<MockHttpState>, uri: Uri, headers: HeaderMap, body: String) -> StatusCode {
+    state.requests.lock().unwrap().push(CapturedRequest {
+        path: uri.path().to_string(),
+        query: uri.query().map(|query| query.to_string()),
+        headers,
+        body,
+    });
+    StatusCode::from_u16(state.status.load(Ordering::SeqCst)).unwrap()
+}
+
+/// A message received by a [`SmtpCaptureServer`].
+#[derive(Debug, Clone, Default)]
+pub struct CapturedMail {
+    pub from: String,
+    pub recipients: Vec<String>,
+    /// The decoded `AUTH PLAIN` credentials, user and password.
+    pub credentials: Option<(String, String)>,
+    /// The session was upgraded with STARTTLS before the message was sent.
+    pub secure: bool,
+    pub data: String,
+}
+
+/// A local SMTP relay offering STARTTLS and `AUTH PLAIN`, it accepts every message and keeps it for the assertions.
+pub struct SmtpCaptureServer {
+    pub port: u16,
+    /// A PEM file with the authority of the certificate presented after STARTTLS, generated for this relay.
+    pub ca_file: String,
+    mails: Arc<Mutex<Vec<CapturedMail>>>,
+}
+
+impl SmtpCaptureServer {
+    pub async fn start() -> SmtpCaptureServer {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, "Test SMTP authority");
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let key = KeyPair::generate().unwrap();
+        let certificate = CertificateParams::new(vec![String::from("localhost"), String::from("127.0.0.1")])
+            .unwrap()
+            .signed_by(&key, &ca, &ca_key)
+            .unwrap();
+        let tls_config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![certificate.der().clone()],
+                PrivateKeyDer::Pkcs8(key.serialize_der().into()),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(tls_config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let ca_file = env::temp_dir().join(format!("smtp-capture-{}.pem", port));
+        fs::write(&ca_file, ca.pem()).unwrap();
+        let ca_file = ca_file.to_string_lossy().into_owned();
+        let mails = Arc::new(Mutex::new(Vec::new()));
+
+        let captured = mails.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                let captured = captured.clone();
+                tokio::spawn(async move {
+                    let mut connection = BufReader::new(stream);
+                    connection
+                        .get_mut()
+                        .write_all(b"220 localhost ESMTP\r\n")
+                        .await
+                        .unwrap();
+                    let mut mail = CapturedMail::default();
+                    if !serve_smtp(&mut connection, true, &mut mail, &captured).await {
+                        return;
+                    }
+                    // Clients which do not trust the certificate give up during the handshake.
+                    if let Ok(stream) = acceptor.accept(connection.into_inner()).await {
+                        let mut mail = CapturedMail {
+                            secure: true,
+                            ..CapturedMail::default()
+                        };
+                        serve_smtp(&mut BufReader::new(stream), false, &mut mail, &captured).await;
+                    }
+                });
+            }
+        });
+
+        SmtpCaptureServer { port, ca_file, mails }
+    }
+
+    pub fn mails(&self) -> Vec<CapturedMail> {
+        self.mails.lock().unwrap().clone()
+    }
+}
+
+/// Answers the commands of a client until it quits, returns true when it asked for STARTTLS.
+async fn serve_smtp<S: AsyncRead + AsyncWrite + Unpin>(
+    connection: &mut BufReader<S>,
+    offer_starttls: bool,
+    mail: &mut CapturedMail,
+    captured: &Mutex<Vec<CapturedMail>>,
+) -> bool {
+    loop {
+        let mut line = String::new();
+        if connection.read_line(&mut line).await.unwrap_or(0) == 0 {
+            return false;
+        }
+        let command = line.trim_end();
+        let verb = command.split(' ').next().unwrap_or_default().to_uppercase();
+
+        let reply = match verb.as_str() {
+            "EHLO" if offer_starttls => "250-localhost\r\n250-STARTTLS\r\n250 AUTH PLAIN\r\n".to_string(),
+            "EHLO" => "250-localhost\r\n250 AUTH PLAIN\r\n".to_string(),
+            "STARTTLS" => {
+                connection
+                    .get_mut()
+                    .write_all(b"220 Ready to start TLS\r\n")
+                    .await
+                    .unwrap();
+                return true;
+            }
+            "AUTH" => {
+                let token = command.rsplit(' ').next().unwrap_or_default();
+                let decoded = String::from_utf8(STANDARD.decode(token).unwrap()).unwrap();
+                let mut parts = decoded.split('\0').skip(1);
+                mail.credentials = Some((
+                    parts.next().unwrap_or_default().to_string(),
+                    parts.next().unwrap_or_default().to_string(),
+                ));
+                "235 Authenticated\r\n".to_string()
+            }
+            "MAIL" => {
+                mail.from = address_of(command);
+                "250 OK\r\n".to_string()
+            }
+            "RCPT" => {
+                mail.recipients.push(address_of(command));
+                "250 OK\r\n".to_string()
+            }
+            "DATA" => {
+                connection
+                    .get_mut()
+                    .write_all(b"354 End data with .\r\n")
+                    .await
+                    .unwrap();
+                let mut data = String::new();
+                loop {
+                    let mut line = String::new();
+                    connection.read_line(&mut line).await.unwrap();
+                    if line == ".\r\n" {
+                        break;
+                    }
+                    data.push_str(line.strip_prefix('.').unwrap_or(&line));
+                }
+                mail.data = data;
+                captured.lock().unwrap().push(mail.clone());
+                "250 Queued\r\n".to_string()
+            }
+            "QUIT" => {
+                connection.get_mut().write_all(b"221 Bye\r\n").await.unwrap();
+                return false;
+            }
+            _ => "502 Command not implemented\r\n".to_string(),
+        };
+        connection.get_mut().write_all(reply.as_bytes()).await.unwrap();
+    }
+}
+
+fn address_of(command: &str) -> String {
+    let start = command.find('<').map(|start| start + 1).unwrap_or_default();
+    let end = command.rfind('>').unwrap_or(command.len());
+    command[start..end].to_string()
+}
+
 static LOG_SETUP_ONCE: OnceLock<bool> = OnceLock::new();
 static GENERATE_DB_DDL_ONCE: OnceLock<String> = OnceLock::new();
 
@@ -56,7 +295,7 @@ pub async fn create_app_state(infra: &TestInfrastructure) -> AppState {
 
     GENERATE_DB_DDL_ONCE.get_or_init(|| match generate_ddl() {
         Ok(value) => value,
-        Err(err) => return err,
+        Err(err) => err,
     });
 
     let pg_pool = setup_postgres(infra, GENERATE_DB_DDL_ONCE.get().unwrap()).await;
@@ -65,10 +304,40 @@ pub async fn create_app_state(infra: &TestInfrastructure) -> AppState {
         ping_msg: "The state of ping.".to_string(),
         categories: vec![],
         metrics: Default::default(),
+        incident_notifications: incident_notifications(),
+        openapi_servers: vec![],
+        max_data_age: None,
+        links: Links::new("http://localhost:8080", "secret"),
+        // The mock receivers listen on the loopback.
+        allow_private_targets: true,
+        subscription_limits: SubscriptionLimits::default(),
         pg_pool: pg_pool.clone(),
     }
 }
 
+/// Creates the subscription and verifies its contact with the code sent to it.
+pub async fn create_verified_subscription(state: &AppState, request: SubscriptionRequest) -> SavedSubscription {
+    let (_, Json(saved)) = subscriptions::create_subscription(State(state.clone()), Json(request))
+        .await
+        .unwrap();
+    let code: String = sqlx::query_scalar("SELECT verification_code FROM subscriptions WHERE id = $1")
+        .bind(saved.subscription.id)
+        .fetch_one(state.pg_pool.deref())
+        .await
+        .unwrap();
+    let Json(verified) = consent::verify_subscription(
+        State(state.clone()),
+        Path(saved.subscription.id),
+        Query(VerificationCode { code }),
+    )
+    .await
+    .unwrap();
+    SavedSubscription {
+        subscription: verified,
+        ..saved
+    }
+}
+
 pub fn setup_logging() {
     LOG_SETUP_ONCE.get_or_init(|| {
         let re = SimpleLogger::new().env().with_level(LevelFilter::Info).init();
@@ -142,6 +411,8 @@ fn get_records() -> Vec<Record> {
             title: "test_title".to_string(),
             description: "test_description".to_string(),
             date: chrono::NaiveDate::from_ymd_opt(2023, 10, 2).unwrap(),
+            start_time: None,
+            end_time: None,
             county: FILTERING_COUNTY.to_string(),
             location: "test_localitate".to_string(),
         },
@@ -150,6 +421,8 @@ fn get_records() -> Vec<Record> {
             title: "test_title2".to_string(),
             description: "test_description2".to_string(),
             date: FILTERING_DAY,
+            start_time: NaiveTime::from_hms_opt(8, 0, 0),
+            end_time: NaiveTime::from_hms_opt(16, 0, 0),
             county: FILTERING_COUNTY.to_string(),
             location: "test_localitate".to_string(),
         },
@@ -158,6 +431,8 @@ fn get_records() -> Vec<Record> {
             title: "test_title3".to_string(),
             description: "test_description3".to_string(),
             date: chrono::NaiveDate::from_ymd_opt(2023, 12, 3).unwrap(),
+            start_time: None,
+            end_time: None,
             county: "test_judet2".to_string(),
             location: "test_localitate2".to_string(),
         },
@@ -166,6 +441,8 @@ fn get_records() -> Vec<Record> {
             title: "test_title3".to_string(),
             description: "test_description3".to_string(),
             date: FILTERING_DAY,
+            start_time: None,
+            end_time: None,
             county: "test_judet2".to_string(),
             location: "test_localitate2".to_string(),
         },
@@ -174,6 +451,8 @@ fn get_records() -> Vec<Record> {
             title: "test_title3".to_string(),
             description: "test_description3".to_string(),
             date: FILTERING_DAY,
+            start_time: None,
+            end_time: None,
             county: "test_judet2".to_string(),
             location: "test_localitate2".to_string(),
         },
@@ -0,0 +1,93 @@
+#![cfg(feature = "graphql")]
+
+mod common;
+
+use crate::common::{FILTERING_COUNTY, TestInfrastructure, create_app_state};
+use ::common::Record;
+use async_graphql::Request;
+use axum::extract::State;
+use chrono::{Days, Utc};
+use serde_json::{Value, json};
+use web_server::AppState;
+use web_server::extract::Json;
+use web_server::graphql::execute_graphql;
+use web_server::scraper::persistence::new_store_record;
+
+async fn query(state: &AppState, query: &str) -> Value {
+    let response = execute_graphql(State(state.clone()), Json(Request::new(query))).await.0;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    response.data.into_json().unwrap()
+}
+
+#[tokio::test]
+async fn test_graphql_incidents_cursor_pagination() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let first_page = query(
+        &state,
+        &format!(
+            r#"{{ incidents(filter: {{ county: "{}" }}, first: 1) {{
+                edges {{ cursor node {{ id }} }} pageInfo {{ hasNextPage endCursor }} }} }}"#,
+            FILTERING_COUNTY
+        ),
+    )
+    .await;
+    let page_info = &first_page["incidents"]["pageInfo"];
+    assert_eq!(json!(true), page_info["hasNextPage"]);
+
+    let second_page = query(
+        &state,
+        &format!(
+            r#"{{ incidents(filter: {{ county: "{}" }}, first: 1, after: {}) {{
+                edges {{ node {{ id }} }} pageInfo {{ hasPreviousPage }} }} }}"#,
+            FILTERING_COUNTY, page_info["endCursor"]
+        ),
+    )
+    .await;
+    assert_eq!(json!(true), second_page["incidents"]["pageInfo"]["hasPreviousPage"]);
+    assert_ne!(
+        first_page["incidents"]["edges"][0]["node"]["id"],
+        second_page["incidents"]["edges"][0]["node"]["id"]
+    );
+}
+
+#[tokio::test]
+async fn test_graphql_counties_with_upcoming_incidents_and_history() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+
+    let mut upcoming = Record {
+        id: "test_graphql".to_string(),
+        title: String::new(),
+        description: "Strada: Pacii".to_string(),
+        date: Utc::now().date_naive() + Days::new(2),
+        start_time: None,
+        end_time: None,
+        county: "TULCEA".to_string(),
+        location: "LOC. ISACCEA".to_string(),
+    };
+    new_store_record(&upcoming, state.pg_pool.clone()).await.unwrap();
+    upcoming.description = "Strada: Pacii nr. 1-10".to_string();
+    new_store_record(&upcoming, state.pg_pool.clone()).await.unwrap();
+
+    let data = query(
+        &state,
+        "{ counties { key upcomingIncidents { edges { node { externalId history { kind revision } } } } } }",
+    )
+    .await;
+    let tulcea = data["counties"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|county| county["key"] == "tulcea")
+        .unwrap();
+
+    assert_eq!(
+        json!([{ "node": { "externalId": "test_graphql", "history": [
+            { "kind": "created", "revision": 1 },
+            { "kind": "updated", "revision": 2 }
+        ] } }]),
+        tulcea["upcomingIncidents"]["edges"]
+    );
+}
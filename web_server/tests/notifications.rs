@@ -0,0 +1,846 @@
+use crate::common::{MockHttpServer, TestInfrastructure, create_app_state, create_verified_subscription};
+use ::common::Record;
+use ::common::configuration::{OutboxConfiguration, TemplatesConfiguration, WebhookConfiguration};
+use async_trait::async_trait;
+use axum::extract::State;
+use axum::http::StatusCode;
+use chrono::{DateTime, Days, NaiveTime, TimeDelta, Utc};
+use sqlx::{Pool, Postgres};
+use std::ops::Deref;
+use std::sync::Arc;
+use web_server::extract::{Json, Path, Query};
+use web_server::notifications::consent::{self, UnsubscribeToken, Verification, VerificationCode};
+use web_server::notifications::digest::{Digest, DigestSchedule};
+use web_server::notifications::dispatcher::Dispatcher;
+use web_server::notifications::local_time;
+use web_server::notifications::notifier::{DeliveryError, Notification, Notifier};
+use web_server::notifications::push::NtfyNotifier;
+use web_server::notifications::quiet_hours::{self, ContactFiltering, QuietHoursRequest};
+use web_server::notifications::reminders::Reminder;
+use web_server::notifications::rules::{NotificationReason, evaluate_incident_changes, latest_event_id};
+use web_server::notifications::subscriptions::{
+    self, ManagementToken, NotificationChannel, SubscriptionRequest, SubscriptionState,
+};
+use web_server::notifications::templates::{Language, Templates};
+use web_server::notifications::webhook::{SIGNATURE_HEADER, TIMESTAMP_HEADER, WebhookNotifier, signature};
+use web_server::scraper::persistence::{cancel_withdrawn_incidents, new_store_record};
+use web_server::web_api::Incident;
+
+mod common;
+
+#[tokio::test]
+async fn test_incident_changes_create_notification_jobs_once_per_revision() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+    let today = Utc::now().date_naive();
+
+    let request = SubscriptionRequest {
+        county: "Tulcea".to_string(),
+        locality: Some("Isaccea".to_string()),
+        street_pattern: Some("Păcii".to_string()),
+        window_start: None,
+        window_end: None,
+        channel: NotificationChannel::Email,
+        contact: "ana@example.ro".to_string(),
+        state: None,
+        reminders: vec![],
+        digest: None,
+        digest_time: None,
+        language: Language::En,
+    };
+    let saved = create_verified_subscription(&state, request).await;
+
+    let record = |id: &str, location: &str| Record {
+        id: id.to_string(),
+        title: String::new(),
+        description: "Strada: Pacii nr. 1-10".to_string(),
+        date: today + Days::new(10),
+        start_time: NaiveTime::from_hms_opt(9, 0, 0),
+        end_time: NaiveTime::from_hms_opt(13, 0, 0),
+        county: "TULCEA".to_string(),
+        location: location.to_string(),
+    };
+    let watched = record("watched", "LOC. ISACCEA");
+    let elsewhere = record("elsewhere", "LOC. MACIN");
+
+    let after = latest_event_id(state.pg_pool.clone()).await.unwrap();
+    for incident in [&watched, &elsewhere] {
+        new_store_record(incident, state.pg_pool.clone()).await.unwrap();
+    }
+    let created_jobs = evaluate_incident_changes(after, today, state.pg_pool.clone()).await;
+    assert_eq!(Ok(1), created_jobs);
+    let created_jobs = evaluate_incident_changes(after, today, state.pg_pool.clone()).await;
+    assert_eq!(Ok(0), created_jobs);
+
+    let rescheduled = Record {
+        start_time: NaiveTime::from_hms_opt(14, 0, 0),
+        end_time: NaiveTime::from_hms_opt(18, 0, 0),
+        ..watched
+    };
+    new_store_record(&rescheduled, state.pg_pool.clone()).await.unwrap();
+    // The feed no longer announces the watched incident.
+    cancel_withdrawn_incidents(&[elsewhere], today, state.pg_pool.clone())
+        .await
+        .unwrap();
+    let created_jobs = evaluate_incident_changes(after, today, state.pg_pool.clone()).await;
+    assert_eq!(Ok(2), created_jobs);
+
+    let jobs: Vec<(i32, String)> =
+        sqlx::query_as("SELECT revision, reason FROM notification_jobs WHERE subscription_id = $1 ORDER BY revision")
+            .bind(saved.subscription.id)
+            .fetch_all(state.pg_pool.deref())
+            .await
+            .unwrap();
+    assert_eq!(
+        vec![
+            (1, "new".to_string()),
+            (2, "rescheduled".to_string()),
+            (3, "cancelled".to_string())
+        ],
+        jobs
+    );
+}
+
+#[tokio::test]
+async fn test_webhooks_are_signed_and_disabled_after_repeated_failures() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+    let receiver = MockHttpServer::start().await;
+
+    let request = SubscriptionRequest {
+        county: "Tulcea".to_string(),
+        locality: None,
+        street_pattern: None,
+        window_start: None,
+        window_end: None,
+        channel: NotificationChannel::Webhook,
+        contact: format!("{}/hooks/outages", receiver.url),
+        state: None,
+        reminders: vec![],
+        digest: None,
+        digest_time: None,
+        language: Language::En,
+    };
+    let saved = create_verified_subscription(&state, request.clone()).await;
+    let secret = saved.signing_secret.clone().unwrap();
+    let subscription_id = saved.subscription.id;
+
+    let incident: Incident = sqlx::query_as("SELECT * FROM incidents ORDER BY id LIMIT 1")
+        .fetch_one(state.pg_pool.deref())
+        .await
+        .unwrap();
+    let notification = Notification {
+        subscription: saved.subscription,
+        reason: NotificationReason::New,
+        incident: incident.clone(),
+    };
+    let notifier = WebhookNotifier::new(Arc::new(Templates::default()), true).unwrap();
+
+    assert_eq!(Ok(()), notifier.notify(&notification).await);
+    let requests = receiver.requests();
+    assert_eq!(1, requests.len());
+    assert_eq!("/hooks/outages", requests[0].path);
+    let timestamp: i64 = requests[0].headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+    assert_eq!(
+        signature(&secret, timestamp, requests[0].body.as_bytes()),
+        requests[0].headers[SIGNATURE_HEADER]
+    );
+    let payload: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!("new", payload["change"]);
+    assert_eq!(subscription_id, payload["subscription_id"]);
+    assert_eq!(incident.id, payload["incident"]["id"]);
+
+    // The notifier tries once, the retries are left to the outbox.
+    receiver.respond_with(StatusCode::SERVICE_UNAVAILABLE);
+    let delivered = notifier.notify(&notification).await;
+    assert!(matches!(delivered, Err(DeliveryError::Transient(_))));
+    assert_eq!(2, receiver.requests().len());
+
+    // Each dead-lettered notification is a failure of the webhook.
+    let dispatcher = Dispatcher::new(
+        state.pg_pool.clone(),
+        vec![Box::new(notifier)],
+        OutboxConfiguration {
+            max_attempts: 1,
+            ..OutboxConfiguration::default()
+        },
+        WebhookConfiguration { max_failures: 2 },
+    );
+    let today = Utc::now().date_naive();
+    let after = latest_event_id(state.pg_pool.clone()).await.unwrap();
+    for id in ["failing-1", "failing-2"] {
+        let record = Record {
+            id: id.to_string(),
+            title: String::new(),
+            description: "Strada: Pacii nr. 1-10".to_string(),
+            date: today + Days::new(10),
+            start_time: NaiveTime::from_hms_opt(9, 0, 0),
+            end_time: NaiveTime::from_hms_opt(13, 0, 0),
+            county: "TULCEA".to_string(),
+            location: "LOC. ISACCEA".to_string(),
+        };
+        new_store_record(&record, state.pg_pool.clone()).await.unwrap();
+    }
+    evaluate_incident_changes(after, today, state.pg_pool.clone())
+        .await
+        .unwrap();
+    assert_eq!(Ok(2), dispatcher.dispatch_due().await);
+    assert_eq!(4, receiver.requests().len());
+    let Json(disabled) = subscriptions::get_subscription(
+        State(state.clone()),
+        Path(subscription_id),
+        Query(ManagementToken {
+            token: state.links.management_token(subscription_id),
+        }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        (SubscriptionState::Disabled, 2),
+        (disabled.state, disabled.failure_count)
+    );
+
+    let Json(enabled) = subscriptions::update_subscription(
+        State(state.clone()),
+        Path(subscription_id),
+        Query(ManagementToken {
+            token: state.links.management_token(subscription_id),
+        }),
+        Json(request),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        (SubscriptionState::Active, 0),
+        (enabled.subscription.state, enabled.subscription.failure_count)
+    );
+    assert_eq!(Some(secret), enabled.signing_secret);
+}
+
+/// The revision, state, attempts and last error of the notification jobs.
+async fn outbox_jobs(pg_pool: &Pool<Postgres>) -> Vec<(i32, String, i32, Option<String>)> {
+    sqlx::query_as("SELECT revision, state, attempts, last_error FROM notification_jobs ORDER BY revision")
+        .fetch_all(pg_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_outbox_retries_and_delivers_each_notification_once() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+    let server = MockHttpServer::start().await;
+    let today = Utc::now().date_naive();
+
+    let request = SubscriptionRequest {
+        county: "Tulcea".to_string(),
+        locality: Some("Isaccea".to_string()),
+        street_pattern: None,
+        window_start: None,
+        window_end: None,
+        channel: NotificationChannel::Ntfy,
+        contact: format!("{}/outages-isaccea", server.url),
+        state: None,
+        reminders: vec![],
+        digest: None,
+        digest_time: None,
+        language: Language::En,
+    };
+    create_verified_subscription(&state, request).await;
+    let notifier = NtfyNotifier::new(
+        "https://enel.lab.wicked".to_string(),
+        Arc::new(Templates::default()),
+        true,
+    )
+    .unwrap();
+    let configuration = OutboxConfiguration {
+        max_attempts: 3,
+        ..OutboxConfiguration::default()
+    };
+    let dispatcher = Dispatcher::new(
+        state.pg_pool.clone(),
+        vec![Box::new(notifier)],
+        configuration,
+        WebhookConfiguration::default(),
+    );
+
+    let record = Record {
+        id: "outbox".to_string(),
+        title: String::new(),
+        description: "Strada: Pacii nr. 1-10".to_string(),
+        date: today + Days::new(10),
+        start_time: NaiveTime::from_hms_opt(9, 0, 0),
+        end_time: NaiveTime::from_hms_opt(13, 0, 0),
+        county: "TULCEA".to_string(),
+        location: "LOC. ISACCEA".to_string(),
+    };
+    let after = latest_event_id(state.pg_pool.clone()).await.unwrap();
+    new_store_record(&record, state.pg_pool.clone()).await.unwrap();
+    evaluate_incident_changes(after, today, state.pg_pool.clone())
+        .await
+        .unwrap();
+
+    server.respond_with(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(Ok(1), dispatcher.dispatch_due().await);
+    let failed = outbox_jobs(&state.pg_pool).await;
+    assert_eq!(
+        (1, "pending".to_string(), 1),
+        (failed[0].0, failed[0].1.clone(), failed[0].2)
+    );
+    assert!(failed[0].3.is_some());
+    // The retry waits for its backoff.
+    assert_eq!(Ok(0), dispatcher.dispatch_due().await);
+
+    sqlx::query("UPDATE notification_jobs SET next_attempt_at = now()")
+        .execute(state.pg_pool.deref())
+        .await
+        .unwrap();
+    server.respond_with(StatusCode::OK);
+    assert_eq!(Ok(1), dispatcher.dispatch_due().await);
+    assert_eq!(Ok(0), dispatcher.dispatch_due().await);
+    assert_eq!(2, server.requests().len());
+    assert_eq!(
+        vec![(1, "delivered".to_string(), 2, None)],
+        outbox_jobs(&state.pg_pool).await
+    );
+
+    // A rejected notification is dead-lettered right away.
+    let rescheduled = Record {
+        start_time: NaiveTime::from_hms_opt(14, 0, 0),
+        end_time: NaiveTime::from_hms_opt(18, 0, 0),
+        ..record
+    };
+    new_store_record(&rescheduled, state.pg_pool.clone()).await.unwrap();
+    evaluate_incident_changes(after, today, state.pg_pool.clone())
+        .await
+        .unwrap();
+    server.respond_with(StatusCode::FORBIDDEN);
+    assert_eq!(Ok(1), dispatcher.dispatch_due().await);
+    let dead = outbox_jobs(&state.pg_pool).await;
+    assert_eq!((2, "dead".to_string(), 1), (dead[1].0, dead[1].1.clone(), dead[1].2));
+    assert_eq!(Ok(0), dispatcher.dispatch_due().await);
+}
+
+/// Delivers as a dispatcher whose lease ran out during the delivery, the job being claimed by another one meanwhile.
+struct OvertakenNotifier {
+    pg_pool: Arc<Pool<Postgres>>,
+}
+
+#[async_trait]
+impl Notifier for OvertakenNotifier {
+    fn channel(&self) -> NotificationChannel {
+        NotificationChannel::Ntfy
+    }
+
+    async fn notify(&self, _: &Notification) -> Result<(), DeliveryError> {
+        sqlx::query("UPDATE notification_jobs SET claim_token = 'another-dispatcher'")
+            .execute(self.pg_pool.deref())
+            .await
+            .unwrap();
+        Ok(())
+    }
+
+    async fn notify_digest(&self, _: &Digest) -> Result<(), DeliveryError> {
+        Ok(())
+    }
+
+    async fn send_verification(&self, _: &Verification) -> Result<(), DeliveryError> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_outcomes_are_recorded_only_by_the_claim_holder() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+    let today = Utc::now().date_naive();
+
+    let request = SubscriptionRequest {
+        county: "Tulcea".to_string(),
+        locality: Some("Isaccea".to_string()),
+        street_pattern: None,
+        window_start: None,
+        window_end: None,
+        channel: NotificationChannel::Ntfy,
+        contact: "https://ntfy.sh/outages-isaccea".to_string(),
+        state: None,
+        reminders: vec![],
+        digest: None,
+        digest_time: None,
+        language: Language::En,
+    };
+    create_verified_subscription(&state, request).await;
+    let notifier = OvertakenNotifier {
+        pg_pool: state.pg_pool.clone(),
+    };
+    let dispatcher = Dispatcher::new(
+        state.pg_pool.clone(),
+        vec![Box::new(notifier)],
+        OutboxConfiguration::default(),
+        WebhookConfiguration::default(),
+    );
+
+    let record = Record {
+        id: "overtaken".to_string(),
+        title: String::new(),
+        description: "Strada: Pacii nr. 1-10".to_string(),
+        date: today + Days::new(10),
+        start_time: NaiveTime::from_hms_opt(9, 0, 0),
+        end_time: NaiveTime::from_hms_opt(13, 0, 0),
+        county: "TULCEA".to_string(),
+        location: "LOC. ISACCEA".to_string(),
+    };
+    let after = latest_event_id(state.pg_pool.clone()).await.unwrap();
+    new_store_record(&record, state.pg_pool.clone()).await.unwrap();
+    evaluate_incident_changes(after, today, state.pg_pool.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(Ok(1), dispatcher.dispatch_due().await);
+    // The job is left to the dispatcher holding its claim now.
+    assert_eq!(
+        vec![(1, "pending".to_string(), 1, None)],
+        outbox_jobs(&state.pg_pool).await
+    );
+}
+
+/// The revision, reminder, state and due time of the reminders.
+async fn reminder_jobs(pg_pool: &Pool<Postgres>) -> Vec<(i32, Option<Reminder>, String, DateTime<Utc>)> {
+    sqlx::query_as(
+        "SELECT revision, reminder, state, next_attempt_at FROM notification_jobs WHERE reminder IS NOT NULL \
+         ORDER BY id",
+    )
+    .fetch_all(pg_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_reminders_follow_the_rescheduled_and_cancelled_outages() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+    let server = MockHttpServer::start().await;
+    let today = Utc::now().date_naive();
+    let day = today + Days::new(10);
+
+    let request = SubscriptionRequest {
+        county: "Tulcea".to_string(),
+        locality: Some("Isaccea".to_string()),
+        street_pattern: None,
+        window_start: None,
+        window_end: None,
+        channel: NotificationChannel::Ntfy,
+        contact: format!("{}/outages-isaccea", server.url),
+        state: None,
+        reminders: vec![Reminder::TwoHoursBefore, Reminder::EveningBefore],
+        digest: None,
+        digest_time: None,
+        language: Language::En,
+    };
+    create_verified_subscription(&state, request).await;
+    let notifier = NtfyNotifier::new(
+        "https://enel.lab.wicked".to_string(),
+        Arc::new(Templates::default()),
+        true,
+    )
+    .unwrap();
+    let dispatcher = Dispatcher::new(
+        state.pg_pool.clone(),
+        vec![Box::new(notifier)],
+        OutboxConfiguration::default(),
+        WebhookConfiguration::default(),
+    );
+
+    let record = Record {
+        id: "reminders".to_string(),
+        title: String::new(),
+        description: "Strada: Pacii nr. 1-10".to_string(),
+        date: day,
+        start_time: NaiveTime::from_hms_opt(9, 0, 0),
+        end_time: NaiveTime::from_hms_opt(13, 0, 0),
+        county: "TULCEA".to_string(),
+        location: "LOC. ISACCEA".to_string(),
+    };
+    let after = latest_event_id(state.pg_pool.clone()).await.unwrap();
+    new_store_record(&record, state.pg_pool.clone()).await.unwrap();
+    let created_jobs = evaluate_incident_changes(after, today, state.pg_pool.clone()).await;
+    assert_eq!(Ok(3), created_jobs);
+    let evening_before = local_time::to_utc((day - Days::new(1)).and_hms_opt(20, 0, 0).unwrap());
+    assert_eq!(
+        vec![
+            (1, Some(Reminder::EveningBefore), "pending".to_string(), evening_before),
+            (
+                1,
+                Some(Reminder::TwoHoursBefore),
+                "pending".to_string(),
+                local_time::to_utc(day.and_hms_opt(7, 0, 0).unwrap())
+            ),
+        ],
+        reminder_jobs(&state.pg_pool).await
+    );
+    // Only the announcement is due.
+    assert_eq!(Ok(1), dispatcher.dispatch_due().await);
+
+    let rescheduled = Record {
+        start_time: NaiveTime::from_hms_opt(14, 0, 0),
+        end_time: NaiveTime::from_hms_opt(18, 0, 0),
+        ..record
+    };
+    new_store_record(&rescheduled, state.pg_pool.clone()).await.unwrap();
+    evaluate_incident_changes(after, today, state.pg_pool.clone())
+        .await
+        .unwrap();
+    let reminders = reminder_jobs(&state.pg_pool).await;
+    assert_eq!(
+        vec![
+            (1, "skipped".to_string()),
+            (1, "skipped".to_string()),
+            (2, "pending".to_string()),
+            (2, "pending".to_string())
+        ],
+        reminders
+            .iter()
+            .map(|(revision, _, state, _)| (*revision, state.clone()))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(local_time::to_utc(day.and_hms_opt(12, 0, 0).unwrap()), reminders[3].3);
+
+    sqlx::query("UPDATE notification_jobs SET next_attempt_at = now() WHERE revision = 2 AND reminder = $1")
+        .bind(Reminder::TwoHoursBefore)
+        .execute(state.pg_pool.deref())
+        .await
+        .unwrap();
+    assert_eq!(Ok(2), dispatcher.dispatch_due().await);
+    let mut titles: Vec<String> = server
+        .requests()
+        .iter()
+        .map(|request| {
+            let message: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+            message["title"].as_str().unwrap().to_string()
+        })
+        .collect();
+    titles.sort();
+    assert_eq!(
+        vec![
+            "Planned power outage in LOC. ISACCEA",
+            "Rescheduled power outage in LOC. ISACCEA",
+            "Upcoming power outage in LOC. ISACCEA"
+        ],
+        titles
+    );
+
+    // The feed no longer announces the outage.
+    let elsewhere = Record {
+        id: "elsewhere".to_string(),
+        location: "LOC. MACIN".to_string(),
+        ..rescheduled
+    };
+    cancel_withdrawn_incidents(&[elsewhere], today, state.pg_pool.clone())
+        .await
+        .unwrap();
+    evaluate_incident_changes(after, today, state.pg_pool.clone())
+        .await
+        .unwrap();
+    let reminders = reminder_jobs(&state.pg_pool).await;
+    assert_eq!(
+        (2, Some(Reminder::EveningBefore), "skipped".to_string()),
+        (reminders[2].0, reminders[2].1, reminders[2].2.clone())
+    );
+    assert_eq!("delivered", reminders[3].2);
+}
+
+#[tokio::test]
+async fn test_digests_collect_the_changes_and_wait_for_the_quiet_hours() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+    let server = MockHttpServer::start().await;
+    let today = Utc::now().date_naive();
+    let contact = format!("{}/outages-tulcea", server.url);
+
+    let request = SubscriptionRequest {
+        county: "Tulcea".to_string(),
+        locality: None,
+        street_pattern: None,
+        window_start: None,
+        window_end: None,
+        channel: NotificationChannel::Ntfy,
+        contact: contact.clone(),
+        state: None,
+        reminders: vec![],
+        digest: Some(DigestSchedule::Daily),
+        digest_time: NaiveTime::from_hms_opt(18, 0, 0),
+        language: Language::En,
+    };
+    let saved = create_verified_subscription(&state, request).await;
+    let notifier = NtfyNotifier::new(
+        "https://enel.lab.wicked".to_string(),
+        Arc::new(Templates::default()),
+        true,
+    )
+    .unwrap();
+    let dispatcher = Dispatcher::new(
+        state.pg_pool.clone(),
+        vec![Box::new(notifier)],
+        OutboxConfiguration::default(),
+        WebhookConfiguration::default(),
+    );
+
+    let record = |id: &str, location: &str| Record {
+        id: id.to_string(),
+        title: String::new(),
+        description: String::new(),
+        date: today + Days::new(10),
+        start_time: NaiveTime::from_hms_opt(9, 0, 0),
+        end_time: NaiveTime::from_hms_opt(13, 0, 0),
+        county: "TULCEA".to_string(),
+        location: location.to_string(),
+    };
+    let after = latest_event_id(state.pg_pool.clone()).await.unwrap();
+    for incident in [record("isaccea", "LOC. ISACCEA"), record("macin", "LOC. MACIN")] {
+        new_store_record(&incident, state.pg_pool.clone()).await.unwrap();
+    }
+    let evaluated_at = Utc::now();
+    evaluate_incident_changes(after, today, state.pg_pool.clone())
+        .await
+        .unwrap();
+    let due_times: Vec<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT DISTINCT next_attempt_at FROM notification_jobs WHERE state = 'pending'")
+            .fetch_all(state.pg_pool.deref())
+            .await
+            .unwrap();
+    assert_eq!(1, due_times.len());
+    assert!(due_times[0] > evaluated_at);
+    assert_eq!(Ok(0), dispatcher.dispatch_due().await);
+
+    // The quiet hours of a contact are managed with the token of one of its verified subscriptions.
+    let access = ManagementToken {
+        token: state.links.management_token(saved.subscription.id),
+    };
+    let foreign = ContactFiltering {
+        channel: NotificationChannel::Ntfy,
+        contact: contact.clone(),
+        token: state.links.management_token(saved.subscription.id + 1),
+    };
+    let refused = quiet_hours::get_quiet_hours(State(state.clone()), Query(foreign)).await;
+    assert_eq!(Err(StatusCode::FORBIDDEN), refused.map(|_| ()).map_err(|e| e.status()));
+
+    // Quiet from an hour ago to an hour from now.
+    let local_now = local_time::to_local(Utc::now()).time();
+    let quiet_hours = QuietHoursRequest {
+        channel: NotificationChannel::Ntfy,
+        contact: contact.clone(),
+        start_time: local_now - TimeDelta::hours(1),
+        end_time: local_now + TimeDelta::hours(1),
+    };
+    let Json(quiet_hours) = quiet_hours::set_quiet_hours(
+        State(state.clone()),
+        Query(ManagementToken {
+            token: access.token.clone(),
+        }),
+        Json(quiet_hours),
+    )
+    .await
+    .unwrap();
+    let quiet_until = quiet_hours.until(Utc::now()).unwrap();
+
+    sqlx::query("UPDATE notification_jobs SET next_attempt_at = now()")
+        .execute(state.pg_pool.deref())
+        .await
+        .unwrap();
+    assert_eq!(Ok(2), dispatcher.dispatch_due().await);
+    assert!(server.requests().is_empty());
+    let deferred: Vec<(String, i32, DateTime<Utc>)> =
+        sqlx::query_as("SELECT state, attempts, next_attempt_at FROM notification_jobs")
+            .fetch_all(state.pg_pool.deref())
+            .await
+            .unwrap();
+    assert_eq!(
+        vec![
+            ("pending".to_string(), 0, quiet_until),
+            ("pending".to_string(), 0, quiet_until)
+        ],
+        deferred
+    );
+
+    let filtering = ContactFiltering {
+        channel: NotificationChannel::Ntfy,
+        contact,
+        token: access.token,
+    };
+    let deleted = quiet_hours::delete_quiet_hours(State(state.clone()), Query(filtering)).await;
+    assert_eq!(Ok(StatusCode::NO_CONTENT), deleted.map_err(|e| e.status()));
+    sqlx::query("UPDATE notification_jobs SET next_attempt_at = now()")
+        .execute(state.pg_pool.deref())
+        .await
+        .unwrap();
+    assert_eq!(Ok(2), dispatcher.dispatch_due().await);
+    let requests = server.requests();
+    assert_eq!(1, requests.len());
+    let digest: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!("Daily power outage digest: 2 outages", digest["title"]);
+    // A line per outage, then the unsubscribe link.
+    assert_eq!(3, digest["message"].as_str().unwrap().lines().count());
+    assert_eq!(
+        vec![
+            (1, "delivered".to_string(), 1, None),
+            (1, "delivered".to_string(), 1, None)
+        ],
+        outbox_jobs(&state.pg_pool).await
+    );
+}
+
+#[tokio::test]
+async fn test_new_contacts_are_verified_before_they_are_notified_and_can_unsubscribe() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+    let server = MockHttpServer::start().await;
+    let today = Utc::now().date_naive();
+
+    let request = SubscriptionRequest {
+        county: "Tulcea".to_string(),
+        locality: Some("Isaccea".to_string()),
+        street_pattern: None,
+        window_start: None,
+        window_end: None,
+        channel: NotificationChannel::Ntfy,
+        contact: format!("{}/outages-isaccea", server.url),
+        state: None,
+        reminders: vec![],
+        digest: None,
+        digest_time: None,
+        language: Language::En,
+    };
+    let (_, Json(saved)) = subscriptions::create_subscription(State(state.clone()), Json(request))
+        .await
+        .unwrap();
+    let subscription_id = saved.subscription.id;
+    assert_eq!(None, saved.subscription.verified_at);
+    let templates = Templates::new(&TemplatesConfiguration::default(), state.links.clone()).unwrap();
+    let notifier = NtfyNotifier::new("https://enel.lab.wicked".to_string(), Arc::new(templates), true).unwrap();
+    let dispatcher = Dispatcher::new(
+        state.pg_pool.clone(),
+        vec![Box::new(notifier)],
+        OutboxConfiguration::default(),
+        WebhookConfiguration::default(),
+    );
+
+    let record = |id: &str| Record {
+        id: id.to_string(),
+        title: String::new(),
+        description: String::new(),
+        date: today + Days::new(10),
+        start_time: NaiveTime::from_hms_opt(9, 0, 0),
+        end_time: NaiveTime::from_hms_opt(13, 0, 0),
+        county: "TULCEA".to_string(),
+        location: "LOC. ISACCEA".to_string(),
+    };
+    let after = latest_event_id(state.pg_pool.clone()).await.unwrap();
+    new_store_record(&record("unverified"), state.pg_pool.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        Ok(0),
+        evaluate_incident_changes(after, today, state.pg_pool.clone()).await
+    );
+
+    // The code is sent once.
+    assert_eq!(Ok(1), dispatcher.dispatch_due().await);
+    assert_eq!(Ok(0), dispatcher.dispatch_due().await);
+    let requests = server.requests();
+    assert_eq!(1, requests.len());
+    let verification: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    let verification_url = verification["click"].as_str().unwrap();
+    let (_, code) = verification_url.split_once("?code=").unwrap();
+    assert!(verification["message"].as_str().unwrap().contains(code));
+
+    // Opening the link only shows the page confirming it.
+    let page = consent::verification_page(
+        State(state.clone()),
+        Path(subscription_id),
+        Query(VerificationCode { code: code.to_string() }),
+    )
+    .await
+    .unwrap();
+    assert!(page.0.contains("<form method=\"post\""));
+    let unverified: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT verified_at FROM subscriptions WHERE id = $1")
+        .bind(subscription_id)
+        .fetch_one(state.pg_pool.deref())
+        .await
+        .unwrap();
+    assert_eq!(None, unverified);
+
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+    let refused = consent::verify_subscription(
+        State(state.clone()),
+        Path(subscription_id),
+        Query(VerificationCode {
+            code: wrong_code.to_string(),
+        }),
+    )
+    .await;
+    assert_eq!(Some(StatusCode::BAD_REQUEST), refused.err().map(|e| e.status()));
+    for _ in 0..2 {
+        let Json(verified) = consent::verify_subscription(
+            State(state.clone()),
+            Path(subscription_id),
+            Query(VerificationCode { code: code.to_string() }),
+        )
+        .await
+        .unwrap();
+        assert!(verified.verified_at.is_some());
+    }
+
+    let after = latest_event_id(state.pg_pool.clone()).await.unwrap();
+    new_store_record(&record("verified"), state.pg_pool.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        Ok(1),
+        evaluate_incident_changes(after, today, state.pg_pool.clone()).await
+    );
+    assert_eq!(Ok(1), dispatcher.dispatch_due().await);
+    let notification: serde_json::Value = serde_json::from_str(&server.requests()[1].body).unwrap();
+    let (_, token) = notification["message"]
+        .as_str()
+        .unwrap()
+        .rsplit_once("?token=")
+        .unwrap();
+
+    let page = consent::unsubscribe_page(
+        State(state.clone()),
+        Query(UnsubscribeToken {
+            token: token.to_string(),
+        }),
+    )
+    .await
+    .unwrap();
+    assert!(page.0.contains("<form method=\"post\""));
+
+    let forged = consent::unsubscribe(
+        State(state.clone()),
+        Query(UnsubscribeToken {
+            token: format!("{}.AAAAAAAAAAAAAAAA", subscription_id),
+        }),
+    )
+    .await;
+    assert_eq!(Some(StatusCode::BAD_REQUEST), forged.err().map(|e| e.status()));
+    for _ in 0..2 {
+        let unsubscribed = consent::unsubscribe(
+            State(state.clone()),
+            Query(UnsubscribeToken {
+                token: token.to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(Ok(StatusCode::NO_CONTENT), unsubscribed.map_err(|e| e.status()));
+    }
+    let deleted = subscriptions::get_subscription(
+        State(state.clone()),
+        Path(subscription_id),
+        Query(ManagementToken {
+            token: state.links.management_token(subscription_id),
+        }),
+    )
+    .await;
+    assert_eq!(Some(StatusCode::NOT_FOUND), deleted.err().map(|e| e.status()));
+}
@@ -0,0 +1,321 @@
+use crate::common::{MockHttpServer, SmtpCaptureServer};
+use ::common::configuration::{SmsGatewayConfiguration, SmtpConfiguration};
+use axum::http::StatusCode;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{NaiveDate, NaiveTime, TimeDelta, Timelike, Utc};
+use std::sync::Arc;
+use web_server::notifications::email::EmailNotifier;
+use web_server::notifications::local_time;
+use web_server::notifications::notifier::{DeliveryError, Notification, Notifier};
+use web_server::notifications::push::{GotifyNotifier, NtfyNotifier};
+use web_server::notifications::rules::NotificationReason;
+use web_server::notifications::sms::SmsNotifier;
+use web_server::notifications::subscriptions::{NotificationChannel, Subscription, SubscriptionState};
+use web_server::notifications::templates::{Language, Templates};
+use web_server::web_api::Incident;
+
+mod common;
+
+fn notification(channel: NotificationChannel, contact: &str, description: &str) -> Notification {
+    Notification {
+        subscription: Subscription {
+            id: 3,
+            county_key: "tulcea".to_string(),
+            locality: Some("Isaccea".to_string()),
+            locality_key: Some("isaccea".to_string()),
+            street_pattern: None,
+            window_start: None,
+            window_end: None,
+            channel,
+            contact: contact.to_string(),
+            state: SubscriptionState::Active,
+            reminders: vec![],
+            digest: None,
+            digest_time: None,
+            language: Language::En,
+            signing_secret: None,
+            failure_count: 0,
+            verified_at: Some(Utc::now()),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        },
+        reason: NotificationReason::New,
+        incident: Incident {
+            external_id: "134691 - Retele Electrice".to_string(),
+            county: "TULCEA".to_string(),
+            location: "LOC. ISACCEA".to_string(),
+            day: NaiveDate::from_ymd_opt(2025, 8, 8).unwrap(),
+            start_time: NaiveTime::from_hms_opt(9, 0, 0),
+            end_time: NaiveTime::from_hms_opt(13, 0, 0),
+            description: description.to_string(),
+            id: 7,
+            revision: 1,
+            updated_at: Utc::now(),
+            cancelled_at: None,
+            duplicate_of: None,
+        },
+    }
+}
+
+#[tokio::test]
+async fn test_sms_is_posted_to_the_gateway() {
+    let gateway = MockHttpServer::start().await;
+    let mut configuration = SmsGatewayConfiguration::new(format!("{}/api/send", gateway.url));
+    configuration.token = Some("secret".to_string());
+    configuration.sender = Some("Enel".to_string());
+    configuration.max_segments = 2;
+    let notifier = SmsNotifier::new(&configuration, Arc::new(Templates::default())).unwrap();
+
+    let delivered = notifier
+        .notify(&notification(
+            NotificationChannel::Sms,
+            "+40722123456",
+            "Strada Păcii nr. 1-10",
+        ))
+        .await;
+
+    assert_eq!(Ok(()), delivered);
+    let requests = gateway.requests();
+    assert_eq!(1, requests.len());
+    assert_eq!("/api/send", requests[0].path);
+    assert_eq!("Bearer secret", requests[0].headers["authorization"]);
+    assert_eq!("application/json", requests[0].headers["content-type"]);
+    let payload: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!(
+        serde_json::json!({
+            "from": "Enel",
+            "to": "+40722123456",
+            "text": format!(
+                "Planned power outage in LOC. ISACCEA, TULCEA on 08.08.2025 09:00-13:00: Strada Pacii nr. 1-10\n\
+                 Unsubscribe: {}",
+                Templates::default().links().unsubscribe_url(3)
+            )
+        }),
+        payload
+    );
+}
+
+#[tokio::test]
+async fn test_romanian_sms_is_sent_without_diacritics() {
+    let gateway = MockHttpServer::start().await;
+    let mut configuration = SmsGatewayConfiguration::new(gateway.url.clone());
+    configuration.max_segments = 2;
+    let notifier = SmsNotifier::new(&configuration, Arc::new(Templates::default())).unwrap();
+    let mut notification = notification(NotificationChannel::Sms, "+40722123456", "Strada Păcii nr. 1-10");
+    notification.subscription.language = Language::Ro;
+
+    let delivered = notifier.notify(&notification).await;
+
+    assert_eq!(Ok(()), delivered);
+    let payload: serde_json::Value = serde_json::from_str(&gateway.requests()[0].body).unwrap();
+    assert_eq!(
+        format!(
+            "Intrerupere de curent programata in LOC. ISACCEA, TULCEA, vineri 08.08.2025, 09:00-13:00: Strada Pacii \
+             nr. 1-10\nDezabonare: {}",
+            Templates::default().links().unsubscribe_url(3)
+        ),
+        payload["text"]
+    );
+}
+
+#[tokio::test]
+async fn test_long_sms_is_truncated_in_a_form_payload() {
+    let gateway = MockHttpServer::start().await;
+    let mut configuration = SmsGatewayConfiguration::new(gateway.url.clone());
+    configuration.username = Some("enel".to_string());
+    configuration.password = Some("parola".to_string());
+    configuration.content_type = "application/x-www-form-urlencoded".to_string();
+    configuration.payload_template = "to={{to}}&text={{message}}".to_string();
+    let notifier = SmsNotifier::new(&configuration, Arc::new(Templates::default())).unwrap();
+
+    let delivered = notifier
+        .notify(&notification(
+            NotificationChannel::Sms,
+            "+40722123456",
+            &"Strada Pacii, ".repeat(20),
+        ))
+        .await;
+
+    assert_eq!(Ok(()), delivered);
+    let requests = gateway.requests();
+    assert_eq!("Basic ZW5lbDpwYXJvbGE=", requests[0].headers["authorization"]);
+    let text = requests[0].body.strip_prefix("to=%2B40722123456&text=").unwrap();
+    let text = text
+        .replace('+', " ")
+        .replace("%2C", ",")
+        .replace("%3A", ":")
+        .replace("%2F", "/")
+        .replace("%3F", "?")
+        .replace("%3D", "=")
+        .replace("%0A", "\n");
+    assert_eq!(160, text.len());
+    // The unsubscribe link is kept whole.
+    assert!(text.ends_with(&format!(
+        "...\nUnsubscribe: {}",
+        Templates::default().links().unsubscribe_url(3)
+    )));
+}
+
+#[tokio::test]
+async fn test_gateway_errors_tell_whether_to_retry() {
+    let gateway = MockHttpServer::start().await;
+    let notifier = SmsNotifier::new(
+        &SmsGatewayConfiguration::new(gateway.url.clone()),
+        Arc::new(Templates::default()),
+    )
+    .unwrap();
+
+    gateway.respond_with(StatusCode::SERVICE_UNAVAILABLE);
+    let delivered = notifier
+        .notify(&notification(NotificationChannel::Sms, "+40722123456", ""))
+        .await;
+    assert!(matches!(delivered, Err(DeliveryError::Transient(_))));
+
+    gateway.respond_with(StatusCode::BAD_REQUEST);
+    let delivered = notifier
+        .notify(&notification(NotificationChannel::Sms, "+40722123456", ""))
+        .await;
+    assert!(matches!(delivered, Err(DeliveryError::Rejected(_))));
+}
+
+/// The base64 encoded body of the part of the given content type.
+fn decoded_part(data: &str, content_type: &str) -> String {
+    let part = data.split(content_type).nth(1).unwrap();
+    let body = part.split("\r\n\r\n").nth(1).unwrap().split("\r\n--").next().unwrap();
+    String::from_utf8(STANDARD.decode(body.replace("\r\n", "")).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_email_is_sent_over_starttls() {
+    let relay = SmtpCaptureServer::start().await;
+    let mut configuration = SmtpConfiguration::new("127.0.0.1".to_string(), "alerte@example.ro".to_string());
+    configuration.port = relay.port;
+    configuration.username = Some("enel".to_string());
+    configuration.password = Some("parola".to_string());
+    configuration.ca_file = Some(relay.ca_file.clone());
+    let notifier = EmailNotifier::new(&configuration, Arc::new(Templates::default())).unwrap();
+
+    let delivered = notifier
+        .notify(&notification(
+            NotificationChannel::Email,
+            "ana@example.ro",
+            "Strada Păcii nr. 1-10",
+        ))
+        .await;
+
+    assert_eq!(Ok(()), delivered);
+    let mails = relay.mails();
+    assert_eq!(1, mails.len());
+    let mail = &mails[0];
+    assert!(mail.secure);
+    assert_eq!(Some(("enel".to_string(), "parola".to_string())), mail.credentials);
+    assert_eq!("alerte@example.ro", mail.from);
+    assert_eq!(vec!["ana@example.ro".to_string()], mail.recipients);
+    assert!(
+        mail.data
+            .contains("Subject: Planned power outage in LOC. ISACCEA on 08.08.2025\r\n")
+    );
+
+    let text = decoded_part(&mail.data, "text/plain");
+    assert!(text.contains("County: TULCEA\r\nLocality: LOC. ISACCEA\r\nDay: 08.08.2025\r\nTime window: 09:00 - 13:00"));
+    assert!(text.contains("Strada Păcii nr. 1-10"));
+    let html = decoded_part(&mail.data, "text/html");
+    assert!(html.contains("<td>09:00 - 13:00</td>"));
+    assert!(html.contains("<p>Strada Păcii nr. 1-10</p>"));
+}
+
+#[tokio::test]
+async fn test_email_is_not_sent_to_an_untrusted_relay() {
+    let relay = SmtpCaptureServer::start().await;
+    let mut configuration = SmtpConfiguration::new("127.0.0.1".to_string(), "alerte@example.ro".to_string());
+    configuration.port = relay.port;
+    let notifier = EmailNotifier::new(&configuration, Arc::new(Templates::default())).unwrap();
+
+    let delivered = notifier
+        .notify(&notification(NotificationChannel::Email, "ana@example.ro", ""))
+        .await;
+
+    assert!(matches!(delivered, Err(DeliveryError::Transient(_))));
+    assert!(relay.mails().is_empty());
+}
+
+/// An outage starting in Romania the given hours from now.
+fn outage_in(hours: i64, channel: NotificationChannel, contact: &str) -> Notification {
+    let start = local_time::to_local(Utc::now() + TimeDelta::hours(hours));
+    let mut notification = notification(channel, contact, "Strada Păcii nr. 1-10");
+    notification.incident.day = start.date();
+    notification.incident.start_time = NaiveTime::from_hms_opt(start.hour(), start.minute(), 0);
+    notification.incident.end_time = None;
+    notification
+}
+
+#[tokio::test]
+async fn test_ntfy_messages_are_published_to_the_topic() {
+    let server = MockHttpServer::start().await;
+    let notifier = NtfyNotifier::new(
+        "https://enel.lab.wicked".to_string(),
+        Arc::new(Templates::default()),
+        true,
+    )
+    .unwrap();
+
+    let topic = format!("{}/outages-isaccea?auth=tk", server.url);
+    let delivered = notifier.notify(&outage_in(1, NotificationChannel::Ntfy, &topic)).await;
+
+    assert_eq!(Ok(()), delivered);
+    let requests = server.requests();
+    assert_eq!(1, requests.len());
+    assert_eq!("/", requests[0].path);
+    assert_eq!(Some("auth=tk".to_string()), requests[0].query);
+    let payload: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!("outages-isaccea", payload["topic"]);
+    assert_eq!("Planned power outage in LOC. ISACCEA", payload["title"]);
+    assert_eq!(5, payload["priority"]);
+    assert_eq!("https://enel.lab.wicked/web/incidents/7", payload["click"]);
+    assert!(payload["message"].as_str().unwrap().ends_with(&format!(
+        "\nStrada Păcii nr. 1-10\nUnsubscribe: {}",
+        Templates::default().links().unsubscribe_url(3)
+    )));
+
+    notifier
+        .notify(&outage_in(24 * 5, NotificationChannel::Ntfy, &topic))
+        .await
+        .unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&server.requests()[1].body).unwrap();
+    assert_eq!(2, payload["priority"]);
+}
+
+#[tokio::test]
+async fn test_gotify_messages_are_pushed_with_the_application_token() {
+    let server = MockHttpServer::start().await;
+    let notifier = GotifyNotifier::new(
+        "https://enel.lab.wicked".to_string(),
+        Arc::new(Templates::default()),
+        true,
+    )
+    .unwrap();
+
+    let contact = format!("{}/?token=AbCdEf", server.url);
+    let delivered = notifier
+        .notify(&outage_in(12, NotificationChannel::Gotify, &contact))
+        .await;
+
+    assert_eq!(Ok(()), delivered);
+    let requests = server.requests();
+    assert_eq!("/message", requests[0].path);
+    assert_eq!(Some("token=AbCdEf".to_string()), requests[0].query);
+    let payload: serde_json::Value = serde_json::from_str(&requests[0].body).unwrap();
+    assert_eq!("Planned power outage in LOC. ISACCEA", payload["title"]);
+    assert_eq!(6, payload["priority"]);
+    assert_eq!(
+        "https://enel.lab.wicked/web/incidents/7",
+        payload["extras"]["client::notification"]["click"]["url"]
+    );
+
+    server.respond_with(StatusCode::UNAUTHORIZED);
+    let delivered = notifier
+        .notify(&outage_in(12, NotificationChannel::Gotify, &contact))
+        .await;
+    assert!(matches!(delivered, Err(DeliveryError::Rejected(_))));
+}
@@ -1,6 +1,6 @@
-use chrono::Utc;
+use chrono::{Days, NaiveTime, Utc};
 
-use crate::common::{generate_ddl, setup_logging};
+use crate::common::{TestInfrastructure, create_app_state, generate_ddl, setup_logging};
 use ::common::Record;
 use log::info;
 use sqlx::postgres::PgPoolOptions;
@@ -10,7 +10,7 @@ use std::time::Duration;
 use testcontainers::runners::AsyncRunner;
 use testcontainers_modules::postgres;
 use tokio::time::sleep;
-use web_server::scraper::persistence::new_store_record;
+use web_server::scraper::persistence::{cancel_withdrawn_incidents, new_store_record};
 use web_server::web_api::Incident;
 
 mod common;
@@ -45,6 +45,8 @@ async fn test_persistence() {
         description: String::from("descr"),
         title: String::from("title"),
         date: current_day,
+        start_time: NaiveTime::from_hms_opt(9, 0, 0),
+        end_time: NaiveTime::from_hms_opt(17, 0, 0),
     };
 
     let _res = new_store_record(&record, pg_pool.clone()).await.unwrap();
@@ -61,4 +63,73 @@ async fn test_persistence() {
     assert_eq!(current_day, incident.day);
     assert_eq!(record.county, incident.county);
     assert_eq!(record.location, incident.location);
+    assert_eq!(record.start_time, incident.start_time);
+    assert_eq!(record.end_time, incident.end_time);
+    assert_eq!(1, incident.revision);
+    assert!(incident.cancelled_at.is_none());
+
+    let unchanged = new_store_record(&record, pg_pool.clone()).await.unwrap();
+    assert_eq!(0, unchanged);
+
+    let rescheduled = Record {
+        end_time: NaiveTime::from_hms_opt(18, 0, 0),
+        ..record
+    };
+    let _res = new_store_record(&rescheduled, pg_pool.clone()).await.unwrap();
+    let cancelled = cancel_withdrawn_incidents(&[], current_day, pg_pool.clone())
+        .await
+        .unwrap();
+    assert_eq!(0, cancelled);
+
+    let incident: Incident = sqlx::query_as("SELECT * FROM incidents WHERE external_id = $1")
+        .bind(&rescheduled.id)
+        .fetch_one(pg_pool.deref())
+        .await
+        .unwrap();
+
+    assert_eq!(rescheduled.end_time, incident.end_time);
+    assert_eq!(2, incident.revision);
+}
+
+#[tokio::test]
+async fn test_only_complete_feeds_cancel_the_withdrawn_incidents() {
+    let infra = TestInfrastructure::new().await;
+    let state = create_app_state(&infra).await;
+    let next_week = Utc::now().date_naive() + Days::new(7);
+    let today = Utc::now().date_naive();
+
+    let record = |id: &str| Record {
+        id: id.to_string(),
+        title: String::new(),
+        description: String::new(),
+        date: next_week,
+        start_time: None,
+        end_time: None,
+        county: "VASLUI".to_string(),
+        location: "LOC. BARLAD".to_string(),
+    };
+    for id in ["kept", "withdrawn", "other"] {
+        new_store_record(&record(id), state.pg_pool.clone()).await.unwrap();
+    }
+    let cancelled_ids = || async {
+        sqlx::query_scalar::<_, String>(
+            "SELECT external_id FROM incidents WHERE cancelled_at IS NOT NULL ORDER BY external_id",
+        )
+        .fetch_all(state.pg_pool.deref())
+        .await
+        .unwrap()
+    };
+
+    // A single incident out of three is too little for a complete feed of the county.
+    let cancelled = cancel_withdrawn_incidents(&[record("kept")], today, state.pg_pool.clone())
+        .await
+        .unwrap();
+    assert_eq!(0, cancelled);
+    assert!(cancelled_ids().await.is_empty());
+
+    let cancelled = cancel_withdrawn_incidents(&[record("kept"), record("other")], today, state.pg_pool.clone())
+        .await
+        .unwrap();
+    assert_eq!(1, cancelled);
+    assert_eq!(vec!["withdrawn".to_string()], cancelled_ids().await);
 }
@@ -1,7 +1,9 @@
-use std::ops::Deref;
 use crate::common::{TestInfrastructure, create_app_state};
 use axum::extract::State;
+use axum::http::StatusCode;
+use chrono::TimeDelta;
 use sqlx::Error;
+use std::ops::Deref;
 
 mod common;
 
@@ -22,6 +24,36 @@ async fn test_scraper_api() {
     assert_eq!(res.unwrap(), 186);
 }
 
+#[tokio::test]
+async fn test_status_follows_the_ingestions_but_not_readiness() {
+    let infra = TestInfrastructure::new().await;
+    let mut state = create_app_state(&infra).await;
+    state.max_data_age = Some(TimeDelta::hours(1));
+
+    // Stale incidents do not take the service out, it would never receive the next feed.
+    let (status, readiness) = web_server::health::get_readiness(State(state.clone())).await;
+    assert_eq!(StatusCode::OK, status);
+    assert!(readiness.database);
+    assert!(readiness.migrations);
+    let status = web_server::health::get_status(State(state.clone())).await.unwrap().0;
+    assert!(!status.fresh_data);
+
+    let body: String = read_rss_file("tests/rss-outages.xml").await;
+    let resp = web_server::scraper::scraper_api::submit_rss(State(state.clone()), body).await;
+    assert!(resp.is_ok());
+
+    let status = web_server::health::get_status(State(state.clone())).await.unwrap().0;
+    assert!(status.fresh_data);
+    let ingestion = status.last_ingestion.unwrap();
+    assert_eq!(181, ingestion.received_count);
+    assert_eq!(181, ingestion.stored_count);
+    assert_eq!(
+        186,
+        status.incidents.incident_count + status.incidents.duplicate_incident_count
+    );
+    assert!(status.incidents.newest_incident_day.is_some());
+}
+
 async fn read_rss_file(file_path: &str) -> String {
     String::from_utf8(tokio::fs::read(file_path).await.unwrap()).unwrap()
 }
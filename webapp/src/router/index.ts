@@ -2,6 +2,7 @@ import {createRouter, createWebHistory} from 'vue-router'
 import HomeView from "../views/HomeView.vue";
 import ListerView from "../views/ListerView.vue";
 import MapView from "../views/MapView.vue";
+import IncidentView from "../views/IncidentView.vue";
 
 const router = createRouter({
     history: createWebHistory('/web/'),
@@ -20,6 +21,11 @@ const router = createRouter({
             path: '/map',
             name: 'map',
             component: MapView
+        },
+        {
+            path: '/incidents/:id',
+            name: 'incident',
+            component: IncidentView
         }
     ]
 })